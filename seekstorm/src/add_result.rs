@@ -3,7 +3,10 @@ use smallvec::{SmallVec, smallvec};
 use std::cmp::Ordering;
 
 use crate::{
-    geo_search::{decode_morton_2_d, euclidian_distance},
+    geo_search::{
+        decode_morton_2_d, euclidian_distance, geo_grid_cell, point_in_bounding_box,
+        point_in_polygon,
+    },
     index::{
         AccessType, CompressionType, FIELD_STOP_BIT_1, FIELD_STOP_BIT_2, FieldType,
         LexicalSimilarity, NgramType, NonUniquePostingListObjectQuery, PostingListObjectQuery,
@@ -11,6 +14,7 @@ use crate::{
     },
     min_heap,
     search::{FilterSparse, Ranges, ResultType, SearchResult},
+    sparse_vector::SPARSE_WEIGHT_SCALE,
     utils::{
         read_f32, read_f64, read_i8, read_i16, read_i32, read_i64, read_u8, read_u16, read_u32,
         read_u64,
@@ -32,6 +36,7 @@ pub(crate) struct PostingListObjectSingle<'a> {
     pub idf_ngram2: f32,
     pub idf_ngram3: f32,
     pub ngram_type: NgramType,
+    pub is_sparse: bool,
 }
 
 #[inline(always)]
@@ -477,6 +482,26 @@ pub(crate) fn is_facet_filter(index: &Shard, facet_filter: &[FilterSparse], doci
                 }
             }
 
+            FilterSparse::BoundingBox(south_west, north_east) => {
+                let morton_code = read_u64(
+                    &index.facets_file_mmap,
+                    (index.facets_size_sum * docid) + facet.offset,
+                );
+                if !point_in_bounding_box(&decode_morton_2_d(morton_code), south_west, north_east) {
+                    return true;
+                }
+            }
+
+            FilterSparse::Polygon(polygon) => {
+                let morton_code = read_u64(
+                    &index.facets_file_mmap,
+                    (index.facets_size_sum * docid) + facet.offset,
+                );
+                if !point_in_polygon(&decode_morton_2_d(morton_code), polygon) {
+                    return true;
+                }
+            }
+
             FilterSparse::None => {}
         }
     }
@@ -617,6 +642,22 @@ pub(crate) fn facet_count(shard: &Shard, search_result: &mut SearchResult, docid
                         as u32
                 }
 
+                Ranges::GeoGrid(grid_type, precision) => {
+                    let point = decode_morton_2_d(read_u64(
+                        &shard.facets_file_mmap,
+                        (shard.facets_size_sum * docid) + facet.offset,
+                    ));
+                    let cell_id = geo_grid_cell(&point, grid_type, *precision);
+                    let cell = search_result.query_facets[i]
+                        .cells
+                        .entry(cell_id)
+                        .or_insert((0, 0.0, 0.0));
+                    cell.0 += 1;
+                    cell.1 += point[0];
+                    cell.2 += point[1];
+                    continue;
+                }
+
                 _ => {
                     if facet.field_type == FieldType::String16
                         || facet.field_type == FieldType::StringSet16
@@ -887,6 +928,9 @@ pub(crate) fn get_bm25f_singleterm_multifield(
             } as usize];
 
         match plo_single.ngram_type {
+            NgramType::SingleTerm if plo_single.is_sparse => {
+                bm25f = plo_single.idf * field_vec[0].1 as f32 / SPARSE_WEIGHT_SCALE;
+            }
             NgramType::SingleTerm => {
                 let tf = field_vec[0].1 as f32;
 
@@ -914,6 +958,10 @@ pub(crate) fn get_bm25f_singleterm_multifield(
                         * ((tf_ngram3 * (K + 1.0) / (tf_ngram3 + bm25_component)) + SIGMA);
             }
         }
+    } else if plo_single.is_sparse {
+        for field in field_vec.iter() {
+            bm25f += plo_single.idf * field.1 as f32 / SPARSE_WEIGHT_SCALE;
+        }
     } else if plo_single.ngram_type == NgramType::SingleTerm
         || shard.meta.lexical_similarity == LexicalSimilarity::Bm25fProximity
     {
@@ -1095,6 +1143,9 @@ pub(crate) fn get_bm25f_singleterm_singlefield(
             } as usize];
 
         match plo_single.ngram_type {
+            NgramType::SingleTerm if plo_single.is_sparse => {
+                bm25f = plo_single.idf * positions_count as f32 / SPARSE_WEIGHT_SCALE;
+            }
             NgramType::SingleTerm => {
                 let tf = positions_count as f32;
 
@@ -1137,6 +1188,9 @@ pub(crate) fn get_bm25f_singleterm_singlefield(
             } as usize];
 
         match plo_single.ngram_type {
+            NgramType::SingleTerm if plo_single.is_sparse => {
+                bm25f = plo_single.idf * positions_count as f32 / SPARSE_WEIGHT_SCALE;
+            }
             NgramType::SingleTerm => {
                 let tf = positions_count as f32;
 
@@ -1191,6 +1245,9 @@ pub(crate) fn get_bm25f_multiterm_multifield(
             }
 
             match plo.ngram_type {
+                NgramType::SingleTerm if plo.is_sparse => {
+                    bm25f += plo.idf * plo.field_vec[0].1 as f32 / SPARSE_WEIGHT_SCALE;
+                }
                 NgramType::SingleTerm => {
                     let tf = plo.field_vec[0].1 as f32;
 
@@ -1231,6 +1288,11 @@ pub(crate) fn get_bm25f_multiterm_multifield(
             }
 
             match plo.ngram_type {
+                NgramType::SingleTerm if plo.is_sparse => {
+                    for field in plo.field_vec.iter() {
+                        bm25f += plo.idf * field.1 as f32 / SPARSE_WEIGHT_SCALE;
+                    }
+                }
                 NgramType::SingleTerm => {
                     for field in plo.field_vec.iter() {
                         let field_id = field.0 as usize;
@@ -1446,6 +1508,9 @@ pub(crate) fn get_bm25f_multiterm_singlefield(
         }
 
         match plo.ngram_type {
+            NgramType::SingleTerm if plo.is_sparse => {
+                bm25f += plo.idf * plo.positions_count as f32 / SPARSE_WEIGHT_SCALE;
+            }
             NgramType::SingleTerm => {
                 let tf = plo.positions_count as f32;
 
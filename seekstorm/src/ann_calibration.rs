@@ -0,0 +1,437 @@
+use std::{fs::File, io::BufReader, path::Path, sync::atomic::Ordering};
+
+use ahash::AHashSet;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    INDEX_RUNTIME,
+    index::{ANN_CALIBRATION_FILENAME, Clustering, Index, IndexArc, Shard},
+    vector::{
+        ChunkAggregation, Embedding, EmbeddingView, Precision, Quantization,
+        SIMILARITY_NORMALIZATION_64_I8, SearchVectorShard, VectorHeader, read_record,
+    },
+    vector_merge::vector_doc_id,
+    vector_similarity::AnnMode,
+};
+
+/// Measured recall of an ANN search parameter (n-probe or ef_search) in the ANN calibration.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct AnnCalibrationPoint {
+    /// ANN search parameter: AnnMode::Nprobe for clustered indices, AnnMode::EfSearch for HNSW indices.
+    pub ann_mode: AnnMode,
+    /// Mean recall@length of the ANN search compared with the exhaustive search of all clusters, between 0.0 and 1.0.
+    pub recall: f32,
+}
+
+/// Recall curve of an index, measured by CalibrateAnn::calibrate_ann, used to resolve AnnMode::TargetRecall at query time.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct AnnCalibration {
+    /// Number of sampled vectors used as held-out queries.
+    pub sample_size: usize,
+    /// Number of nearest neighbors the recall was measured for (recall@length).
+    pub length: usize,
+    /// Number of index levels at calibration time: commits and CalibrateAnn::recalibrate_ann re-calibrate only if the level count changed since.
+    pub level_count: usize,
+    /// Recall per ANN search parameter, in ascending order of the parameter (and latency).
+    pub points: Vec<AnnCalibrationPoint>,
+}
+
+impl AnnCalibration {
+    pub(crate) fn load(index_path: &Path) -> Option<AnnCalibration> {
+        let file = File::open(index_path.join(ANN_CALIBRATION_FILENAME)).ok()?;
+        serde_json::from_reader(BufReader::new(file)).ok()
+    }
+
+    fn save(&self, index_path: &Path) {
+        match File::create(index_path.join(ANN_CALIBRATION_FILENAME)) {
+            Ok(file) => {
+                if let Err(e) = serde_json::to_writer(file, self) {
+                    println!("Unable to write ann calibration {:?}", e)
+                }
+            }
+            Err(e) => println!("Unable to create ann calibration file {:?}", e),
+        }
+    }
+}
+
+/// Vector search query prepared for search_vector_shard: embedding with scale, norm, zero_point and sum_q.
+type PreparedQuery = (Embedding, f32, f32, i16, i32);
+
+impl Shard {
+    /// Record type and record size of the stored vectors.
+    pub(crate) fn vector_record_size(&self) -> (Precision, usize) {
+        let vector_type = match self.quantization {
+            Quantization::ScalarQuantizationI8 | Quantization::TurboQuantI8 => Precision::I8,
+            _ => self.vector_precision,
+        };
+        let vector_size = size_of::<VectorHeader>()
+            + (self.vector_dimensions
+                * match vector_type {
+                    Precision::F32 => 4,
+                    Precision::F16 | Precision::BF16 => 2,
+                    Precision::I8 => 1,
+                    Precision::None => 0,
+                });
+        (vector_type, vector_size)
+    }
+
+    /// Factor mapping raw Dot/Cosine scores of the stored vectors to -1.0..1.0: i8 scores are scaled, float scores are not.
+    pub(crate) fn score_normalization(&self) -> f32 {
+        if self.vector_record_size().0 == Precision::I8 {
+            SIMILARITY_NORMALIZATION_64_I8
+        } else {
+            1.0
+        }
+    }
+
+    /// Committed vector levels of the shard: offset of the first vector record, number of clusters and number of vectors per level.
+    pub(crate) fn vector_levels(&self) -> Vec<(usize, usize, usize)> {
+        let (_, vector_size) = self.vector_record_size();
+        let mut levels = Vec::new();
+        let mut offset = 0;
+        for _level_id in 0..self.level_index.len() {
+            if offset + 4 > self.vector_file_mmap.len() {
+                break;
+            }
+            let cluster_number = u32::from_le_bytes(
+                self.vector_file_mmap[offset..offset + 4]
+                    .try_into()
+                    .unwrap(),
+            ) as usize;
+            offset += 4;
+            let mut vector_count = 0;
+            for _cluster_id in 0..cluster_number {
+                vector_count += u32::from_le_bytes(
+                    self.vector_file_mmap[offset..offset + 4]
+                        .try_into()
+                        .unwrap(),
+                ) as usize;
+                offset += 4;
+            }
+            levels.push((offset, cluster_number, vector_count));
+            offset += vector_count * vector_size;
+        }
+        levels
+    }
+
+    /// Evenly spaced sample of the committed vectors of the shard, as prepared queries with the local document id of the vector.
+    fn sample_vectors(&self, sample_count: usize) -> Vec<(usize, PreparedQuery)> {
+        let (vector_type, vector_size) = self.vector_record_size();
+        let levels = self.vector_levels();
+        let vector_count: usize = levels.iter().map(|level| level.2).sum();
+        if sample_count == 0 || vector_count == 0 {
+            return Vec::new();
+        }
+
+        let stride = (vector_count / sample_count).max(1);
+        let mut samples = Vec::new();
+        let mut vector_index = stride / 2;
+        let mut level_start = 0;
+        for (level_id, (offset, _, level_vector_count)) in levels.iter().enumerate() {
+            let merged_vector_level = self.merged_vector_levels.get(&level_id);
+            while vector_index < level_start + level_vector_count && samples.len() < sample_count {
+                let record = read_record(
+                    &self.vector_file_mmap[offset + (vector_index - level_start) * vector_size..],
+                    self.vector_dimensions,
+                    vector_type,
+                );
+                let embedding = match record.embedding {
+                    EmbeddingView::I8(e) => Embedding::I8(e.to_vec()),
+                    EmbeddingView::F32(e) => Embedding::F32(e.to_vec()),
+                    EmbeddingView::F16(e) => Embedding::F16(e.to_vec()),
+                    EmbeddingView::BF16(e) => Embedding::BF16(e.to_vec()),
+                };
+                samples.push((
+                    vector_doc_id(
+                        merged_vector_level,
+                        level_id,
+                        vector_index - level_start,
+                        record.header.doc_id,
+                    ),
+                    (
+                        embedding.widen(),
+                        record.header.scale,
+                        record.header.norm,
+                        record.header.zero_point,
+                        record.header.sum_q,
+                    ),
+                ));
+                vector_index += stride;
+            }
+            level_start += level_vector_count;
+        }
+        samples
+    }
+}
+
+impl Index {
+    /// Resolves AnnMode::TargetRecall to the ANN search parameter with the lowest latency that reached the target recall in the ANN calibration.
+    /// Without calibration, or if the target recall was not reached, all clusters are searched (AnnMode::All).
+    pub(crate) fn resolve_ann_mode(&self, ann_mode: &AnnMode) -> AnnMode {
+        match ann_mode {
+            AnnMode::TargetRecall(target_recall) => self
+                .ann_calibration
+                .as_ref()
+                .and_then(|ann_calibration| {
+                    ann_calibration
+                        .points
+                        .iter()
+                        .find(|point| point.recall >= *target_recall)
+                })
+                .map(|point| point.ann_mode.clone())
+                .unwrap_or(AnnMode::All),
+            _ => ann_mode.clone(),
+        }
+    }
+
+    /// Get the ANN calibration (recall curve) of the index, if the index was calibrated with CalibrateAnn::calibrate_ann.
+    pub fn get_ann_calibration(&self) -> Option<&AnnCalibration> {
+        self.ann_calibration.as_ref()
+    }
+
+    /// Global document ids of the nearest committed vectors of all shards, without the held-out document itself.
+    async fn nearest_neighbors(
+        &self,
+        query: &PreparedQuery,
+        exclude_doc_id: usize,
+        length: usize,
+        ann_mode: &AnnMode,
+    ) -> Vec<usize> {
+        let mut results = Vec::new();
+        for (shard_id, shard) in self.shard_vec.iter().enumerate() {
+            let rlo_shard = shard
+                .search_vector_shard(
+                    Some(query.clone()),
+                    length + 1,
+                    false,
+                    None,
+                    ann_mode.clone(),
+                    ChunkAggregation::Max,
+                    Vec::new(),
+                )
+                .await;
+            results.extend(
+                rlo_shard
+                    .results
+                    .into_iter()
+                    .map(|result| (result.score, (result.doc_id * self.shard_number) + shard_id)),
+            );
+        }
+        results.sort_unstable_by(|a, b| b.0.total_cmp(&a.0));
+        results
+            .into_iter()
+            .map(|(_, doc_id)| doc_id)
+            .filter(|doc_id| *doc_id != exclude_doc_id)
+            .take(length)
+            .collect()
+    }
+}
+
+/// ANN calibration: measures the recall of approximate nearest neighbor search for increasing n-probe (or ef_search for HNSW) values,
+/// so that queries can specify a target recall with AnnMode::TargetRecall instead of hand-tuning AnnMode::Nprobe.
+#[allow(async_fn_in_trait)]
+pub trait CalibrateAnn {
+    /// Calibrates the ANN search of the index: samples held-out vectors from the committed vectors of the index,
+    /// computes their exact nearest neighbors by exhaustive search of all clusters (as with Clustering::None),
+    /// and sweeps n-probe (clustered indices) or ef_search (HNSW indices) until the recall reaches 1.0.
+    /// The recall curve is stored in the index (ann_calibration.json) and used to resolve AnnMode::TargetRecall at query time.
+    /// Commits that change the level count re-calibrate the index with the same sample_size and length in a background task,
+    /// the recall curve of the last calibration is used until the re-calibration is finished.
+    /// Vector fields with their own inference are calibrated as well.
+    /// * `sample_size`: number of vectors sampled as queries, e.g. 100..1000. Higher values give a more reliable recall estimate, but take longer.
+    /// * `length`: number of nearest neighbors the recall is measured for (recall@length), should match the length of the queries.
+    ///
+    /// Returns the recall curve. For indices with Clustering::None every search is exhaustive, the curve is empty.
+    async fn calibrate_ann(&self, sample_size: usize, length: usize) -> AnnCalibration;
+
+    /// Re-calibrates the ANN search of a calibrated index with the sample_size and length of the last calibration, if the level count changed since.
+    /// Commits re-calibrate in a background task already, recalibrate_ann waits for the re-calibration, e.g. before measuring the recall.
+    /// Vector field indices are re-calibrated as well.
+    ///
+    /// Returns the new recall curve, or None if the index was not calibrated or the level count is unchanged.
+    async fn recalibrate_ann(&self) -> Option<AnnCalibration>;
+}
+
+impl CalibrateAnn for IndexArc {
+    async fn calibrate_ann(&self, sample_size: usize, length: usize) -> AnnCalibration {
+        let ann_calibration = calibrate(self, sample_size, length).await;
+
+        let vector_field_indices = self.read().await.vector_field_indices.clone();
+        for vector_field_index in vector_field_indices.iter() {
+            Box::pin(
+                vector_field_index
+                    .index_arc
+                    .calibrate_ann(sample_size, length),
+            )
+            .await;
+        }
+
+        ann_calibration
+    }
+
+    async fn recalibrate_ann(&self) -> Option<AnnCalibration> {
+        let recalibration = self.read().await.recalibration().await;
+        let ann_calibration = match recalibration {
+            Some((sample_size, length)) => Some(calibrate(self, sample_size, length).await),
+            None => None,
+        };
+
+        let vector_field_indices = self.read().await.vector_field_indices.clone();
+        for vector_field_index in vector_field_indices.iter() {
+            Box::pin(vector_field_index.index_arc.recalibrate_ann()).await;
+        }
+
+        ann_calibration
+    }
+}
+
+impl Index {
+    /// sample_size and length of the last calibration, if the index was calibrated and its level count changed since.
+    async fn recalibration(&self) -> Option<(usize, usize)> {
+        match self.ann_calibration.as_ref() {
+            Some(ann_calibration) if ann_calibration.level_count != self.level_count().await => {
+                Some((ann_calibration.sample_size, ann_calibration.length))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Re-calibrates the ANN search of a single index (without its vector field indices) in a background task, if its level count changed since the last calibration.
+/// Called by commit, which returns without waiting for the exhaustive search. Only one re-calibration per index runs at a time.
+pub(crate) async fn spawn_recalibration(index_arc: &IndexArc) {
+    let (recalibration, ann_recalibrating) = {
+        let index_ref = index_arc.read().await;
+        (
+            index_ref.recalibration().await,
+            index_ref.ann_recalibrating.clone(),
+        )
+    };
+    if let Some((sample_size, length)) = recalibration
+        && !ann_recalibrating.swap(true, Ordering::AcqRel)
+    {
+        let index_arc = index_arc.clone();
+        INDEX_RUNTIME.handle().spawn(async move {
+            calibrate(&index_arc, sample_size, length).await;
+            ann_recalibrating.store(false, Ordering::Release);
+        });
+    }
+}
+
+/// Calibrates the ANN search of a single index (without its vector field indices) and stores the recall curve.
+pub(crate) async fn calibrate(
+    index_arc: &IndexArc,
+    sample_size: usize,
+    length: usize,
+) -> AnnCalibration {
+    let index_ref = index_arc.read().await;
+    let mut ann_calibration = AnnCalibration {
+        sample_size,
+        length,
+        level_count: index_ref.level_count().await,
+        points: Vec::new(),
+    };
+
+    if index_ref.is_vector_indexing
+        && sample_size > 0
+        && length > 0
+        && !matches!(index_ref.meta.clustering, Clustering::None)
+    {
+        let mut shard_levels = Vec::new();
+        for shard in index_ref.shard_vec.iter() {
+            shard_levels.push(shard.read().await.vector_levels());
+        }
+        let vector_count: usize = shard_levels.iter().flatten().map(|level| level.2).sum();
+        let max_cluster_count = shard_levels
+            .iter()
+            .flatten()
+            .map(|level| level.1)
+            .max()
+            .unwrap_or(0);
+        let max_level_vector_count = shard_levels
+            .iter()
+            .flatten()
+            .map(|level| level.2)
+            .max()
+            .unwrap_or(0);
+
+        let mut samples = Vec::new();
+        if vector_count > 0 {
+            for (shard_id, shard) in index_ref.shard_vec.iter().enumerate() {
+                let shard_vector_count: usize =
+                    shard_levels[shard_id].iter().map(|level| level.2).sum();
+                let sample_count = (sample_size * shard_vector_count).div_ceil(vector_count);
+                samples.extend(
+                    shard
+                        .read()
+                        .await
+                        .sample_vectors(sample_count)
+                        .into_iter()
+                        .map(|(doc_id, query)| {
+                            ((doc_id * index_ref.shard_number) + shard_id, query)
+                        }),
+                );
+            }
+            samples.truncate(sample_size);
+        }
+
+        let mut ann_modes = Vec::new();
+        if let Clustering::Hnsw { .. } = index_ref.meta.clustering {
+            let mut ef_search = length;
+            while ef_search < max_level_vector_count {
+                ann_modes.push(AnnMode::EfSearch(ef_search));
+                ef_search *= 2;
+            }
+            ann_modes.push(AnnMode::EfSearch(max_level_vector_count.max(length)));
+        } else {
+            let mut n_probe = 1;
+            while n_probe < max_cluster_count {
+                ann_modes.push(AnnMode::Nprobe(n_probe));
+                n_probe = (n_probe + 1).max(n_probe * 3 / 2);
+            }
+            ann_modes.push(AnnMode::Nprobe(max_cluster_count.max(1)));
+        }
+
+        let mut exact_neighbors = Vec::with_capacity(samples.len());
+        for (doc_id, query) in samples.iter() {
+            let neighbors: AHashSet<usize> = index_ref
+                .nearest_neighbors(query, *doc_id, length, &AnnMode::All)
+                .await
+                .into_iter()
+                .collect();
+            exact_neighbors.push(neighbors);
+        }
+        let exact_count: usize = exact_neighbors
+            .iter()
+            .map(|neighbors| neighbors.len())
+            .sum();
+
+        if exact_count > 0 {
+            for ann_mode in ann_modes {
+                let mut hit_count = 0;
+                for ((doc_id, query), neighbors) in samples.iter().zip(exact_neighbors.iter()) {
+                    hit_count += index_ref
+                        .nearest_neighbors(query, *doc_id, length, &ann_mode)
+                        .await
+                        .iter()
+                        .filter(|doc_id| neighbors.contains(doc_id))
+                        .count();
+                }
+                let recall = hit_count as f32 / exact_count as f32;
+                ann_calibration
+                    .points
+                    .push(AnnCalibrationPoint { ann_mode, recall });
+                if recall >= 1.0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    ann_calibration.save(Path::new(&index_ref.index_path_string));
+    drop(index_ref);
+    index_arc.write().await.ann_calibration = Some(ann_calibration.clone());
+
+    ann_calibration
+}
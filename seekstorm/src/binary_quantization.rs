@@ -0,0 +1,224 @@
+use bytemuck::try_cast_slice;
+
+use crate::{
+    clustering::{accumulate, accumulate_simd},
+    index::Shard,
+    utils::{read_u16, read_u32},
+    vector::Embedding,
+    vector_similarity::{hamming_distance, hamming_distance_simd},
+};
+
+/// Size of the per level header: dimensions, vector count (both u32)
+pub(crate) const BINARY_HEADER_SIZE: usize = 8;
+/// Per vector id record: doc_id (u16) and field_id (u16)
+const BINARY_ID_SIZE: usize = 4;
+
+/// number of u64 words per bit vector
+#[inline(always)]
+fn word_count(dimensions: usize) -> usize {
+    dimensions.div_ceil(64)
+}
+
+/// byte sizes of the mean vector, the bit vectors and the id records of a level, each padded to a multiple of 8 bytes
+#[inline(always)]
+fn section_sizes(dimensions: usize, vector_count: usize) -> (usize, usize, usize) {
+    (
+        (4 * dimensions).next_multiple_of(8),
+        8 * word_count(dimensions) * vector_count,
+        (BINARY_ID_SIZE * vector_count).next_multiple_of(8),
+    )
+}
+
+/// Sets bit i if dimension i is above the mean of the level.
+fn binarize<T: Copy + Into<f32>>(vector: &[T], mean: &[f32], bits: &mut [u64]) {
+    bits.fill(0);
+    for (i, (value, mean)) in vector.iter().zip(mean.iter()).enumerate() {
+        if (*value).into() > *mean {
+            bits[i >> 6] |= 1 << (i & 63);
+        }
+    }
+}
+
+fn binarize_embedding(embedding: &Embedding, mean: &[f32], bits: &mut [u64]) {
+    match embedding {
+        Embedding::F32(vector) => binarize(vector, mean, bits),
+        Embedding::F16(vector) => binarize(vector, mean, bits),
+        Embedding::BF16(vector) => binarize(vector, mean, bits),
+        Embedding::I8(_) => binarize(&embedding.to_f32(), mean, bits),
+    }
+}
+
+impl Shard {
+    /// Encodes the vectors in the block_vector_buffer as bit vectors, in the order they are stored in the vector file.
+    /// Returns the serialized level block:
+    /// header: dimensions, vector count (u32 little-endian)
+    /// mean: dimensions f32 values, padded to a multiple of 8 bytes
+    /// bit vectors: per vector dimensions.div_ceil(64) u64 words
+    /// ids: per vector doc_id (u16) and field_id (u16), padded to a multiple of 8 bytes
+    pub(crate) fn encode_binary_quantization(&self) -> Vec<u8> {
+        let dimensions = self.vector_dimensions;
+        let vector_count = self.block_vector_buffer.len();
+        let (mean_size, _, _) = section_sizes(dimensions, vector_count);
+
+        let mut mean = vec![0f32; dimensions];
+        for record in self.block_vector_buffer.iter() {
+            if self.is_simd {
+                accumulate_simd(&mut mean, &record.embedding);
+            } else {
+                accumulate(&mut mean, &record.embedding);
+            }
+        }
+        for value in mean.iter_mut() {
+            *value /= vector_count.max(1) as f32;
+        }
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(dimensions as u32).to_le_bytes());
+        bytes.extend_from_slice(&(vector_count as u32).to_le_bytes());
+        for value in mean.iter() {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.resize(BINARY_HEADER_SIZE + mean_size, 0);
+
+        let mut bits = vec![0u64; word_count(dimensions)];
+        for record in self.block_vector_buffer.iter() {
+            binarize_embedding(&record.embedding, &mean, &mut bits);
+            for word in bits.iter() {
+                bytes.extend_from_slice(&word.to_le_bytes());
+            }
+        }
+
+        for record in self.block_vector_buffer.iter() {
+            bytes.extend_from_slice(&record.doc_id.to_le_bytes());
+            bytes.extend_from_slice(&(record.field_id as u16).to_le_bytes());
+        }
+        bytes.resize(bytes.len().next_multiple_of(8), 0);
+
+        bytes
+    }
+
+    /// Encodes the level that is currently committed with binary quantization,
+    /// and appends it to the binary quantization file, or replaces the bit vectors of an incomplete last level.
+    pub(crate) fn commit_binary_quantization(&mut self) {
+        let bytes = self.encode_binary_quantization();
+        let level_id = self.committed_level_id();
+        self.binary_file.write_level(level_id, &bytes);
+    }
+}
+
+/// Read-only view of the bit vectors of a level, directly on the memory mapped file.
+pub(crate) struct BinaryLevel<'a> {
+    words: usize,
+    pub vector_count: usize,
+    mean: &'a [f32],
+    bits: &'a [u64],
+    ids: &'a [u8],
+}
+
+impl<'a> BinaryLevel<'a> {
+    /// bytes: memory mapped binary quantization file, starting at the level offset
+    pub(crate) fn new(bytes: &'a [u8]) -> Option<Self> {
+        let dimensions = read_u32(bytes, 0) as usize;
+        let vector_count = read_u32(bytes, 4) as usize;
+        let (mean_size, bits_size, ids_size) = section_sizes(dimensions, vector_count);
+        let bits_start = BINARY_HEADER_SIZE + mean_size;
+        let ids_start = bits_start + bits_size;
+        Some(BinaryLevel {
+            words: word_count(dimensions),
+            vector_count,
+            mean: try_cast_slice(
+                bytes.get(BINARY_HEADER_SIZE..BINARY_HEADER_SIZE + 4 * dimensions)?,
+            )
+            .ok()?,
+            bits: try_cast_slice(bytes.get(bits_start..ids_start)?).ok()?,
+            ids: bytes.get(ids_start..ids_start + ids_size)?,
+        })
+    }
+
+    /// bit vector of the query, relative to the mean vector of the level
+    pub(crate) fn binarize_query(&self, query: &Embedding) -> Vec<u64> {
+        let mut bits = vec![0u64; self.words];
+        binarize_embedding(query, self.mean, &mut bits);
+        bits
+    }
+
+    /// approximate similarity of a vector: negative Hamming distance to the query bit vector
+    #[inline(always)]
+    pub(crate) fn similarity(&self, query_bits: &[u64], vector_id: usize, is_simd: bool) -> f32 {
+        let bits = &self.bits[vector_id * self.words..(vector_id + 1) * self.words];
+        let distance = if is_simd {
+            unsafe { hamming_distance_simd(query_bits, bits) }
+        } else {
+            hamming_distance(query_bits, bits)
+        };
+        -(distance as f32)
+    }
+
+    /// doc_id and field_id of a vector, without accessing the full precision vector file
+    #[inline(always)]
+    pub(crate) fn doc_field_id(&self, vector_id: usize) -> (u16, u16) {
+        let record_offset = vector_id * BINARY_ID_SIZE;
+        (
+            read_u16(self.ids, record_offset),
+            read_u16(self.ids, record_offset + 2),
+        )
+    }
+}
+
+/// Offsets of the level blocks within the memory mapped binary quantization file.
+pub(crate) fn binary_level_offsets(bytes: &[u8]) -> Vec<usize> {
+    let mut level_offsets = Vec::new();
+    let mut offset = 0;
+    while offset + BINARY_HEADER_SIZE <= bytes.len() {
+        level_offsets.push(offset);
+        offset += binary_level_size(&bytes[offset..]);
+    }
+    level_offsets
+}
+
+/// Size of the level block at the start of bytes, from its header.
+pub(crate) fn binary_level_size(bytes: &[u8]) -> usize {
+    let dimensions = read_u32(bytes, 0) as usize;
+    let vector_count = read_u32(bytes, 4) as usize;
+    let (mean_size, bits_size, ids_size) = section_sizes(dimensions, vector_count);
+    BINARY_HEADER_SIZE + mean_size + bits_size + ids_size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::Precision;
+
+    #[test]
+    fn binarize_signs() {
+        // 96 dimensions: 2 words per bit vector, the second one half used
+        let dimensions = 96;
+        let vector: Vec<f32> = (0..dimensions)
+            .map(|i| ((i * 37 % 11) as f32) - 5.0)
+            .collect();
+        let mean: Vec<f32> = (0..dimensions).map(|i| (i % 3) as f32 - 1.0).collect();
+        let mut bits = vec![u64::MAX; word_count(dimensions)];
+        binarize(&vector, &mean, &mut bits);
+        assert_eq!(bits.len(), 2);
+        for dimension in 0..dimensions {
+            assert_eq!(
+                bits[dimension >> 6] >> (dimension & 63) & 1 == 1,
+                vector[dimension] > mean[dimension]
+            );
+        }
+        assert_eq!(bits[1] >> 32, 0);
+
+        // half precision embeddings of the same (exactly representable) values have the same bits
+        for precision in [Precision::F16, Precision::BF16] {
+            let mut half_bits = vec![0u64; word_count(dimensions)];
+            binarize_embedding(
+                &Embedding::F32(vector.clone()).narrow(precision),
+                &mean,
+                &mut half_bits,
+            );
+            assert_eq!(half_bits, bits);
+        }
+
+        assert_eq!(section_sizes(dimensions, 3), (4 * 96, 8 * 2 * 3, 16));
+    }
+}
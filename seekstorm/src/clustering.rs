@@ -7,6 +7,10 @@ use std::arch::aarch64::*;
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
 
+#[cfg(target_arch = "x86_64")]
+use crate::vector_similarity::{load_bf16_avx2, load_f16_avx2};
+#[cfg(target_arch = "aarch64")]
+use crate::vector_similarity::{load_bf16_neon, load_f16_neon};
 use crate::{
     index::{Clustering, Shard},
     vector::{Embedding, Quantization},
@@ -45,6 +49,8 @@ pub(crate) struct ParentMedoid {
     pub is_medoid: bool,
     pub similarity: f32,
 
+    /// level of the document, differs from the level of the vector file section only for merged vector levels
+    pub level_id: u32,
     pub doc_id: u16,
     pub field_id: u32,
     pub chunk_id: u32,
@@ -127,11 +133,35 @@ unsafe fn accumulate_i8_avx2(sum: &mut [f32], emb: &[i8]) {
     }
 }
 
+#[cfg(target_arch = "x86_64")]
+unsafe fn accumulate_half_avx2<T: Copy + Into<f32>>(
+    sum: &mut [f32],
+    emb: &[T],
+    load: unsafe fn(*const T) -> __m256,
+) {
+    unsafe {
+        let len = emb.len();
+        let mut i = 0;
+        while i + 8 <= len {
+            let v = load(emb.as_ptr().add(i));
+            let s = _mm256_loadu_ps(sum.as_ptr().add(i));
+            _mm256_storeu_ps(sum.as_mut_ptr().add(i), _mm256_add_ps(s, v));
+            i += 8;
+        }
+
+        for j in i..len {
+            *sum.get_unchecked_mut(j) += (*emb.get_unchecked(j)).into();
+        }
+    }
+}
+
 #[cfg(target_arch = "x86_64")]
 pub(crate) fn accumulate_avx2(sum: &mut [f32], emb: &Embedding) {
     match emb {
         Embedding::I8(emb) => unsafe { accumulate_i8_avx2(sum, emb) },
         Embedding::F32(emb) => unsafe { accumulate_f32_avx2(sum, emb) },
+        Embedding::F16(emb) => unsafe { accumulate_half_avx2(sum, emb, load_f16_avx2) },
+        Embedding::BF16(emb) => unsafe { accumulate_half_avx2(sum, emb, load_bf16_avx2) },
     }
 }
 
@@ -190,11 +220,34 @@ unsafe fn accumulate_i8_neon(sum: &mut [f32], emb: &[i8]) {
     }
 }
 
+#[cfg(target_arch = "aarch64")]
+unsafe fn accumulate_half_neon<T: Copy + Into<f32>>(
+    sum: &mut [f32],
+    emb: &[T],
+    load: unsafe fn(*const T) -> float32x4_t,
+) {
+    unsafe {
+        let len = emb.len();
+        let mut i = 0;
+        while i + 4 <= len {
+            let v = load(emb.as_ptr().add(i));
+            let s = vld1q_f32(sum.as_ptr().add(i));
+            vst1q_f32(sum.as_mut_ptr().add(i), vaddq_f32(s, v));
+            i += 4;
+        }
+        for j in i..len {
+            *sum.get_unchecked_mut(j) += (*emb.get_unchecked(j)).into();
+        }
+    }
+}
+
 #[cfg(target_arch = "aarch64")]
 pub(crate) fn accumulate_neon(sum: &mut [f32], emb: &Embedding) {
     match emb {
         Embedding::I8(emb) => unsafe { accumulate_i8_neon(sum, emb) },
         Embedding::F32(emb) => unsafe { accumulate_f32_neon(sum, emb) },
+        Embedding::F16(emb) => unsafe { accumulate_half_neon(sum, emb, load_f16_neon) },
+        Embedding::BF16(emb) => unsafe { accumulate_half_neon(sum, emb, load_bf16_neon) },
     }
 }
 
@@ -221,6 +274,14 @@ pub(crate) fn accumulate(sum: &mut [f32], emb: &Embedding) {
             .zip(emb.iter())
             .for_each(|(a, b)| *a += *b as f32),
         Embedding::F32(emb) => sum.iter_mut().zip(emb.iter()).for_each(|(a, b)| *a += *b),
+        Embedding::F16(emb) => sum
+            .iter_mut()
+            .zip(emb.iter())
+            .for_each(|(a, b)| *a += b.to_f32()),
+        Embedding::BF16(emb) => sum
+            .iter_mut()
+            .zip(emb.iter())
+            .for_each(|(a, b)| *a += b.to_f32()),
     }
 }
 
@@ -233,7 +294,7 @@ impl Shard {
 
         let cluster_number = match self.meta.clustering {
             Clustering::Auto => (vector_count_block.sqrt() * 2).max(1),
-            Clustering::None => 1,
+            Clustering::None | Clustering::Hnsw { .. } => 1,
             Clustering::Fixed(n) => n.min(vector_count_block).max(1),
         };
         let vector_similarity = self.vector_similarity;
@@ -275,7 +336,7 @@ impl Shard {
                         .map(|x| (x / vector_count_block_step as f32) as i8)
                         .collect::<Vec<_>>(),
                 ),
-                Embedding::F32(_) => Embedding::F32(
+                Embedding::F32(_) | Embedding::F16(_) | Embedding::BF16(_) => Embedding::F32(
                     sum_vector
                         .iter()
                         .map(|x| x / vector_count_block_step as f32)
@@ -555,13 +616,15 @@ impl Shard {
                                 .map(|x| (x / centroid.child_count as f32) as i8)
                                 .collect::<Vec<_>>(),
                         ),
-                        Embedding::F32(_) => Embedding::F32(
-                            centroid
-                                .sum_vector
-                                .iter()
-                                .map(|x| x / centroid.child_count as f32)
-                                .collect::<Vec<_>>(),
-                        ),
+                        Embedding::F32(_) | Embedding::F16(_) | Embedding::BF16(_) => {
+                            Embedding::F32(
+                                centroid
+                                    .sum_vector
+                                    .iter()
+                                    .map(|x| x / centroid.child_count as f32)
+                                    .collect::<Vec<_>>(),
+                            )
+                        }
                     };
                     centroid.centroid = sum_vector;
                     centroid.query_simd = QuerySimd::new(&centroid.centroid);
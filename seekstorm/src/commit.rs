@@ -13,6 +13,7 @@ use crate::{
         B, K, decode_positions_multiterm_multifield, decode_positions_multiterm_singlefield,
         get_next_position_multifield, get_next_position_singlefield,
     },
+    ann_calibration::spawn_recalibration,
     compatible::{_blsr_u64, _mm_tzcnt_64},
     compress_postinglist::compress_postinglist,
     index::{
@@ -165,6 +166,13 @@ impl Commit for IndexArc {
             }
         }
 
+        let vector_field_indices = self.read().await.vector_field_indices.clone();
+        for vector_field_index in vector_field_indices.iter() {
+            Box::pin(vector_field_index.index_arc.commit()).await;
+        }
+
+        spawn_recalibration(self).await;
+
         let index_ref = self.read().await;
 
         if !index_ref.mute {
@@ -260,6 +268,7 @@ impl Shard {
             self.last_level_index_file_start_pos = self.index_file.stream_position().unwrap();
             self.last_level_docstore_file_start_pos = self.docstore_file.stream_position().unwrap();
         };
+        self.level_hasher.start_index_level();
 
         if self.committed_doc_count / ROARING_BLOCK_SIZE == 0 {
             write_u16(
@@ -271,6 +280,8 @@ impl Shard {
             let _ = self
                 .index_file
                 .write(&self.compressed_index_segment_block_buffer[0..2]);
+            self.level_hasher
+                .update_index(&self.compressed_index_segment_block_buffer[0..2]);
         }
 
         let document_length_compressed_array_pointer =
@@ -278,6 +289,8 @@ impl Shard {
 
         for document_length_compressed_array in self.document_length_compressed_array.iter_mut() {
             let _ = self.index_file.write(document_length_compressed_array);
+            self.level_hasher
+                .update_index(document_length_compressed_array.as_slice());
         }
 
         if !self.mute {
@@ -309,6 +322,8 @@ impl Shard {
         let _ = self
             .index_file
             .write(&self.compressed_index_segment_block_buffer[0..16]);
+        self.level_hasher
+            .update_index(&self.compressed_index_segment_block_buffer[0..16]);
 
         let segment_head_position = self.index_file.stream_position().unwrap() as usize;
         self.index_file
@@ -361,6 +376,10 @@ impl Shard {
         let _ = self
             .index_file
             .write(&self.compressed_index_segment_block_buffer[segment_head_position3..]);
+        self.level_hasher.set_index_head(
+            segment_head_position as u64,
+            &self.compressed_index_segment_block_buffer[segment_head_position3..],
+        );
 
         let _ = self.index_file.flush();
 
@@ -403,6 +422,8 @@ impl Shard {
             .unwrap();
         }
 
+        self.write_level_checksum(indexed_doc_count);
+
         self.string_set_to_single_term_id();
 
         update_list_max_impact_score(self);
@@ -410,6 +431,8 @@ impl Shard {
         self.committed_doc_count = indexed_doc_count;
         self.is_last_level_incomplete =
             !(self.committed_doc_count).is_multiple_of(ROARING_BLOCK_SIZE);
+        // a commit that completes a level starts the next level, as after open_index
+        self.block_id = self.committed_doc_count >> 16;
 
         if let Some(root_index_arc) = &self.index_option {
             let root_index = root_index_arc.read().await;
@@ -543,6 +566,9 @@ impl Shard {
         let _ = self
             .index_file
             .write(&self.compressed_index_segment_block_buffer[0..compressed_segment_block_size]);
+        self.level_hasher.update_index(
+            &self.compressed_index_segment_block_buffer[0..compressed_segment_block_size],
+        );
 
         if self.meta.access_type == AccessType::Mmap {
             self.segments_index[key0].byte_array_blocks_pointer.push((
@@ -6,10 +6,11 @@ use crate::{
     add_result::{B, K, SIGMA, decode_positions_commit},
     compatible::_lzcnt_u32,
     index::{
-        AccessType, CompressionType, DOCUMENT_LENGTH_COMPRESSION, LexicalSimilarity, NgramType,
-        STOP_BIT, Shard, hash32, hash64, int_to_byte4,
+        AccessType, CompressionType, DOCUMENT_LENGTH_COMPRESSION, FieldType, LexicalSimilarity,
+        NgramType, STOP_BIT, Shard, hash32, hash64, int_to_byte4,
     },
     search::decode_posting_list_count,
+    sparse_vector::SPARSE_WEIGHT_SCALE,
     utils::{
         block_copy, read_u16_ref, read_u32_ref, write_u8_ref, write_u16, write_u16_ref,
         write_u32_ref, write_u64_ref,
@@ -531,6 +532,11 @@ pub(crate) fn docid_iterator(
     {
         let mut posting_score = 0.0;
         for field in field_vec.iter() {
+            if shard.indexed_schema_vec[field.0 as usize].field_type == FieldType::SparseVector {
+                posting_score += field.1 as f32 / SPARSE_WEIGHT_SCALE;
+                continue;
+            }
+
             let document_length_compressed =
                 shard.document_length_compressed_array[field.0 as usize][*doc_id as usize];
 
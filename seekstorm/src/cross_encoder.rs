@@ -0,0 +1,751 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use futures::future::BoxFuture;
+use half::{bf16, f16};
+use ndarray::{Array1, Array2, Axis, s};
+use rayon::prelude::*;
+use safetensors::{Dtype, SafeTensors};
+use serde::Deserialize;
+use tokenizers::{Tokenizer, TruncationParams, TruncationStrategy};
+
+use crate::rerank::Reranker;
+
+struct Linear {
+    /// Transposed weight: input dimensions x output dimensions.
+    weight: Array2<f32>,
+    bias: Array1<f32>,
+}
+
+impl Linear {
+    fn forward(&self, x: &Array2<f32>) -> Array2<f32> {
+        x.dot(&self.weight) + &self.bias
+    }
+}
+
+struct LayerNorm {
+    weight: Array1<f32>,
+    bias: Array1<f32>,
+    eps: f32,
+}
+
+impl LayerNorm {
+    fn forward(&self, x: &mut Array2<f32>) {
+        for mut row in x.rows_mut() {
+            let mean = row.mean().unwrap_or(0.0);
+            let variance =
+                row.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / row.len() as f32;
+            let inv_std = 1.0 / (variance + self.eps).sqrt();
+            for ((v, w), b) in row.iter_mut().zip(self.weight.iter()).zip(self.bias.iter()) {
+                *v = (*v - mean) * inv_std * w + b;
+            }
+        }
+    }
+}
+
+struct EncoderLayer {
+    query: Linear,
+    key: Linear,
+    value: Linear,
+    attention_output: Linear,
+    attention_norm: LayerNorm,
+    intermediate: Linear,
+    output: Linear,
+    output_norm: LayerNorm,
+}
+
+enum ClassificationHead {
+    /// BertForSequenceClassification: pooler (dense + tanh) of the [CLS] token, then the classifier.
+    Bert { pooler: Linear, classifier: Linear },
+    /// RobertaForSequenceClassification: dense + tanh of the <s> token, then the output projection.
+    Roberta { dense: Linear, out_proj: Linear },
+}
+
+#[derive(Deserialize)]
+struct CrossEncoderConfig {
+    #[serde(default)]
+    model_type: String,
+    num_attention_heads: usize,
+    #[serde(default = "layer_norm_eps_default")]
+    layer_norm_eps: f32,
+    #[serde(default = "max_position_embeddings_default")]
+    max_position_embeddings: usize,
+    #[serde(default)]
+    pad_token_id: usize,
+}
+
+fn layer_norm_eps_default() -> f32 {
+    1e-12
+}
+
+fn max_position_embeddings_default() -> usize {
+    512
+}
+
+/// Weights and tokenizer of a loaded cross-encoder, shared by the clones of a CrossEncoder.
+struct CrossEncoderModel {
+    tokenizer: Tokenizer,
+    word_embeddings: Array2<f32>,
+    position_embeddings: Array2<f32>,
+    token_type_embeddings: Array2<f32>,
+    embedding_norm: LayerNorm,
+    layers: Vec<EncoderLayer>,
+    head: ClassificationHead,
+    num_attention_heads: usize,
+    /// RoBERTa position ids start after the padding token id.
+    position_offset: usize,
+}
+
+/// Cross-encoder reranker: BERT or RoBERTa/XLM-RoBERTa sequence classification model (e.g. cross-encoder/ms-marco-MiniLM-L-6-v2, BAAI/bge-reranker-base),
+/// loaded from safetensors and evaluated in-process on the CPU, the (query, document) pairs in parallel on a blocking thread.
+/// Clones share the loaded model.
+#[derive(Clone)]
+pub struct CrossEncoder {
+    model: Arc<CrossEncoderModel>,
+}
+
+fn model_file(model: &str, file: &str) -> Result<PathBuf, String> {
+    let path = Path::new(model);
+    if path.is_dir() {
+        return Ok(path.join(file));
+    }
+    hf_hub::api::sync::Api::new()
+        .and_then(|api| api.model(model.to_string()).get(file))
+        .map_err(|e| format!("Unable to load {} of model {}: {}", file, model, e))
+}
+
+fn tensor(tensors: &SafeTensors, name: &str) -> Result<(Vec<f32>, Vec<usize>), String> {
+    let view = tensors
+        .tensor(name)
+        .map_err(|_| format!("Missing tensor {}", name))?;
+    let data = view.data();
+    let values = match view.dtype() {
+        Dtype::F32 => data
+            .chunks_exact(4)
+            .map(|v| f32::from_le_bytes(v.try_into().unwrap()))
+            .collect(),
+        Dtype::F16 => data
+            .chunks_exact(2)
+            .map(|v| f16::from_le_bytes(v.try_into().unwrap()).to_f32())
+            .collect(),
+        Dtype::BF16 => data
+            .chunks_exact(2)
+            .map(|v| bf16::from_le_bytes(v.try_into().unwrap()).to_f32())
+            .collect(),
+        dtype => return Err(format!("Unsupported dtype {:?} of tensor {}", dtype, name)),
+    };
+    Ok((values, view.shape().to_vec()))
+}
+
+fn matrix(tensors: &SafeTensors, name: &str) -> Result<Array2<f32>, String> {
+    let (values, shape) = tensor(tensors, name)?;
+    if shape.len() != 2 {
+        return Err(format!(
+            "Tensor {} with shape {:?} is not a matrix",
+            name, shape
+        ));
+    }
+    Array2::from_shape_vec((shape[0], shape[1]), values).map_err(|e| e.to_string())
+}
+
+fn vector(tensors: &SafeTensors, name: &str) -> Result<Array1<f32>, String> {
+    Ok(Array1::from_vec(tensor(tensors, name)?.0))
+}
+
+fn linear(tensors: &SafeTensors, name: &str) -> Result<Linear, String> {
+    Ok(Linear {
+        weight: matrix(tensors, &format!("{}.weight", name))?.reversed_axes(),
+        bias: vector(tensors, &format!("{}.bias", name))?,
+    })
+}
+
+fn layer_norm(tensors: &SafeTensors, name: &str, eps: f32) -> Result<LayerNorm, String> {
+    // older checkpoints name the LayerNorm parameters gamma and beta
+    let (weight, bias) = if tensors.tensor(&format!("{}.weight", name)).is_ok() {
+        ("weight", "bias")
+    } else {
+        ("gamma", "beta")
+    };
+    Ok(LayerNorm {
+        weight: vector(tensors, &format!("{}.{}", name, weight))?,
+        bias: vector(tensors, &format!("{}.{}", name, bias))?,
+        eps,
+    })
+}
+
+fn gelu(x: f32) -> f32 {
+    0.5 * x * (1.0 + erf(x / std::f32::consts::SQRT_2))
+}
+
+/// Error function, Abramowitz and Stegun 7.1.26 (maximum error 1.5e-7).
+fn erf(x: f32) -> f32 {
+    let t = 1.0 / (1.0 + 0.327_591_1 * x.abs());
+    let y = 1.0
+        - (((((1.061_405_4 * t - 1.453_152_1) * t) + 1.421_413_7) * t - 0.284_496_74) * t
+            + 0.254_829_6)
+            * t
+            * (-x * x).exp();
+    if x >= 0.0 { y } else { -y }
+}
+
+impl CrossEncoder {
+    /// Loads a cross-encoder from a local model directory or from Hugging Face.
+    /// Blocking file and network I/O: call it once per model, from async code within tokio::task::spawn_blocking, and share the clones.
+    /// * `model`: Hugging Face model id or local path to the model directory with config.json, tokenizer.json and model.safetensors.
+    pub fn load(model: &str) -> Result<CrossEncoder, String> {
+        Ok(CrossEncoder {
+            model: Arc::new(CrossEncoderModel::from_pretrained(model)?),
+        })
+    }
+}
+
+impl CrossEncoderModel {
+    fn from_pretrained(model: &str) -> Result<CrossEncoderModel, String> {
+        let config: CrossEncoderConfig = serde_json::from_slice(
+            &fs::read(model_file(model, "config.json")?).map_err(|e| e.to_string())?,
+        )
+        .map_err(|e| format!("Invalid config.json of model {}: {}", model, e))?;
+        let bytes = fs::read(model_file(model, "model.safetensors")?).map_err(|e| e.to_string())?;
+        let tensors = SafeTensors::deserialize(&bytes).map_err(|e| e.to_string())?;
+
+        // the encoder weights are prefixed with the model type (bert., roberta., ...), if saved with the classification head
+        let prefix = tensors
+            .names()
+            .into_iter()
+            .find_map(|name| name.strip_suffix("embeddings.word_embeddings.weight"))
+            .ok_or(format!("Model {} without word embeddings", model))?
+            .to_string();
+        let is_roberta = config.model_type.contains("roberta");
+        let position_offset = if is_roberta {
+            config.pad_token_id + 1
+        } else {
+            0
+        };
+
+        let mut layers = Vec::new();
+        while tensors
+            .tensor(&format!(
+                "{}encoder.layer.{}.attention.self.query.weight",
+                prefix,
+                layers.len()
+            ))
+            .is_ok()
+        {
+            let layer = format!("{}encoder.layer.{}", prefix, layers.len());
+            layers.push(EncoderLayer {
+                query: linear(&tensors, &format!("{}.attention.self.query", layer))?,
+                key: linear(&tensors, &format!("{}.attention.self.key", layer))?,
+                value: linear(&tensors, &format!("{}.attention.self.value", layer))?,
+                attention_output: linear(&tensors, &format!("{}.attention.output.dense", layer))?,
+                attention_norm: layer_norm(
+                    &tensors,
+                    &format!("{}.attention.output.LayerNorm", layer),
+                    config.layer_norm_eps,
+                )?,
+                intermediate: linear(&tensors, &format!("{}.intermediate.dense", layer))?,
+                output: linear(&tensors, &format!("{}.output.dense", layer))?,
+                output_norm: layer_norm(
+                    &tensors,
+                    &format!("{}.output.LayerNorm", layer),
+                    config.layer_norm_eps,
+                )?,
+            });
+        }
+        if layers.is_empty() {
+            return Err(format!("Model {} without encoder layers", model));
+        }
+
+        let head = if tensors.tensor("classifier.out_proj.weight").is_ok() {
+            ClassificationHead::Roberta {
+                dense: linear(&tensors, "classifier.dense")?,
+                out_proj: linear(&tensors, "classifier.out_proj")?,
+            }
+        } else {
+            ClassificationHead::Bert {
+                pooler: linear(&tensors, &format!("{}pooler.dense", prefix))?,
+                classifier: linear(&tensors, "classifier")?,
+            }
+        };
+
+        let mut tokenizer = Tokenizer::from_file(model_file(model, "tokenizer.json")?)
+            .map_err(|e| format!("Invalid tokenizer.json of model {}: {}", model, e))?;
+        tokenizer
+            .with_truncation(Some(TruncationParams {
+                max_length: config
+                    .max_position_embeddings
+                    .saturating_sub(position_offset)
+                    .min(512),
+                strategy: TruncationStrategy::LongestFirst,
+                ..Default::default()
+            }))
+            .map_err(|e| e.to_string())?;
+        tokenizer.with_padding(None);
+
+        Ok(CrossEncoderModel {
+            tokenizer,
+            word_embeddings: matrix(
+                &tensors,
+                &format!("{}embeddings.word_embeddings.weight", prefix),
+            )?,
+            position_embeddings: matrix(
+                &tensors,
+                &format!("{}embeddings.position_embeddings.weight", prefix),
+            )?,
+            token_type_embeddings: matrix(
+                &tensors,
+                &format!("{}embeddings.token_type_embeddings.weight", prefix),
+            )?,
+            embedding_norm: layer_norm(
+                &tensors,
+                &format!("{}embeddings.LayerNorm", prefix),
+                config.layer_norm_eps,
+            )?,
+            layers,
+            head,
+            num_attention_heads: config.num_attention_heads,
+            position_offset,
+        })
+    }
+
+    /// Relevance logit of a (query, document) pair.
+    fn score_pair(&self, query: &str, document: &str) -> Result<f32, String> {
+        let encoding = self
+            .tokenizer
+            .encode((query, document), true)
+            .map_err(|e| e.to_string())?;
+        let token_count = encoding.get_ids().len();
+        let hidden_size = self.word_embeddings.ncols();
+
+        let mut hidden = Array2::<f32>::zeros((token_count, hidden_size));
+        for (i, (id, type_id)) in encoding
+            .get_ids()
+            .iter()
+            .zip(encoding.get_type_ids())
+            .enumerate()
+        {
+            let type_id = (*type_id as usize).min(self.token_type_embeddings.nrows() - 1);
+            let mut row = hidden.row_mut(i);
+            row += &self.word_embeddings.row(*id as usize);
+            row += &self.position_embeddings.row(i + self.position_offset);
+            row += &self.token_type_embeddings.row(type_id);
+        }
+        self.embedding_norm.forward(&mut hidden);
+
+        let head_size = hidden_size / self.num_attention_heads;
+        let scale = 1.0 / (head_size as f32).sqrt();
+        for layer in self.layers.iter() {
+            let query = layer.query.forward(&hidden);
+            let key = layer.key.forward(&hidden);
+            let value = layer.value.forward(&hidden);
+            let mut context = Array2::<f32>::zeros((token_count, hidden_size));
+            for head in 0..self.num_attention_heads {
+                let range = s![.., head * head_size..(head + 1) * head_size];
+                let mut scores = query.slice(range).dot(&key.slice(range).t()) * scale;
+                for mut row in scores.rows_mut() {
+                    let max = row.fold(f32::MIN, |max, v| max.max(*v));
+                    row.mapv_inplace(|v| (v - max).exp());
+                    let sum = row.sum();
+                    row /= sum;
+                }
+                context
+                    .slice_mut(range)
+                    .assign(&scores.dot(&value.slice(range)));
+            }
+
+            let mut attention = layer.attention_output.forward(&context) + &hidden;
+            layer.attention_norm.forward(&mut attention);
+            let mut intermediate = layer.intermediate.forward(&attention);
+            intermediate.mapv_inplace(gelu);
+            hidden = layer.output.forward(&intermediate) + &attention;
+            layer.output_norm.forward(&mut hidden);
+        }
+
+        let cls = hidden.slice(s![0..1, ..]).to_owned();
+        let logits = match &self.head {
+            ClassificationHead::Bert { pooler, classifier } => {
+                classifier.forward(&pooler.forward(&cls).mapv(f32::tanh))
+            }
+            ClassificationHead::Roberta { dense, out_proj } => {
+                out_proj.forward(&dense.forward(&cls).mapv(f32::tanh))
+            }
+        };
+        // single relevance logit, or the log-odds of the last (relevant) class against the first
+        let logits = logits.index_axis(Axis(0), 0);
+        Ok(if logits.len() > 1 {
+            logits[logits.len() - 1] - logits[0]
+        } else {
+            logits[0]
+        })
+    }
+}
+
+impl Reranker for CrossEncoder {
+    fn score<'a>(
+        &'a self,
+        query: &'a str,
+        documents: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<f32>, String>> {
+        let model = self.model.clone();
+        let query = query.to_string();
+        let documents = documents.to_vec();
+        // the forward passes are CPU bound: they run on the rayon pool, without blocking the async runtime threads
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || {
+                documents
+                    .par_iter()
+                    .map(|document| model.score_pair(&query, document))
+                    .collect()
+            })
+            .await
+            .map_err(|e| e.to_string())?
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random values in [-1.0, 1.0).
+    fn random_values(count: usize, seed: u64) -> Vec<f32> {
+        let mut state = seed;
+        (0..count)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                ((state >> 40) as f32 / (1u64 << 23) as f32) - 1.0
+            })
+            .collect()
+    }
+
+    const HIDDEN: usize = 8;
+    const HEADS: usize = 2;
+    const INTERMEDIATE: usize = 16;
+    const LAYERS: usize = 2;
+    const VOCABULARY: [&str; 12] = [
+        "[PAD]", "[UNK]", "[CLS]", "[SEP]", "rust", "search", "engine", "fast", "index", "vector",
+        "query", "the",
+    ];
+
+    /// Tiny random BERT or RoBERTa sequence classification model: tensors (name, shape, values) in the layout of Hugging Face transformers.
+    fn tiny_model(is_roberta: bool) -> Vec<(String, Vec<usize>, Vec<f32>)> {
+        let prefix = if is_roberta { "roberta." } else { "bert." };
+        let mut shapes: Vec<(String, Vec<usize>)> = vec![
+            (
+                format!("{prefix}embeddings.word_embeddings.weight"),
+                vec![VOCABULARY.len(), HIDDEN],
+            ),
+            (
+                format!("{prefix}embeddings.position_embeddings.weight"),
+                vec![32, HIDDEN],
+            ),
+            (
+                format!("{prefix}embeddings.token_type_embeddings.weight"),
+                vec![2, HIDDEN],
+            ),
+        ];
+        let mut linear = |name: String, inputs: usize, outputs: usize| {
+            shapes.push((format!("{name}.weight"), vec![outputs, inputs]));
+            shapes.push((format!("{name}.bias"), vec![outputs]));
+        };
+        for layer in 0..LAYERS {
+            let layer = format!("{prefix}encoder.layer.{layer}");
+            for name in ["query", "key", "value"] {
+                linear(format!("{layer}.attention.self.{name}"), HIDDEN, HIDDEN);
+            }
+            linear(format!("{layer}.attention.output.dense"), HIDDEN, HIDDEN);
+            linear(format!("{layer}.intermediate.dense"), HIDDEN, INTERMEDIATE);
+            linear(format!("{layer}.output.dense"), INTERMEDIATE, HIDDEN);
+        }
+        if is_roberta {
+            linear("classifier.dense".into(), HIDDEN, HIDDEN);
+            linear("classifier.out_proj".into(), HIDDEN, 2);
+        } else {
+            linear(format!("{prefix}pooler.dense"), HIDDEN, HIDDEN);
+            linear("classifier".into(), HIDDEN, 1);
+        }
+        let mut layer_norms = vec![format!("{prefix}embeddings.LayerNorm")];
+        for layer in 0..LAYERS {
+            layer_norms.push(format!(
+                "{prefix}encoder.layer.{layer}.attention.output.LayerNorm"
+            ));
+            layer_norms.push(format!("{prefix}encoder.layer.{layer}.output.LayerNorm"));
+        }
+        for name in layer_norms {
+            shapes.push((format!("{name}.weight"), vec![HIDDEN]));
+            shapes.push((format!("{name}.bias"), vec![HIDDEN]));
+        }
+
+        shapes
+            .into_iter()
+            .enumerate()
+            .map(|(i, (name, shape))| {
+                let values = random_values(shape.iter().product(), i as u64)
+                    .into_iter()
+                    .map(|value| {
+                        if name.contains("LayerNorm.weight") {
+                            1.0 + value * 0.2
+                        } else {
+                            value * 0.5
+                        }
+                    })
+                    .collect();
+                (name, shape, values)
+            })
+            .collect()
+    }
+
+    /// Writes config.json, tokenizer.json (word level, BERT pair template) and model.safetensors of a tiny model.
+    fn write_model(path: &Path, is_roberta: bool, tensors: &[(String, Vec<usize>, Vec<f32>)]) {
+        fs::create_dir_all(path).unwrap();
+        fs::write(
+            path.join("config.json"),
+            serde_json::json!({
+                "model_type": if is_roberta { "xlm-roberta" } else { "bert" },
+                "num_attention_heads": HEADS,
+                "layer_norm_eps": 1e-5,
+                "max_position_embeddings": 32,
+                "pad_token_id": if is_roberta { 1 } else { 0 },
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let vocabulary: serde_json::Map<String, serde_json::Value> = VOCABULARY
+            .iter()
+            .enumerate()
+            .map(|(id, token)| (token.to_string(), serde_json::json!(id)))
+            .collect();
+        let special_token = |token: &str, type_id: u32| serde_json::json!({"SpecialToken": {"id": token, "type_id": type_id}});
+        let sequence = |id: &str, type_id: u32| serde_json::json!({"Sequence": {"id": id, "type_id": type_id}});
+        fs::write(
+            path.join("tokenizer.json"),
+            serde_json::json!({
+                "version": "1.0",
+                "truncation": null,
+                "padding": null,
+                "added_tokens": [],
+                "normalizer": null,
+                "pre_tokenizer": {"type": "Whitespace"},
+                "post_processor": {
+                    "type": "TemplateProcessing",
+                    "single": [special_token("[CLS]", 0), sequence("A", 0), special_token("[SEP]", 0)],
+                    "pair": [special_token("[CLS]", 0), sequence("A", 0), special_token("[SEP]", 0), sequence("B", 1), special_token("[SEP]", 1)],
+                    "special_tokens": {
+                        "[CLS]": {"id": "[CLS]", "ids": [2], "tokens": ["[CLS]"]},
+                        "[SEP]": {"id": "[SEP]", "ids": [3], "tokens": ["[SEP]"]},
+                    },
+                },
+                "decoder": null,
+                "model": {"type": "WordLevel", "vocab": vocabulary, "unk_token": "[UNK]"},
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let mut header = serde_json::Map::new();
+        let mut data = Vec::new();
+        for (name, shape, values) in tensors.iter() {
+            let start = data.len();
+            for value in values.iter() {
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            header.insert(
+                name.clone(),
+                serde_json::json!({"dtype": "F32", "shape": shape, "data_offsets": [start, data.len()]}),
+            );
+        }
+        let header = serde_json::Value::Object(header).to_string();
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(&data);
+        fs::write(path.join("model.safetensors"), bytes).unwrap();
+    }
+
+    /// Reference implementation of the forward pass of BertForSequenceClassification and XLMRobertaForSequenceClassification,
+    /// in f64 with plain loops, following the Hugging Face transformers implementation.
+    fn reference_logits(
+        tensors: &[(String, Vec<usize>, Vec<f32>)],
+        is_roberta: bool,
+        ids: &[u32],
+        type_ids: &[u32],
+    ) -> Vec<f64> {
+        let prefix = if is_roberta { "roberta." } else { "bert." };
+        let tensor = |name: &str| -> Vec<f64> {
+            tensors
+                .iter()
+                .find(|(tensor_name, _, _)| tensor_name == name)
+                .unwrap()
+                .2
+                .iter()
+                .map(|value| *value as f64)
+                .collect()
+        };
+        // y = x W^T + b, with W of shape [outputs, inputs]
+        let linear = |name: &str, x: &[Vec<f64>]| -> Vec<Vec<f64>> {
+            let weight = tensor(&format!("{name}.weight"));
+            let bias = tensor(&format!("{name}.bias"));
+            let inputs = x[0].len();
+            x.iter()
+                .map(|row| {
+                    (0..bias.len())
+                        .map(|o| {
+                            bias[o]
+                                + (0..inputs)
+                                    .map(|i| row[i] * weight[o * inputs + i])
+                                    .sum::<f64>()
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+        let layer_norm = |name: &str, x: &mut Vec<Vec<f64>>| {
+            let weight = tensor(&format!("{name}.weight"));
+            let bias = tensor(&format!("{name}.bias"));
+            for row in x.iter_mut() {
+                let mean = row.iter().sum::<f64>() / row.len() as f64;
+                let variance =
+                    row.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / row.len() as f64;
+                for (i, v) in row.iter_mut().enumerate() {
+                    *v = (*v - mean) / (variance + 1e-5).sqrt() * weight[i] + bias[i];
+                }
+            }
+        };
+        // erf by its Taylor series, exact in f64 for the small activations of the tiny model
+        let gelu = |x: f64| {
+            let z = x / std::f64::consts::SQRT_2;
+            let mut term = z;
+            let mut erf = z;
+            for n in 1..80 {
+                term *= -z * z / n as f64;
+                erf += term / (2 * n + 1) as f64;
+            }
+            0.5 * x * (1.0 + erf * 2.0 / std::f64::consts::PI.sqrt())
+        };
+
+        let word = tensor(&format!("{prefix}embeddings.word_embeddings.weight"));
+        let position = tensor(&format!("{prefix}embeddings.position_embeddings.weight"));
+        let token_type = tensor(&format!("{prefix}embeddings.token_type_embeddings.weight"));
+        let position_offset = if is_roberta { 2 } else { 0 };
+        let mut hidden: Vec<Vec<f64>> = ids
+            .iter()
+            .zip(type_ids.iter())
+            .enumerate()
+            .map(|(p, (id, type_id))| {
+                (0..HIDDEN)
+                    .map(|h| {
+                        word[*id as usize * HIDDEN + h]
+                            + position[(p + position_offset) * HIDDEN + h]
+                            + token_type[*type_id as usize * HIDDEN + h]
+                    })
+                    .collect()
+            })
+            .collect();
+        layer_norm(&format!("{prefix}embeddings.LayerNorm"), &mut hidden);
+
+        let head_size = HIDDEN / HEADS;
+        for layer in 0..LAYERS {
+            let layer = format!("{prefix}encoder.layer.{layer}");
+            let query = linear(&format!("{layer}.attention.self.query"), &hidden);
+            let key = linear(&format!("{layer}.attention.self.key"), &hidden);
+            let value = linear(&format!("{layer}.attention.self.value"), &hidden);
+            let mut context = vec![vec![0.0; HIDDEN]; ids.len()];
+            for head in 0..HEADS {
+                let range = head * head_size..(head + 1) * head_size;
+                for i in 0..ids.len() {
+                    let scores: Vec<f64> = (0..ids.len())
+                        .map(|j| {
+                            range.clone().map(|h| query[i][h] * key[j][h]).sum::<f64>()
+                                / (head_size as f64).sqrt()
+                        })
+                        .collect();
+                    let sum: f64 = scores.iter().map(|score| score.exp()).sum();
+                    for (j, score) in scores.iter().enumerate() {
+                        for h in range.clone() {
+                            context[i][h] += score.exp() / sum * value[j][h];
+                        }
+                    }
+                }
+            }
+            let mut attention = linear(&format!("{layer}.attention.output.dense"), &context);
+            for (row, residual) in attention.iter_mut().zip(hidden.iter()) {
+                row.iter_mut().zip(residual).for_each(|(v, r)| *v += r);
+            }
+            layer_norm(
+                &format!("{layer}.attention.output.LayerNorm"),
+                &mut attention,
+            );
+            let mut intermediate = linear(&format!("{layer}.intermediate.dense"), &attention);
+            intermediate
+                .iter_mut()
+                .flatten()
+                .for_each(|v| *v = gelu(*v));
+            hidden = linear(&format!("{layer}.output.dense"), &intermediate);
+            for (row, residual) in hidden.iter_mut().zip(attention.iter()) {
+                row.iter_mut().zip(residual).for_each(|(v, r)| *v += r);
+            }
+            layer_norm(&format!("{layer}.output.LayerNorm"), &mut hidden);
+        }
+
+        let cls = vec![hidden[0].clone()];
+        let (dense, output) = if is_roberta {
+            ("classifier.dense".to_string(), "classifier.out_proj")
+        } else {
+            (format!("{prefix}pooler.dense"), "classifier")
+        };
+        let mut pooled = linear(&dense, &cls);
+        pooled.iter_mut().flatten().for_each(|v| *v = v.tanh());
+        linear(output, &pooled).remove(0)
+    }
+
+    #[test]
+    fn cross_encoder_parity() {
+        let pairs = [
+            ("rust search engine", "the fast rust index"),
+            ("vector query", "the vector index of the search engine"),
+            ("fast", "unknown words are mapped to unk"),
+        ];
+        for is_roberta in [false, true] {
+            let path = std::env::temp_dir()
+                .join("seekstorm_test")
+                .join(format!("cross_encoder_{is_roberta}"));
+            let tensors = tiny_model(is_roberta);
+            write_model(&path, is_roberta, &tensors);
+            let cross_encoder = CrossEncoder::load(path.to_str().unwrap()).unwrap();
+
+            let encoding = cross_encoder
+                .model
+                .tokenizer
+                .encode(pairs[0], true)
+                .unwrap();
+            assert_eq!(encoding.get_ids(), &[2, 4, 5, 6, 3, 11, 7, 4, 8, 3]);
+            assert_eq!(encoding.get_type_ids(), &[0, 0, 0, 0, 0, 1, 1, 1, 1, 1]);
+
+            for (query, document) in pairs {
+                let encoding = cross_encoder
+                    .model
+                    .tokenizer
+                    .encode((query, document), true)
+                    .unwrap();
+                let logits = reference_logits(
+                    &tensors,
+                    is_roberta,
+                    encoding.get_ids(),
+                    encoding.get_type_ids(),
+                );
+                // single relevance logit, or the log-odds of the relevant class
+                let expected = if is_roberta {
+                    logits[1] - logits[0]
+                } else {
+                    logits[0]
+                };
+                let score = cross_encoder.model.score_pair(query, document).unwrap();
+                assert!((score as f64 - expected).abs() < 1e-4, "{score} {expected}");
+            }
+        }
+    }
+}
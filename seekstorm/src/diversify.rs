@@ -0,0 +1,447 @@
+use std::collections::HashSet;
+
+use ahash::AHashMap;
+#[cfg(feature = "vb")]
+use ahash::AHashSet;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{index::Index, min_heap::Result};
+#[cfg(feature = "vb")]
+use crate::{
+    index::{Shard, ShardArc},
+    vector::{Embedding, EmbeddingView, Quantization, ResultSource, read_record},
+    vector_merge::vector_doc_id,
+    vector_similarity::{VectorSimilarity, similarity_embedding},
+};
+
+/// Grouping of the search results for the Diversify.max_per_group cap.
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum DiversifyGroup {
+    /// Cluster of the matched vector (shard, level and cluster id of min_heap::Result), e.g. to limit near-duplicates of vector results.
+    /// Results of HNSW indices, of uncommitted documents and lexical results are not grouped.
+    #[default]
+    Cluster,
+    /// Value of a stored field, e.g. the id or url of the source document, if documents are chunks or pages of larger documents.
+    /// Results without a value of the field are not grouped.
+    Field(String),
+}
+
+/// Diversification of the top-ranked search results: reranking with maximal marginal relevance (MMR) and a cap of results per group.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Diversify {
+    /// Number of top-ranked candidates that are diversified (default=100): the search is executed with max(candidates, offset+length) results,
+    /// and offset and length are applied to the diversified results.
+    #[serde(default = "candidates_default")]
+    #[schema(required = false, minimum = 1, default = 100, example = 100)]
+    pub candidates: usize,
+    /// MMR trade-off between relevance (1.0) and diversity (0.0), e.g. 0.7. If None, the candidates are not reranked, only max_per_group is applied.
+    /// The redundancy of a candidate is its maximum similarity to the already selected results, computed from the stored embeddings of their matched chunks.
+    /// Results without stored embedding (lexical results) are not penalized.
+    #[serde(default)]
+    #[schema(required = false, minimum = 0.0, maximum = 1.0, example = 0.7)]
+    pub lambda: Option<f32>,
+    /// Maximum number of results per group, results exceeding the cap are removed. If None, the number of results per group is not capped.
+    #[serde(default)]
+    #[schema(required = false, minimum = 1, example = 2)]
+    pub max_per_group: Option<usize>,
+    /// Grouping of the results for max_per_group (default=Cluster).
+    #[serde(default)]
+    pub group_by: DiversifyGroup,
+}
+
+fn candidates_default() -> usize {
+    100
+}
+
+impl Default for Diversify {
+    fn default() -> Self {
+        Diversify {
+            candidates: candidates_default(),
+            lambda: None,
+            max_per_group: None,
+            group_by: DiversifyGroup::Cluster,
+        }
+    }
+}
+
+/// Stored embedding of the matched chunk of a result, as prepared query with scale, norm, zero_point and sum_q.
+#[cfg(feature = "vb")]
+pub(crate) type StoredEmbedding = (Embedding, f32, f32, i16, i32);
+
+/// Stored vector of a shard: local document id, field id (None matches any field) and chunk id.
+#[cfg(feature = "vb")]
+type VectorKey = (usize, Option<u32>, u32);
+
+/// Vector similarity settings of the index that stores an embedding: embeddings of different indices are not compared.
+#[cfg(feature = "vb")]
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct SimilaritySettings {
+    vector_similarity: VectorSimilarity,
+    quantization: Quantization,
+    non_affine: bool,
+}
+
+#[cfg(feature = "vb")]
+impl Shard {
+    /// Stored embeddings of vectors of the shard, committed or uncommitted, for the requested (local document id, field id, chunk id).
+    /// A field id of None matches any field, e.g. in the single-field index of a vector field.
+    fn stored_embeddings(
+        &self,
+        requested: &[VectorKey],
+        include_uncommitted: bool,
+    ) -> Vec<Option<StoredEmbedding>> {
+        let mut embeddings = vec![None; requested.len()];
+        let mut requested_map: AHashMap<(usize, u32), Vec<usize>> = AHashMap::new();
+        for (i, (doc_id, _field_id, chunk_id)) in requested.iter().enumerate() {
+            requested_map
+                .entry((*doc_id, *chunk_id))
+                .or_default()
+                .push(i);
+        }
+        let requested_levels: AHashSet<usize> = requested
+            .iter()
+            .map(|(doc_id, _, _)| self.vector_section_id(doc_id >> 16))
+            .collect();
+
+        let mut store = |doc_id: usize,
+                         field_id: u32,
+                         chunk_id: u32,
+                         embedding: &dyn Fn() -> StoredEmbedding| {
+            if let Some(indices) = requested_map.get(&(doc_id, chunk_id)) {
+                for i in indices {
+                    if embeddings[*i].is_none()
+                        && requested[*i]
+                            .1
+                            .is_none_or(|requested_field_id| requested_field_id == field_id)
+                    {
+                        embeddings[*i] = Some(embedding());
+                    }
+                }
+            }
+        };
+
+        let (vector_type, vector_size) = self.vector_record_size();
+        for (level_id, (offset, _, vector_count)) in self.vector_levels().into_iter().enumerate() {
+            if !requested_levels.contains(&level_id) {
+                continue;
+            }
+            let merged_vector_level = self.merged_vector_levels.get(&level_id);
+            for i in 0..vector_count {
+                let record = read_record(
+                    &self.vector_file_mmap[offset + i * vector_size..],
+                    self.vector_dimensions,
+                    vector_type,
+                );
+                store(
+                    vector_doc_id(merged_vector_level, level_id, i, record.header.doc_id),
+                    record.header.field_id,
+                    record.header.chunk_id,
+                    &|| {
+                        (
+                            match record.embedding {
+                                EmbeddingView::I8(e) => Embedding::I8(e.to_vec()),
+                                EmbeddingView::F32(e) => Embedding::F32(e.to_vec()),
+                                EmbeddingView::F16(e) => Embedding::F16(e.to_vec()),
+                                EmbeddingView::BF16(e) => Embedding::BF16(e.to_vec()),
+                            },
+                            record.header.scale,
+                            record.header.norm,
+                            record.header.zero_point,
+                            record.header.sum_q,
+                        )
+                    },
+                );
+            }
+        }
+
+        let level_id = self.committed_level_id();
+        if include_uncommitted && requested_levels.contains(&level_id) {
+            for record in self.block_vector_buffer.iter() {
+                store(
+                    (level_id << 16) | record.doc_id as usize,
+                    record.field_id,
+                    record.chunk_id,
+                    &|| {
+                        (
+                            record.embedding.clone(),
+                            record.scale,
+                            record.norm,
+                            record.zero_point,
+                            record.sum_q,
+                        )
+                    },
+                );
+            }
+        }
+
+        embeddings
+    }
+
+    fn similarity_settings(&self) -> SimilaritySettings {
+        SimilaritySettings {
+            vector_similarity: self.vector_similarity,
+            quantization: self.quantization,
+            non_affine: self.max_vector_value == f32::MIN,
+        }
+    }
+}
+
+#[cfg(feature = "vb")]
+fn similarity(a: &StoredEmbedding, b: &StoredEmbedding, settings: SimilaritySettings) -> f32 {
+    let scale_norm = if settings.quantization != Quantization::None
+        && settings.vector_similarity != VectorSimilarity::Cosine
+    {
+        Some((a.1, a.2, a.3, a.4, b.1, b.2, b.3, b.4))
+    } else {
+        None
+    };
+    similarity_embedding(
+        &a.0,
+        &b.0,
+        scale_norm,
+        settings.vector_similarity,
+        settings.quantization,
+        settings.non_affine,
+    )
+}
+
+impl Index {
+    /// Diversifies search results, e.g. to avoid near-duplicate vector results of similar documents:
+    /// reranks the results with maximal marginal relevance (MMR) if Diversify.lambda is set, then removes results exceeding Diversify.max_per_group.
+    /// * `results`: top-ranked results as returned by search (ResultObject.results) with offset=0 and length=Diversify.candidates.
+    ///   Offset and length are applied to the diversified results by the caller.
+    /// * `diversify`: MMR lambda and the cap of results per group.
+    /// * `include_uncommitted`: Use also embeddings and stored fields of documents which have not yet been committed.
+    ///
+    /// MMR selects the result that maximizes lambda * relevance - (1 - lambda) * redundancy, where relevance is the min-max normalized result score,
+    /// and redundancy is the maximum min-max normalized similarity between the stored embedding of its matched chunk and those of the already selected results.
+    pub async fn diversify(
+        &self,
+        results: &mut Vec<Result>,
+        diversify: &Diversify,
+        include_uncommitted: bool,
+    ) {
+        #[cfg(feature = "vb")]
+        if let Some(lambda) = diversify.lambda
+            && results.len() > 1
+        {
+            self.rerank_mmr(results, lambda.clamp(0.0, 1.0), include_uncommitted)
+                .await;
+        }
+
+        if let Some(max_per_group) = diversify.max_per_group {
+            let mut group_counts: AHashMap<String, usize> = AHashMap::new();
+            let mut keep = Vec::with_capacity(results.len());
+            for result in results.iter() {
+                let group = match &diversify.group_by {
+                    #[cfg(feature = "vb")]
+                    DiversifyGroup::Cluster => self.result_cluster(result).await,
+                    #[cfg(not(feature = "vb"))]
+                    DiversifyGroup::Cluster => None,
+                    DiversifyGroup::Field(field) => self
+                        .get_document(
+                            result.doc_id,
+                            include_uncommitted,
+                            &None,
+                            &HashSet::from([field.clone()]),
+                            &[],
+                        )
+                        .await
+                        .ok()
+                        .and_then(|document| document.get(field).map(|value| value.to_string())),
+                };
+                keep.push(match group {
+                    Some(group) => {
+                        let count = group_counts.entry(group).or_insert(0);
+                        *count += 1;
+                        *count <= max_per_group
+                    }
+                    None => true,
+                });
+            }
+            let mut keep = keep.into_iter();
+            results.retain(|_| keep.next().unwrap());
+        }
+    }
+
+    /// Cluster of the matched vector of a committed vector result of a clustered index.
+    #[cfg(feature = "vb")]
+    async fn result_cluster(&self, result: &Result) -> Option<String> {
+        if !matches!(result.source, ResultSource::Vector | ResultSource::Hybrid) {
+            return None;
+        }
+        let (shard, shard_number) = self.vector_result_shard(result).await?;
+        let shard_ref = shard.read().await;
+        let level_id = result.level_id as usize;
+        let doc_id = (result.doc_id / shard_number) & 0xFFFF;
+        if level_id >= shard_ref.vector_levels().len()
+            || shard_ref.hnsw_file.level(level_id).is_some()
+            || (level_id == shard_ref.committed_level_id()
+                && shard_ref
+                    .block_vector_buffer
+                    .iter()
+                    .any(|record| record.doc_id as usize == doc_id))
+        {
+            return None;
+        }
+        Some(format!(
+            "{} {} {} {}",
+            result.field_id, result.shard_id, result.level_id, result.cluster_id
+        ))
+    }
+
+    /// Shard (and shard number of its index) that stores the matched vector of a vector result:
+    /// a shard of the index, or of the index of a vector field with its own inference.
+    #[cfg(feature = "vb")]
+    async fn vector_result_shard(&self, result: &Result) -> Option<(ShardArc, usize)> {
+        match self.vector_field_index_position(result) {
+            Some(position) => {
+                let index_ref = self.vector_field_indices[position].index_arc.read().await;
+                Some((
+                    index_ref.shard_vec.get(result.shard_id as usize)?.clone(),
+                    index_ref.shard_number,
+                ))
+            }
+            None => Some((
+                self.shard_vec.get(result.shard_id as usize)?.clone(),
+                self.shard_number,
+            )),
+        }
+    }
+
+    /// Position of the vector field index that a vector result was found in, None if it was found in the index itself.
+    #[cfg(feature = "vb")]
+    fn vector_field_index_position(&self, result: &Result) -> Option<usize> {
+        self.vector_field_indices
+            .iter()
+            .position(|vector_field_index| {
+                vector_field_index.indexed_field_id == result.field_id as usize
+            })
+    }
+
+    /// Stored embeddings of the matched chunks of vector results, with the similarity settings of the index that stores them,
+    /// and the source of the embedding: 0 for the index itself, position+1 for the index of a vector field with its own inference.
+    #[cfg(feature = "vb")]
+    pub(crate) async fn result_embeddings(
+        &self,
+        results: &[Result],
+        include_uncommitted: bool,
+    ) -> Vec<Option<(StoredEmbedding, SimilaritySettings, usize)>> {
+        let mut embeddings: Vec<Option<(StoredEmbedding, SimilaritySettings, usize)>> =
+            vec![None; results.len()];
+
+        let mut requested_by_shard: AHashMap<(usize, usize), Vec<(usize, VectorKey)>> =
+            AHashMap::new();
+        for (i, result) in results.iter().enumerate() {
+            if !matches!(result.source, ResultSource::Vector | ResultSource::Hybrid) {
+                continue;
+            }
+            let vector_field_index = self.vector_field_index_position(result);
+            let Some((_, shard_number)) = self.vector_result_shard(result).await else {
+                continue;
+            };
+            requested_by_shard
+                .entry((
+                    vector_field_index.map_or(0, |position| position + 1),
+                    result.shard_id as usize,
+                ))
+                .or_default()
+                .push((
+                    i,
+                    (
+                        result.doc_id / shard_number,
+                        vector_field_index.is_none().then_some(result.field_id),
+                        result.chunk_id,
+                    ),
+                ));
+        }
+
+        for ((source, _), requested) in requested_by_shard.iter() {
+            let Some((shard, _)) = self.vector_result_shard(&results[requested[0].0]).await else {
+                continue;
+            };
+            let shard_ref = shard.read().await;
+            let settings = shard_ref.similarity_settings();
+            let shard_embeddings = shard_ref.stored_embeddings(
+                &requested.iter().map(|(_, key)| *key).collect::<Vec<_>>(),
+                include_uncommitted,
+            );
+            for ((i, _), embedding) in requested.iter().zip(shard_embeddings) {
+                embeddings[*i] = embedding.map(|embedding| (embedding, settings, *source));
+            }
+        }
+
+        embeddings
+    }
+
+    /// Reranks the results with maximal marginal relevance (MMR).
+    #[cfg(feature = "vb")]
+    async fn rerank_mmr(&self, results: &mut Vec<Result>, lambda: f32, include_uncommitted: bool) {
+        let embeddings = self.result_embeddings(results, include_uncommitted).await;
+
+        let mut similarities = vec![vec![None; results.len()]; results.len()];
+        let (mut min_similarity, mut max_similarity) = (f32::MAX, f32::MIN);
+        for i in 0..results.len() {
+            for j in i + 1..results.len() {
+                if let (Some((a, settings, source_a)), Some((b, _, source_b))) =
+                    (&embeddings[i], &embeddings[j])
+                    && source_a == source_b
+                {
+                    let similarity = similarity(a, b, *settings);
+                    min_similarity = min_similarity.min(similarity);
+                    max_similarity = max_similarity.max(similarity);
+                    similarities[i][j] = Some(similarity);
+                    similarities[j][i] = Some(similarity);
+                }
+            }
+        }
+        let similarity_range = max_similarity - min_similarity;
+
+        let (min_score, max_score) = results
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), result| {
+                (min.min(result.score), max.max(result.score))
+            });
+        let score_range = max_score - min_score;
+
+        let mut redundancy = vec![0.0f32; results.len()];
+        let mut selected = vec![false; results.len()];
+        let mut order = Vec::with_capacity(results.len());
+        for _ in 0..results.len() {
+            let mut best: Option<(usize, f32)> = None;
+            for i in 0..results.len() {
+                if selected[i] {
+                    continue;
+                }
+                let relevance = if score_range > 0.0 {
+                    (results[i].score - min_score) / score_range
+                } else {
+                    1.0
+                };
+                let mmr = lambda * relevance - (1.0 - lambda) * redundancy[i];
+                if best.is_none_or(|(_, best_mmr)| mmr > best_mmr) {
+                    best = Some((i, mmr));
+                }
+            }
+            let Some((best, _)) = best else {
+                break;
+            };
+            selected[best] = true;
+            order.push(best);
+            for i in 0..results.len() {
+                if let Some(similarity) = similarities[best][i] {
+                    let similarity = if similarity_range > 0.0 {
+                        (similarity - min_similarity) / similarity_range
+                    } else {
+                        1.0
+                    };
+                    redundancy[i] = redundancy[i].max(similarity);
+                }
+            }
+        }
+
+        let mut reranked: Vec<Option<Result>> = results.drain(..).map(Some).collect();
+        results.extend(order.into_iter().filter_map(|i| reranked[i].take()));
+    }
+}
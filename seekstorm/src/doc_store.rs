@@ -5,14 +5,15 @@ use std::fs;
 use std::io::{self, Seek, SeekFrom, Write};
 use std::path::Path;
 
-use crate::geo_search::euclidian_distance;
+use crate::geo_search::distance;
 use crate::highlighter::{Highlighter, top_fragments_from_field};
 use crate::index::{
-    AccessType, DistanceField, Document, DocumentCompression, FILE_PATH, FieldType, Index,
-    ROARING_BLOCK_SIZE, Shard,
+    AccessType, DOCSTORE_FILENAME, DistanceField, Document, DocumentCompression, FILE_PATH,
+    FieldType, Index, ROARING_BLOCK_SIZE, Shard,
 };
 use crate::search::FacetValue;
 use crate::utils::{read_u32, write_u32};
+use crate::verify::map_file;
 
 impl Shard {
     pub(crate) fn get_file_shard(&self, doc_id: usize) -> Result<Vec<u8>, String> {
@@ -181,8 +182,12 @@ impl Shard {
                 && let FacetValue::Point(point) =
                     self.get_facet_value_shard(&distance_field.field, doc_id)
             {
-                let distance =
-                    euclidian_distance(&point, &distance_field.base, &distance_field.unit);
+                let distance = distance(
+                    &point,
+                    &distance_field.base,
+                    &distance_field.unit,
+                    &distance_field.method,
+                );
 
                 doc.insert(distance_field.distance.clone(), json!(distance));
             }
@@ -237,7 +242,15 @@ impl Shard {
             }
         }
 
+        // documents without stored fields get an empty docstore entry (pointer == previous pointer),
+        // otherwise the zero pointer would break the entries of the following documents
         if document.is_empty() {
+            let pointer = self.compressed_docstore_segment_block_buffer.len() as u32;
+            write_u32(
+                pointer,
+                &mut self.compressed_docstore_segment_block_buffer,
+                (doc_id & 0b11111111_11111111) * 4,
+            );
             return;
         }
 
@@ -292,6 +305,21 @@ impl Shard {
             let committed_doc_count = ((self.committed_doc_count - 1) % ROARING_BLOCK_SIZE) + 1;
             let indexed_doc_count = ((indexed_doc_count - 1) % ROARING_BLOCK_SIZE) + 1;
 
+            // after open_index the committed documents of the incomplete last level are hashed once from docstore.bin
+            if !self.level_hasher.is_docstore_level_hashed {
+                let _ = self.docstore_file.flush();
+                if let Some(docstore_mmap) =
+                    map_file(&Path::new(&self.index_path_string).join(DOCSTORE_FILENAME))
+                {
+                    self.level_hasher.hash_docstore_level(
+                        &docstore_mmap,
+                        self.last_level_docstore_file_start_pos as usize,
+                        docstore_file_end as usize,
+                        committed_doc_count,
+                    );
+                }
+            }
+
             for i in committed_doc_count..indexed_doc_count {
                 let pointer = read_u32(&self.compressed_docstore_segment_block_buffer, i * 4);
 
@@ -316,6 +344,11 @@ impl Shard {
             let _ = self
                 .docstore_file
                 .write(&self.compressed_docstore_segment_block_buffer[4 * ROARING_BLOCK_SIZE..]);
+            self.level_hasher.update_docstore(
+                &self.compressed_docstore_segment_block_buffer
+                    [committed_doc_count * 4..indexed_doc_count * 4],
+                &self.compressed_docstore_segment_block_buffer[4 * ROARING_BLOCK_SIZE..],
+            );
 
             if self.meta.access_type == AccessType::Ram {
                 self.level_index[level]
@@ -341,6 +374,15 @@ impl Shard {
             let _ = self
                 .docstore_file
                 .write(&self.compressed_docstore_segment_block_buffer);
+            let level_doc_count = match indexed_doc_count {
+                0 => 0,
+                indexed_doc_count => ((indexed_doc_count - 1) % ROARING_BLOCK_SIZE) + 1,
+            };
+            self.level_hasher.start_docstore_level();
+            self.level_hasher.update_docstore(
+                &self.compressed_docstore_segment_block_buffer[..level_doc_count * 4],
+                &self.compressed_docstore_segment_block_buffer[4 * ROARING_BLOCK_SIZE..],
+            );
 
             if self.meta.access_type == AccessType::Ram {
                 self.level_index[level].docstore_pointer_docs.append(
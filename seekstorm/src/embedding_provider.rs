@@ -0,0 +1,458 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use ahash::AHashMap;
+use futures::future::BoxFuture;
+use http_body_util::{BodyExt, Full};
+use hyper::{
+    Method, Request, StatusCode,
+    body::Bytes,
+    header::{AUTHORIZATION, CONTENT_TYPE, HOST},
+};
+use hyper_util::rt::TokioIo;
+use model2vec_rs::model::StaticModel;
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use tokio::net::TcpStream;
+use url::Url;
+
+use crate::{
+    index::{EMBEDDING_CACHE_FILENAME, Index},
+    vector::Inference,
+};
+
+/// Transforms input text into vector embeddings, at ingest for Text fields with index_vector, and at search time for the query string.
+/// The built-in providers are Model2Vec (Inference::Model2Vec, Inference::Model2VecCustom) and an OpenAI-compatible /v1/embeddings endpoint (Inference::OpenAiCompatible).
+/// A custom provider can be plugged into an index with Index::set_embedding_provider.
+pub trait EmbeddingProvider: Send + Sync {
+    /// Number of dimensions of the returned embeddings.
+    fn dimensions(&self) -> usize;
+
+    /// Embeds a batch of texts, returns one embedding per text, in the order of the texts.
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, String>>;
+}
+
+/// Built-in Model2Vec embedding provider, inference in-process.
+pub(crate) struct Model2VecProvider {
+    pub(crate) model: StaticModel,
+    pub(crate) dimensions: usize,
+}
+
+impl Model2VecProvider {
+    pub(crate) fn new(model_path: &str) -> Result<Model2VecProvider, String> {
+        let model = StaticModel::from_pretrained(model_path, None, None, None)
+            .map_err(|e| format!("Unable to load model {}: {:?}", model_path, e))?;
+        let dimensions = model.encode(&["test".to_string()])[0].len();
+        Ok(Model2VecProvider { model, dimensions })
+    }
+}
+
+impl EmbeddingProvider for Model2VecProvider {
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, String>> {
+        Box::pin(async move { Ok(self.model.encode(texts)) })
+    }
+}
+
+#[derive(Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+    #[serde(default)]
+    index: usize,
+}
+
+/// On-disk embedding cache: append-only records of [sha256(model, text)][u32 dimensions][f32 embedding].
+/// Texts that were embedded before (re-indexed documents, repeated queries) are not sent to the endpoint again.
+struct EmbeddingCache {
+    path: PathBuf,
+    map: AHashMap<[u8; 32], Vec<f32>>,
+    /// set after the first failed write: the cache is no longer written to disk, only kept in memory
+    is_write_failed: bool,
+}
+
+impl EmbeddingCache {
+    fn load(index_path: &Path) -> EmbeddingCache {
+        let path = index_path.join(EMBEDDING_CACHE_FILENAME);
+        let mut map = AHashMap::new();
+        let mut bytes = Vec::new();
+        let mut valid_length = 0;
+        if let Ok(mut file) = File::open(&path)
+            && file.read_to_end(&mut bytes).is_ok()
+        {
+            let mut pos = 0;
+            while pos + 36 <= bytes.len() {
+                let key: [u8; 32] = bytes[pos..pos + 32].try_into().unwrap();
+                let dimensions =
+                    u32::from_le_bytes(bytes[pos + 32..pos + 36].try_into().unwrap()) as usize;
+                pos += 36;
+                if pos + dimensions * 4 > bytes.len() {
+                    break;
+                }
+                let embedding = bytes[pos..pos + dimensions * 4]
+                    .chunks_exact(4)
+                    .map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+                    .collect();
+                pos += dimensions * 4;
+                map.insert(key, embedding);
+                valid_length = pos;
+            }
+        }
+
+        // a truncated last record (interrupted write) is removed, records appended after it would be misaligned
+        if valid_length < bytes.len()
+            && let Err(e) = OpenOptions::new()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_len(valid_length as u64))
+        {
+            println!("Unable to truncate embedding cache {:?}", e)
+        }
+
+        EmbeddingCache {
+            path,
+            map,
+            is_write_failed: false,
+        }
+    }
+
+    /// Adds embeddings to the cache and appends them to the cache file.
+    /// After the first failed write the cache file is no longer written, the embeddings are only cached in memory.
+    fn append(&mut self, entries: Vec<([u8; 32], Vec<f32>)>) {
+        if !self.is_write_failed {
+            let mut bytes = Vec::new();
+            for (key, embedding) in entries.iter() {
+                bytes.extend_from_slice(key);
+                bytes.extend_from_slice(&(embedding.len() as u32).to_le_bytes());
+                for value in embedding.iter() {
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            if let Err(e) = self.write(&bytes) {
+                println!(
+                    "Unable to write embedding cache, caching in memory only {:?}",
+                    e
+                );
+                self.is_write_failed = true;
+            }
+        }
+        self.map.extend(entries);
+    }
+
+    /// Appends the records to the cache file, a partially written record is removed again.
+    fn write(&self, bytes: &[u8]) -> std::io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        let length = file.metadata()?.len();
+        let result = file.write_all(bytes).and_then(|_| file.flush());
+        if result.is_err() {
+            let _ = file.set_len(length);
+        }
+        result
+    }
+}
+
+/// Embedding provider for OpenAI-compatible /v1/embeddings endpoints, e.g. a local llama.cpp server, Ollama, vLLM, or text-embeddings-inference.
+/// Texts are sent in batches, failed requests (connection errors, HTTP 429 and 5xx) are retried with exponential backoff,
+/// and embeddings are cached on disk in the index directory.
+pub struct OpenAiEmbeddingProvider {
+    url: Url,
+    model: String,
+    dimensions: usize,
+    api_key: Option<String>,
+    batch_size: usize,
+    max_retries: usize,
+    cache: Option<Mutex<EmbeddingCache>>,
+}
+
+impl OpenAiEmbeddingProvider {
+    /// Creates an embedding provider for an OpenAI-compatible endpoint.
+    /// * `url`: URL of the embeddings endpoint, e.g. "http://localhost:11434/v1/embeddings". Only plain http is supported: use a local endpoint or a TLS-terminating proxy.
+    /// * `model`: Model name sent with each request.
+    /// * `dimensions`: Number of dimensions of the embeddings returned by the model.
+    /// * `api_key`: Bearer token sent with each request. If None, the environment variable OPENAI_API_KEY is used, if set.
+    /// * `batch_size`: Maximum number of texts per request.
+    /// * `max_retries`: Number of retries of a failed request.
+    /// * `cache_path`: Directory of the on-disk embedding cache, or None to disable the cache.
+    pub fn new(
+        url: &str,
+        model: &str,
+        dimensions: usize,
+        api_key: Option<String>,
+        batch_size: usize,
+        max_retries: usize,
+        cache_path: Option<&Path>,
+    ) -> Result<OpenAiEmbeddingProvider, String> {
+        let url = Url::parse(url).map_err(|e| format!("Invalid embedding url {}: {}", url, e))?;
+        if url.scheme() != "http" {
+            return Err(format!(
+                "Unsupported embedding url scheme {}: only http is supported",
+                url.scheme()
+            ));
+        }
+        if url.host_str().is_none() {
+            return Err(format!("Embedding url {} without host", url));
+        }
+
+        Ok(OpenAiEmbeddingProvider {
+            url,
+            model: model.to_string(),
+            dimensions,
+            api_key: api_key.or_else(|| std::env::var("OPENAI_API_KEY").ok()),
+            batch_size: batch_size.max(1),
+            max_retries,
+            cache: cache_path.map(|cache_path| Mutex::new(EmbeddingCache::load(cache_path))),
+        })
+    }
+
+    fn cache_key(&self, text: &str) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.model.as_bytes());
+        hasher.update([0u8]);
+        hasher.update(text.as_bytes());
+        hasher.finalize().into()
+    }
+
+    async fn post(&self, body: Bytes) -> Result<(StatusCode, Bytes), String> {
+        let host = self.url.host_str().unwrap_or_default();
+        let port = self.url.port_or_known_default().unwrap_or(80);
+        let stream = TcpStream::connect((host, port))
+            .await
+            .map_err(|e| format!("Unable to connect to {}: {}", self.url, e))?;
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .map_err(|e| e.to_string())?;
+        tokio::spawn(async move {
+            let _ = connection.await;
+        });
+
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(&self.url[url::Position::BeforePath..])
+            .header(
+                HOST,
+                &self.url[url::Position::BeforeHost..url::Position::AfterPort],
+            )
+            .header(CONTENT_TYPE, "application/json");
+        if let Some(api_key) = self.api_key.as_ref() {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", api_key));
+        }
+        let request = request.body(Full::new(body)).map_err(|e| e.to_string())?;
+
+        let response = sender
+            .send_request(request)
+            .await
+            .map_err(|e| e.to_string())?;
+        let status = response.status();
+        let bytes = response
+            .into_body()
+            .collect()
+            .await
+            .map_err(|e| e.to_string())?
+            .to_bytes();
+        Ok((status, bytes))
+    }
+
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, String> {
+        let body =
+            Bytes::from(serde_json::to_vec(&json!({"model": self.model, "input": texts})).unwrap());
+
+        let mut attempt = 0;
+        let bytes = loop {
+            let error = match tokio::time::timeout(Duration::from_secs(60), self.post(body.clone()))
+                .await
+            {
+                Ok(Ok((status, bytes))) if status.is_success() => break bytes,
+                Ok(Ok((status, bytes)))
+                    if status != StatusCode::TOO_MANY_REQUESTS && !status.is_server_error() =>
+                {
+                    return Err(format!(
+                        "Embedding request failed with {}: {}",
+                        status,
+                        String::from_utf8_lossy(&bytes)
+                    ));
+                }
+                Ok(Ok((status, _))) => format!("Embedding request failed with {}", status),
+                Ok(Err(e)) => e,
+                Err(_) => "Embedding request timed out".to_string(),
+            };
+            if attempt >= self.max_retries {
+                return Err(format!("{} after {} retries", error, attempt));
+            }
+            tokio::time::sleep(Duration::from_millis(200 << attempt.min(8))).await;
+            attempt += 1;
+        };
+
+        let mut response: EmbeddingResponse = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Invalid embedding response: {}", e))?;
+        if response.data.len() != texts.len() {
+            return Err(format!(
+                "Embedding response with {} embeddings for {} texts",
+                response.data.len(),
+                texts.len()
+            ));
+        }
+        response.data.sort_by_key(|data| data.index);
+
+        response
+            .data
+            .into_iter()
+            .map(|data| {
+                if data.embedding.len() == self.dimensions {
+                    Ok(data.embedding)
+                } else {
+                    Err(format!(
+                        "Embedding with {} dimensions instead of {}",
+                        data.embedding.len(),
+                        self.dimensions
+                    ))
+                }
+            })
+            .collect()
+    }
+}
+
+impl EmbeddingProvider for OpenAiEmbeddingProvider {
+    fn dimensions(&self) -> usize {
+        self.dimensions
+    }
+
+    fn embed<'a>(&'a self, texts: &'a [String]) -> BoxFuture<'a, Result<Vec<Vec<f32>>, String>> {
+        Box::pin(async move {
+            let keys: Vec<[u8; 32]> = if self.cache.is_some() {
+                texts.iter().map(|text| self.cache_key(text)).collect()
+            } else {
+                Vec::new()
+            };
+
+            let mut embeddings: Vec<Option<Vec<f32>>> = match self.cache.as_ref() {
+                Some(cache) => {
+                    let cache = cache.lock().unwrap();
+                    keys.iter().map(|key| cache.map.get(key).cloned()).collect()
+                }
+                None => vec![None; texts.len()],
+            };
+
+            let missing: Vec<usize> = embeddings
+                .iter()
+                .enumerate()
+                .filter(|(_, embedding)| embedding.is_none())
+                .map(|(i, _)| i)
+                .collect();
+
+            let mut new_entries = Vec::new();
+            for batch in missing.chunks(self.batch_size) {
+                let batch_texts: Vec<String> = batch.iter().map(|&i| texts[i].clone()).collect();
+                for (&i, embedding) in batch.iter().zip(self.embed_batch(&batch_texts).await?) {
+                    if self.cache.is_some() {
+                        new_entries.push((keys[i], embedding.clone()));
+                    }
+                    embeddings[i] = Some(embedding);
+                }
+            }
+
+            if !new_entries.is_empty()
+                && let Some(cache) = self.cache.as_ref()
+            {
+                cache.lock().unwrap().append(new_entries);
+            }
+
+            Ok(embeddings.into_iter().map(Option::unwrap).collect())
+        })
+    }
+}
+
+impl Index {
+    /// Replaces the embedding provider of an index with text inference (Inference::Model2Vec, Inference::Model2VecCustom, Inference::OpenAiCompatible),
+    /// e.g. with a custom provider for another embeddings API. The provider is not persisted: set it again after open_index.
+    /// The provider must return embeddings with the dimensions of the index, produced by the same model as the already indexed vectors.
+    pub fn set_embedding_provider(
+        &mut self,
+        embedding_provider: Arc<dyn EmbeddingProvider>,
+    ) -> Result<(), String> {
+        if !matches!(
+            self.meta.inference,
+            Inference::Model2Vec { .. }
+                | Inference::Model2VecCustom { .. }
+                | Inference::OpenAiCompatible { .. }
+        ) {
+            return Err(format!(
+                "Index inference {} does not embed text",
+                self.meta.inference
+            ));
+        }
+        if embedding_provider.dimensions() != self.vector_dimensions_original {
+            return Err(format!(
+                "Embedding provider with {} dimensions instead of {}",
+                embedding_provider.dimensions(),
+                self.vector_dimensions_original
+            ));
+        }
+        self.embedding_provider_option = Some(embedding_provider);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embedding_cache_records() {
+        let cache_path = std::env::temp_dir()
+            .join("seekstorm_test")
+            .join("embedding_cache_records");
+        let _ = std::fs::remove_dir_all(&cache_path);
+        std::fs::create_dir_all(&cache_path).unwrap();
+
+        let mut cache = EmbeddingCache::load(&cache_path);
+        assert!(cache.map.is_empty());
+        cache.append(vec![
+            ([0u8; 32], vec![1.0, 2.0]),
+            ([1u8; 32], vec![3.0, 4.0]),
+        ]);
+        assert!(!cache.is_write_failed);
+        let cache = EmbeddingCache::load(&cache_path);
+        assert_eq!(cache.map[&[1u8; 32]], vec![3.0, 4.0]);
+
+        // a truncated last record is ignored and removed from the cache file
+        let file_path = cache_path.join(EMBEDDING_CACHE_FILENAME);
+        let length = std::fs::metadata(&file_path).unwrap().len();
+        assert_eq!(length, 2 * (32 + 4 + 2 * 4));
+        OpenOptions::new()
+            .write(true)
+            .open(&file_path)
+            .unwrap()
+            .set_len(length - 4)
+            .unwrap();
+        let cache = EmbeddingCache::load(&cache_path);
+        assert_eq!(cache.map.len(), 1);
+        assert_eq!(std::fs::metadata(&file_path).unwrap().len(), length / 2);
+
+        // after a failed write (the cache path is a directory) the cache is kept in memory only
+        let mut cache = EmbeddingCache {
+            path: cache_path.clone(),
+            map: AHashMap::new(),
+            is_write_failed: false,
+        };
+        cache.append(vec![([0u8; 32], vec![1.0, 2.0])]);
+        assert!(cache.is_write_failed);
+        cache.append(vec![([1u8; 32], vec![3.0, 4.0])]);
+        assert_eq!(cache.map.len(), 2);
+    }
+}
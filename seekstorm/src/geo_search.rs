@@ -4,8 +4,8 @@ use std::cmp::Ordering;
 use std::arch::x86_64::{_pdep_u64, _pext_u64};
 
 use crate::{
-    index::DistanceUnit,
-    search::{Point, SortOrder},
+    index::{DistanceMethod, DistanceUnit},
+    search::{GeoGridType, Point, Polygon, SortOrder},
 };
 
 #[inline]
@@ -92,12 +92,22 @@ pub fn morton_ordering(
     morton2: u64,
     base_point: &Point,
     order: &SortOrder,
+    method: &DistanceMethod,
 ) -> Ordering {
     let point1 = decode_morton_2_d(morton1);
     let point2 = decode_morton_2_d(morton2);
 
-    let distance1 = simplified_distance(&point1, base_point);
-    let distance2 = simplified_distance(&point2, base_point);
+    let (distance1, distance2) = if *method == DistanceMethod::Equirectangular {
+        (
+            simplified_distance(&point1, base_point),
+            simplified_distance(&point2, base_point),
+        )
+    } else {
+        (
+            distance(&point1, base_point, &DistanceUnit::Kilometers, method),
+            distance(&point2, base_point, &DistanceUnit::Kilometers, method),
+        )
+    };
 
     if order == &SortOrder::Descending {
         distance1.partial_cmp(&distance2).unwrap_or(Ordering::Equal)
@@ -110,6 +120,12 @@ const EARTH_RADIUS_KM: f64 = 6371.0087714;
 const EARTH_RADIUS_MI: f64 = 3_958.761_315_801_475;
 const DEG2RAD: f64 = 0.017_453_292_519_943_295;
 
+/// WGS-84 ellipsoid: semi-major axis in meters, flattening, semi-minor axis in meters
+const WGS84_A: f64 = 6_378_137.0;
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+const WGS84_B: f64 = WGS84_A * (1.0 - WGS84_F);
+const METERS_PER_MILE: f64 = 1_609.344;
+
 /// calculates distance in kilometers or miles between two 2D-coordinates using Euclidian distance (Pythagoras theorem) with Equirectangular approximation.
 #[inline]
 pub fn euclidian_distance(point1: &Point, point2: &Point, unit: &DistanceUnit) -> f64 {
@@ -123,6 +139,185 @@ pub fn euclidian_distance(point1: &Point, point2: &Point, unit: &DistanceUnit) -
     }) * (x * x + y * y).sqrt()
 }
 
+/// calculates the great-circle distance in kilometers or miles between two 2D-coordinates using the haversine formula (spherical earth model).
+#[inline]
+pub fn haversine_distance(point1: &Point, point2: &Point, unit: &DistanceUnit) -> f64 {
+    let lat1 = DEG2RAD * point1[0];
+    let lat2 = DEG2RAD * point2[0];
+    let sin_dlat = f64::sin((lat2 - lat1) / 2.0);
+    let sin_dlon = f64::sin(DEG2RAD * (point2[1] - point1[1]) / 2.0);
+
+    let a = sin_dlat * sin_dlat + f64::cos(lat1) * f64::cos(lat2) * sin_dlon * sin_dlon;
+
+    (if *unit == DistanceUnit::Kilometers {
+        EARTH_RADIUS_KM
+    } else {
+        EARTH_RADIUS_MI
+    }) * 2.0
+        * f64::asin(a.sqrt().min(1.0))
+}
+
+/// calculates the geodesic distance in kilometers or miles between two 2D-coordinates using the Vincenty inverse formula on the WGS-84 ellipsoid.
+/// Accurate to within millimeters. Falls back to the haversine distance for nearly antipodal points, where the iteration does not converge.
+pub fn vincenty_distance(point1: &Point, point2: &Point, unit: &DistanceUnit) -> f64 {
+    let u1 = f64::atan((1.0 - WGS84_F) * f64::tan(DEG2RAD * point1[0]));
+    let u2 = f64::atan((1.0 - WGS84_F) * f64::tan(DEG2RAD * point2[0]));
+    let l = DEG2RAD * (point2[1] - point1[1]);
+    let (sin_u1, cos_u1) = u1.sin_cos();
+    let (sin_u2, cos_u2) = u2.sin_cos();
+
+    let mut lambda = l;
+    for _ in 0..200 {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        let sin_sigma = ((cos_u2 * sin_lambda) * (cos_u2 * sin_lambda)
+            + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda)
+                * (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda))
+            .sqrt();
+        if sin_sigma == 0.0 {
+            return 0.0;
+        }
+        let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        let sigma = f64::atan2(sin_sigma, cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        let cos_sq_alpha = 1.0 - sin_alpha * sin_alpha;
+        let cos_2sigma_m = if cos_sq_alpha != 0.0 {
+            cos_sigma - 2.0 * sin_u1 * sin_u2 / cos_sq_alpha
+        } else {
+            0.0
+        };
+        let c = WGS84_F / 16.0 * cos_sq_alpha * (4.0 + WGS84_F * (4.0 - 3.0 * cos_sq_alpha));
+        let lambda_prev = lambda;
+        lambda = l
+            + (1.0 - c)
+                * WGS84_F
+                * sin_alpha
+                * (sigma
+                    + c * sin_sigma
+                        * (cos_2sigma_m
+                            + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m * cos_2sigma_m)));
+
+        if (lambda - lambda_prev).abs() < 1e-12 {
+            let u_sq = cos_sq_alpha * (WGS84_A * WGS84_A - WGS84_B * WGS84_B) / (WGS84_B * WGS84_B);
+            let a =
+                1.0 + u_sq / 16384.0 * (4096.0 + u_sq * (-768.0 + u_sq * (320.0 - 175.0 * u_sq)));
+            let b = u_sq / 1024.0 * (256.0 + u_sq * (-128.0 + u_sq * (74.0 - 47.0 * u_sq)));
+            let delta_sigma = b
+                * sin_sigma
+                * (cos_2sigma_m
+                    + b / 4.0
+                        * (cos_sigma * (-1.0 + 2.0 * cos_2sigma_m * cos_2sigma_m)
+                            - b / 6.0
+                                * cos_2sigma_m
+                                * (-3.0 + 4.0 * sin_sigma * sin_sigma)
+                                * (-3.0 + 4.0 * cos_2sigma_m * cos_2sigma_m)));
+            let meters = WGS84_B * a * (sigma - delta_sigma);
+
+            return if *unit == DistanceUnit::Kilometers {
+                meters / 1000.0
+            } else {
+                meters / METERS_PER_MILE
+            };
+        }
+    }
+
+    haversine_distance(point1, point2, unit)
+}
+
+/// calculates the distance in kilometers or miles between two 2D-coordinates with the specified distance method.
+#[inline]
+pub fn distance(
+    point1: &Point,
+    point2: &Point,
+    unit: &DistanceUnit,
+    method: &DistanceMethod,
+) -> f64 {
+    match method {
+        DistanceMethod::Equirectangular => euclidian_distance(point1, point2, unit),
+        DistanceMethod::Haversine => haversine_distance(point1, point2, unit),
+        DistanceMethod::Vincenty => vincenty_distance(point1, point2, unit),
+    }
+}
+
+/// Checks that a point has a latitude within -90..=90 and a longitude within -180..=180.
+pub fn validate_point(point: &Point) -> Result<(), String> {
+    if point.len() != 2 {
+        return Err(format!(
+            "point {:?} needs 2 coordinates (latitude, longitude)",
+            point
+        ));
+    }
+    if !(-90.0..=90.0).contains(&point[0]) || !(-180.0..=180.0).contains(&point[1]) {
+        return Err(format!(
+            "point {:?} latitude must be within -90..=90 and longitude within -180..=180",
+            point
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that a polygon has an outer ring, that all rings have at least 3 points, and that all points are valid.
+pub fn validate_polygon(polygon: &Polygon) -> Result<(), String> {
+    if polygon.is_empty() {
+        return Err("polygon needs an outer ring".to_string());
+    }
+    for ring in polygon.iter() {
+        if ring.len() < 3 {
+            return Err(format!("polygon ring {:?} needs at least 3 points", ring));
+        }
+        for point in ring.iter() {
+            validate_point(point)?;
+        }
+    }
+    Ok(())
+}
+
+/// Checks whether a 2D-coordinate (lat/lon) lies within a bounding box, defined by its south-west (min lat, min lon) and north-east (max lat, max lon) corners.
+/// If the longitude of the south-west corner is greater than that of the north-east corner, the bounding box crosses the antimeridian.
+#[inline]
+pub fn point_in_bounding_box(point: &Point, south_west: &Point, north_east: &Point) -> bool {
+    if point[0] < south_west[0] || point[0] > north_east[0] {
+        return false;
+    }
+
+    if south_west[1] <= north_east[1] {
+        point[1] >= south_west[1] && point[1] <= north_east[1]
+    } else {
+        point[1] >= south_west[1] || point[1] <= north_east[1]
+    }
+}
+
+/// even-odd ray casting test of a 2D-coordinate against a single closed ring of lat/lon vertices
+#[inline]
+fn point_in_ring(point: &Point, ring: &[Point]) -> bool {
+    let mut inside = false;
+    let mut j = ring.len().wrapping_sub(1);
+    for (i, vertex) in ring.iter().enumerate() {
+        let previous = &ring[j];
+        if (vertex[0] > point[0]) != (previous[0] > point[0])
+            && point[1]
+                < (previous[1] - vertex[1]) * (point[0] - vertex[0]) / (previous[0] - vertex[0])
+                    + vertex[1]
+        {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
+
+/// Checks whether a 2D-coordinate (lat/lon) lies within a polygon.
+/// The first ring of the polygon is the outer boundary, all subsequent rings are holes.
+/// Rings are implicitly closed, the first vertex doesn't need to be repeated as last vertex.
+/// Edges are treated as straight lines in the lat/lon plane.
+pub fn point_in_polygon(point: &Point, polygon: &Polygon) -> bool {
+    match polygon.split_first() {
+        Some((outer, holes)) => {
+            point_in_ring(point, outer) && !holes.iter().any(|hole| point_in_ring(point, hole))
+        }
+        None => false,
+    }
+}
+
 /// Converts a Point and a distance radius into a range of morton_codes for geo search range filtering.
 /// The conversion is lossy due to coordinate to Morton code rounding errors and Equirectangular approximation of Euclidian distance.
 pub fn point_distance_to_morton_range(
@@ -142,3 +337,251 @@ pub fn point_distance_to_morton_range(
 
     morton_min..morton_max
 }
+
+const GEOHASH_BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+const GEOTILE_MAX_LATITUDE: f64 = 85.051_128_779_806_59;
+
+/// Computes the geo grid cell of a 2D-coordinate (lat/lon).
+/// Geohash: bit-interleaved longitude/latitude (longitude first) with 5 bits per geohash character, precision = geohash length 1..12.
+/// Geotile: slippy map tile x/y in Web Mercator projection, precision = zoom level 0..29.
+pub fn geo_grid_cell(point: &Point, grid_type: &GeoGridType, precision: u8) -> u64 {
+    match grid_type {
+        GeoGridType::Geohash => {
+            let bits = 5 * precision.clamp(1, 12) as u32;
+            let lon_bits = bits.div_ceil(2);
+            let lat_bits = bits / 2;
+            let lon_cell = (((point[1] + 180.0) / 360.0 * (1u64 << lon_bits) as f64) as u64)
+                .min((1u64 << lon_bits) - 1);
+            let lat_cell = (((point[0] + 90.0) / 180.0 * (1u64 << lat_bits) as f64) as u64)
+                .min((1u64 << lat_bits) - 1);
+
+            let mut cell = 0u64;
+            for i in 0..bits {
+                let bit = if i & 1 == 0 {
+                    (lon_cell >> (lon_bits - 1 - i / 2)) & 1
+                } else {
+                    (lat_cell >> (lat_bits - 1 - i / 2)) & 1
+                };
+                cell = (cell << 1) | bit;
+            }
+            cell
+        }
+        GeoGridType::Geotile => {
+            let zoom = precision.min(29) as u32;
+            let tiles = (1u64 << zoom) as f64;
+            let lat = DEG2RAD * point[0].clamp(-GEOTILE_MAX_LATITUDE, GEOTILE_MAX_LATITUDE);
+            let x = (((point[1] + 180.0) / 360.0 * tiles) as u64).min((1u64 << zoom) - 1);
+            let y = ((1.0 - f64::ln(f64::tan(lat) + 1.0 / f64::cos(lat)) / std::f64::consts::PI)
+                / 2.0
+                * tiles)
+                .max(0.0) as u64;
+            (x << 32) | y.min((1u64 << zoom) - 1)
+        }
+    }
+}
+
+/// Converts a geo grid cell into its key: geohash string for Geohash, "zoom/x/y" for Geotile.
+pub fn geo_grid_cell_key(cell: u64, grid_type: &GeoGridType, precision: u8) -> String {
+    match grid_type {
+        GeoGridType::Geohash => {
+            let length = precision.clamp(1, 12) as u32;
+            (0..length)
+                .map(|i| GEOHASH_BASE32[((cell >> (5 * (length - 1 - i))) & 0x1f) as usize] as char)
+                .collect()
+        }
+        GeoGridType::Geotile => {
+            format!(
+                "{}/{}/{}",
+                precision.min(29),
+                cell >> 32,
+                cell & 0xffff_ffff
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::FacetFilter;
+
+    #[test]
+    fn vincenty_matches_reference_geodesic() {
+        // Flinders Peak to Buninyong, the reference example of Vincenty (1975): 54972.271 m
+        let flinders_peak = vec![-37.951_033_42, 144.424_867_89];
+        let buninyong = vec![-37.652_821_14, 143.926_495_54];
+        let meters =
+            vincenty_distance(&flinders_peak, &buninyong, &DistanceUnit::Kilometers) * 1000.0;
+        assert!((meters - 54_972.271).abs() < 0.01, "{}", meters);
+
+        // one degree of longitude at the equator: 111319.491 m on the WGS-84 ellipsoid
+        let meters =
+            vincenty_distance(&vec![0.0, 0.0], &vec![0.0, 1.0], &DistanceUnit::Kilometers) * 1000.0;
+        assert!((meters - 111_319.491).abs() < 0.01, "{}", meters);
+    }
+
+    #[test]
+    fn haversine_close_to_vincenty() {
+        let paris = vec![48.8566, 2.3522];
+        let new_york = vec![40.7128, -74.0060];
+        let haversine = haversine_distance(&paris, &new_york, &DistanceUnit::Kilometers);
+        let vincenty = vincenty_distance(&paris, &new_york, &DistanceUnit::Kilometers);
+
+        // the spherical earth model deviates up to 0.5% from the ellipsoid
+        assert!((haversine - vincenty).abs() / vincenty < 0.005);
+        assert!((haversine - 5_837.0).abs() < 10.0, "{}", haversine);
+
+        let miles = haversine_distance(&paris, &new_york, &DistanceUnit::Miles);
+        assert!((miles * METERS_PER_MILE / 1000.0 - haversine).abs() < 0.01);
+
+        assert_eq!(
+            distance(
+                &paris,
+                &new_york,
+                &DistanceUnit::Kilometers,
+                &DistanceMethod::Vincenty
+            ),
+            vincenty
+        );
+    }
+
+    #[test]
+    fn vincenty_falls_back_for_antipodal_points() {
+        let distance = vincenty_distance(
+            &vec![0.0, 0.0],
+            &vec![0.5, 179.7],
+            &DistanceUnit::Kilometers,
+        );
+        assert!(distance.is_finite());
+        assert!((distance - 20_000.0).abs() < 100.0, "{}", distance);
+    }
+
+    #[test]
+    fn bounding_box_across_antimeridian() {
+        let south_west = vec![-10.0, 170.0];
+        let north_east = vec![10.0, -170.0];
+
+        assert!(point_in_bounding_box(
+            &vec![0.0, 175.0],
+            &south_west,
+            &north_east
+        ));
+        assert!(point_in_bounding_box(
+            &vec![0.0, -175.0],
+            &south_west,
+            &north_east
+        ));
+        assert!(point_in_bounding_box(
+            &vec![10.0, 180.0],
+            &south_west,
+            &north_east
+        ));
+        assert!(!point_in_bounding_box(
+            &vec![0.0, 0.0],
+            &south_west,
+            &north_east
+        ));
+        assert!(!point_in_bounding_box(
+            &vec![20.0, 175.0],
+            &south_west,
+            &north_east
+        ));
+
+        // without crossing the antimeridian
+        assert!(point_in_bounding_box(
+            &vec![0.0, 0.0],
+            &vec![-10.0, -10.0],
+            &vec![10.0, 10.0]
+        ));
+        assert!(!point_in_bounding_box(
+            &vec![0.0, 175.0],
+            &vec![-10.0, -10.0],
+            &vec![10.0, 10.0]
+        ));
+    }
+
+    #[test]
+    fn polygon_with_hole() {
+        let polygon = vec![
+            vec![
+                vec![0.0, 0.0],
+                vec![0.0, 10.0],
+                vec![10.0, 10.0],
+                vec![10.0, 0.0],
+            ],
+            vec![
+                vec![4.0, 4.0],
+                vec![4.0, 6.0],
+                vec![6.0, 6.0],
+                vec![6.0, 4.0],
+            ],
+        ];
+
+        assert!(point_in_polygon(&vec![2.0, 2.0], &polygon));
+        assert!(point_in_polygon(&vec![5.0, 8.0], &polygon));
+        assert!(!point_in_polygon(&vec![5.0, 5.0], &polygon));
+        assert!(!point_in_polygon(&vec![11.0, 5.0], &polygon));
+        assert!(!point_in_polygon(&vec![5.0, 5.0], &Vec::new()));
+
+        // concave outer ring
+        let polygon = vec![vec![
+            vec![0.0, 0.0],
+            vec![10.0, 0.0],
+            vec![10.0, 10.0],
+            vec![5.0, 5.0],
+            vec![0.0, 10.0],
+        ]];
+        assert!(point_in_polygon(&vec![2.0, 5.0], &polygon));
+        assert!(!point_in_polygon(&vec![5.0, 8.0], &polygon));
+    }
+
+    #[test]
+    fn malformed_geo_filters_are_rejected() {
+        assert!(validate_point(&vec![1.0, 2.0]).is_ok());
+        assert!(validate_point(&vec![1.0]).is_err());
+        assert!(validate_point(&vec![1.0, 2.0, 3.0]).is_err());
+        assert!(validate_point(&vec![91.0, 0.0]).is_err());
+        assert!(validate_point(&vec![0.0, f64::NAN]).is_err());
+
+        let bounding_box = |south_west: Point, north_east: Point| FacetFilter::PointBoundingBox {
+            field: "location".into(),
+            filter: (south_west, north_east),
+        };
+        assert!(
+            bounding_box(vec![-10.0, 170.0], vec![10.0, -170.0])
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            bounding_box(vec![-10.0], vec![10.0, -170.0])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            bounding_box(vec![10.0, 0.0], vec![-10.0, 1.0])
+                .validate()
+                .is_err()
+        );
+
+        let polygon = |polygon: Polygon| FacetFilter::PointPolygon {
+            field: "location".into(),
+            filter: polygon,
+        };
+        assert!(polygon(Vec::new()).validate().is_err());
+        assert!(
+            polygon(vec![vec![vec![0.0, 0.0], vec![1.0, 1.0]]])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            polygon(vec![vec![vec![0.0, 0.0], vec![1.0, 1.0], vec![1.0]]])
+                .validate()
+                .is_err()
+        );
+        assert!(
+            polygon(vec![vec![vec![0.0, 0.0], vec![1.0, 1.0], vec![1.0, 0.0]]])
+                .validate()
+                .is_ok()
+        );
+    }
+}
@@ -0,0 +1,569 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+use crate::{
+    clustering::ParentMedoid,
+    index::Shard,
+    utils::read_u32,
+    vector::Quantization,
+    vector_similarity::{
+        QuerySimd, VectorSimilarity, similarity_embedding, similarity_embedding_simd,
+    },
+};
+
+/// Size of the per level graph header: node count, entry point, max layer, m, adjacency length (all u32)
+pub(crate) const HNSW_HEADER_SIZE: usize = 20;
+/// Upper bound for the number of layers, reached only with a probability of m^-16
+const HNSW_MAX_LAYER: usize = 16;
+
+/// Graph node with its similarity score to the query vector.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Candidate {
+    pub score: f32,
+    pub node: u32,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+/// Visited set for graph traversal, reset in O(1) by incrementing the epoch.
+struct Visited {
+    marks: Vec<u32>,
+    epoch: u32,
+}
+
+impl Visited {
+    fn new(node_count: usize) -> Self {
+        Visited {
+            marks: vec![0; node_count],
+            epoch: 0,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.epoch += 1;
+    }
+
+    #[inline(always)]
+    fn insert(&mut self, node: u32) -> bool {
+        let mark = &mut self.marks[node as usize];
+        if *mark == self.epoch {
+            false
+        } else {
+            *mark = self.epoch;
+            true
+        }
+    }
+}
+
+/// Greedy beam search within a single graph layer (HNSW paper, algorithm 2).
+/// Returns up to ef accepted nodes, ordered by descending similarity score.
+/// Nodes that are not accepted (deleted documents, filtered fields) are traversed to keep the graph navigable, but not returned,
+/// so that the search continues until ef accepted nodes are found or the reachable graph is exhausted.
+fn search_layer(
+    entry_points: &[Candidate],
+    ef: usize,
+    neighbors: &mut impl FnMut(u32, &mut Vec<u32>),
+    score: &mut impl FnMut(u32) -> f32,
+    accept: &mut impl FnMut(u32) -> bool,
+    visited: &mut Visited,
+) -> Vec<Candidate> {
+    visited.clear();
+    let mut candidates: BinaryHeap<Candidate> = BinaryHeap::with_capacity(ef * 2);
+    let mut results: BinaryHeap<Reverse<Candidate>> = BinaryHeap::with_capacity(ef + 1);
+    for entry_point in entry_points.iter() {
+        if visited.insert(entry_point.node) {
+            candidates.push(*entry_point);
+            if accept(entry_point.node) {
+                results.push(Reverse(*entry_point));
+            }
+        }
+    }
+    while results.len() > ef {
+        results.pop();
+    }
+
+    let mut neighbor_buffer = Vec::new();
+    while let Some(candidate) = candidates.pop() {
+        if results.len() >= ef && candidate.score < results.peek().unwrap().0.score {
+            break;
+        }
+
+        neighbors(candidate.node, &mut neighbor_buffer);
+        for &neighbor in neighbor_buffer.iter() {
+            if !visited.insert(neighbor) {
+                continue;
+            }
+
+            let neighbor_score = score(neighbor);
+            if results.len() < ef || neighbor_score > results.peek().unwrap().0.score {
+                let neighbor = Candidate {
+                    score: neighbor_score,
+                    node: neighbor,
+                };
+                candidates.push(neighbor);
+                if accept(neighbor.node) {
+                    results.push(Reverse(neighbor));
+                    if results.len() > ef {
+                        results.pop();
+                    }
+                }
+            }
+        }
+    }
+
+    let mut results: Vec<Candidate> = results.into_iter().map(|result| result.0).collect();
+    results.sort_unstable_by(|a, b| b.cmp(a));
+    results
+}
+
+/// Vectors of a level during graph construction: similarity between two vectors of the level.
+struct LevelVectors<'a> {
+    vectors: &'a [ParentMedoid],
+    vector_similarity: VectorSimilarity,
+    quantization: Quantization,
+    non_affine: bool,
+    enable_scale: bool,
+    is_simd: bool,
+}
+
+impl LevelVectors<'_> {
+    fn query(&self, node: usize) -> Option<QuerySimd> {
+        if self.is_simd {
+            Some(unsafe { QuerySimd::new(&self.vectors[node].embedding) })
+        } else {
+            None
+        }
+    }
+
+    fn similarity(&self, query_simd: &Option<QuerySimd>, query: usize, node: usize) -> f32 {
+        let (a, b) = (&self.vectors[query], &self.vectors[node]);
+        let scale_norm = if self.enable_scale {
+            Some((
+                a.scale,
+                a.norm,
+                a.zero_point,
+                a.sum_q,
+                b.scale,
+                b.norm,
+                b.zero_point,
+                b.sum_q,
+            ))
+        } else {
+            None
+        };
+
+        if let Some(query_simd) = query_simd {
+            unsafe {
+                similarity_embedding_simd(
+                    query_simd,
+                    &b.embedding,
+                    scale_norm,
+                    self.vector_similarity,
+                    self.quantization,
+                    self.non_affine,
+                )
+            }
+        } else {
+            similarity_embedding(
+                &a.embedding,
+                &b.embedding,
+                scale_norm,
+                self.vector_similarity,
+                self.quantization,
+                self.non_affine,
+            )
+        }
+    }
+
+    /// Neighbor selection heuristic (HNSW paper, algorithm 4):
+    /// a candidate is only connected if it is more similar to the base node than to any already selected neighbor,
+    /// which keeps the graph navigable for clustered data.
+    fn select_neighbors(&self, candidates: &[Candidate], max_neighbors: usize) -> Vec<u32> {
+        let mut selected: Vec<u32> = Vec::with_capacity(max_neighbors);
+        for candidate in candidates.iter() {
+            if selected.len() >= max_neighbors {
+                break;
+            }
+
+            let query_simd = self.query(candidate.node as usize);
+            if selected.iter().all(|&neighbor| {
+                self.similarity(&query_simd, candidate.node as usize, neighbor as usize)
+                    < candidate.score
+            }) {
+                selected.push(candidate.node);
+            }
+        }
+        selected
+    }
+}
+
+/// Deterministic random layer of a node: exponentially decaying probability with level multiplier 1/ln(m).
+fn random_layer(node: usize, level_multiplier: f64) -> usize {
+    let mut x = (node as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^= x >> 31;
+    let uniform = ((x >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+    ((-uniform.ln() * level_multiplier) as usize).min(HNSW_MAX_LAYER)
+}
+
+/// Maximum number of neighbors per node: 2*m in the base layer, m in all upper layers.
+#[inline(always)]
+fn layer_capacity(m: usize, layer: usize) -> usize {
+    if layer == 0 { 2 * m } else { m }
+}
+
+impl Shard {
+    /// Builds the HNSW graph of the level that is currently committed from the block_vector_buffer,
+    /// and appends it to the HNSW file, or replaces the graph of an incomplete last level.
+    pub(crate) fn commit_hnsw_graph(&mut self, m: usize, ef_construction: usize) {
+        let graph = self.build_hnsw_graph(m, ef_construction);
+        let level_id = self.committed_level_id();
+        self.hnsw_file.write_level(level_id, &graph);
+    }
+
+    /// Builds the HNSW graph for the vectors in the block_vector_buffer, in the order they are stored in the vector file,
+    /// and returns it serialized in the mmap-able level graph format.
+    pub(crate) fn build_hnsw_graph(&self, m: usize, ef_construction: usize) -> Vec<u8> {
+        let m = m.max(2);
+        let ef_construction = ef_construction.max(m);
+        let node_count = self.block_vector_buffer.len();
+        let level_vectors = LevelVectors {
+            vectors: &self.block_vector_buffer,
+            vector_similarity: self.vector_similarity,
+            quantization: self.quantization,
+            non_affine: self.max_vector_value == f32::MIN,
+            enable_scale: self.quantization != Quantization::None
+                && self.vector_similarity != VectorSimilarity::Cosine,
+            is_simd: self.is_simd,
+        };
+
+        let level_multiplier = 1.0 / (m as f64).ln();
+        let mut layers: Vec<Vec<Vec<u32>>> = Vec::with_capacity(node_count);
+        let mut entry_point = 0u32;
+        let mut max_layer = 0usize;
+        let mut visited = Visited::new(node_count);
+
+        for node in 0..node_count {
+            let node_layer = random_layer(node, level_multiplier);
+            layers.push(vec![Vec::new(); node_layer + 1]);
+            if node == 0 {
+                max_layer = node_layer;
+                continue;
+            }
+
+            let query_simd = level_vectors.query(node);
+            let mut score =
+                |neighbor: u32| level_vectors.similarity(&query_simd, node, neighbor as usize);
+            let mut entry_points = vec![Candidate {
+                score: score(entry_point),
+                node: entry_point,
+            }];
+
+            for layer in (node_layer + 1..=max_layer).rev() {
+                entry_points = search_layer(
+                    &entry_points,
+                    1,
+                    &mut |n, buffer: &mut Vec<u32>| buffer.clone_from(&layers[n as usize][layer]),
+                    &mut score,
+                    &mut |_| true,
+                    &mut visited,
+                );
+            }
+
+            for layer in (0..=node_layer.min(max_layer)).rev() {
+                let found = search_layer(
+                    &entry_points,
+                    ef_construction,
+                    &mut |n, buffer: &mut Vec<u32>| buffer.clone_from(&layers[n as usize][layer]),
+                    &mut score,
+                    &mut |_| true,
+                    &mut visited,
+                );
+
+                let selected = level_vectors.select_neighbors(&found, m);
+                let capacity = layer_capacity(m, layer);
+                for &neighbor in selected.iter() {
+                    let neighbor_list = &mut layers[neighbor as usize][layer];
+                    neighbor_list.push(node as u32);
+                    if neighbor_list.len() > capacity {
+                        let neighbor_simd = level_vectors.query(neighbor as usize);
+                        let mut neighbor_candidates: Vec<Candidate> = neighbor_list
+                            .iter()
+                            .map(|&n| Candidate {
+                                score: level_vectors.similarity(
+                                    &neighbor_simd,
+                                    neighbor as usize,
+                                    n as usize,
+                                ),
+                                node: n,
+                            })
+                            .collect();
+                        neighbor_candidates.sort_unstable_by(|a, b| b.cmp(a));
+                        layers[neighbor as usize][layer] =
+                            level_vectors.select_neighbors(&neighbor_candidates, capacity);
+                    }
+                }
+                layers[node][layer] = selected;
+                entry_points = found;
+            }
+
+            if node_layer > max_layer {
+                max_layer = node_layer;
+                entry_point = node as u32;
+            }
+        }
+
+        serialize_graph(&layers, entry_point, max_layer, m)
+    }
+}
+
+/// Level graph format (all values u32 little-endian):
+/// header: node count, entry point, max layer, m, adjacency length
+/// node layers: top layer per node
+/// node offsets: start of the node adjacency lists within the adjacency array
+/// adjacency: per node and layer a neighbor count followed by a fixed number of neighbor slots (2*m in layer 0, m in the upper layers)
+fn serialize_graph(
+    layers: &[Vec<Vec<u32>>],
+    entry_point: u32,
+    max_layer: usize,
+    m: usize,
+) -> Vec<u8> {
+    let node_count = layers.len();
+    let mut node_offsets = Vec::with_capacity(node_count);
+    let mut adjacency_len = 0;
+    for node_layers in layers.iter() {
+        node_offsets.push(adjacency_len as u32);
+        adjacency_len += (0..node_layers.len())
+            .map(|layer| 1 + layer_capacity(m, layer))
+            .sum::<usize>();
+    }
+
+    let mut bytes = Vec::with_capacity(HNSW_HEADER_SIZE + 8 * node_count + 4 * adjacency_len);
+    for value in [
+        node_count as u32,
+        entry_point,
+        max_layer as u32,
+        m as u32,
+        adjacency_len as u32,
+    ] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    for node_layers in layers.iter() {
+        bytes.extend_from_slice(&((node_layers.len() - 1) as u32).to_le_bytes());
+    }
+    for node_offset in node_offsets.iter() {
+        bytes.extend_from_slice(&node_offset.to_le_bytes());
+    }
+    for node_layers in layers.iter() {
+        for (layer, neighbors) in node_layers.iter().enumerate() {
+            bytes.extend_from_slice(&(neighbors.len() as u32).to_le_bytes());
+            for slot in 0..layer_capacity(m, layer) {
+                let neighbor = neighbors.get(slot).copied().unwrap_or_default();
+                bytes.extend_from_slice(&neighbor.to_le_bytes());
+            }
+        }
+    }
+    bytes
+}
+
+/// Read-only view of a serialized level graph, directly on the memory mapped HNSW file.
+pub(crate) struct HnswGraph<'a> {
+    bytes: &'a [u8],
+    pub node_count: usize,
+    entry_point: u32,
+    max_layer: usize,
+    m: usize,
+    adjacency_len: usize,
+}
+
+impl<'a> HnswGraph<'a> {
+    /// bytes: memory mapped HNSW file, starting at the level graph offset.
+    /// Returns an error if the bytes are shorter than the graph size in the header, e.g. for a truncated HNSW file, or the header is invalid.
+    pub(crate) fn new(bytes: &'a [u8]) -> Result<Self, String> {
+        if bytes.len() < HNSW_HEADER_SIZE {
+            return Err(format!(
+                "hnsw graph header truncated: {} of {} bytes",
+                bytes.len(),
+                HNSW_HEADER_SIZE
+            ));
+        }
+
+        let graph = HnswGraph {
+            bytes,
+            node_count: read_u32(bytes, 0) as usize,
+            entry_point: read_u32(bytes, 4),
+            max_layer: read_u32(bytes, 8) as usize,
+            m: read_u32(bytes, 12) as usize,
+            adjacency_len: read_u32(bytes, 16) as usize,
+        };
+
+        if graph.size() > bytes.len() {
+            return Err(format!(
+                "hnsw graph truncated: {} of {} bytes",
+                bytes.len(),
+                graph.size()
+            ));
+        }
+        if graph.max_layer > HNSW_MAX_LAYER
+            || (graph.node_count > 0 && graph.entry_point as usize >= graph.node_count)
+        {
+            return Err(format!(
+                "invalid hnsw graph header: entry point {} max layer {} node count {}",
+                graph.entry_point, graph.max_layer, graph.node_count
+            ));
+        }
+        Ok(graph)
+    }
+
+    /// size of the serialized level graph in bytes
+    pub(crate) fn size(&self) -> usize {
+        graph_size(self.node_count, self.adjacency_len)
+    }
+
+    /// Neighbors of a node within a layer. Neighbor lists outside of the adjacency array and neighbors outside of the graph are skipped.
+    fn neighbors(&self, node: u32, layer: usize, buffer: &mut Vec<u32>) {
+        buffer.clear();
+        let node = node as usize;
+        let node_layer = read_u32(self.bytes, HNSW_HEADER_SIZE + 4 * node) as usize;
+        if layer > node_layer {
+            return;
+        }
+
+        let node_offset =
+            read_u32(self.bytes, HNSW_HEADER_SIZE + 4 * (self.node_count + node)) as usize;
+        let layer_offset = if layer == 0 {
+            0
+        } else {
+            1 + layer_capacity(self.m, 0) + (layer - 1) * (1 + self.m)
+        };
+        let slot_count = 1 + layer_capacity(self.m, layer);
+        if node_offset + layer_offset + slot_count > self.adjacency_len {
+            return;
+        }
+
+        let mut pos = HNSW_HEADER_SIZE + 8 * self.node_count + 4 * (node_offset + layer_offset);
+        let count = (read_u32(self.bytes, pos) as usize).min(slot_count - 1);
+        for _ in 0..count {
+            pos += 4;
+            let neighbor = read_u32(self.bytes, pos);
+            if (neighbor as usize) < self.node_count {
+                buffer.push(neighbor);
+            }
+        }
+    }
+
+    /// Searches the graph for the ef accepted nodes most similar to the query:
+    /// greedy descent through the upper layers, then beam search with width ef in the base layer.
+    /// accept: filter of the base layer search, e.g. for deleted documents and field filters.
+    /// Returns the found nodes ordered by descending similarity, and the number of evaluated nodes.
+    pub(crate) fn search(
+        &self,
+        ef: usize,
+        score: &mut impl FnMut(u32) -> f32,
+        accept: &mut impl FnMut(u32) -> bool,
+    ) -> (Vec<Candidate>, usize) {
+        if self.node_count == 0 {
+            return (Vec::new(), 0);
+        }
+
+        let mut evaluated_count = 0;
+        let mut counted_score = |node: u32| {
+            evaluated_count += 1;
+            score(node)
+        };
+        let mut visited = Visited::new(self.node_count);
+        let mut entry_points = vec![Candidate {
+            score: counted_score(self.entry_point),
+            node: self.entry_point,
+        }];
+
+        for layer in (1..=self.max_layer).rev() {
+            entry_points = search_layer(
+                &entry_points,
+                1,
+                &mut |node, buffer| self.neighbors(node, layer, buffer),
+                &mut counted_score,
+                &mut |_| true,
+                &mut visited,
+            );
+        }
+
+        let results = search_layer(
+            &entry_points,
+            ef.max(1),
+            &mut |node, buffer| self.neighbors(node, 0, buffer),
+            &mut counted_score,
+            accept,
+            &mut visited,
+        );
+        (results, evaluated_count)
+    }
+}
+
+/// size of a serialized level graph in bytes
+fn graph_size(node_count: usize, adjacency_len: usize) -> usize {
+    HNSW_HEADER_SIZE + 8 * node_count + 4 * adjacency_len
+}
+
+/// Offsets of the level graphs within the memory mapped HNSW file.
+pub(crate) fn hnsw_level_offsets(bytes: &[u8]) -> Vec<usize> {
+    let mut level_offsets = Vec::new();
+    let mut offset = 0;
+    while offset + HNSW_HEADER_SIZE <= bytes.len() {
+        level_offsets.push(offset);
+        offset += hnsw_level_size(&bytes[offset..]);
+    }
+    level_offsets
+}
+
+/// Size of the level graph at the start of bytes, from its header. The bytes must contain at least the header.
+pub(crate) fn hnsw_level_size(bytes: &[u8]) -> usize {
+    graph_size(read_u32(bytes, 0) as usize, read_u32(bytes, 16) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncated_graph_is_rejected() {
+        // 3 nodes, fully connected in layer 0, node 2 also in layer 1
+        let layers = vec![vec![vec![1, 2]], vec![vec![0, 2]], vec![vec![0, 1], vec![]]];
+        let bytes = serialize_graph(&layers, 2, 1, 2);
+        let graph = HnswGraph::new(&bytes).unwrap();
+        assert_eq!(graph.size(), bytes.len());
+        assert_eq!(hnsw_level_size(&bytes), bytes.len());
+
+        let (results, _) = graph.search(3, &mut |node| -(node as f32), &mut |node| node != 0);
+        let nodes: Vec<u32> = results.iter().map(|candidate| candidate.node).collect();
+        assert_eq!(nodes, vec![1, 2]);
+
+        assert!(HnswGraph::new(&bytes[..HNSW_HEADER_SIZE - 1]).is_err());
+        assert!(HnswGraph::new(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut invalid_entry_point = bytes.clone();
+        invalid_entry_point[4..8].copy_from_slice(&3u32.to_le_bytes());
+        assert!(HnswGraph::new(&invalid_entry_point).is_err());
+    }
+}
@@ -4,7 +4,6 @@ use futures::future;
 use indexmap::IndexMap;
 use itertools::Itertools;
 use memmap2::{Mmap, MmapMut, MmapOptions};
-use model2vec_rs::model::StaticModel;
 use num::FromPrimitive;
 use num_derive::FromPrimitive;
 
@@ -18,9 +17,9 @@ use std::{
     collections::HashMap,
     fmt::{self},
     fs::{self, File},
-    io::{BufRead, BufReader, Read, Seek, Write},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom, Write},
     path::Path,
-    sync::{Arc, LazyLock},
+    sync::{Arc, LazyLock, atomic::AtomicBool},
     thread::available_parallelism,
     time::Instant,
 };
@@ -34,21 +33,37 @@ use crate::word_segmentation::WordSegmentationTM;
 use crate::{
     INDEX_RUNTIME,
     add_result::{self, B, K, SIGMA},
+    ann_calibration::AnnCalibration,
+    binary_quantization::binary_level_offsets,
     clustering::{ClusterHeader, ParentMedoid},
     commit::Commit,
+    diversify::Diversify,
+    embedding_provider::{EmbeddingProvider, Model2VecProvider, OpenAiEmbeddingProvider},
     geo_search::encode_morton_2_d,
     highlighter::Highlight,
+    hnsw::hnsw_level_offsets,
+    level_file::LevelFile,
+    product_quantization::pq_level_offsets,
+    query_expansion::QueryExpansion,
+    rerank::Rerank,
     search::{
-        self, FacetFilter, Point, QueryFacet, QueryRewriting, Ranges, ResultObject, ResultSort,
-        ResultType, SearchLexicalShard, SearchMode,
+        self, FacetFilter, FacetValueSort, GeoGridCell, Point, QueryFacet, QueryRewriting, Ranges,
+        ResultObject, ResultSort, ResultType, SearchLexicalShard, SearchMode,
     },
+    significant_terms::{SignificanceHeuristic, SignificantTerm},
+    sparse_vector::{SPARSE_WEIGHT_SCALE, tokenize_sparse_vector},
     tokenizer::tokenizer,
     utils::{
         self, read_u8_ref, read_u16, read_u16_ref, read_u32_ref, read_u64, read_u64_ref, write_f32,
         write_f64, write_i8, write_i16, write_i32, write_i64, write_u32, write_u64,
     },
-    vector::{Inference, Model, Precision, Quantization, VectorHeader, read_min_max},
-    vector_similarity::{TurboQuant, VectorSimilarity},
+    vector::{
+        ChunkAggregation, Inference, Model, Precision, Quantization, VectorHeader, read_min_max,
+    },
+    vector_field::{VectorFieldIndex, create_vector_field_indices},
+    vector_merge::{MergedVectorLevel, load_merged_vector_levels},
+    vector_similarity::{AnnMode, TurboQuant, VectorSimilarity},
+    verify::LevelHasher,
 };
 
 #[cfg(any(
@@ -99,12 +114,21 @@ pub(crate) const COMPLETIONS_FILENAME: &str = "completions.csv";
 pub(crate) const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub(crate) const VECTOR_FILENAME: &str = "vector.bin";
-
-const INDEX_HEADER_SIZE: u64 = 4;
+pub(crate) const HNSW_FILENAME: &str = "hnsw.bin";
+pub(crate) const PQ_FILENAME: &str = "pq.bin";
+pub(crate) const BINARY_FILENAME: &str = "binary.bin";
+pub(crate) const MERGED_VECTOR_LEVELS_FILENAME: &str = "merged_vector_levels.bin";
+pub(crate) const ANN_CALIBRATION_FILENAME: &str = "ann_calibration.json";
+pub(crate) const EMBEDDING_CACHE_FILENAME: &str = "embedding_cache.bin";
+pub(crate) const CHECKSUM_FILENAME: &str = "checksum.bin";
+
+pub(crate) const INDEX_HEADER_SIZE: u64 = 4;
 /// Incompatible index  format change: new library can't open old format, and old library can't open new format
 pub const INDEX_FORMAT_VERSION_MAJOR: u16 = 6;
 /// Backward compatible format change: new library can open old format, but old library can't open new format
-pub const INDEX_FORMAT_VERSION_MINOR: u16 = 1;
+/// - 6.1: Snappy document compression (6.0 indices use Zstd)
+/// - 6.2: hnsw.bin and Clustering::Hnsw, pq.bin and Quantization::ProductQuantization, binary.bin and Quantization::Binary, Precision::F16 and Precision::BF16 vectors, merged_vector_levels.bin, checksum.bin
+pub const INDEX_FORMAT_VERSION_MINOR: u16 = 2;
 
 /// Maximum processed positions per term per document: default=65_536. E.g. 65,536 * 'the' per document, exceeding positions are ignored for search.
 pub const MAX_POSITIONS_PER_TERM: usize = 65_536;
@@ -189,8 +213,8 @@ pub struct SearchRequestObject {
     /// A special _score field (BM25x), reflecting how relevant the result is for a given search query (phrase match, match in title etc.) can be combined with any of the other sort fields as primary, secondary or n-th search criterium.
     /// Sort is only enabled on facet fields that are defined in schema at create_index!
     /// Examples:
-    /// - result_sort = vec![ResultSort {field: "price".into(), order: SortOrder::Descending, base: FacetValue::None},ResultSort {field: "language".into(), order: SortOrder::Ascending, base: FacetValue::None}];
-    /// - result_sort = vec![ResultSort {field: "location".into(),order: SortOrder::Ascending, base: FacetValue::Point(vec![38.8951, -77.0364])}];
+    /// - result_sort = vec![ResultSort {field: "price".into(), order: SortOrder::Descending, base: FacetValue::None, distance_method: DistanceMethod::Equirectangular},ResultSort {field: "language".into(), order: SortOrder::Ascending, base: FacetValue::None, distance_method: DistanceMethod::Equirectangular}];
+    /// - result_sort = vec![ResultSort {field: "location".into(),order: SortOrder::Ascending, base: FacetValue::Point(vec![38.8951, -77.0364]), distance_method: DistanceMethod::Haversine}];
     #[schema(required = false, example = json!([{"field": "date", "order": "Ascending", "base": "None" }]))]
     #[serde(default)]
     pub result_sort: Vec<ResultSort>,
@@ -206,6 +230,20 @@ pub struct SearchRequestObject {
     #[schema(required = false, example = SearchMode::Lexical)]
     #[serde(default = "search_mode_api")]
     pub search_mode: SearchMode,
+    /// Diversify the top-ranked results with maximal marginal relevance (MMR) and/or cap the number of results per cluster or field value: (default=None).
+    #[serde(default)]
+    pub diversify: Option<Diversify>,
+    /// Rerank the top-ranked results with a cross-encoder, after the fusion of lexical and vector results and before diversification: (default=None).
+    #[serde(default)]
+    pub rerank: Option<Rerank>,
+    /// Expand the query with pseudo-relevance feedback from the top-ranked results of an initial retrieval (RM3 terms and/or Rocchio vector), and re-run it: (default=None).
+    #[serde(default)]
+    pub query_expansion: Option<QueryExpansion>,
+    /// Routing hint: value of the routing field (SchemaField.routing), e.g. a tenant_id. Only the shard of the routing value is searched instead of all shards (default=None),
+    /// also by the initial retrieval and the re-run of query_expansion.
+    /// Add a facet filter for the routing field to return only the documents of the routing value.
+    #[serde(default)]
+    pub routing: Option<String>,
 }
 
 fn search_mode_api() -> SearchMode {
@@ -249,10 +287,111 @@ pub struct SearchResultObject {
     #[schema(value_type=HashMap<String, Vec<(String, usize)>>)]
     /// Facets with their values and corresponding document counts
     pub facets: AHashMap<String, Facet>,
+    #[schema(value_type=HashMap<String, Vec<GeoGridCell>>)]
+    /// Geo grid facets with their grid cells, corresponding document counts and centroids
+    #[serde(default)]
+    pub geo_grids: AHashMap<String, Vec<GeoGridCell>>,
+    #[schema(value_type=HashMap<String, Vec<SignificantTerm>>)]
+    /// Significant terms facets with their significant terms, significance scores and document counts
+    #[serde(default)]
+    pub significant_terms: AHashMap<String, Vec<SignificantTerm>>,
     /// Suggestions for query correction or completion
     pub suggestions: Vec<String>,
 }
 
+/// Batch vector search request object
+#[derive(Deserialize, Serialize, Clone, ToSchema, Debug)]
+pub struct SearchBatchRequestObject {
+    /// Query vectors: JSON arrays of numbers, or base64 encoded byte strings in big endian (network order), as SearchRequestObject.query_vector.
+    #[schema(value_type=Vec<serde_json::Value>)]
+    pub query_vectors: Vec<Value>,
+    /// Number of search results to return per query vector.
+    #[serde(default = "length_api")]
+    #[schema(required = false, minimum = 1, default = 10, example = 10)]
+    pub length: usize,
+    /// Minimum similarity score of the results, see SearchMode::Vector.
+    #[serde(default)]
+    pub similarity_threshold: Option<f32>,
+    /// Specifies the clusters to search: (default=All).
+    #[serde(default)]
+    pub ann_mode: AnnMode,
+    /// Aggregation of the chunk scores of a document: (default=Max).
+    #[serde(default)]
+    pub chunk_aggregation: ChunkAggregation,
+    /// True realtime search: include indexed, but uncommitted documents into search results.
+    #[serde(default)]
+    pub realtime: bool,
+    /// Vector fields to search, if empty all vector fields are searched.
+    #[serde(default)]
+    pub field_filter: Vec<String>,
+}
+
+/// Search result of a batch vector search: document id and score.
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct SearchBatchResult {
+    /// Document id
+    #[serde(rename = "_id")]
+    pub doc_id: usize,
+    /// Similarity score
+    #[serde(rename = "_score")]
+    pub score: f32,
+}
+
+/// Batch vector search result object
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct SearchBatchResultObject {
+    /// Time taken to execute the batch search in nanoseconds
+    pub time: u128,
+    /// Search results per query vector, in the order of the query vectors. Query vectors that could not be decoded return no results.
+    pub results: Vec<Vec<SearchBatchResult>>,
+}
+
+/// Radius vector search request object
+#[derive(Deserialize, Serialize, Clone, ToSchema, Debug)]
+pub struct SearchRadiusRequestObject {
+    /// Query vector: JSON array of numbers, or base64 encoded byte string in big endian (network order), as SearchRequestObject.query_vector.
+    #[schema(value_type=serde_json::Value)]
+    pub query_vector: Value,
+    /// Maximum Euclidean distance (VectorSimilarity::Euclidean), or minimum similarity between 0.0 and 1.0 (VectorSimilarity::Cosine and VectorSimilarity::Dot) of the results.
+    pub radius: f32,
+    /// Specifies the clusters to search: (default=All). All counts exactly, other modes count only the searched clusters.
+    #[serde(default)]
+    pub ann_mode: AnnMode,
+    /// Offset of the returned results, to page through the documents within the radius.
+    #[serde(default)]
+    #[schema(required = false, minimum = 0, default = 0, example = 0)]
+    pub offset: usize,
+    /// Number of results to return.
+    #[serde(default = "length_api")]
+    #[schema(required = false, minimum = 1, default = 10, example = 10)]
+    pub length: usize,
+    /// True realtime search: include indexed, but uncommitted documents into search results.
+    #[serde(default)]
+    pub realtime: bool,
+    /// Vector fields to search, if empty all vector fields are searched.
+    #[serde(default)]
+    pub field_filter: Vec<String>,
+}
+
+/// Radius vector search result object
+#[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
+pub struct SearchRadiusResultObject {
+    /// Time taken to execute the radius search in nanoseconds
+    pub time: u128,
+    /// Offset of the returned search results
+    pub offset: usize,
+    /// Number of requested search results
+    pub length: usize,
+    /// Number of returned search results
+    pub count: usize,
+    /// Number of documents within the radius found by the search: exact for exhaustive search, a lower bound for ANN search
+    pub count_total: usize,
+    /// True if count_total is a lower bound (ANN search counts only the searched clusters), false if counted exactly by exhaustive search
+    pub count_lower_bound: bool,
+    /// Document ids and scores of the documents within the radius, sorted by similarity
+    pub results: Vec<SearchBatchResult>,
+}
+
 /// Quota per API key
 #[derive(Default, Debug, Clone, Deserialize, Serialize, ToSchema)]
 pub struct ApikeyQuotaObject {
@@ -898,6 +1037,7 @@ pub(crate) struct PostingListObjectQuery<'a> {
     pub tf_ngram2: u32,
     pub tf_ngram3: u32,
     pub ngram_type: NgramType,
+    pub is_sparse: bool,
 
     pub end_flag: bool,
     pub end_flag_block: bool,
@@ -948,6 +1088,7 @@ impl Default for PostingListObjectQuery<'_> {
             idf_ngram2: 0.0,
             idf_ngram3: 0.0,
             ngram_type: NgramType::SingleTerm,
+            is_sparse: false,
             is_embedded: false,
             embedded_positions: [0; 4],
             field_vec: SmallVec::new(),
@@ -1072,6 +1213,15 @@ pub enum FieldType {
     /// document.body.appendChild(image);
     ///```
     Binary,
+    /// SparseVector is a learned sparse vector, e.g. the output of SPLADE or BM42: a JSON object with terms as keys and weights as values, e.g. {"search": 1.2, "engine": 0.8}.
+    /// Its terms are indexed into posting lists of the inverted index (index_lexical is always set), with the weights quantized to a resolution of 1/16 as term frequencies,
+    /// searched with SearchMode::Sparse or as third source in SearchMode::Hybrid, scored by the dot product of query and document weights.
+    /// The terms of a sparse vector field are separate from the terms of text fields and are not matched by lexical search.
+    SparseVector,
+    /// MultiVector is a bag of token-level vectors per document, e.g. the output of a late interaction model like ColBERT: a JSON array of vectors with identical dimensions, e.g. [[0.1, 0.2], [0.3, 0.4]].
+    /// Its token vectors are indexed as chunks of the document into the vector index (index_vector is always set, IndexMetaObject.inference is required and the vector dimensions have to match the index),
+    /// searched with SearchMode::LateInteraction: the vector index retrieves the candidate documents, which are scored by MaxSim: the sum over the query vectors of the maximum similarity to any of the document vectors.
+    MultiVector,
 }
 
 /// Defines synonyms for terms per index.
@@ -1148,6 +1298,24 @@ pub struct SchemaField {
     #[serde(default = "default_false")]
     pub completion_source: bool,
 
+    /// Optional vector inference for this field (requires index_vector=true), overriding IndexMetaObject.inference:
+    /// e.g. a Model2Vec text embedding for one field and an external image embedding with its own dimensions, precision, quantization and similarity for another field.
+    /// The field is indexed into its own vector index within the index directory. Target it in vector and hybrid search with field_filter,
+    /// the results of several vector fields are fused with RRF (Reciprocal Rank Fusion).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub inference: Option<Inference>,
+
+    /// Route documents to a shard by the hash of this field value (e.g. tenant_id), instead of round-robin: all documents with the same value are indexed into the same shard,
+    /// and a search with that value as routing hint (SearchRouted::search_routed) searches only that shard. Only one field per schema can be used for routing.
+    /// String and numerical values are hashed by their string representation, documents without the field are indexed into the shard with the fewest documents.
+    /// The global document id of a routed document is docid_local * shard_number + shard_id, with docid_local counted per shard:
+    /// unlike round-robin indexing, the document ids are not contiguous if the shards have different numbers of documents,
+    /// and the largest document id can be much higher than the number of indexed documents (Index::indexed_doc_count).
+    #[serde(skip_serializing_if = "is_default_bool")]
+    #[serde(default = "default_false")]
+    pub routing: bool,
+
     #[serde(skip)]
     pub(crate) indexed_field_id: usize,
     #[serde(skip_deserializing)]
@@ -1199,6 +1367,8 @@ impl SchemaField {
             boost,
             dictionary_source,
             completion_source,
+            inference: None,
+            routing: false,
 
             indexed_field_id: 0,
             field_id: 0,
@@ -1322,6 +1492,15 @@ pub enum Clustering {
     Auto,
     /// Set the number of clusters to a fixed value per level and shard.
     Fixed(usize),
+    /// HNSW (Hierarchical Navigable Small World) graph per level and shard instead of clusters.
+    /// Searched with `AnnMode::EfSearch(ef_search)`, all other AnnMode values search the level exhaustively.
+    /// The graph is persisted in a separate memory mapped file and works with all quantizations.
+    Hnsw {
+        /// Number of neighbors per node in the upper graph layers (2*m in the base layer), e.g. 16. Higher values increase recall, memory and indexing time.
+        m: usize,
+        /// Width of the candidate list during graph construction, e.g. 100. Higher values increase graph quality and indexing time.
+        ef_construction: usize,
+    },
 }
 
 impl fmt::Display for Clustering {
@@ -1330,6 +1509,9 @@ impl fmt::Display for Clustering {
             Clustering::None => write!(f, "None"),
             Clustering::Auto => write!(f, "Auto"),
             Clustering::Fixed(value) => write!(f, "Fixed({})", value),
+            Clustering::Hnsw { m, ef_construction } => {
+                write!(f, "Hnsw(m: {}, ef_construction: {})", m, ef_construction)
+            }
         }
     }
 }
@@ -1427,15 +1609,102 @@ fn doc_store_compression_default() -> DocumentCompression {
     DocumentCompression::Snappy
 }
 
+/// Number of facet values per shard, as multiple of offset + length, that are aggregated into the final facet values of a multi-shard index sorted by count.
+pub(crate) const FACET_SHARD_OVERSAMPLING: usize = 4;
+
 #[derive(Debug, Clone, Default)]
 pub(crate) struct ResultFacet {
     pub field: String,
     pub values: AHashMap<u32, usize>,
     pub prefix: String,
     pub length: u32,
+    pub offset: u32,
+    pub sort: FacetValueSort,
+    pub min_count: usize,
     pub ranges: Ranges,
+    pub cells: AHashMap<u64, GeoGridCellSum>,
+    pub significance: Option<SignificanceHeuristic>,
+}
+
+impl ResultFacet {
+    /// Applies prefix filter, minimum count, sort order, offset and length to the final (aggregated over all shards) facet values.
+    pub(crate) fn page(&self, values: impl Iterator<Item = (String, usize)>) -> Facet {
+        page_facet_values(
+            values,
+            &self.prefix,
+            self.min_count,
+            &self.sort,
+            self.offset as usize,
+            self.length as usize,
+        )
+    }
+
+    /// Applies prefix filter, minimum count, sort order, offset and length to the facet values of a single shard.
+    /// With a single shard the shard facet values are final, with multiple shards they are aggregated over all shards before paging:
+    /// then each shard returns its values with a count of at least min_count / shard_number (rounded up) in sort order, without offset:
+    /// a value that reaches the minimum count over all shards reaches it on at least one shard.
+    /// Sorted by value all values are returned, so that the pages are exact. Sorted by count the first (offset + length) * FACET_SHARD_OVERSAMPLING values are returned:
+    /// facet counts of multiple shards are approximate for values that are not among the returned values of every shard.
+    pub(crate) fn page_shard(
+        &self,
+        values: impl Iterator<Item = (String, usize)>,
+        shard_number: usize,
+    ) -> Facet {
+        if shard_number == 1 {
+            page_facet_values(
+                values,
+                &self.prefix,
+                self.min_count,
+                &self.sort,
+                self.offset as usize,
+                self.length as usize,
+            )
+        } else {
+            let length = match self.sort {
+                FacetValueSort::ValueAscending | FacetValueSort::ValueDescending => usize::MAX,
+                FacetValueSort::CountDescending | FacetValueSort::CountAscending => {
+                    (self.offset as usize + self.length as usize)
+                        .saturating_mul(FACET_SHARD_OVERSAMPLING)
+                }
+            };
+            page_facet_values(
+                values,
+                &self.prefix,
+                self.min_count.div_ceil(shard_number),
+                &self.sort,
+                0,
+                length,
+            )
+        }
+    }
 }
 
+fn page_facet_values(
+    values: impl Iterator<Item = (String, usize)>,
+    prefix: &str,
+    min_count: usize,
+    sort: &FacetValueSort,
+    offset: usize,
+    length: usize,
+) -> Facet {
+    values
+        .filter(|(value, count)| {
+            *count >= min_count && (prefix.is_empty() || value.starts_with(prefix))
+        })
+        .sorted_unstable_by(|a, b| match sort {
+            FacetValueSort::CountDescending => b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)),
+            FacetValueSort::CountAscending => a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)),
+            FacetValueSort::ValueAscending => a.0.cmp(&b.0),
+            FacetValueSort::ValueDescending => b.0.cmp(&a.0),
+        })
+        .skip(offset)
+        .take(length)
+        .collect()
+}
+
+/// geo grid cell aggregate: document count, latitude sum, longitude sum
+pub(crate) type GeoGridCellSum = (usize, f64, f64);
+
 /// DistanceUnit defines the unit for distance calculation: kilometers or miles.
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
 pub enum DistanceUnit {
@@ -1445,6 +1714,18 @@ pub enum DistanceUnit {
     Miles,
 }
 
+/// DistanceMethod defines the formula used for distance calculation between two points.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub enum DistanceMethod {
+    /// Euclidian distance with Equirectangular approximation: fastest, but the approximation error grows with distance and latitude.
+    #[default]
+    Equirectangular,
+    /// Great-circle distance on a spherical earth model via the haversine formula.
+    Haversine,
+    /// Geodesic distance on the WGS-84 ellipsoid via the Vincenty inverse formula: most accurate, but slowest.
+    Vincenty,
+}
+
 /// DistanceField defines a field for proximity search.
 #[derive(Debug, Clone, Deserialize, Serialize, ToSchema)]
 pub struct DistanceField {
@@ -1456,6 +1737,9 @@ pub struct DistanceField {
     pub base: Point,
     /// distance unit for the distance field: kilometers or miles
     pub unit: DistanceUnit,
+    /// distance method for the distance field: Equirectangular (default), Haversine or Vincenty
+    #[serde(default)]
+    pub method: DistanceMethod,
 }
 
 impl Default for DistanceField {
@@ -1465,6 +1749,7 @@ impl Default for DistanceField {
             distance: String::new(),
             base: Vec::new(),
             unit: DistanceUnit::Kilometers,
+            method: DistanceMethod::Equirectangular,
         }
     }
 }
@@ -1584,6 +1869,8 @@ pub struct Shard {
     pub(crate) last_level_index_file_start_pos: u64,
     pub(crate) last_level_docstore_file_start_pos: u64,
     pub(crate) last_level_vector_file_start_pos: u64,
+    /// Checksum of the last level, updated with the buffers written at commit.
+    pub(crate) level_hasher: LevelHasher,
 
     /// Number of allowed parallel indexed documents (default=available_parallelism). Can be used to detect wehen all indexing processes are finished.
     pub(crate) semaphore: Arc<Semaphore>,
@@ -1680,7 +1967,12 @@ pub struct Shard {
     pub(crate) chunks_string: Vec<String>,
     pub(crate) vector_file: File,
     pub(crate) vector_file_mmap: Mmap,
+    pub(crate) hnsw_file: LevelFile,
+    pub(crate) pq_file: LevelFile,
+    pub(crate) binary_file: LevelFile,
     pub(crate) block_vector_buffer: Vec<ParentMedoid>,
+    /// Vector file sections that contain the vectors of several merged levels, by the level id of the section.
+    pub(crate) merged_vector_levels: AHashMap<usize, MergedVectorLevel>,
     pub(crate) vector_dimensions: usize,
     pub(crate) vector_dimensions_original: usize,
     pub(crate) vector_precision: Precision,
@@ -1696,7 +1988,12 @@ pub struct Shard {
 /// The root object of the index. It contains all levels and all segments of the index.
 /// It also contains all properties that control indexing and intersection.
 pub struct Index {
+    /// Next global document id for round-robin indexing. With a routing field it keeps counting the indexed documents, but is not the document id.
     pub(crate) docid_global: Arc<RwLock<usize>>,
+    /// Next local document id per shard, for documents routed to a shard by SchemaField.routing: the global document id is docid_local * shard_number + shard_id
+    pub(crate) shard_docid_vec: Arc<RwLock<Vec<usize>>>,
+    /// Schema field with SchemaField.routing
+    pub(crate) routing_field: Option<String>,
 
     /// Incompatible index  format change: new library can't open old format, and old library can't open new format
     pub index_format_version_major: u16,
@@ -1725,6 +2022,7 @@ pub struct Index {
     pub(crate) compressed_index_segment_block_buffer: Vec<u8>,
 
     pub(crate) segment_number1: usize,
+    pub(crate) segment_number_bits1: usize,
     pub(crate) segment_number_mask1: u32,
 
     pub(crate) indexed_field_vec: Vec<IndexedField>,
@@ -1746,7 +2044,7 @@ pub struct Index {
 
     pub(crate) frequent_hashset: AHashSet<String>,
 
-    pub(crate) embedding_model_option: Option<StaticModel>,
+    pub(crate) embedding_provider_option: Option<Arc<dyn EmbeddingProvider>>,
     /// The precision of the vectors: Float32 or Int8
     pub vector_precision: Precision,
     pub(crate) quantization: Quantization,
@@ -1769,6 +2067,12 @@ pub struct Index {
     pub(crate) is_lexical_indexing: bool,
     pub(crate) chunk_size: usize,
     pub(crate) turbo_quant: TurboQuant,
+    /// Vector fields with their own inference, each indexed into its own vector index.
+    pub(crate) vector_field_indices: Vec<VectorFieldIndex>,
+    /// Recall curve of the ANN search, used to resolve AnnMode::TargetRecall.
+    pub(crate) ann_calibration: Option<AnnCalibration>,
+    /// Indicates whether a background re-calibration of the ANN search, started by a commit, is running.
+    pub(crate) ann_recalibrating: Arc<AtomicBool>,
 }
 
 ///SynonymItem is a vector of tuples: (synonym term, (64-bit synonym term hash, 64-bit synonym term hash))
@@ -1779,6 +2083,29 @@ pub fn version() -> &'static str {
     VERSION
 }
 
+/// Size of the value of a facet field in facet.bin, per document.
+pub(crate) fn facet_size(field_type: &FieldType) -> usize {
+    match field_type {
+        FieldType::U8 => 1,
+        FieldType::U16 => 2,
+        FieldType::U32 => 4,
+        FieldType::U64 => 8,
+        FieldType::I8 => 1,
+        FieldType::I16 => 2,
+        FieldType::I32 => 4,
+        FieldType::I64 => 8,
+        FieldType::Timestamp => 8,
+        FieldType::F32 => 4,
+        FieldType::F64 => 8,
+        FieldType::String16 => 2,
+        FieldType::String32 => 4,
+        FieldType::StringSet16 => 2,
+        FieldType::StringSet32 => 4,
+        FieldType::Point => 8,
+        _ => 1,
+    }
+}
+
 pub(crate) fn get_synonyms_map(
     synonyms: &[Synonym],
     segment_number_mask1: u32,
@@ -1955,10 +2282,32 @@ pub(crate) async fn create_index_root(
             let mut is_lexical_indexing = false;
             let mut schema = schema.clone();
             for schema_field in schema.iter_mut() {
-                if schema_field.field_type == FieldType::Binary && schema_field.index_lexical {
+                if matches!(
+                    schema_field.field_type,
+                    FieldType::Binary | FieldType::MultiVector
+                ) && schema_field.index_lexical
+                {
                     schema_field.index_lexical = false;
                 }
-                if schema_field.index_vector {
+                // the token vectors of multi-vector fields are stored in the vector index
+                if schema_field.field_type == FieldType::MultiVector {
+                    if meta.inference == Inference::None {
+                        return Err(format!(
+                            "multi-vector field {} requires a vector index, set IndexMetaObject.inference",
+                            schema_field.field
+                        ));
+                    }
+                    schema_field.index_vector = true;
+                    schema_field.inference = None;
+                }
+                // sparse vector terms are indexed into posting lists of the inverted index, but not into the dictionaries
+                if schema_field.field_type == FieldType::SparseVector {
+                    schema_field.index_lexical = true;
+                    schema_field.longest = false;
+                    schema_field.dictionary_source = false;
+                    schema_field.completion_source = false;
+                }
+                if schema_field.index_vector && schema_field.inference.is_none() {
                     is_vector_indexing = true;
                 }
                 if schema_field.index_lexical {
@@ -1988,25 +2337,7 @@ pub(crate) async fn create_index_root(
                 schema_map.insert(schema_field.field.clone(), schema_field_clone.clone());
 
                 if schema_field.facet {
-                    let facet_size = match schema_field.field_type {
-                        FieldType::U8 => 1,
-                        FieldType::U16 => 2,
-                        FieldType::U32 => 4,
-                        FieldType::U64 => 8,
-                        FieldType::I8 => 1,
-                        FieldType::I16 => 2,
-                        FieldType::I32 => 4,
-                        FieldType::I64 => 8,
-                        FieldType::Timestamp => 8,
-                        FieldType::F32 => 4,
-                        FieldType::F64 => 8,
-                        FieldType::String16 => 2,
-                        FieldType::String32 => 4,
-                        FieldType::StringSet16 => 2,
-                        FieldType::StringSet32 => 4,
-                        FieldType::Point => 8,
-                        _ => 1,
-                    };
+                    let facet_size = facet_size(&schema_field.field_type);
 
                     facets_map.insert(schema_field.field.clone(), facets_vec.len());
                     facets_vec.push(FacetField {
@@ -2065,7 +2396,7 @@ pub(crate) async fn create_index_root(
 
             let (
                 vector_dimensions,
-                embedding_model_option,
+                embedding_provider_option,
                 vector_precision,
                 chunk_size,
                 quantization,
@@ -2156,6 +2487,20 @@ pub(crate) async fn create_index_root(
                         *quantization,
                         VectorSimilarity::Cosine,
                     ),
+                    Inference::OpenAiCompatible {
+                        dimensions,
+                        chunk_size,
+                        quantization,
+                        similarity,
+                        ..
+                    } => (
+                        *dimensions,
+                        "",
+                        Precision::F32,
+                        *chunk_size.max(&10),
+                        *quantization,
+                        *similarity,
+                    ),
                     Inference::External {
                         dimensions: vector_dimensions,
                         precision: vector_precision,
@@ -2180,17 +2525,44 @@ pub(crate) async fn create_index_root(
                 };
 
                 if !model_path.is_empty() {
-                    let model =
-                        Some(StaticModel::from_pretrained(model_path, None, None, None).unwrap());
-                    let dimensions = model.as_ref().unwrap().encode(&["test".to_string()])[0].len();
+                    let embedding_provider: Arc<dyn EmbeddingProvider> =
+                        Arc::new(Model2VecProvider::new(model_path)?);
                     (
-                        dimensions,
-                        model,
+                        embedding_provider.dimensions(),
+                        Some(embedding_provider),
                         precision,
                         chunk_size,
                         quantization,
                         VectorSimilarity::Cosine,
                     )
+                } else if let Inference::OpenAiCompatible {
+                    url,
+                    model,
+                    api_key,
+                    batch_size,
+                    max_retries,
+                    cache,
+                    ..
+                } = &meta.inference
+                {
+                    let embedding_provider: Arc<dyn EmbeddingProvider> =
+                        Arc::new(OpenAiEmbeddingProvider::new(
+                            url,
+                            model,
+                            dimensions,
+                            api_key.clone(),
+                            *batch_size,
+                            *max_retries,
+                            cache.then_some(index_path),
+                        )?);
+                    (
+                        dimensions,
+                        Some(embedding_provider),
+                        precision,
+                        chunk_size,
+                        quantization,
+                        vector_similarity,
+                    )
                 } else {
                     (
                         dimensions,
@@ -2220,8 +2592,10 @@ pub(crate) async fn create_index_root(
 
             let vector_dimensions_original = vector_dimensions;
             let vector_dimensions = if quantization == Quantization::TurboQuantI8
-                && vector_precision == Precision::F32
-            {
+                && matches!(
+                    vector_precision,
+                    Precision::F32 | Precision::F16 | Precision::BF16
+                ) {
                 TurboQuant::next_power_of_two(vector_dimensions)
             } else {
                 vector_dimensions
@@ -2276,6 +2650,11 @@ pub(crate) async fn create_index_root(
 
             let mut index = Index {
                 docid_global: Arc::new(RwLock::new(0)),
+                shard_docid_vec: Arc::new(RwLock::new(vec![0; shard_number])),
+                routing_field: schema
+                    .iter()
+                    .find(|schema_field| schema_field.routing)
+                    .map(|schema_field| schema_field.field.clone()),
                 index_format_version_major: INDEX_FORMAT_VERSION_MAJOR,
                 index_format_version_minor: INDEX_FORMAT_VERSION_MINOR,
 
@@ -2289,6 +2668,7 @@ pub(crate) async fn create_index_root(
                 indexed_cluster_count: 0,
                 deleted_doc_count: 0,
                 segment_number1: 0,
+                segment_number_bits1: 0,
                 segment_number_mask1: 0,
                 schema_map,
                 indexed_field_vec,
@@ -2331,7 +2711,7 @@ pub(crate) async fn create_index_root(
 
                 frequent_hashset,
 
-                embedding_model_option,
+                embedding_provider_option,
                 vector_dimensions,
                 vector_dimensions_original,
                 vector_precision,
@@ -2344,6 +2724,9 @@ pub(crate) async fn create_index_root(
                 is_lexical_indexing,
                 chunk_size,
                 turbo_quant,
+                vector_field_indices: Vec::new(),
+                ann_calibration: AnnCalibration::load(index_path),
+                ann_recalibrating: Arc::new(AtomicBool::new(false)),
             };
 
             let file_len = index.index_file.metadata().unwrap().len();
@@ -2377,12 +2760,24 @@ pub(crate) async fn create_index_root(
                         + &index.index_format_version_major.to_string());
                 };
 
+                if INDEX_FORMAT_VERSION_MINOR < index.index_format_version_minor {
+                    return Err("incompatible index format version ".to_string()
+                        + &INDEX_FORMAT_VERSION_MAJOR.to_string()
+                        + "."
+                        + &INDEX_FORMAT_VERSION_MINOR.to_string()
+                        + " "
+                        + &index.index_format_version_major.to_string()
+                        + "."
+                        + &index.index_format_version_minor.to_string());
+                };
+
                 if index.index_format_version_major == 6 && index.index_format_version_minor == 0 {
                     index.meta.document_compression = DocumentCompression::Zstd;
                 }
             }
 
             index.segment_number1 = segment_number1;
+            index.segment_number_bits1 = segment_number_bits1;
             index.segment_number_mask1 = segment_number_mask1;
 
             if serialize_schema {
@@ -2415,6 +2810,14 @@ pub(crate) async fn create_index_root(
                 }
             }
 
+            create_vector_field_indices(
+                &index_arc,
+                index_path,
+                serialize_schema,
+                segment_number_bits1,
+            )
+            .await?;
+
             Ok(index_arc)
         }
         Err(e) => {
@@ -2490,6 +2893,10 @@ pub(crate) fn create_shard(
                 .open(Path::new(index_path).join(VECTOR_FILENAME))
                 .unwrap();
 
+            let hnsw_file = LevelFile::open(&Path::new(index_path).join(HNSW_FILENAME));
+            let pq_file = LevelFile::open(&Path::new(index_path).join(PQ_FILENAME));
+            let binary_file = LevelFile::open(&Path::new(index_path).join(BINARY_FILENAME));
+
             let mut document_length_compressed_array: Vec<[u8; ROARING_BLOCK_SIZE]> = Vec::new();
             let mut indexed_field_vec: Vec<IndexedField> = Vec::new();
             let mut facets_vec: Vec<FacetField> = Vec::new();
@@ -2503,7 +2910,7 @@ pub(crate) fn create_shard(
             let mut stored_field_names = Vec::new();
             let mut facets_size_sum = 0;
             for (i, schema_field) in schema.iter().enumerate() {
-                if schema_field.index_vector {
+                if schema_field.index_vector && schema_field.inference.is_none() {
                     is_vector_indexing = true;
                 }
                 if schema_field.index_lexical {
@@ -2517,25 +2924,7 @@ pub(crate) fn create_shard(
                 schema_map.insert(schema_field.field.clone(), schema_field_clone.clone());
 
                 if schema_field.facet {
-                    let facet_size = match schema_field.field_type {
-                        FieldType::U8 => 1,
-                        FieldType::U16 => 2,
-                        FieldType::U32 => 4,
-                        FieldType::U64 => 8,
-                        FieldType::I8 => 1,
-                        FieldType::I16 => 2,
-                        FieldType::I32 => 4,
-                        FieldType::I64 => 8,
-                        FieldType::Timestamp => 8,
-                        FieldType::F32 => 4,
-                        FieldType::F64 => 8,
-                        FieldType::String16 => 2,
-                        FieldType::String32 => 4,
-                        FieldType::StringSet16 => 2,
-                        FieldType::StringSet32 => 4,
-                        FieldType::Point => 8,
-                        _ => 1,
-                    };
+                    let facet_size = facet_size(&schema_field.field_type);
 
                     facets_map.insert(schema_field.field.clone(), facets_vec.len());
                     facets_vec.push(FacetField {
@@ -2748,6 +3137,7 @@ pub(crate) fn create_shard(
                 last_level_index_file_start_pos: 0,
                 last_level_docstore_file_start_pos: 0,
                 last_level_vector_file_start_pos: 0,
+                level_hasher: LevelHasher::default(),
                 positions_sum_normalized: 0,
                 segment_number1: 0,
                 segment_number_bits1,
@@ -2817,11 +3207,15 @@ pub(crate) fn create_shard(
                 chunks_string: Vec::new(),
                 vector_file,
                 vector_file_mmap,
+                hnsw_file,
+                pq_file,
+                binary_file,
                 indexed_vector_count: 0,
                 indexed_cluster_count: 0,
                 is_vector_indexing,
                 is_lexical_indexing,
                 block_vector_buffer: Vec::new(),
+                merged_vector_levels: load_merged_vector_levels(index_path),
                 vector_dimensions: 0,
                 vector_dimensions_original: 0,
                 vector_precision: Precision::None,
@@ -2867,6 +3261,17 @@ pub(crate) fn create_shard(
                         + &index.index_format_version_major.to_string());
                 };
 
+                if INDEX_FORMAT_VERSION_MINOR < index.index_format_version_minor {
+                    return Err("incompatible index format version ".to_string()
+                        + &INDEX_FORMAT_VERSION_MAJOR.to_string()
+                        + "."
+                        + &INDEX_FORMAT_VERSION_MINOR.to_string()
+                        + " "
+                        + &index.index_format_version_major.to_string()
+                        + "."
+                        + &index.index_format_version_minor.to_string());
+                };
+
                 if index.index_format_version_major == 6 && index.index_format_version_minor == 0 {
                     index.meta.document_compression = DocumentCompression::Zstd;
                 }
@@ -3026,6 +3431,11 @@ pub(crate) fn get_max_score(
             .ln();
 
         for field in field_vec.iter() {
+            if index.indexed_schema_vec[field.0 as usize].field_type == FieldType::SparseVector {
+                bm25f += field.1 as f32 / SPARSE_WEIGHT_SCALE;
+                continue;
+            }
+
             let document_length_normalized = DOCUMENT_LENGTH_COMPRESSION[if index.meta.access_type
                 == AccessType::Mmap
             {
@@ -3267,6 +3677,7 @@ pub(crate) async fn open_shard(
         + (vector_dimensions
             * match vector_type {
                 Precision::F32 => 4,
+                Precision::F16 | Precision::BF16 => 2,
                 Precision::I8 => 1,
                 Precision::None => 0,
             });
@@ -3745,6 +4156,13 @@ pub(crate) async fn open_shard(
                             shard.is_last_level_incomplete =
                                 !shard.committed_doc_count.is_multiple_of(ROARING_BLOCK_SIZE);
 
+                            shard.block_id = shard.committed_doc_count >> 16;
+
+                            // with Mmap the files are not read sequentially: a new level after a complete last level is appended at the end
+                            let _ = shard.index_file.seek(SeekFrom::End(0));
+                            let _ = shard.docstore_file.seek(SeekFrom::End(0));
+                            let _ = shard.vector_file.seek(SeekFrom::End(0));
+
                             if shard.is_vector_indexing && !shard.vector_file_mmap.is_empty() {
                                 shard.indexed_vector_count = 0;
 
@@ -3783,6 +4201,12 @@ pub(crate) async fn open_shard(
                                 }
                             }
 
+                            shard.hnsw_file.level_offsets =
+                                hnsw_level_offsets(&shard.hnsw_file.mmap);
+                            shard.pq_file.level_offsets = pq_level_offsets(&shard.pq_file.mmap);
+                            shard.binary_file.level_offsets =
+                                binary_level_offsets(&shard.binary_file.mmap);
+
                             for (i, component) in shard.bm25_component_cache.iter_mut().enumerate()
                             {
                                 let document_length_quotient = DOCUMENT_LENGTH_COMPRESSION[i]
@@ -3984,6 +4408,12 @@ pub async fn open_index(index_path: &Path) -> Result<IndexArc, String> {
 
                             let indexed_doc_count = index_arc.read().await.indexed_doc_count;
                             *index_arc.write().await.docid_global.write().await = indexed_doc_count;
+                            let mut shard_docid_vec = Vec::new();
+                            for shard in shard_vec.iter() {
+                                shard_docid_vec.push(shard.read().await.indexed_doc_count);
+                            }
+                            *index_arc.write().await.shard_docid_vec.write().await =
+                                shard_docid_vec;
 
                             index_arc.write().await.shard_number = shard_vec.len();
 
@@ -4012,21 +4442,33 @@ pub(crate) async fn warmup(shard_object_arc: &ShardArc) {
                 field: facet.name.clone(),
                 prefix: "".into(),
                 length: u16::MAX,
+                offset: 0,
+                sort: FacetValueSort::CountDescending,
+                min_count: 0,
             }),
             FieldType::String32 => query_facets.push(QueryFacet::String32 {
                 field: facet.name.clone(),
                 prefix: "".into(),
                 length: u32::MAX,
+                offset: 0,
+                sort: FacetValueSort::CountDescending,
+                min_count: 0,
             }),
             FieldType::StringSet16 => query_facets.push(QueryFacet::StringSet16 {
                 field: facet.name.clone(),
                 prefix: "".into(),
                 length: u16::MAX,
+                offset: 0,
+                sort: FacetValueSort::CountDescending,
+                min_count: 0,
             }),
             FieldType::StringSet32 => query_facets.push(QueryFacet::StringSet32 {
                 field: facet.name.clone(),
                 prefix: "".into(),
                 length: u32::MAX,
+                offset: 0,
+                sort: FacetValueSort::CountDescending,
+                min_count: 0,
             }),
             _ => {}
         }
@@ -4047,6 +4489,7 @@ pub(crate) async fn warmup(shard_object_arc: &ShardArc) {
                 query_facets.clone(),
                 Vec::new(),
                 Vec::new(),
+                Vec::new(),
             )
             .await;
 
@@ -4224,6 +4667,15 @@ pub(crate) fn hash64(term_bytes: &[u8]) -> u64 {
         & 0b1111111111111111111111111111111111111111111111111111111111111000
 }
 
+/// Shard of a document or query routed by the value of the routing field (SchemaField.routing).
+pub(crate) fn routing_shard(value: &Value, shard_number: usize) -> usize {
+    let value_string = match value {
+        Value::String(value_string) => value_string.clone(),
+        _ => value.to_string(),
+    };
+    hash32(value_string.as_bytes()) as usize % shard_number
+}
+
 static FREQUENT_EN: &str = include_str!("../assets/dictionaries/frequent_en.txt");
 static FREQUENT_DE: &str = include_str!("../assets/dictionaries/frequent_de.txt");
 static FREQUENT_FR: &str = include_str!("../assets/dictionaries/frequent_fr.txt");
@@ -4379,6 +4831,7 @@ impl Shard {
             unsafe { MmapMut::map_mut(&self.facets_file).expect("Unable to create Mmap") };
         let index_path = Path::new(&self.index_path_string);
         let _ = fs::remove_file(index_path.join(FACET_VALUES_FILENAME));
+        let _ = fs::remove_file(index_path.join(CHECKSUM_FILENAME));
         for facet in self.facets.iter_mut() {
             facet.values.clear();
             facet.min = ValueType::None;
@@ -4403,6 +4856,10 @@ impl Shard {
         let _ = self.vector_file.flush();
         self.vector_file_mmap =
             unsafe { Mmap::map(&self.vector_file).expect("Unable to create Mmap") };
+        self.hnsw_file.clear();
+        self.pq_file.clear();
+        self.binary_file.clear();
+        self.clear_merged_vector_levels();
         self.indexed_vector_count = 0;
         self.indexed_cluster_count = 0;
 
@@ -4434,6 +4891,7 @@ impl Shard {
         self.postinglist_count = 0;
 
         self.is_last_level_incomplete = false;
+        self.level_hasher = LevelHasher::default();
 
         drop(permit);
     }
@@ -4455,6 +4913,9 @@ impl Shard {
                         field,
                         prefix,
                         length,
+                        offset,
+                        sort,
+                        min_count,
                     } => {
                         if let Some(idx) = self.facets_map.get(field)
                             && self.facets[*idx].field_type == FieldType::String16
@@ -4463,6 +4924,9 @@ impl Shard {
                                 field: field.clone(),
                                 prefix: prefix.clone(),
                                 length: *length as u32,
+                                offset: *offset as u32,
+                                sort: sort.clone(),
+                                min_count: *min_count,
                                 ..Default::default()
                             }
                         }
@@ -4471,6 +4935,9 @@ impl Shard {
                         field,
                         prefix,
                         length,
+                        offset,
+                        sort,
+                        min_count,
                     } => {
                         if let Some(idx) = self.facets_map.get(field)
                             && self.facets[*idx].field_type == FieldType::StringSet16
@@ -4479,6 +4946,9 @@ impl Shard {
                                 field: field.clone(),
                                 prefix: prefix.clone(),
                                 length: *length as u32,
+                                offset: *offset as u32,
+                                sort: sort.clone(),
+                                min_count: *min_count,
                                 ..Default::default()
                             }
                         }
@@ -4488,6 +4958,9 @@ impl Shard {
                         field,
                         prefix,
                         length,
+                        offset,
+                        sort,
+                        min_count,
                     } => {
                         if let Some(idx) = self.facets_map.get(field)
                             && self.facets[*idx].field_type == FieldType::String32
@@ -4496,6 +4969,9 @@ impl Shard {
                                 field: field.clone(),
                                 prefix: prefix.clone(),
                                 length: *length,
+                                offset: *offset,
+                                sort: sort.clone(),
+                                min_count: *min_count,
                                 ..Default::default()
                             }
                         }
@@ -4504,6 +4980,9 @@ impl Shard {
                         field,
                         prefix,
                         length,
+                        offset,
+                        sort,
+                        min_count,
                     } => {
                         if let Some(idx) = self.facets_map.get(field)
                             && self.facets[*idx].field_type == FieldType::StringSet32
@@ -4512,6 +4991,9 @@ impl Shard {
                                 field: field.clone(),
                                 prefix: prefix.clone(),
                                 length: *length,
+                                offset: *offset,
+                                sort: sort.clone(),
+                                min_count: *min_count,
                                 ..Default::default()
                             }
                         }
@@ -4538,26 +5020,19 @@ impl Shard {
                     }
                 }
 
-                let v = hash_map
-                    .iter()
-                    .sorted_unstable_by(|a, b| b.1.cmp(a.1))
-                    .map(|(a, c)| (a.to_string(), *c))
-                    .filter(|(a, _c)| facet.prefix.is_empty() || a.starts_with(&facet.prefix))
-                    .take(facet.length as usize)
-                    .collect::<Vec<_>>();
+                let v = facet.page_shard(hash_map.into_iter(), self.shard_number);
 
                 if !v.is_empty() {
                     facets.insert(facet.field.clone(), v);
                 }
             } else {
-                let v = self.facets[i]
-                    .values
-                    .iter()
-                    .sorted_unstable_by(|a, b| b.1.cmp(a.1))
-                    .map(|(a, c)| (a.to_string(), c.1))
-                    .filter(|(a, _c)| facet.prefix.is_empty() || a.starts_with(&facet.prefix))
-                    .take(facet.length as usize)
-                    .collect::<Vec<_>>();
+                let v = facet.page_shard(
+                    self.facets[i]
+                        .values
+                        .iter()
+                        .map(|(a, c)| (a.to_string(), c.1)),
+                    self.shard_number,
+                );
 
                 if !v.is_empty() {
                     facets.insert(facet.field.clone(), v);
@@ -4600,6 +5075,7 @@ impl Index {
     }
 
     /// Get number of indexed documents.
+    /// With a routing field (SchemaField.routing) the document ids are not contiguous: they don't range from 0 to indexed_doc_count-1, the largest document id can be much higher.
     pub async fn indexed_doc_count(&self) -> usize {
         let mut indexed_doc_count = 0;
         for shard in self.shard_vec.iter() {
@@ -4608,12 +5084,29 @@ impl Index {
         indexed_doc_count
     }
 
-    /// Get number of indexed vectors.
+    /// Shard of a routing value (SchemaField.routing), or None if the schema has no routing field and all shards are searched.
+    pub(crate) fn routing_shard_filter(&self, routing: &str) -> Option<usize> {
+        self.routing_field
+            .as_ref()
+            .map(|_| routing_shard(&Value::String(routing.to_string()), self.shard_number))
+    }
+
+    /// Get number of indexed vectors, including the vectors of vector fields with their own inference.
     pub async fn indexed_vector_count(&self) -> usize {
         let mut indexed_vector_count = 0;
         for shard in self.shard_vec.iter() {
             indexed_vector_count += shard.read().await.indexed_vector_count;
         }
+        for vector_field_index in self.vector_field_indices.iter() {
+            indexed_vector_count += Box::pin(
+                vector_field_index
+                    .index_arc
+                    .read()
+                    .await
+                    .indexed_vector_count(),
+            )
+            .await;
+        }
         indexed_vector_count
     }
 
@@ -4852,37 +5345,74 @@ impl Index {
 
         let mut result: AHashMap<String, Facet> = AHashMap::new();
 
-        let mut result_facets: AHashMap<String, (AHashMap<String, usize>, u32)> = AHashMap::new();
+        let mut result_facets: AHashMap<String, (AHashMap<String, usize>, ResultFacet)> =
+            AHashMap::new();
         for query_facet in query_facets.iter() {
             match query_facet {
                 QueryFacet::String16 {
                     field,
-                    prefix: _,
+                    prefix,
                     length,
-                } => {
-                    result_facets.insert(field.into(), (AHashMap::new(), *length as u32));
+                    offset,
+                    sort,
+                    min_count,
                 }
-                QueryFacet::StringSet16 {
+                | QueryFacet::StringSet16 {
                     field,
-                    prefix: _,
+                    prefix,
                     length,
+                    offset,
+                    sort,
+                    min_count,
                 } => {
-                    result_facets.insert(field.into(), (AHashMap::new(), *length as u32));
+                    result_facets.insert(
+                        field.into(),
+                        (
+                            AHashMap::new(),
+                            ResultFacet {
+                                field: field.clone(),
+                                prefix: prefix.clone(),
+                                length: *length as u32,
+                                offset: *offset as u32,
+                                sort: sort.clone(),
+                                min_count: *min_count,
+                                ..Default::default()
+                            },
+                        ),
+                    );
                 }
 
                 QueryFacet::String32 {
                     field,
-                    prefix: _,
+                    prefix,
                     length,
-                } => {
-                    result_facets.insert(field.into(), (AHashMap::new(), *length));
+                    offset,
+                    sort,
+                    min_count,
                 }
-                QueryFacet::StringSet32 {
+                | QueryFacet::StringSet32 {
                     field,
-                    prefix: _,
+                    prefix,
                     length,
+                    offset,
+                    sort,
+                    min_count,
                 } => {
-                    result_facets.insert(field.into(), (AHashMap::new(), *length));
+                    result_facets.insert(
+                        field.into(),
+                        (
+                            AHashMap::new(),
+                            ResultFacet {
+                                field: field.clone(),
+                                prefix: prefix.clone(),
+                                length: *length,
+                                offset: *offset,
+                                sort: sort.clone(),
+                                min_count: *min_count,
+                                ..Default::default()
+                            },
+                        ),
+                    );
                 }
 
                 _ => {}
@@ -4903,14 +5433,7 @@ impl Index {
         }
 
         for (key, value) in result_facets.iter_mut() {
-            let sum = value
-                .0
-                .iter()
-                .sorted_unstable_by(|a, b| b.1.cmp(a.1))
-                .map(|(a, c)| (a.clone(), *c))
-                .take(value.1 as usize)
-                .collect::<Vec<_>>();
-            result.insert(key.clone(), sum);
+            result.insert(key.clone(), value.1.page(value.0.drain()));
         }
 
         Some(result)
@@ -4942,6 +5465,18 @@ impl Index {
             }));
         }
         future::join_all(result_object_list).await;
+        self.shard_docid_vec
+            .write()
+            .await
+            .iter_mut()
+            .for_each(|docid| *docid = 0);
+
+        let _ = fs::remove_file(index_path.join(ANN_CALIBRATION_FILENAME));
+        self.ann_calibration = None;
+
+        for vector_field_index in self.vector_field_indices.iter() {
+            Box::pin(vector_field_index.index_arc.write().await.clear_index()).await;
+        }
     }
 
     /// Delete index from disc and ram
@@ -5072,6 +5607,11 @@ impl Close for IndexArc {
             }));
         }
         future::join_all(result_object_list).await;
+
+        let vector_field_indices = self.read().await.vector_field_indices.clone();
+        for vector_field_index in vector_field_indices.iter() {
+            Box::pin(vector_field_index.index_arc.close()).await;
+        }
     }
 }
 
@@ -5113,6 +5653,11 @@ impl DeleteDocument for IndexArc {
             let _ = shard_mut.delete_file.write(&buffer);
             let _ = shard_mut.delete_file.flush();
         }
+        drop(shard_mut);
+
+        for vector_field_index in index_ref.vector_field_indices.iter() {
+            Box::pin(vector_field_index.index_arc.delete_document(docid)).await;
+        }
     }
 }
 
@@ -5277,16 +5822,49 @@ impl IndexDocument for IndexArc {
     /// Index document
     /// May block, if the threshold of documents indexed in parallel is exceeded.
     async fn index_document(&self, document: Document, file: FileType) {
+        self.index_document_docid(document, file).await;
+    }
+}
+
+/// Indexes a single document and returns its assigned global document id
+#[allow(async_fn_in_trait)]
+pub(crate) trait IndexDocumentDocid {
+    async fn index_document_docid(&self, document: Document, file: FileType) -> usize;
+}
+
+impl IndexDocumentDocid for IndexArc {
+    async fn index_document_docid(&self, document: Document, file: FileType) -> usize {
         let shard_number = self.read().await.shard_number;
         let docid_global_arc = self.read().await.docid_global.clone();
         let mut docid_global = docid_global_arc.write().await;
-        let docid_global_clone = *docid_global;
-        let shard_id = *docid_global % shard_number;
+        let routing_field = self.read().await.routing_field.clone();
+        let docid_global_clone = if let Some(routing_field) = routing_field {
+            let shard_docid_vec_arc = self.read().await.shard_docid_vec.clone();
+            let mut shard_docid_vec = shard_docid_vec_arc.write().await;
+            let shard_id = match document.get(&routing_field) {
+                Some(value) => routing_shard(value, shard_number),
+                None => (0..shard_number)
+                    .min_by_key(|shard_id| shard_docid_vec[*shard_id])
+                    .unwrap_or_default(),
+            };
+            let docid_local = shard_docid_vec[shard_id];
+            shard_docid_vec[shard_id] += 1;
+            (docid_local * shard_number) + shard_id
+        } else {
+            *docid_global
+        };
+        let shard_id = docid_global_clone % shard_number;
 
         let shard_arc = self.read().await.shard_vec[shard_id].clone();
         let semaphore = shard_arc.read().await.semaphore.clone();
         let permit = semaphore.acquire_owned().await.unwrap();
 
+        for vector_field_index in self.read().await.vector_field_indices.iter() {
+            vector_field_index
+                .index_document(&document, docid_global_clone)
+                .await;
+        }
+
         *docid_global += 1;
         drop(docid_global);
 
@@ -5296,6 +5874,8 @@ impl IndexDocument for IndexArc {
                 .await;
             drop(permit);
         });
+
+        docid_global_clone
     }
 }
 
@@ -5351,6 +5931,19 @@ impl IndexDocumentShard for ShardArc {
                 continue;
             }
 
+            if schema_field.field_type == FieldType::SparseVector {
+                if let Some(Value::Object(terms)) = document.get(&schema_field.field) {
+                    tokenize_sparse_vector(
+                        terms,
+                        schema_field.indexed_field_id,
+                        indexed_field_vec_len,
+                        segment_number_mask1,
+                        &mut unique_terms,
+                    );
+                }
+                continue;
+            }
+
             if let Some(field_value) = document.get(&schema_field.field) {
                 let mut non_unique_terms: Vec<NonUniqueTermObject> = Vec::new();
                 let mut nonunique_terms_count = 0u32;
@@ -5932,3 +6525,48 @@ pub(crate) struct DocumentItem {
     pub unique_terms: AHashMap<String, TermObject>,
     pub field_vec: Vec<(usize, u8, u32, u32)>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn facet_page_shard_cap() {
+        let result_facet = ResultFacet {
+            offset: 1,
+            length: 2,
+            min_count: 5,
+            ..Default::default()
+        };
+        let values = || (0..100).map(|i| (format!("{i:03}"), i));
+
+        // single shard: final page
+        assert_eq!(
+            result_facet.page_shard(values(), 1),
+            vec![("098".to_string(), 98), ("097".to_string(), 97)]
+        );
+
+        // multiple shards, sorted by count: (offset + length) * FACET_SHARD_OVERSAMPLING values with minimum count / shard number, without offset
+        let page = result_facet.page_shard(values(), 4);
+        assert_eq!(page.len(), 3 * FACET_SHARD_OVERSAMPLING);
+        assert_eq!(page[0], ("099".to_string(), 99));
+
+        let result_facet = ResultFacet {
+            sort: FacetValueSort::CountAscending,
+            ..result_facet
+        };
+        let page = result_facet.page_shard(values(), 4);
+        assert_eq!(page.len(), 3 * FACET_SHARD_OVERSAMPLING);
+        assert_eq!(page[0], ("002".to_string(), 2));
+
+        // multiple shards, sorted by value: all values with minimum count / shard number
+        let result_facet = ResultFacet {
+            sort: FacetValueSort::ValueDescending,
+            ..result_facet
+        };
+        let page = result_facet.page_shard(values(), 4);
+        assert_eq!(page.len(), 98);
+        assert_eq!(page[0], ("099".to_string(), 99));
+        assert_eq!(page[97], ("002".to_string(), 2));
+    }
+}
@@ -334,6 +334,7 @@ pub(crate) async fn search_iterator_shard(
         idf_ngram2: 0.0,
         idf_ngram3: 0.0,
         ngram_type: NgramType::SingleTerm,
+        is_sparse: false,
     };
 
     let mut not_query_list = Vec::new();
@@ -375,6 +376,8 @@ pub(crate) async fn search_iterator_index(
         observed_cluster_count: 0,
         results: Vec::new(),
         facets: AHashMap::new(),
+        geo_grids: AHashMap::new(),
+        significant_terms: AHashMap::new(),
         suggestions: Vec::new(),
     };
 
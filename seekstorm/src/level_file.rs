@@ -0,0 +1,72 @@
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// File with one data block per committed level (e.g. HNSW graph, product quantization codes), memory mapped for search.
+/// Blocks are appended in level order, the block of an incomplete last level is replaced when the level is committed again.
+pub(crate) struct LevelFile {
+    pub(crate) file: File,
+    pub(crate) mmap: Mmap,
+    /// start of the level blocks within the memory mapped file
+    pub(crate) level_offsets: Vec<usize>,
+}
+
+impl LevelFile {
+    pub(crate) fn open(path: &Path) -> Self {
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .unwrap();
+        let mmap = unsafe { Mmap::map(&file).expect("Unable to create Mmap") };
+        LevelFile {
+            file,
+            mmap,
+            level_offsets: Vec::new(),
+        }
+    }
+
+    /// block of the level, from its offset to the end of the file
+    pub(crate) fn level(&self, level_id: usize) -> Option<&[u8]> {
+        self.level_offsets
+            .get(level_id)
+            .map(|&offset| &self.mmap[offset..])
+    }
+
+    /// Writes the block of a level: appends a new level, or replaces the last level and truncates the file after it.
+    /// Levels committed before the file existed have no block, then no block is written to keep the level ids aligned.
+    pub(crate) fn write_level(&mut self, level_id: usize, bytes: &[u8]) {
+        if self.level_offsets.len() < level_id {
+            return;
+        }
+
+        let level_offset = self
+            .level_offsets
+            .get(level_id)
+            .copied()
+            .unwrap_or(self.mmap.len());
+        self.level_offsets.truncate(level_id);
+
+        let _ = self.file.seek(SeekFrom::Start(level_offset as u64));
+        let _ = self.file.write_all(bytes);
+        if let Err(e) = self.file.set_len((level_offset + bytes.len()) as u64) {
+            println!("Unable to set_len in write_level {:?}", e)
+        }
+        self.file.flush().expect("Unable to flush Mmap");
+        self.mmap = unsafe { Mmap::map(&self.file).expect("Unable to create Mmap") };
+        self.level_offsets.push(level_offset);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        let _ = self.file.rewind();
+        if let Err(e) = self.file.set_len(0) {
+            println!("Unable to set_len in clear {:?}", e)
+        };
+        let _ = self.file.flush();
+        self.mmap = unsafe { Mmap::map(&self.file).expect("Unable to create Mmap") };
+        self.level_offsets = Vec::new();
+    }
+}
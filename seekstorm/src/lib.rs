@@ -416,7 +416,7 @@
 //! # use seekstorm::index::open_index;
 //! # let index_path=Path::new("C:/index/");
 //! # let index_arc=open_index(index_path).await.unwrap();
-//! use seekstorm::search::{QueryType, SearchMode, ResultType, QueryFacet, FacetFilter, QueryRewriting,Search};
+//! use seekstorm::search::{QueryType, SearchMode, ResultType, QueryFacet, FacetValueSort, FacetFilter, QueryRewriting,Search};
 //! let query="test".to_string();
 //! let query_vector=None;
 //! let search_mode=SearchMode::Lexical;
@@ -427,7 +427,7 @@
 //! let result_type=ResultType::TopkCount;
 //! let include_uncommitted=false;
 //! let field_filter=Vec::new();
-//! let query_facets = vec![QueryFacet::String16 {field: "town".to_string(),prefix: "".to_string(),length: u16::MAX}];
+//! let query_facets = vec![QueryFacet::String16 {field: "town".to_string(),prefix: "".to_string(),length: u16::MAX,offset: 0,sort: FacetValueSort::CountDescending,min_count: 0}];
 //! let facet_filter=Vec::new();
 //! //let facet_filter = vec![FacetFilter {field: "town".to_string(),   filter:Filter::String(vec!["Berlin".to_string()])}];
 //! let result_sort=Vec::new();
@@ -489,18 +489,29 @@ pub static INDEX_RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
 });
 
 pub(crate) mod add_result;
+/// ANN calibration: measures the recall curve of approximate nearest neighbor search, to search with a target recall (AnnMode::TargetRecall) instead of a hand-tuned n-probe.
+pub mod ann_calibration;
+pub(crate) mod binary_quantization;
 pub(crate) mod clustering;
 /// Commit moves indexed documents from the intermediate uncompressed data structure in RAM
 /// to the final compressed data structure on disk.
 pub mod commit;
 pub(crate) mod compatible;
 pub(crate) mod compress_postinglist;
+/// Cross-encoder reranker: BERT or RoBERTa sequence classification models loaded from safetensors and evaluated in-process (feature "rerank").
+#[cfg(feature = "rerank")]
+pub mod cross_encoder;
+/// Diversification of search results: reranking of the top candidates with maximal marginal relevance (MMR) and a cap of results per cluster or field value.
+pub mod diversify;
 pub(crate) mod doc_store;
+/// Embedding providers transform text into vector embeddings at ingest and search time: built-in Model2Vec, OpenAI-compatible embeddings endpoints (e.g. llama.cpp, Ollama), or a custom provider.
+pub mod embedding_provider;
 /// Geo search by indexing geo points (latitude, longitude), proximity searching for points within a specified radius, and proximity sorting.
 pub mod geo_search;
 /// Extracts the most relevant fragments (snippets, summaries) from specified fields of the document to provide a "keyword in context" (KWIC) functionality.
 /// With highlight_markup the matching query terms within the fragments can be highlighted with HTML markup.
 pub mod highlighter;
+pub(crate) mod hnsw;
 /// Operate the index: reate_index, open_index, clear_index, close_index, delete_index, index_document(s)
 pub mod index;
 pub(crate) mod index_posting;
@@ -510,12 +521,32 @@ pub(crate) mod intersection;
 pub(crate) mod intersection_simd;
 /// Iterator over all documents, also for search with empty query.
 pub mod iterator;
+pub(crate) mod level_file;
+/// Merging of multiple indices with the same schema into a new index, e.g. indices built in parallel on several machines.
+pub mod merge;
 pub(crate) mod min_heap;
+pub(crate) mod multi_vector;
+pub(crate) mod product_quantization;
+/// Pseudo-relevance feedback: query expansion with the top-ranked documents of an initial retrieval, lexical (RM3) and vector (Rocchio), before the query is re-run.
+pub mod query_expansion;
+pub(crate) mod query_range;
 pub(crate) mod realtime_search;
+/// Reranking of the top-ranked search results with a cross-encoder (two-stage retrieval): the built-in CrossEncoder loads BERT or RoBERTa models from safetensors, or a custom Reranker.
+pub mod rerank;
+/// Resharding: rebuilds an index with a different number of shards, e.g. after moving it to a machine with more or fewer cores.
+pub mod reshard;
 /// Search the index for all indexed documents, both for committed and uncommitted documents.
 /// The latter enables true realtime search: documents are available for search in exact the same millisecond they are indexed.
 pub mod search;
+/// Batch vector search: searches many query vectors at once, scanning each selected cluster once for all queries.
+pub mod search_batch;
+/// Radius (range) vector search: all documents within a distance or above a similarity of the query vector, paginated, without top-k cap.
+pub mod search_radius;
+/// Significant terms aggregation: facet values most over-represented in the documents matching the query compared with the whole index (JLH or chi-square scoring).
+pub mod significant_terms;
 pub(crate) mod single;
+/// Sparse vector search (learned sparse retrieval, e.g. SPLADE or BM42): term weight maps indexed into the posting lists of the inverted index with quantized weights, scored by dot product.
+pub mod sparse_vector;
 /// Tokenizes text into tokens (words), supports Chinese word segmentation, folds (converts) diacritics, accents, zalgo text, umlaut, bold, italic, full-width UTF-8 characters into their basic representation.
 pub(crate) mod tokenizer;
 pub(crate) mod union;
@@ -523,7 +554,12 @@ pub(crate) mod union;
 pub mod utils;
 /// Vector search by indexing vectors and searching for similar vectors based on cosine similarity, inner product, and Euclidean distance.
 pub mod vector;
+pub(crate) mod vector_field;
+/// Vector level merging: merges the vectors of consecutive committed levels into fewer, larger levels with fresh clusters, and purges the vectors of deleted documents.
+pub mod vector_merge;
 /// Vector quantization and similarity measure definitions for vector search.
 pub mod vector_similarity;
+/// Index integrity verification with the level checksums written at commit, and repair by rolling back to the last consistent level.
+pub mod verify;
 #[cfg(feature = "zh")]
 pub(crate) mod word_segmentation;
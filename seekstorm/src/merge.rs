@@ -0,0 +1,819 @@
+use std::{
+    cmp::Ordering,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use ahash::AHashMap;
+use indexmap::IndexMap;
+use memmap2::Mmap;
+use serde_json::Value;
+
+use crate::{
+    commit::Commit,
+    index::{
+        COMPLETIONS_FILENAME, Close, DELETE_FILENAME, DICTIONARY_FILENAME, DOCSTORE_FILENAME,
+        FACET_FILENAME, FACET_VALUES_FILENAME, FacetField, FieldType, INDEX_FILENAME,
+        INDEX_HEADER_SIZE, IndexArc, MERGED_VECTOR_LEVELS_FILENAME, ROARING_BLOCK_SIZE,
+        SCHEMA_FILENAME, SYNONYMS_FILENAME, SchemaField, Shard, ShardArc, Synonym, VECTOR_FILENAME,
+        ValueType, create_index, open_index,
+    },
+    reshard::{copy_embedding_caches, copy_files, read_json},
+    utils::{read_u16, read_u32, read_u64, write_u16, write_u32},
+    vector_field::VECTOR_FIELDS_PATH,
+    vector_merge::{MergedVectorLevel, load_merged_vector_levels, merged_vector_levels_bytes},
+    verify::{
+        LEVEL_FILES, LevelChecksum, LevelHasher, fill_doc_id_gaps, level_block_ends, map_file,
+        vector_section_ends, write_level_checksums,
+    },
+};
+
+/// Result of merge_indices.
+pub struct MergeResult {
+    /// The merged index, opened at the target path.
+    pub index_arc: IndexArc,
+    /// Number of documents merged into the target index, without deleted documents.
+    pub doc_count: usize,
+    /// Global document ids per source index, in the order of the sources: (document id in the source index, document id in the merged index).
+    pub doc_id_mapping: Vec<Vec<(u64, u64)>>,
+}
+
+/// Merges multiple indices with the same schema into a new index at target_path, e.g. indices that were built in parallel on several machines, one per data partition.
+/// The schema, the index settings (meta, except id, name and access_type), the shard number and the segment number bits of all sources have to be identical.
+/// The committed levels of each source shard are appended to the target shard without re-indexing, in the order of the sources.
+/// Document ids within a level are local to the level, therefore only the level headers, the facet value ids and the document ids of deleted documents are rewritten. The facet value dictionaries (String16, String32, StringSet16, StringSet32) of the sources are merged.
+/// The incomplete last level of a source shard is followed by the next level of the next source,
+/// the document ids in between are marked as deleted (they count as deleted documents, but don't lower the average document length of BM25).
+/// With round-robin shard assignment, shards with fewer documents are padded with deleted placeholder documents, so that new documents are assigned to the shards in turn again.
+/// Document ids are remapped and returned in MergeResult.doc_id_mapping, deleted documents are not merged.
+/// Synonyms are united, and the term counts of the spelling correction dictionaries and completion lists of the sources are added up.
+/// Vectors, HNSW graphs and quantization codes are carried over per level, the ANN calibration is not carried over.
+/// The sources remain open and unchanged, documents that are indexed into a source during merging may be missing in the merged index.
+/// * `sources`: indices to merge.
+/// * `target_path`: path of the merged index, must not exist or be empty.
+pub async fn merge_indices(
+    sources: &[IndexArc],
+    target_path: &Path,
+) -> Result<MergeResult, String> {
+    if sources.is_empty() {
+        return Err("no source indices to merge".to_string());
+    }
+    if target_path.exists()
+        && fs::read_dir(target_path)
+            .map_err(|e| e.to_string())?
+            .next()
+            .is_some()
+    {
+        return Err(format!(
+            "target path {} exists and is not empty",
+            target_path.display()
+        ));
+    }
+
+    let mut source_paths = Vec::new();
+    let mut schema: Vec<SchemaField> = Vec::new();
+    let mut first_meta = Value::Null;
+    let mut shard_number = 0;
+    let mut segment_number_bits1 = 0;
+    let mut synonyms: Vec<Synonym> = Vec::new();
+    let mut synonym_keys: Vec<Value> = Vec::new();
+    for (source_id, source) in sources.iter().enumerate() {
+        source.commit().await;
+
+        let index_ref = source.read().await;
+        let index_path = PathBuf::from(&index_ref.index_path_string);
+        let source_schema: Vec<SchemaField> = read_json(&index_path.join(SCHEMA_FILENAME))?;
+        let source_meta = comparable_meta(&serde_json::to_value(&index_ref.meta).unwrap());
+        if source_id == 0 {
+            schema = source_schema;
+            first_meta = source_meta;
+            shard_number = index_ref.shard_number;
+            segment_number_bits1 = index_ref.segment_number_bits1;
+        } else if serde_json::to_value(&source_schema).unwrap()
+            != serde_json::to_value(&schema).unwrap()
+        {
+            return Err(format!(
+                "schema of source {} differs from the schema of source 0",
+                source_id
+            ));
+        } else if source_meta != first_meta {
+            return Err(format!(
+                "meta of source {} differs from the meta of source 0",
+                source_id
+            ));
+        } else if index_ref.shard_number != shard_number {
+            return Err(format!(
+                "source {} has {} shards, but source 0 has {} shards",
+                source_id, index_ref.shard_number, shard_number
+            ));
+        } else if index_ref.segment_number_bits1 != segment_number_bits1 {
+            return Err(format!(
+                "segment number bits {} of source {} differ from the segment number bits {} of source 0",
+                index_ref.segment_number_bits1, source_id, segment_number_bits1
+            ));
+        }
+
+        let source_synonyms: Vec<Synonym> =
+            read_json(&index_path.join(SYNONYMS_FILENAME)).unwrap_or_default();
+        for synonym in source_synonyms {
+            let synonym_key = serde_json::to_value(&synonym).unwrap();
+            if !synonym_keys.contains(&synonym_key) {
+                synonym_keys.push(synonym_key);
+                synonyms.push(synonym);
+            }
+        }
+
+        if index_path == target_path {
+            return Err(format!("source {} is the target index", source_id));
+        }
+        source_paths.push(index_path);
+    }
+
+    for source_path in source_paths.iter() {
+        copy_embedding_caches(source_path, target_path)?;
+    }
+
+    let (meta, mute) = {
+        let index_ref = sources[0].read().await;
+        (index_ref.meta.clone(), index_ref.mute)
+    };
+    let target_arc = create_index(
+        target_path,
+        meta,
+        &schema,
+        &synonyms,
+        segment_number_bits1,
+        mute,
+        Some(shard_number),
+    )
+    .await?;
+    target_arc.close().await;
+    drop(target_arc);
+
+    let mut doc_id_mapping = vec![Vec::new(); sources.len()];
+    for shard_id in 0..shard_number {
+        let mut shards = Vec::new();
+        for source in sources.iter() {
+            shards.push(source.read().await.shard_vec[shard_id].clone());
+        }
+        let shard_levels = concatenate_shards(
+            &shards,
+            None,
+            &target_path.join("shards").join(shard_id.to_string()),
+        )
+        .await?;
+
+        for (source_doc_id_mapping, levels) in doc_id_mapping.iter_mut().zip(shard_levels.iter()) {
+            source_doc_id_mapping.extend(levels.live_doc_ids.iter().map(|doc_id| {
+                (
+                    (doc_id * shard_number + shard_id) as u64,
+                    ((doc_id + levels.level_offset * ROARING_BLOCK_SIZE) * shard_number + shard_id)
+                        as u64,
+                )
+            }));
+        }
+
+        // vector fields with their own inference have the same document ids as the index, their levels are appended at the same level offsets
+        let vector_field_count = sources[0].read().await.vector_field_indices.len();
+        for vector_field_id in 0..vector_field_count {
+            let mut vector_field_shards = Vec::new();
+            let mut vector_field_name = Default::default();
+            for source in sources.iter() {
+                let index_ref = source.read().await;
+                let vector_field_index_ref = index_ref.vector_field_indices[vector_field_id]
+                    .index_arc
+                    .read()
+                    .await;
+                vector_field_name = Path::new(&vector_field_index_ref.index_path_string)
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_os_string();
+                vector_field_shards.push(vector_field_index_ref.shard_vec[shard_id].clone());
+            }
+            concatenate_shards(
+                &vector_field_shards,
+                Some(&shard_levels),
+                &target_path
+                    .join(VECTOR_FIELDS_PATH)
+                    .join(vector_field_name)
+                    .join("shards")
+                    .join(shard_id.to_string()),
+            )
+            .await?;
+        }
+    }
+
+    let mut doc_count = 0;
+    for (source_doc_id_mapping, source_path) in doc_id_mapping.iter_mut().zip(source_paths.iter()) {
+        source_doc_id_mapping.sort_unstable();
+        doc_count += source_doc_id_mapping.len();
+        let target_doc_ids: AHashMap<u64, u64> = source_doc_id_mapping.iter().copied().collect();
+        copy_files(source_path, target_path, &target_doc_ids)?;
+    }
+
+    // the spelling correction dictionaries and completion lists of open indices are only saved on close,
+    // therefore they are exported from the sources for merging
+    let mut dictionary_paths = Vec::new();
+    let mut completions_paths = Vec::new();
+    for (source_id, source) in sources.iter().enumerate() {
+        let index_ref = source.read().await;
+        if let Some(symspell) = index_ref.symspell_option.as_ref() {
+            let path = target_path.join(format!("{}.{}", source_id, DICTIONARY_FILENAME));
+            symspell
+                .read()
+                .await
+                .save_dictionary(&path, " ")
+                .map_err(|e| e.to_string())?;
+            dictionary_paths.push(path);
+        }
+        if let Some(completion_option) = index_ref.completion_option.as_ref() {
+            let path = target_path.join(format!("{}.{}", source_id, COMPLETIONS_FILENAME));
+            completion_option
+                .read()
+                .await
+                .save_completions(&path, ":")
+                .map_err(|e| e.to_string())?;
+            completions_paths.push(path);
+        }
+    }
+    for (paths, filename, separator) in [
+        (dictionary_paths, DICTIONARY_FILENAME, " "),
+        (completions_paths, COMPLETIONS_FILENAME, ":"),
+    ] {
+        if !paths.is_empty() {
+            merge_term_counts(&paths, &target_path.join(filename), separator)?;
+            for path in paths.iter() {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    let index_arc = open_index(target_path).await?;
+    fill_doc_id_gaps(&index_arc).await;
+
+    Ok(MergeResult {
+        index_arc,
+        doc_count,
+        doc_id_mapping,
+    })
+}
+
+/// Committed levels of a source shard, appended to the target shard from level_offset on.
+struct ShardLevels {
+    level_offset: usize,
+    level_count: usize,
+    /// shard-local ids of the committed, non-deleted documents of the source shard
+    live_doc_ids: Vec<usize>,
+}
+
+/// Appends the committed levels of the source shards to the closed and empty target shard, in the order of the sources.
+/// The level data is copied unchanged, only the level headers, the facet value ids and the document ids of
+/// deleted documents and merged vector levels are remapped.
+/// The document ids after the incomplete last level of a source shard that is followed by the levels of another source shard are deleted.
+/// * `index_levels`: for the shards of a vector field index, the levels of the shards of its index, which the shards have to match.
+async fn concatenate_shards(
+    shards: &[ShardArc],
+    index_levels: Option<&[ShardLevels]>,
+    target_path: &Path,
+) -> Result<Vec<ShardLevels>, String> {
+    let mut shard_refs = Vec::new();
+    for shard in shards.iter() {
+        shard_refs.push(shard.read().await);
+    }
+
+    let mut shard_levels = Vec::new();
+    let mut level_offset = 0;
+    for (source_id, shard_ref) in shard_refs.iter().enumerate() {
+        let level_count = shard_ref.level_index.len();
+        if let Some(index_levels) = index_levels
+            && index_levels[source_id].level_count != level_count
+        {
+            return Err(format!(
+                "{} has {} levels, but the shard of its index has {} levels",
+                shard_ref.index_path_string, level_count, index_levels[source_id].level_count
+            ));
+        }
+        shard_levels.push(ShardLevels {
+            level_offset,
+            level_count,
+            live_doc_ids: (0..shard_ref.committed_doc_count)
+                .filter(|doc_id| !shard_ref.delete_hashset.contains(doc_id))
+                .collect(),
+        });
+        level_offset += level_count;
+    }
+
+    let sources: Vec<(&Shard, &ShardLevels)> = shard_refs
+        .iter()
+        .map(|shard_ref| &**shard_ref)
+        .zip(shard_levels.iter())
+        .filter(|(_, levels)| levels.level_count > 0)
+        .collect();
+    let Some(&(first_shard, _)) = sources.first() else {
+        return Ok(shard_levels);
+    };
+    if let Some((shard, _)) = sources
+        .iter()
+        .find(|(shard, _)| shard.longest_field_id != first_shard.longest_field_id)
+    {
+        return Err(format!(
+            "{} has a different longest field than {}, set SchemaField.longest for merging",
+            shard.index_path_string, first_shard.index_path_string
+        ));
+    }
+
+    let level_checksums = concatenate_index_levels(&sources, target_path)?;
+
+    if !first_shard.facets.is_empty() {
+        concatenate_facets(&sources, target_path)?;
+    }
+
+    if first_shard.is_vector_indexing {
+        concatenate_vector_levels(&sources, target_path)?;
+    }
+
+    let mut delete_bytes = Vec::new();
+    for (source_id, (shard, levels)) in sources.iter().enumerate() {
+        let doc_id_offset = levels.level_offset * ROARING_BLOCK_SIZE;
+        let mut deleted_doc_ids: Vec<usize> = shard
+            .delete_hashset
+            .iter()
+            .filter(|doc_id| **doc_id < shard.committed_doc_count)
+            .map(|doc_id| doc_id + doc_id_offset)
+            .collect();
+        if source_id < sources.len() - 1 {
+            deleted_doc_ids.extend(
+                doc_id_offset + shard.committed_doc_count
+                    ..(levels.level_offset + levels.level_count) * ROARING_BLOCK_SIZE,
+            );
+        }
+        deleted_doc_ids.sort_unstable();
+        for doc_id in deleted_doc_ids {
+            delete_bytes.extend_from_slice(&(doc_id as u64).to_le_bytes());
+        }
+    }
+    fs::write(target_path.join(DELETE_FILENAME), delete_bytes).map_err(|e| e.to_string())?;
+
+    write_level_checksums(target_path, &level_checksums)?;
+
+    Ok(shard_levels)
+}
+
+/// Appends the levels of index.bin and docstore.bin of the source shards to the target shard and returns the checksum records of the levels,
+/// hashed as they are written.
+/// The document count and the positions sum in the level headers are cumulative, they are rewritten:
+/// the positions sum is scaled to the document count including the deleted gaps, so that the average document length of BM25 is not lowered.
+fn concatenate_index_levels(
+    sources: &[(&Shard, &ShardLevels)],
+    target_path: &Path,
+) -> Result<Vec<LevelChecksum>, String> {
+    let mut index_writer = FileWriter::open(&target_path.join(INDEX_FILENAME), INDEX_HEADER_SIZE)?;
+    let mut docstore_writer = FileWriter::open(&target_path.join(DOCSTORE_FILENAME), 0)?;
+    let mut level_checksums = Vec::new();
+    let mut merged_doc_count = 0;
+    let mut merged_positions_sum = 0;
+    for (source_id, (shard, levels)) in sources.iter().enumerate() {
+        let index_bytes = map_shard_file(shard, INDEX_FILENAME)?;
+        let docstore_bytes = if shard.stored_field_names.is_empty() {
+            None
+        } else {
+            Some(map_shard_file(shard, DOCSTORE_FILENAME)?)
+        };
+        let vector_size = if shard.is_vector_indexing {
+            shard.vector_record_size().1
+        } else {
+            0
+        };
+
+        // the longest field id precedes the first level
+        let mut index_position = INDEX_HEADER_SIZE as usize + 2;
+        let mut docstore_position = 0;
+        let mut doc_count = 0;
+        let mut positions_sum = 0;
+        for level_id in 0..levels.level_count {
+            let target_level_id = levels.level_offset + level_id;
+            let index_start = index_writer.position;
+            let docstore_start = docstore_writer.position;
+
+            let header_position =
+                index_position + shard.indexed_field_vec.len() * ROARING_BLOCK_SIZE;
+            let segment_head_position = header_position + 16;
+            let blocks_position = segment_head_position + shard.segment_number1 * 8;
+            if blocks_position > index_bytes.len() {
+                return Err(truncated_level_error(shard, INDEX_FILENAME, level_id));
+            }
+            let level_end = blocks_position
+                + (0..shard.segment_number1)
+                    .map(|key0| read_u32(&index_bytes, segment_head_position + key0 * 8) as usize)
+                    .sum::<usize>();
+            if level_end > index_bytes.len() {
+                return Err(truncated_level_error(shard, INDEX_FILENAME, level_id));
+            }
+            doc_count = read_u64(&index_bytes, header_position) as usize;
+            positions_sum = read_u64(&index_bytes, header_position + 8);
+
+            let target_doc_count = levels.level_offset * ROARING_BLOCK_SIZE + doc_count;
+            let target_positions_sum =
+                ((merged_positions_sum + positions_sum) as u128 * target_doc_count as u128
+                    / (merged_doc_count + doc_count).max(1) as u128) as u64;
+
+            let mut level_hasher = LevelHasher::default();
+            let mut level_header = Vec::new();
+            if target_level_id == 0 {
+                level_header.extend_from_slice(&(shard.longest_field_id as u16).to_le_bytes());
+            }
+            level_header.extend_from_slice(&index_bytes[index_position..header_position]);
+            level_header.extend_from_slice(&(target_doc_count as u64).to_le_bytes());
+            level_header.extend_from_slice(&target_positions_sum.to_le_bytes());
+            index_writer.write(&level_header)?;
+            level_hasher.update_index(&level_header);
+            level_hasher.set_index_head(
+                index_writer.position,
+                &index_bytes[segment_head_position..blocks_position],
+            );
+            index_writer.write(&index_bytes[segment_head_position..blocks_position])?;
+            index_writer.write(&index_bytes[blocks_position..level_end])?;
+            level_hasher.update_index(&index_bytes[blocks_position..level_end]);
+            index_position = level_end;
+
+            if let Some(docstore_bytes) = docstore_bytes.as_ref() {
+                let level_start = docstore_position + 4;
+                let level_size = if level_start <= docstore_bytes.len() {
+                    read_u32(docstore_bytes, docstore_position) as usize
+                } else {
+                    0
+                };
+                if level_size < ROARING_BLOCK_SIZE * 4
+                    || level_start + level_size > docstore_bytes.len()
+                {
+                    return Err(truncated_level_error(shard, DOCSTORE_FILENAME, level_id));
+                }
+                let level = &docstore_bytes[level_start..level_start + level_size];
+                docstore_writer.write(&(level_size as u32).to_le_bytes())?;
+
+                let level_doc_count = doc_count - level_id * ROARING_BLOCK_SIZE;
+                level_hasher.start_docstore_level();
+                level_hasher.update_docstore(
+                    &level[..level_doc_count.min(ROARING_BLOCK_SIZE) * 4],
+                    &level[ROARING_BLOCK_SIZE * 4..],
+                );
+                if source_id < sources.len() - 1 && level_doc_count < ROARING_BLOCK_SIZE {
+                    // the deleted documents of the gap after the incomplete level are empty
+                    let mut pointers = level[..ROARING_BLOCK_SIZE * 4].to_vec();
+                    let last_pointer = if level_doc_count == 0 {
+                        (ROARING_BLOCK_SIZE * 4) as u32
+                    } else {
+                        read_u32(&pointers, (level_doc_count - 1) * 4)
+                    };
+                    for doc_id in level_doc_count..ROARING_BLOCK_SIZE {
+                        write_u32(last_pointer, &mut pointers, doc_id * 4);
+                    }
+                    docstore_writer.write(&pointers)?;
+                    docstore_writer.write(&level[ROARING_BLOCK_SIZE * 4..])?;
+                } else {
+                    docstore_writer.write(level)?;
+                }
+                docstore_position = level_start + level_size;
+            }
+
+            level_checksums.push(LevelChecksum::new(
+                index_start..index_writer.position,
+                docstore_start..docstore_writer.position,
+                target_doc_count,
+                vector_size,
+                &level_hasher,
+            ));
+        }
+        merged_doc_count += doc_count;
+        merged_positions_sum += positions_sum;
+    }
+    index_writer.finish()?;
+    docstore_writer.finish()?;
+
+    Ok(level_checksums)
+}
+
+/// Appends the facet records of the levels of the source shards to facet.bin of the target shard and writes the merged facet values to facet.json.
+/// The value ids of string facet fields are remapped to the merged dictionaries.
+fn concatenate_facets(
+    sources: &[(&Shard, &ShardLevels)],
+    target_path: &Path,
+) -> Result<(), String> {
+    let first_shard = sources[0].0;
+    let level_size = first_shard.facets_size_sum * ROARING_BLOCK_SIZE;
+    let mut merged_facets: Vec<FacetField> = first_shard
+        .facets
+        .iter()
+        .map(|facet| FacetField {
+            name: facet.name.clone(),
+            values: IndexMap::new(),
+            min: ValueType::None,
+            max: ValueType::None,
+            offset: facet.offset,
+            field_type: facet.field_type.clone(),
+        })
+        .collect();
+
+    let mut facet_writer = FileWriter::open(&target_path.join(FACET_FILENAME), 0)?;
+    let mut level_bytes = vec![0u8; level_size];
+    for (shard, levels) in sources.iter() {
+        let facets: Vec<FacetField> =
+            read_json(&Path::new(&shard.index_path_string).join(FACET_VALUES_FILENAME))
+                .unwrap_or_default();
+        let value_id_maps = merge_facet_values(&mut merged_facets, facets)?;
+
+        let facet_bytes = map_shard_file(shard, FACET_FILENAME)?;
+        for level_id in 0..levels.level_count {
+            let start = (level_id * level_size).min(facet_bytes.len());
+            let end = ((level_id + 1) * level_size).min(facet_bytes.len());
+            level_bytes.fill(0);
+            level_bytes[..end - start].copy_from_slice(&facet_bytes[start..end]);
+            remap_facet_value_ids(&mut level_bytes, &merged_facets, &value_id_maps);
+            facet_writer.write(&level_bytes)?;
+        }
+    }
+    // facet.bin is one level larger than the committed levels, as after a commit
+    level_bytes.fill(0);
+    facet_writer.write(&level_bytes)?;
+    facet_writer.finish()?;
+
+    let file = File::create(target_path.join(FACET_VALUES_FILENAME)).map_err(|e| e.to_string())?;
+    serde_json::to_writer(file, &merged_facets).map_err(|e| e.to_string())
+}
+
+/// Merges the facet values of a source shard into the merged facet values: the values are added with their counts, minimum and maximum are widened.
+/// Returns per facet field the merged value id of each value id of the source shard for string facet fields, an empty map for other facet fields.
+fn merge_facet_values(
+    merged_facets: &mut [FacetField],
+    facets: Vec<FacetField>,
+) -> Result<Vec<Vec<u32>>, String> {
+    let mut value_id_maps = Vec::new();
+    for (merged_facet, facet) in merged_facets.iter_mut().zip(facets) {
+        if merged_facet.min == ValueType::None
+            || value_ordering(&facet.min, &merged_facet.min) == Some(Ordering::Less)
+        {
+            merged_facet.min = facet.min;
+        }
+        if merged_facet.max == ValueType::None
+            || value_ordering(&facet.max, &merged_facet.max) == Some(Ordering::Greater)
+        {
+            merged_facet.max = facet.max;
+        }
+
+        let mut value_id_map = Vec::with_capacity(facet.values.len());
+        for (key, (value, count)) in facet.values {
+            let entry = merged_facet.values.entry(key);
+            value_id_map.push(entry.index() as u32);
+            entry.or_insert((value, 0)).1 += count;
+        }
+
+        let max_value_count = match merged_facet.field_type {
+            FieldType::String16 | FieldType::StringSet16 => u16::MAX as usize,
+            FieldType::String32 | FieldType::StringSet32 => u32::MAX as usize,
+            _ => {
+                value_id_maps.push(Vec::new());
+                continue;
+            }
+        };
+        if merged_facet.values.len() > max_value_count {
+            return Err(format!(
+                "facet field {} has more than {} distinct values after merging",
+                merged_facet.name, max_value_count
+            ));
+        }
+        value_id_maps.push(value_id_map);
+    }
+    Ok(value_id_maps)
+}
+
+/// Order of two facet minimum or maximum values of the same type, None for different types.
+fn value_ordering(a: &ValueType, b: &ValueType) -> Option<Ordering> {
+    match (a, b) {
+        (ValueType::U8(a), ValueType::U8(b)) => a.partial_cmp(b),
+        (ValueType::U16(a), ValueType::U16(b)) => a.partial_cmp(b),
+        (ValueType::U32(a), ValueType::U32(b)) => a.partial_cmp(b),
+        (ValueType::U64(a), ValueType::U64(b)) => a.partial_cmp(b),
+        (ValueType::I8(a), ValueType::I8(b)) => a.partial_cmp(b),
+        (ValueType::I16(a), ValueType::I16(b)) => a.partial_cmp(b),
+        (ValueType::I32(a), ValueType::I32(b)) => a.partial_cmp(b),
+        (ValueType::I64(a), ValueType::I64(b)) => a.partial_cmp(b),
+        (ValueType::Timestamp(a), ValueType::Timestamp(b)) => a.partial_cmp(b),
+        (ValueType::F32(a), ValueType::F32(b)) => a.partial_cmp(b),
+        (ValueType::F64(a), ValueType::F64(b)) => a.partial_cmp(b),
+        _ => None,
+    }
+}
+
+/// Replaces the value ids of the string facet fields in the facet records of a level with the merged value ids.
+fn remap_facet_value_ids(
+    level_bytes: &mut [u8],
+    facets: &[FacetField],
+    value_id_maps: &[Vec<u32>],
+) {
+    if value_id_maps
+        .iter()
+        .all(|value_id_map| value_id_map.is_empty())
+    {
+        return;
+    }
+    let facets_size_sum = level_bytes.len() / ROARING_BLOCK_SIZE;
+    for record in level_bytes.chunks_exact_mut(facets_size_sum) {
+        for (facet, value_id_map) in facets.iter().zip(value_id_maps.iter()) {
+            match facet.field_type {
+                FieldType::String16 | FieldType::StringSet16 => {
+                    if let Some(value_id) =
+                        value_id_map.get(read_u16(record, facet.offset) as usize)
+                    {
+                        write_u16(*value_id as u16, record, facet.offset);
+                    }
+                }
+                FieldType::String32 | FieldType::StringSet32 => {
+                    if let Some(value_id) =
+                        value_id_map.get(read_u32(record, facet.offset) as usize)
+                    {
+                        write_u32(*value_id, record, facet.offset);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Appends the vector.bin sections, the merged vector levels and the level blocks of hnsw.bin, pq.bin and binary.bin of the source shards to the target shard.
+/// Sources without vectors get empty sections. Level blocks are only carried over if all sources have a block for each level,
+/// as a level file can't have blocks after levels without a block.
+fn concatenate_vector_levels(
+    sources: &[(&Shard, &ShardLevels)],
+    target_path: &Path,
+) -> Result<(), String> {
+    let vector_size = sources[0].0.vector_record_size().1;
+    let mut vector_sections = Vec::new();
+    for (shard, levels) in sources.iter() {
+        let vector_bytes = map_shard_file(shard, VECTOR_FILENAME)?;
+        if vector_bytes.is_empty() {
+            vector_sections.push(None);
+            continue;
+        }
+        let (section_ends, _) = vector_section_ends(&vector_bytes, vector_size, levels.level_count);
+        if section_ends.len() < levels.level_count {
+            return Err(truncated_level_error(
+                shard,
+                VECTOR_FILENAME,
+                section_ends.len(),
+            ));
+        }
+        vector_sections.push(Some((vector_bytes, section_ends[levels.level_count - 1])));
+    }
+    if vector_sections.iter().any(Option::is_some) {
+        let mut vector_writer = FileWriter::open(&target_path.join(VECTOR_FILENAME), 0)?;
+        for ((_, levels), vector_section) in sources.iter().zip(vector_sections.iter()) {
+            match vector_section {
+                Some((vector_bytes, sections_end)) => {
+                    vector_writer.write(&vector_bytes[..*sections_end])?
+                }
+                None => {
+                    for _ in 0..levels.level_count {
+                        vector_writer.write(&0u32.to_le_bytes())?;
+                    }
+                }
+            }
+        }
+        vector_writer.finish()?;
+    }
+
+    let mut merged_vector_levels = AHashMap::new();
+    for (shard, levels) in sources.iter() {
+        for (level_id, merged_vector_level) in
+            load_merged_vector_levels(Path::new(&shard.index_path_string))
+        {
+            if level_id < levels.level_count {
+                merged_vector_levels.insert(
+                    levels.level_offset + level_id,
+                    MergedVectorLevel {
+                        first_level_id: levels.level_offset + merged_vector_level.first_level_id,
+                        level_ids: merged_vector_level
+                            .level_ids
+                            .iter()
+                            .map(|level_id| levels.level_offset as u32 + level_id)
+                            .collect(),
+                    },
+                );
+            }
+        }
+    }
+    if !merged_vector_levels.is_empty() {
+        fs::write(
+            target_path.join(MERGED_VECTOR_LEVELS_FILENAME),
+            merged_vector_levels_bytes(&merged_vector_levels),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    for (filename, header_size, level_size) in LEVEL_FILES {
+        let mut level_blocks = Vec::new();
+        for (shard, levels) in sources.iter() {
+            let level_bytes = map_shard_file(shard, filename)?;
+            let (block_ends, _) = level_block_ends(&level_bytes, header_size, level_size);
+            if block_ends.len() < levels.level_count {
+                break;
+            }
+            level_blocks.push((level_bytes, block_ends[levels.level_count - 1]));
+        }
+        if level_blocks.len() == sources.len() {
+            let mut level_writer = FileWriter::open(&target_path.join(filename), 0)?;
+            for (level_bytes, blocks_end) in level_blocks.iter() {
+                level_writer.write(&level_bytes[..*blocks_end])?;
+            }
+            level_writer.finish()?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Memory maps a file of a source shard for reading.
+fn map_shard_file(shard: &Shard, filename: &str) -> Result<Mmap, String> {
+    let path = Path::new(&shard.index_path_string).join(filename);
+    map_file(&path).ok_or_else(|| format!("{} is missing", path.display()))
+}
+
+fn truncated_level_error(shard: &Shard, filename: &str, level_id: usize) -> String {
+    format!(
+        "{} level {} of {} is truncated",
+        filename, level_id, shard.index_path_string
+    )
+}
+
+/// Buffered writer of a target shard file that keeps track of the write position.
+struct FileWriter {
+    writer: BufWriter<File>,
+    position: u64,
+}
+
+impl FileWriter {
+    /// Opens the file truncated to position, for writing from position on.
+    fn open(path: &Path, position: u64) -> Result<FileWriter, String> {
+        let mut file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|e| e.to_string())?;
+        file.set_len(position).map_err(|e| e.to_string())?;
+        file.seek(SeekFrom::Start(position))
+            .map_err(|e| e.to_string())?;
+        Ok(FileWriter {
+            writer: BufWriter::new(file),
+            position,
+        })
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.writer.write_all(bytes).map_err(|e| e.to_string())?;
+        self.position += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), String> {
+        self.writer.flush().map_err(|e| e.to_string())
+    }
+}
+
+/// Index meta without the properties that may differ between merged indices.
+fn comparable_meta(meta: &Value) -> Value {
+    let mut meta = meta.clone();
+    if let Some(meta) = meta.as_object_mut() {
+        for key in ["id", "name", "access_type"] {
+            meta.remove(key);
+        }
+    }
+    meta
+}
+
+/// Merges term count files (spelling correction dictionary: "term count", completion list: "term:count") by adding up the counts of identical terms.
+fn merge_term_counts(paths: &[PathBuf], target_path: &Path, separator: &str) -> Result<(), String> {
+    let mut term_counts: AHashMap<String, usize> = AHashMap::new();
+    for path in paths.iter() {
+        let file = File::open(path).map_err(|e| e.to_string())?;
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            if let Some((term, count)) = line.rsplit_once(separator)
+                && let Ok(count) = count.trim().parse::<usize>()
+            {
+                *term_counts.entry(term.to_string()).or_insert(0) += count;
+            }
+        }
+    }
+
+    let mut term_counts: Vec<(String, usize)> = term_counts.into_iter().collect();
+    term_counts.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let file = File::create(target_path).map_err(|e| e.to_string())?;
+    let mut writer = BufWriter::new(file);
+    for (term, count) in term_counts.iter() {
+        writeln!(writer, "{}{}{}", term, separator, count).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())
+}
@@ -520,7 +520,13 @@ pub(crate) fn result_ordering_root(
                         (shard2.facets_size_sum * doc_id2) + offset,
                     );
 
-                    let order = morton_ordering(facet_value_1, facet_value_2, base, &field.order);
+                    let order = morton_ordering(
+                        facet_value_1,
+                        facet_value_2,
+                        base,
+                        &field.order,
+                        field.distance_method,
+                    );
 
                     if order != core::cmp::Ordering::Equal {
                         return order;
@@ -1027,8 +1033,13 @@ impl<'a> MinHeap<'a> {
                             (self.index.facets_size_sum * result2.doc_id) + offset,
                         );
 
-                        let order =
-                            morton_ordering(facet_value_1, facet_value_2, base, &field.order);
+                        let order = morton_ordering(
+                            facet_value_1,
+                            facet_value_2,
+                            base,
+                            &field.order,
+                            field.distance_method,
+                        );
 
                         if order != core::cmp::Ordering::Equal {
                             return order;
@@ -0,0 +1,366 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+};
+
+use ahash::{AHashMap, AHashSet};
+
+use crate::{
+    index::{FieldType, Index, Shard},
+    min_heap::Result,
+    search::ResultObject,
+    vector::{
+        ChunkAggregation, Embedding, EmbeddingView, Quantization, SearchVectorShard, read_record,
+    },
+    vector_merge::vector_doc_id,
+    vector_similarity::{
+        AnnMode, QuerySimd, VectorSimilarity, similarity_embedding_view,
+        similarity_embedding_view_simd,
+    },
+};
+
+#[cfg(feature = "vb")]
+use crate::vector::ResultSource;
+
+/// Number of candidate documents retrieved per query vector and shard from the vector index, if not specified.
+const LATE_INTERACTION_CANDIDATES: usize = 100;
+
+/// Query vector prepared for the vector index: embedding with scale, norm, zero_point and sum_q.
+pub(crate) type PreparedQuery = (Embedding, f32, f32, i16, i32);
+
+/// Multi-vector field of the schema (FieldType::MultiVector), whose token vectors are stored in the vector index.
+#[derive(Clone)]
+pub(crate) struct MultiVectorField {
+    pub field: String,
+    pub indexed_field_id: u32,
+    pub boost: f32,
+}
+
+#[derive(PartialEq)]
+struct LateInteractionHit {
+    score: f32,
+    doc_id: usize,
+}
+
+impl Eq for LateInteractionHit {}
+
+impl PartialOrd for LateInteractionHit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LateInteractionHit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| other.doc_id.cmp(&self.doc_id))
+    }
+}
+
+/// Maximum similarity per query vector of the token vectors of a document field seen so far.
+struct MaxSim<'a> {
+    query_vectors: &'a [PreparedQuery],
+    query_simd: Vec<QuerySimd>,
+    max_similarities: AHashMap<(usize, u32), Vec<f32>>,
+}
+
+impl MaxSim<'_> {
+    /// Updates the maximum similarities of the document field with a token vector of the vector index.
+    #[allow(clippy::too_many_arguments)]
+    fn add_vector(
+        &mut self,
+        shard: &Shard,
+        doc_id: usize,
+        field_id: u32,
+        embedding: &EmbeddingView,
+        scale: f32,
+        norm: f32,
+        zero_point: i16,
+        sum_q: i32,
+    ) {
+        let enable_scale = shard.quantization != Quantization::None
+            && shard.vector_similarity != VectorSimilarity::Cosine;
+        let non_affine = shard.max_vector_value == f32::MIN;
+
+        let max_similarities = self
+            .max_similarities
+            .entry((doc_id, field_id))
+            .or_insert_with(|| vec![f32::MIN; self.query_vectors.len()]);
+        for ((query_vector, query_simd), max_similarity) in self
+            .query_vectors
+            .iter()
+            .zip(self.query_simd.iter())
+            .zip(max_similarities.iter_mut())
+        {
+            let scale_norm = enable_scale.then_some((
+                query_vector.1,
+                query_vector.2,
+                query_vector.3,
+                query_vector.4,
+                scale,
+                norm,
+                zero_point,
+                sum_q,
+            ));
+            let similarity = if shard.is_simd {
+                unsafe {
+                    similarity_embedding_view_simd(
+                        query_simd,
+                        embedding,
+                        scale_norm,
+                        shard.vector_similarity,
+                        shard.quantization,
+                        non_affine,
+                    )
+                }
+            } else {
+                similarity_embedding_view(
+                    &query_vector.0,
+                    embedding,
+                    scale_norm,
+                    shard.vector_similarity,
+                    shard.quantization,
+                    non_affine,
+                )
+            };
+            *max_similarity = max_similarity.max(similarity);
+        }
+    }
+}
+
+impl Shard {
+    /// Top-k of the candidate documents by MaxSim of the query vectors and the token vectors of the document, the best field per document (weighted by its boost) counts.
+    /// The token vectors of the candidates are read from the vector index: the committed vector file sections of the levels that contain candidates are scanned,
+    /// and with include_uncommitted the uncommitted vectors.
+    fn search_max_sim_shard(
+        &self,
+        query_vectors: &[PreparedQuery],
+        fields: &[MultiVectorField],
+        candidates: &AHashSet<usize>,
+        length: usize,
+        include_uncommitted: bool,
+    ) -> ResultObject {
+        let mut result_object: ResultObject = Default::default();
+        if length == 0 || candidates.is_empty() {
+            return result_object;
+        }
+
+        let field_boosts: AHashMap<u32, f32> = fields
+            .iter()
+            .map(|field| (field.indexed_field_id, field.boost))
+            .collect();
+        let candidate_levels: AHashSet<usize> =
+            candidates.iter().map(|doc_id| doc_id >> 16).collect();
+        let mut max_sim = MaxSim {
+            query_vectors,
+            query_simd: query_vectors
+                .iter()
+                .map(|query_vector| unsafe { QuerySimd::new(&query_vector.0) })
+                .collect(),
+            max_similarities: AHashMap::new(),
+        };
+
+        let (vector_type, vector_size) = self.vector_record_size();
+        for (level_id, (offset, _, vector_count)) in self.vector_levels().into_iter().enumerate() {
+            let merged_vector_level = self.merged_vector_levels.get(&level_id);
+            if merged_vector_level.is_none() && !candidate_levels.contains(&level_id) {
+                continue;
+            }
+            for vector_index in 0..vector_count {
+                let record = read_record(
+                    &self.vector_file_mmap[offset + vector_index * vector_size..],
+                    self.vector_dimensions,
+                    vector_type,
+                );
+                let doc_id = vector_doc_id(
+                    merged_vector_level,
+                    level_id,
+                    vector_index,
+                    record.header.doc_id,
+                );
+                let field_id = record.header.field_id;
+                if field_boosts.contains_key(&field_id) && candidates.contains(&doc_id) {
+                    max_sim.add_vector(
+                        self,
+                        doc_id,
+                        field_id,
+                        &record.embedding,
+                        record.header.scale,
+                        record.header.norm,
+                        record.header.zero_point,
+                        record.header.sum_q,
+                    );
+                }
+            }
+        }
+
+        if include_uncommitted && self.uncommitted {
+            let level_id = self.committed_level_id();
+            for record in self.block_vector_buffer.iter() {
+                let doc_id = (level_id << 16) | record.doc_id as usize;
+                if field_boosts.contains_key(&record.field_id) && candidates.contains(&doc_id) {
+                    max_sim.add_vector(
+                        self,
+                        doc_id,
+                        record.field_id,
+                        &record.embedding.view(),
+                        record.scale,
+                        record.norm,
+                        record.zero_point,
+                        record.sum_q,
+                    );
+                }
+            }
+        }
+
+        let score_normalization = self.score_normalization();
+        let mut doc_scores: AHashMap<usize, f32> = AHashMap::new();
+        for ((doc_id, field_id), max_similarities) in max_sim.max_similarities.iter() {
+            if !self.delete_hashset.is_empty() && self.delete_hashset.contains(doc_id) {
+                continue;
+            }
+            let score =
+                field_boosts[field_id] * max_similarities.iter().sum::<f32>() * score_normalization;
+            doc_scores
+                .entry(*doc_id)
+                .and_modify(|best_score| *best_score = best_score.max(score))
+                .or_insert(score);
+        }
+
+        let mut top_k: BinaryHeap<Reverse<LateInteractionHit>> = BinaryHeap::new();
+        for (doc_id, score) in doc_scores.iter() {
+            let hit = LateInteractionHit {
+                score: *score,
+                doc_id: *doc_id,
+            };
+            if top_k.len() < length {
+                top_k.push(Reverse(hit));
+            } else if hit > top_k.peek().unwrap().0 {
+                top_k.pop();
+                top_k.push(Reverse(hit));
+            }
+        }
+
+        result_object.results = top_k
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(hit)| Result {
+                doc_id: hit.doc_id,
+                score: hit.score,
+                #[cfg(feature = "vb")]
+                shard_id: self.meta.id as u32,
+                #[cfg(feature = "vb")]
+                vector_score: hit.score,
+                #[cfg(feature = "vb")]
+                source: ResultSource::Vector,
+                ..Default::default()
+            })
+            .collect();
+        result_object.result_count = result_object.results.len();
+        result_object.result_count_total = doc_scores.len();
+        result_object
+    }
+}
+
+impl Index {
+    /// Multi-vector fields to search: the multi-vector fields in field_filter, or all multi-vector fields if field_filter contains none.
+    pub(crate) async fn multi_vector_fields(
+        &self,
+        field_filter: &[String],
+    ) -> Vec<MultiVectorField> {
+        let fields: Vec<MultiVectorField> = self.shard_vec[0]
+            .read()
+            .await
+            .indexed_schema_vec
+            .iter()
+            .filter(|schema_field| schema_field.field_type == FieldType::MultiVector)
+            .map(|schema_field| MultiVectorField {
+                field: schema_field.field.clone(),
+                indexed_field_id: schema_field.indexed_field_id as u32,
+                boost: schema_field.boost,
+            })
+            .collect();
+        let filtered_fields: Vec<MultiVectorField> = fields
+            .iter()
+            .filter(|field| field_filter.contains(&field.field))
+            .cloned()
+            .collect();
+        if filtered_fields.is_empty() {
+            fields
+        } else {
+            filtered_fields
+        }
+    }
+
+    /// Late interaction search (ColBERT-style) over the multi-vector fields of all shards (or only the shard of shard_filter), returns the top-k results by MaxSim with global document ids.
+    /// The vector index serves as candidate generator: per query vector and shard the documents of the candidate_count nearest token vectors are retrieved
+    /// from the clusters (or HNSW graph) selected by ann_mode, only those candidates are scored with MaxSim.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) async fn search_late_interaction(
+        &self,
+        query_vectors: &[PreparedQuery],
+        ann_mode: &AnnMode,
+        candidate_count: usize,
+        fields: &[MultiVectorField],
+        length: usize,
+        include_uncommitted: bool,
+        shard_filter: Option<usize>,
+    ) -> ResultObject {
+        let shard_number = self.shard_number;
+        let mut result_object: ResultObject = Default::default();
+        if query_vectors.is_empty() || fields.is_empty() || !self.is_vector_indexing {
+            return result_object;
+        }
+        let candidate_count = if candidate_count == 0 {
+            LATE_INTERACTION_CANDIDATES.max(length)
+        } else {
+            candidate_count
+        };
+        let field_filter: Vec<String> = fields.iter().map(|field| field.field.clone()).collect();
+
+        for (shard_id, shard) in self.shard_vec.iter().enumerate().filter(|(shard_id, _)| {
+            shard_filter.is_none_or(|shard_filter| shard_filter == *shard_id)
+        }) {
+            let mut candidates: AHashSet<usize> = AHashSet::new();
+            for query_vector in query_vectors.iter() {
+                let rlo_candidates = shard
+                    .search_vector_shard(
+                        Some(query_vector.clone()),
+                        candidate_count,
+                        include_uncommitted,
+                        None,
+                        ann_mode.clone(),
+                        ChunkAggregation::Max,
+                        field_filter.clone(),
+                    )
+                    .await;
+                result_object.observed_vector_count += rlo_candidates.observed_vector_count;
+                result_object.observed_cluster_count += rlo_candidates.observed_cluster_count;
+                candidates.extend(rlo_candidates.results.iter().map(|result| result.doc_id));
+            }
+
+            let rlo_shard = shard.read().await.search_max_sim_shard(
+                query_vectors,
+                fields,
+                &candidates,
+                length,
+                include_uncommitted,
+            );
+            result_object.result_count_total += rlo_shard.result_count_total;
+            result_object
+                .results
+                .extend(rlo_shard.results.into_iter().map(|mut result| {
+                    result.doc_id = (result.doc_id * shard_number) + shard_id;
+                    result
+                }));
+        }
+
+        result_object
+            .results
+            .sort_unstable_by(|a, b| b.score.total_cmp(&a.score));
+        result_object.results.truncate(length);
+        result_object.result_count = result_object.results.len();
+        result_object
+    }
+}
@@ -0,0 +1,318 @@
+use bytemuck::try_cast_slice;
+
+use crate::{
+    index::Shard,
+    utils::{read_u16, read_u32},
+    vector::Embedding,
+    vector_similarity::{VectorSimilarity, dot_f32, euclidean_f32},
+};
+
+/// Size of the per level header: subvectors, centroids per subvector, dimensions, vector count (all u32)
+pub(crate) const PQ_HEADER_SIZE: usize = 16;
+/// Maximum number of centroids per subvector codebook, so that a code fits into one byte
+const PQ_CENTROIDS: usize = 256;
+/// Number of training vectors per centroid sampled for k-means
+const PQ_TRAINING_SAMPLES_PER_CENTROID: usize = 16;
+/// Number of k-means iterations for codebook training
+const PQ_TRAINING_ITERATIONS: usize = 10;
+/// Per vector record prefix: doc_id (u16) and field_id (u16), followed by one code byte per subvector
+const PQ_RECORD_PREFIX_SIZE: usize = 4;
+
+/// start and end dimension of a subvector
+#[inline(always)]
+fn subvector_range(subvector: usize, subvectors: usize, dimensions: usize) -> (usize, usize) {
+    (
+        subvector * dimensions / subvectors,
+        (subvector + 1) * dimensions / subvectors,
+    )
+}
+
+/// Trains the codebook of a single subvector with k-means (Lloyd's algorithm) on a sample of the vectors.
+/// Returns centroids * (end - start) values.
+fn train_codebook(vectors: &[&[f32]], start: usize, end: usize, centroids: usize) -> Vec<f32> {
+    let width = end - start;
+    let sample_size = (centroids * PQ_TRAINING_SAMPLES_PER_CENTROID).min(vectors.len());
+    let sample_step = vectors.len() / sample_size;
+    let sample: Vec<&[f32]> = (0..sample_size)
+        .map(|i| &vectors[i * sample_step][start..end])
+        .collect();
+
+    let centroid_step = sample.len() / centroids;
+    let mut codebook: Vec<f32> = (0..centroids)
+        .flat_map(|c| sample[c * centroid_step].iter().copied())
+        .collect();
+
+    let mut assignments = vec![0usize; sample.len()];
+    for _iteration in 0..PQ_TRAINING_ITERATIONS {
+        for (i, point) in sample.iter().enumerate() {
+            assignments[i] = nearest_centroid(&codebook, width, point);
+        }
+
+        let mut sums = vec![0f32; centroids * width];
+        let mut counts = vec![0usize; centroids];
+        for (point, &centroid) in sample.iter().zip(assignments.iter()) {
+            counts[centroid] += 1;
+            for (sum, value) in sums[centroid * width..(centroid + 1) * width]
+                .iter_mut()
+                .zip(point.iter())
+            {
+                *sum += value;
+            }
+        }
+
+        for centroid in 0..centroids {
+            if counts[centroid] > 0 {
+                for (value, sum) in codebook[centroid * width..(centroid + 1) * width]
+                    .iter_mut()
+                    .zip(sums[centroid * width..(centroid + 1) * width].iter())
+                {
+                    *value = sum / counts[centroid] as f32;
+                }
+            } else {
+                // re-seed empty clusters with a sample point
+                let point = sample[(centroid * 7919) % sample.len()];
+                codebook[centroid * width..(centroid + 1) * width].copy_from_slice(point);
+            }
+        }
+    }
+
+    codebook
+}
+
+#[inline(always)]
+fn nearest_centroid(codebook: &[f32], width: usize, point: &[f32]) -> usize {
+    let mut best_centroid = 0;
+    let mut best_distance = f32::MAX;
+    for (centroid, centroid_vector) in codebook.chunks_exact(width).enumerate() {
+        let distance = euclidean_f32(centroid_vector, point);
+        if distance < best_distance {
+            best_distance = distance;
+            best_centroid = centroid;
+        }
+    }
+    best_centroid
+}
+
+impl Shard {
+    /// Trains the product quantization codebooks for the vectors in the block_vector_buffer and encodes them,
+    /// in the order they are stored in the vector file. Returns the serialized level block:
+    /// header: subvectors, centroids per subvector, dimensions, vector count (u32 little-endian)
+    /// codebooks: per subvector centroids * subvector width f32 values
+    /// records: per vector doc_id (u16), field_id (u16) and one code byte per subvector, padded to a multiple of 4 bytes
+    pub(crate) fn encode_product_quantization(&self, subvectors: usize) -> Vec<u8> {
+        let vectors: Vec<&[f32]> = self
+            .block_vector_buffer
+            .iter()
+            .filter_map(|record| match &record.embedding {
+                Embedding::F32(embedding) => Some(embedding.as_slice()),
+                Embedding::F16(_) | Embedding::BF16(_) | Embedding::I8(_) => None,
+            })
+            .collect();
+        if vectors.len() != self.block_vector_buffer.len() {
+            return Vec::new();
+        }
+
+        let dimensions = self.vector_dimensions;
+        let subvectors = subvectors.clamp(1, dimensions.max(1));
+        let centroids = PQ_CENTROIDS.min(vectors.len());
+
+        let mut bytes = Vec::new();
+        for value in [subvectors, centroids, dimensions, vectors.len()] {
+            bytes.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+        if vectors.is_empty() {
+            return bytes;
+        }
+
+        let codebooks: Vec<Vec<f32>> = (0..subvectors)
+            .map(|subvector| {
+                let (start, end) = subvector_range(subvector, subvectors, dimensions);
+                train_codebook(&vectors, start, end, centroids)
+            })
+            .collect();
+        for codebook in codebooks.iter() {
+            for value in codebook.iter() {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        for (record, vector) in self.block_vector_buffer.iter().zip(vectors.iter()) {
+            bytes.extend_from_slice(&record.doc_id.to_le_bytes());
+            bytes.extend_from_slice(&(record.field_id as u16).to_le_bytes());
+            for (subvector, codebook) in codebooks.iter().enumerate() {
+                let (start, end) = subvector_range(subvector, subvectors, dimensions);
+                bytes.push(nearest_centroid(codebook, end - start, &vector[start..end]) as u8);
+            }
+        }
+        bytes.resize(bytes.len().next_multiple_of(4), 0);
+
+        bytes
+    }
+
+    /// Encodes the level that is currently committed with product quantization,
+    /// and appends it to the product quantization file, or replaces the codes of an incomplete last level.
+    pub(crate) fn commit_product_quantization(&mut self, subvectors: usize) {
+        let bytes = self.encode_product_quantization(subvectors);
+        if bytes.is_empty() {
+            return;
+        }
+        let level_id = self.committed_level_id();
+        self.pq_file.write_level(level_id, &bytes);
+    }
+}
+
+/// Read-only view of the product quantization codebooks and codes of a level, directly on the memory mapped file.
+pub(crate) struct PqLevel<'a> {
+    subvectors: usize,
+    centroids: usize,
+    dimensions: usize,
+    pub vector_count: usize,
+    codebooks: &'a [f32],
+    records: &'a [u8],
+}
+
+impl<'a> PqLevel<'a> {
+    /// bytes: memory mapped product quantization file, starting at the level offset
+    pub(crate) fn new(bytes: &'a [u8]) -> Option<Self> {
+        let (subvectors, centroids, dimensions, vector_count) = level_header(bytes);
+        let codebooks_end = PQ_HEADER_SIZE + 4 * centroids * dimensions;
+        let records_end = codebooks_end + vector_count * (PQ_RECORD_PREFIX_SIZE + subvectors);
+        Some(PqLevel {
+            subvectors,
+            centroids,
+            dimensions,
+            vector_count,
+            codebooks: try_cast_slice(bytes.get(PQ_HEADER_SIZE..codebooks_end)?).ok()?,
+            records: bytes.get(codebooks_end..records_end)?,
+        })
+    }
+
+    /// Asymmetric distance computation (ADC) lookup table:
+    /// similarity of each query subvector with each centroid of the corresponding codebook.
+    /// The approximate similarity of a vector is the sum of the table entries of its codes.
+    pub(crate) fn similarity_table(
+        &self,
+        query: &[f32],
+        vector_similarity: VectorSimilarity,
+    ) -> Vec<f32> {
+        let mut table = Vec::with_capacity(self.subvectors * self.centroids);
+        for subvector in 0..self.subvectors {
+            let (start, end) = subvector_range(subvector, self.subvectors, self.dimensions);
+            let width = end - start;
+            let codebook =
+                &self.codebooks[self.centroids * start..self.centroids * (start + width)];
+            for centroid in codebook.chunks_exact(width) {
+                table.push(match vector_similarity {
+                    VectorSimilarity::Euclidean => -euclidean_f32(&query[start..end], centroid),
+                    VectorSimilarity::Dot | VectorSimilarity::Cosine => {
+                        dot_f32(&query[start..end], centroid)
+                    }
+                });
+            }
+        }
+        table
+    }
+
+    /// approximate similarity of a vector, from the similarity table and its codes
+    #[inline(always)]
+    pub(crate) fn similarity(&self, table: &[f32], vector_id: usize) -> f32 {
+        let record_size = PQ_RECORD_PREFIX_SIZE + self.subvectors;
+        let codes = &self.records
+            [vector_id * record_size + PQ_RECORD_PREFIX_SIZE..(vector_id + 1) * record_size];
+        codes
+            .iter()
+            .enumerate()
+            .map(|(subvector, &code)| table[subvector * self.centroids + code as usize])
+            .sum()
+    }
+
+    /// doc_id and field_id of a vector, without accessing the full precision vector file
+    #[inline(always)]
+    pub(crate) fn doc_field_id(&self, vector_id: usize) -> (u16, u16) {
+        let record_offset = vector_id * (PQ_RECORD_PREFIX_SIZE + self.subvectors);
+        (
+            read_u16(self.records, record_offset),
+            read_u16(self.records, record_offset + 2),
+        )
+    }
+}
+
+fn level_header(bytes: &[u8]) -> (usize, usize, usize, usize) {
+    (
+        read_u32(bytes, 0) as usize,
+        read_u32(bytes, 4) as usize,
+        read_u32(bytes, 8) as usize,
+        read_u32(bytes, 12) as usize,
+    )
+}
+
+/// Offsets of the level blocks within the memory mapped product quantization file.
+pub(crate) fn pq_level_offsets(bytes: &[u8]) -> Vec<usize> {
+    let mut level_offsets = Vec::new();
+    let mut offset = 0;
+    while offset + PQ_HEADER_SIZE <= bytes.len() {
+        level_offsets.push(offset);
+        offset += pq_level_size(&bytes[offset..]);
+    }
+    level_offsets
+}
+
+/// Size of the level block at the start of bytes, from its header.
+pub(crate) fn pq_level_size(bytes: &[u8]) -> usize {
+    let (subvectors, centroids, dimensions, vector_count) = level_header(bytes);
+    PQ_HEADER_SIZE
+        + 4 * centroids * dimensions
+        + (vector_count * (PQ_RECORD_PREFIX_SIZE + subvectors)).next_multiple_of(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codebook_reconstruction() {
+        // uniform values in [-1,1)^4 per subvector, 256 centroids
+        let (dimensions, subvectors) = (16, 4);
+        let mut state = 3u64;
+        let vectors: Vec<Vec<f32>> = (0..2000)
+            .map(|_| {
+                (0..dimensions)
+                    .map(|_| {
+                        state = state
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        ((state >> 40) as f32 / (1u64 << 23) as f32) - 1.0
+                    })
+                    .collect()
+            })
+            .collect();
+        let vector_slices: Vec<&[f32]> = vectors.iter().map(|vector| vector.as_slice()).collect();
+
+        let mut squared_error = 0.0;
+        let mut squared_norm = 0.0;
+        for subvector in 0..subvectors {
+            let (start, end) = subvector_range(subvector, subvectors, dimensions);
+            assert_eq!(end - start, 4);
+            let codebook = train_codebook(&vector_slices, start, end, PQ_CENTROIDS);
+            assert_eq!(codebook.len(), PQ_CENTROIDS * 4);
+            for vector in vectors.iter() {
+                let point = &vector[start..end];
+                let code = nearest_centroid(&codebook, 4, point);
+                let centroid = &codebook[code * 4..(code + 1) * 4];
+                // the nearest centroid is not farther than any other centroid
+                let distance = euclidean_f32(centroid, point);
+                assert!(
+                    codebook
+                        .chunks_exact(4)
+                        .all(|other| euclidean_f32(other, point) >= distance)
+                );
+                squared_error += distance;
+                squared_norm += dot_f32(point, point);
+            }
+        }
+
+        // reconstruction error relative to the vector energy
+        let relative_error = squared_error / squared_norm;
+        assert!(relative_error < 0.08, "relative error {relative_error}");
+    }
+}
@@ -0,0 +1,570 @@
+use std::collections::HashSet;
+
+use ahash::{AHashMap, AHashSet};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    index::{FieldType, Index, IndexArc},
+    min_heap::Result,
+    search::{
+        FacetFilter, QueryFacet, QueryRewriting, QueryType, ResultObject, ResultSort, ResultType,
+        SearchMode, SearchShards,
+    },
+    significant_terms::{background_count, unique_single_terms},
+    vector::Embedding,
+    vector_field::embedding_dimensions,
+};
+#[cfg(feature = "vb")]
+use crate::{
+    vector::Quantization,
+    vector_similarity::{VectorSimilarity, normalize_f32},
+};
+
+/// Pseudo-relevance feedback: the query is expanded with the top-ranked documents of an initial retrieval, and then re-run.
+/// Improves the recall of short and ambiguous queries, at the cost of a second retrieval.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct QueryExpansion {
+    /// Number of top-ranked results of the initial retrieval that are used as feedback documents (default=10).
+    #[serde(default = "depth_default")]
+    #[schema(required = false, minimum = 1, default = 10, example = 10)]
+    pub depth: usize,
+    /// Weight of the feedback (default=0.5): 0.0 keeps the original query, 1.0 uses the feedback only.
+    /// Lexical expansion: the result scores are interpolated as (1 - weight) * original query score + weight * expansion terms score, both max normalized.
+    /// Vector expansion: the expanded query vector is (1 - weight) * query vector + weight * average of the feedback document embeddings.
+    #[serde(default = "weight_default")]
+    #[schema(
+        required = false,
+        minimum = 0.0,
+        maximum = 1.0,
+        default = 0.5,
+        example = 0.5
+    )]
+    pub weight: f32,
+    /// Lexical expansion (RM3) for SearchMode::Lexical and SearchMode::Hybrid: number of expansion terms extracted from the feedback documents (default=10), 0 disables lexical expansion.
+    /// Terms are ranked by the relevance model P(term|feedback), the term frequencies in the feedback documents weighted by their normalized result scores,
+    /// multiplied by the inverse document frequency of the term in the index. Terms of the original query are not added.
+    #[serde(default = "terms_default")]
+    #[schema(required = false, minimum = 0, default = 10, example = 10)]
+    pub terms: usize,
+    /// Stored and lexically indexed text fields to extract the expansion terms from. If empty then all stored and lexically indexed text fields are used.
+    #[serde(default)]
+    pub fields: Vec<String>,
+    /// Vector expansion (Rocchio) for SearchMode::Vector and SearchMode::Hybrid (default=true): the query vector is moved towards the average of the stored embeddings
+    /// of the matched chunks of the feedback documents. Requires a query vector or an index with inference, not supported for Quantization::ScalarQuantizationI8 and Quantization::TurboQuantI8.
+    #[serde(default = "vector_default")]
+    #[schema(required = false, default = true, example = true)]
+    pub vector: bool,
+}
+
+fn depth_default() -> usize {
+    10
+}
+
+fn weight_default() -> f32 {
+    0.5
+}
+
+fn terms_default() -> usize {
+    10
+}
+
+fn vector_default() -> bool {
+    true
+}
+
+impl Default for QueryExpansion {
+    fn default() -> Self {
+        QueryExpansion {
+            depth: depth_default(),
+            weight: weight_default(),
+            terms: terms_default(),
+            fields: Vec::new(),
+            vector: vector_default(),
+        }
+    }
+}
+
+/// Search with pseudo-relevance feedback: runs an initial retrieval, expands the query with the top-ranked documents, and re-runs the expanded query.
+#[allow(async_fn_in_trait)]
+#[allow(clippy::too_many_arguments)]
+pub trait SearchExpanded {
+    /// Search with pseudo-relevance feedback (query expansion). The parameters are those of Search::search, plus:
+    /// * `query_expansion`: Feedback depth and weight, lexical (RM3) and vector (Rocchio) expansion.
+    /// * `routing`: Optional routing hint, the value of the routing field (SchemaField.routing): the initial retrieval and the re-run search only the shard of the routing value, see SearchRouted::search_routed.
+    ///
+    /// The initial retrieval returns max(depth, offset+length) results, the top depth results are the feedback documents.
+    /// Lexical expansion re-runs the expansion terms as union query and interpolates its results with those of the initial retrieval,
+    /// vector expansion re-runs the search with the expanded query vector. In SearchMode::Hybrid the re-run combines the expansion terms and the expanded query vector.
+    /// The returned ResultObject.query contains the query string followed by the expansion terms, which are also added to ResultObject.query_terms.
+    /// Facets, result_count_total and suggestions are those of the initial retrieval.
+    /// If the initial retrieval returns no results, or no expansion is possible, the results of the initial retrieval are returned.
+    async fn search_expanded(
+        &self,
+        query_string: String,
+        query_vector: Option<Embedding>,
+        query_type_default: QueryType,
+        search_mode: SearchMode,
+        enable_empty_query: bool,
+        offset: usize,
+        length: usize,
+        result_type: ResultType,
+        include_uncommitted: bool,
+        field_filter: Vec<String>,
+        query_facets: Vec<QueryFacet>,
+        facet_filter: Vec<FacetFilter>,
+        result_sort: Vec<ResultSort>,
+        query_rewriting: QueryRewriting,
+        query_expansion: &QueryExpansion,
+        routing: Option<&str>,
+    ) -> ResultObject;
+}
+
+impl SearchExpanded for IndexArc {
+    async fn search_expanded(
+        &self,
+        query_string: String,
+        query_vector: Option<Embedding>,
+        query_type_default: QueryType,
+        search_mode: SearchMode,
+        enable_empty_query: bool,
+        offset: usize,
+        length: usize,
+        result_type: ResultType,
+        include_uncommitted: bool,
+        field_filter: Vec<String>,
+        query_facets: Vec<QueryFacet>,
+        facet_filter: Vec<FacetFilter>,
+        result_sort: Vec<ResultSort>,
+        query_rewriting: QueryRewriting,
+        query_expansion: &QueryExpansion,
+        routing: Option<&str>,
+    ) -> ResultObject {
+        let shard_filter = match routing {
+            Some(routing) => self.read().await.routing_shard_filter(routing),
+            None => None,
+        };
+        let is_lexical = matches!(search_mode, SearchMode::Lexical | SearchMode::Hybrid { .. });
+        let is_vector = matches!(
+            search_mode,
+            SearchMode::Vector { .. } | SearchMode::Hybrid { .. }
+        );
+        if result_type == ResultType::Count
+            || query_expansion.depth == 0
+            || (!is_lexical && !is_vector)
+        {
+            return self
+                .search_shards(
+                    query_string,
+                    query_vector,
+                    query_type_default,
+                    search_mode,
+                    enable_empty_query,
+                    offset,
+                    length,
+                    result_type,
+                    include_uncommitted,
+                    field_filter,
+                    query_facets,
+                    facet_filter,
+                    result_sort,
+                    query_rewriting,
+                    shard_filter,
+                )
+                .await;
+        }
+
+        let mut result_object = self
+            .search_shards(
+                query_string,
+                query_vector.clone(),
+                query_type_default.clone(),
+                search_mode.clone(),
+                enable_empty_query,
+                0,
+                (offset + length).max(query_expansion.depth),
+                result_type,
+                include_uncommitted,
+                field_filter.clone(),
+                query_facets,
+                facet_filter.clone(),
+                result_sort.clone(),
+                query_rewriting,
+                shard_filter,
+            )
+            .await;
+
+        let weight = query_expansion.weight.clamp(0.0, 1.0);
+        let feedback_len = query_expansion.depth.min(result_object.results.len());
+        let (expansion_terms, original_query_vector, expanded_query_vector) = if feedback_len > 0 {
+            let index_ref = self.read().await;
+            let feedback = &result_object.results[..feedback_len];
+            let expansion_terms = if is_lexical && query_expansion.terms > 0 {
+                expansion_terms(
+                    &index_ref,
+                    &result_object.query,
+                    feedback,
+                    &query_expansion.fields,
+                    query_expansion.terms,
+                )
+                .await
+            } else {
+                Vec::new()
+            };
+            let original_query_vector = if is_vector {
+                query_embedding(&index_ref, &result_object.query, query_vector).await
+            } else {
+                None
+            };
+            #[cfg(feature = "vb")]
+            let expanded_query_vector = if query_expansion.vector
+                && let Some(original_query_vector) = original_query_vector.as_ref()
+            {
+                expanded_query_vector(
+                    &index_ref,
+                    original_query_vector,
+                    feedback,
+                    weight,
+                    include_uncommitted,
+                )
+                .await
+            } else {
+                None
+            };
+            #[cfg(not(feature = "vb"))]
+            let expanded_query_vector: Option<Embedding> = None;
+            (
+                expansion_terms,
+                original_query_vector,
+                expanded_query_vector,
+            )
+        } else {
+            (Vec::new(), None, None)
+        };
+
+        if expansion_terms.is_empty() && expanded_query_vector.is_none() {
+            result_object.results = result_object
+                .results
+                .into_iter()
+                .skip(offset)
+                .take(length)
+                .collect();
+            result_object.result_count = result_object.results.len();
+            return result_object;
+        }
+
+        let (rerun_query_string, rerun_query_type) = if expansion_terms.is_empty() {
+            (result_object.query.clone(), query_type_default)
+        } else {
+            (expansion_terms.join(" "), QueryType::Union)
+        };
+        let rlo_rerun = self
+            .search_shards(
+                rerun_query_string,
+                expanded_query_vector.or(original_query_vector),
+                rerun_query_type,
+                search_mode,
+                false,
+                0,
+                offset + length,
+                ResultType::Topk,
+                include_uncommitted,
+                field_filter,
+                Vec::new(),
+                facet_filter,
+                result_sort,
+                QueryRewriting::SearchOnly,
+                shard_filter,
+            )
+            .await;
+
+        let results = if expansion_terms.is_empty() {
+            rlo_rerun.results
+        } else {
+            result_object.query = [result_object.query.as_str(), &expansion_terms.join(" ")]
+                .join(" ")
+                .trim()
+                .to_string();
+            result_object
+                .query_terms
+                .extend(expansion_terms.iter().cloned());
+            interpolate(&result_object.results, &rlo_rerun.results, weight)
+        };
+
+        result_object.observed_vector_count += rlo_rerun.observed_vector_count;
+        result_object.observed_cluster_count += rlo_rerun.observed_cluster_count;
+        result_object.results = results.into_iter().skip(offset).take(length).collect();
+        result_object.result_count = result_object.results.len();
+        result_object
+    }
+}
+
+/// Expansion terms (RM3) of the feedback documents, ranked by relevance model P(term|feedback) * inverse document frequency.
+async fn expansion_terms(
+    index_ref: &Index,
+    query_string: &str,
+    feedback: &[Result],
+    fields: &[String],
+    length: usize,
+) -> Vec<String> {
+    let field_names: Vec<String> = if fields.is_empty() {
+        index_ref
+            .schema_map
+            .values()
+            .filter(|schema_field| {
+                schema_field.store
+                    && schema_field.index_lexical
+                    && schema_field.field_type == FieldType::Text
+            })
+            .map(|schema_field| schema_field.field.clone())
+            .collect()
+    } else {
+        fields.to_vec()
+    };
+    let fields_hashset: HashSet<String> = HashSet::from_iter(field_names.iter().cloned());
+
+    let query_terms: AHashSet<String> = {
+        let shard_ref = index_ref.shard_vec[0].read().await;
+        unique_single_terms(&shard_ref, query_string)
+            .await
+            .into_iter()
+            .map(|(term, _, _, _)| term)
+            .collect()
+    };
+
+    let score_sum: f32 = feedback.iter().map(|result| result.score).sum();
+    let is_score_weighted = feedback.iter().all(|result| result.score > 0.0);
+
+    let mut relevance_model: AHashMap<String, (f64, u32, u64)> = AHashMap::new();
+    for result in feedback.iter() {
+        let Ok(doc) = index_ref
+            .get_document(result.doc_id, true, &None, &fields_hashset, &[])
+            .await
+        else {
+            continue;
+        };
+
+        let shard_ref = index_ref.shard_vec[result.doc_id % index_ref.shard_number]
+            .read()
+            .await;
+        let mut doc_terms: AHashMap<String, (usize, u32, u64)> = AHashMap::new();
+        for field_name in field_names.iter() {
+            let Some(text) = doc.get(field_name).and_then(|value| value.as_str()) else {
+                continue;
+            };
+            for (term, key0, key_hash, term_frequency) in
+                unique_single_terms(&shard_ref, text).await
+            {
+                doc_terms.entry(term).or_insert((0, key0, key_hash)).0 += term_frequency;
+            }
+        }
+
+        let doc_length: usize = doc_terms
+            .values()
+            .map(|(term_frequency, _, _)| term_frequency)
+            .sum();
+        if doc_length == 0 {
+            continue;
+        }
+        let doc_weight = if is_score_weighted {
+            result.score / score_sum
+        } else {
+            1.0 / feedback.len() as f32
+        } as f64;
+        for (term, (term_frequency, key0, key_hash)) in doc_terms {
+            if query_terms.contains(&term) {
+                continue;
+            }
+            relevance_model
+                .entry(term)
+                .or_insert((0.0, key0, key_hash))
+                .0 += doc_weight * term_frequency as f64 / doc_length as f64;
+        }
+    }
+
+    let doc_count = index_ref.indexed_doc_count().await as f64;
+    let mut expansion_terms: Vec<(String, f64)> = Vec::with_capacity(relevance_model.len());
+    for (term, (relevance, key0, key_hash)) in relevance_model {
+        let background_count = background_count(index_ref, &term, key0, key_hash).await as f64;
+        let idf = (1.0 + (doc_count - background_count + 0.5) / (background_count + 0.5)).ln();
+        expansion_terms.push((term, relevance * idf));
+    }
+
+    expansion_terms.sort_unstable_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.0.cmp(&b.0))
+    });
+    expansion_terms
+        .into_iter()
+        .take(length)
+        .map(|(term, _)| term)
+        .collect()
+}
+
+/// Query vector of the index: the query vector if it has the dimensions of the index, otherwise the embedding of the query string if the index has inference.
+async fn query_embedding(
+    index_ref: &Index,
+    query_string: &str,
+    query_vector: Option<Embedding>,
+) -> Option<Embedding> {
+    if !index_ref.is_vector_indexing {
+        return None;
+    }
+    match query_vector {
+        Some(query_vector)
+            if embedding_dimensions(&query_vector) == index_ref.vector_dimensions_original =>
+        {
+            Some(query_vector)
+        }
+        Some(_) => None,
+        None => {
+            let embedding_provider = index_ref.embedding_provider_option.as_ref()?;
+            match embedding_provider.embed(&[query_string.to_string()]).await {
+                Ok(mut embeddings) if !embeddings.is_empty() => {
+                    Some(Embedding::F32(embeddings.remove(0)))
+                }
+                Ok(_) => None,
+                Err(e) => {
+                    println!("Unable to embed query: {}", e);
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Expanded query vector (Rocchio): (1 - weight) * query vector + weight * average of the stored embeddings of the matched chunks of the feedback documents.
+/// Only embeddings stored in the index itself are used, not those of vector fields with their own inference.
+#[cfg(feature = "vb")]
+async fn expanded_query_vector(
+    index_ref: &Index,
+    query_vector: &Embedding,
+    feedback: &[Result],
+    weight: f32,
+    include_uncommitted: bool,
+) -> Option<Embedding> {
+    // scalar quantized and TurboQuant embeddings are stored in a SIMD lane layout (and rotated), they can't be mapped back to the query vector space
+    if matches!(
+        index_ref.quantization,
+        Quantization::ScalarQuantizationI8 | Quantization::TurboQuantI8
+    ) {
+        return None;
+    }
+    let is_cosine = index_ref.vector_similarity == VectorSimilarity::Cosine;
+
+    let mut centroid = vec![0.0f32; index_ref.vector_dimensions_original];
+    let mut count = 0;
+    for ((embedding, ..), _, _) in index_ref
+        .result_embeddings(feedback, include_uncommitted)
+        .await
+        .into_iter()
+        .flatten()
+        .filter(|(_, _, source)| *source == 0)
+    {
+        let mut values = embedding.to_f32();
+        if values.len() != centroid.len() {
+            continue;
+        }
+        if is_cosine {
+            normalize_f32(&mut values);
+        }
+        for (sum, value) in centroid.iter_mut().zip(values) {
+            *sum += value;
+        }
+        count += 1;
+    }
+    if count == 0 {
+        return None;
+    }
+
+    let mut query_values = query_vector.to_f32();
+    if is_cosine {
+        normalize_f32(&mut query_values);
+    }
+    let mut expanded: Vec<f32> = query_values
+        .iter()
+        .zip(centroid.iter())
+        .map(|(query_value, sum)| (1.0 - weight) * query_value + weight * sum / count as f32)
+        .collect();
+    if is_cosine {
+        normalize_f32(&mut expanded);
+    }
+
+    Some(match query_vector {
+        Embedding::I8(_) => Embedding::I8(
+            expanded
+                .iter()
+                .map(|value| value.round().clamp(-128.0, 127.0) as i8)
+                .collect(),
+        ),
+        _ => Embedding::F32(expanded),
+    })
+}
+
+/// Interpolates the max normalized scores of the initial results and the feedback results: (1 - weight) * initial + weight * feedback.
+fn interpolate(initial: &[Result], feedback: &[Result], weight: f32) -> Vec<Result> {
+    let max_score = |results: &[Result]| {
+        results
+            .iter()
+            .map(|result| result.score)
+            .fold(0.0f32, f32::max)
+    };
+    let (initial_max, feedback_max) = (max_score(initial), max_score(feedback));
+
+    let mut interpolated: AHashMap<usize, Result> = AHashMap::new();
+    for (results, result_weight, max) in [
+        (initial, 1.0 - weight, initial_max),
+        (feedback, weight, feedback_max),
+    ] {
+        if max <= 0.0 {
+            continue;
+        }
+        for result in results.iter() {
+            let score = result_weight * result.score / max;
+            interpolated
+                .entry(result.doc_id)
+                .and_modify(|interpolated_result| interpolated_result.score += score)
+                .or_insert(Result { score, ..*result });
+        }
+    }
+
+    let mut results: Vec<Result> = interpolated.into_values().collect();
+    results.sort_unstable_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.doc_id.cmp(&b.doc_id))
+    });
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolation() {
+        let results = |scores: &[(usize, f32)]| {
+            scores
+                .iter()
+                .map(|(doc_id, score)| Result {
+                    doc_id: *doc_id,
+                    score: *score,
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>()
+        };
+        // max normalized: initial 1.0, 0.5; feedback 1.0, 0.25, 0.25
+        let interpolated = interpolate(
+            &results(&[(1, 4.0), (2, 2.0)]),
+            &results(&[(3, 8.0), (2, 2.0), (4, 2.0)]),
+            0.25,
+        );
+        let scores: Vec<(usize, f32)> = interpolated
+            .iter()
+            .map(|result| (result.doc_id, result.score))
+            .collect();
+        assert_eq!(scores, vec![(1, 0.75), (2, 0.4375), (3, 0.25), (4, 0.0625)]);
+    }
+}
@@ -0,0 +1,569 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+use ahash::AHashMap;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use num::Bounded;
+
+use crate::{
+    index::{FieldType, SchemaField},
+    search::{FacetFilter, FilterSparse},
+};
+
+/// Half-open value range [start, end) of a numerical facet field.
+/// Integer and timestamp fields are represented as i128, to cover the whole u64 and i64 domain.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ValueRange {
+    Integer(i128, i128),
+    Float(f64, f64),
+}
+
+/// Range filter of the range clauses of a facet field in the query string.
+/// Unlike the half-open ranges of FacetFilter, the range is converted into an inclusive range of the field type,
+/// so that a clause ending at the maximum value of the type (`u8_field:>=255`) includes it.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct RangeFilter {
+    pub field: String,
+    pub field_type: FieldType,
+    range: ValueRange,
+}
+
+impl RangeFilter {
+    /// Inclusive range of the facet field type, an empty range if the range is outside of the value domain of the type.
+    pub(crate) fn filter_sparse(&self) -> FilterSparse {
+        match (&self.field_type, self.range) {
+            (FieldType::U8, ValueRange::Integer(start, end)) => {
+                FilterSparse::U8(integer_range(start, end))
+            }
+            (FieldType::U16, ValueRange::Integer(start, end)) => {
+                FilterSparse::U16(integer_range(start, end))
+            }
+            (FieldType::U32, ValueRange::Integer(start, end)) => {
+                FilterSparse::U32(integer_range(start, end))
+            }
+            (FieldType::U64, ValueRange::Integer(start, end)) => {
+                FilterSparse::U64(integer_range(start, end))
+            }
+            (FieldType::I8, ValueRange::Integer(start, end)) => {
+                FilterSparse::I8(integer_range(start, end))
+            }
+            (FieldType::I16, ValueRange::Integer(start, end)) => {
+                FilterSparse::I16(integer_range(start, end))
+            }
+            (FieldType::I32, ValueRange::Integer(start, end)) => {
+                FilterSparse::I32(integer_range(start, end))
+            }
+            (FieldType::I64, ValueRange::Integer(start, end)) => {
+                FilterSparse::I64(integer_range(start, end))
+            }
+            (FieldType::Timestamp, ValueRange::Integer(start, end)) => {
+                FilterSparse::Timestamp(integer_range(start, end))
+            }
+            (FieldType::F32, ValueRange::Float(start, end)) => {
+                FilterSparse::F32(start as f32..end as f32)
+            }
+            (FieldType::F64, ValueRange::Float(start, end)) => FilterSparse::F64(start..end),
+            _ => FilterSparse::None,
+        }
+    }
+}
+
+/// Range clause bound: value string and whether the bound is inclusive.
+type ClauseBound<'a> = Option<(&'a str, bool)>;
+
+impl ValueRange {
+    /// Converts the lower and upper clause bounds into a half-open value range.
+    /// Each bound value covers the smallest representable range: one integer, one float step, one second, or one day for dates without time.
+    fn from_bounds(
+        lower: ClauseBound,
+        upper: ClauseBound,
+        field_type: &FieldType,
+    ) -> Option<ValueRange> {
+        let mut value_range = match field_type {
+            FieldType::F32 | FieldType::F64 => ValueRange::Float(f64::NEG_INFINITY, f64::INFINITY),
+            _ => ValueRange::Integer(i128::MIN, i128::MAX),
+        };
+
+        if let Some((value, inclusive)) = lower {
+            value_range = match (value_range, parse_value(value, field_type)?) {
+                (ValueRange::Integer(_, end), ValueRange::Integer(start, next)) => {
+                    ValueRange::Integer(if inclusive { start } else { next }, end)
+                }
+                (ValueRange::Float(_, end), ValueRange::Float(start, next)) => {
+                    ValueRange::Float(if inclusive { start } else { next }, end)
+                }
+                _ => return None,
+            };
+        }
+
+        if let Some((value, inclusive)) = upper {
+            value_range = match (value_range, parse_value(value, field_type)?) {
+                (ValueRange::Integer(start, _), ValueRange::Integer(end, next)) => {
+                    ValueRange::Integer(start, if inclusive { next } else { end })
+                }
+                (ValueRange::Float(start, _), ValueRange::Float(end, next)) => {
+                    ValueRange::Float(start, if inclusive { next } else { end })
+                }
+                _ => return None,
+            };
+        }
+
+        Some(value_range)
+    }
+
+    fn intersect(self, other: ValueRange) -> ValueRange {
+        match (self, other) {
+            (ValueRange::Integer(s1, e1), ValueRange::Integer(s2, e2)) => {
+                ValueRange::Integer(s1.max(s2), e1.min(e2))
+            }
+            (ValueRange::Float(s1, e1), ValueRange::Float(s2, e2)) => {
+                ValueRange::Float(s1.max(s2), e1.min(e2))
+            }
+            _ => self,
+        }
+    }
+}
+
+/// Extracts numerical and date range clauses from the query string and translates them into facet filters:
+/// * `field:[10 TO 50]` inclusive range, `field:{10 TO 50}` exclusive range, mixed `field:[10 TO 50}`, `*` for an open bound.
+/// * `field:>10`, `field:>=10`, `field:<10`, `field:<=10`
+/// * `field:10..50` inclusive range, `*` for an open bound.
+/// * `field:42` exact value
+///
+/// Only facet fields of numerical or timestamp type are recognized, all other clauses remain part of the query string.
+/// Timestamp values are either Unix timestamps or ISO-8601 dates (`2024-01-01`), date-times (`2024-01-01T10:00:00`) or RFC 3339 date-times (`2024-01-01T10:00:00+02:00`).
+/// A date without time covers the whole day: `date:<=2024-01-31` includes all of January 31.
+/// Multiple clauses for the same field, and an explicit facet filter for the same field, are intersected:
+/// the explicit facet filter is removed from facet_filter and its half-open range is part of the range filter.
+/// Returns the query string without the extracted range clauses, and the range filters.
+pub(crate) fn extract_range_filters(
+    query_string: &str,
+    schema_map: &HashMap<String, SchemaField>,
+    facet_filter: &mut Vec<FacetFilter>,
+) -> (String, Vec<RangeFilter>) {
+    if !query_string.contains(':') {
+        return (query_string.to_string(), Vec::new());
+    }
+
+    let mut field_ranges: AHashMap<&str, (FieldType, ValueRange)> = AHashMap::new();
+    let mut query = String::with_capacity(query_string.len());
+    let mut in_phrase = false;
+    let mut pos = 0;
+    while pos < query_string.len() {
+        let remainder = &query_string[pos..];
+        let first_char = remainder.chars().next().unwrap();
+        if first_char.is_whitespace() {
+            query.push(first_char);
+            pos += first_char.len_utf8();
+            continue;
+        }
+
+        let token_len = remainder
+            .find(char::is_whitespace)
+            .unwrap_or(remainder.len());
+        let token = &remainder[..token_len];
+
+        if !in_phrase
+            && let Some((field, field_type, value_range, clause_len)) =
+                parse_range_clause(remainder, token, schema_map)
+        {
+            field_ranges
+                .entry(field)
+                .and_modify(|(_, range)| *range = range.intersect(value_range))
+                .or_insert((field_type, value_range));
+            pos += clause_len;
+            continue;
+        }
+
+        in_phrase ^= token.matches('"').count() % 2 == 1;
+        query.push_str(token);
+        pos += token_len;
+    }
+
+    if field_ranges.is_empty() {
+        return (query_string.to_string(), Vec::new());
+    }
+
+    let mut range_filters = Vec::with_capacity(field_ranges.len());
+    for (field, (field_type, mut value_range)) in field_ranges {
+        if let Some(index) = facet_filter
+            .iter()
+            .position(|filter| facet_filter_field(filter) == Some(field))
+        {
+            if let Some(explicit_range) = facet_filter_range(&facet_filter[index]) {
+                value_range = value_range.intersect(explicit_range);
+                facet_filter.remove(index);
+            } else {
+                continue;
+            }
+        }
+        range_filters.push(RangeFilter {
+            field: field.to_string(),
+            field_type,
+            range: value_range,
+        });
+    }
+
+    (
+        query.split_whitespace().collect::<Vec<_>>().join(" "),
+        range_filters,
+    )
+}
+
+/// Parses a range clause `field:value` at the start of the remainder of the query string.
+/// Returns field name, field type, value range and length of the clause within the query string.
+fn parse_range_clause<'a>(
+    remainder: &'a str,
+    token: &'a str,
+    schema_map: &'a HashMap<String, SchemaField>,
+) -> Option<(&'a str, FieldType, ValueRange, usize)> {
+    let (field, value) = token.strip_prefix('+').unwrap_or(token).split_once(':')?;
+    let schema_field = schema_map.get(field)?;
+    if !schema_field.facet || !is_range_field_type(&schema_field.field_type) {
+        return None;
+    }
+    let field = schema_field.field.as_str();
+    let value_offset = token.len() - value.len();
+
+    let (lower, upper, clause_len) = if value.starts_with('[') || value.starts_with('{') {
+        let value = &remainder[value_offset..];
+        let value_len = value.find([']', '}'])? + 1;
+        let (lower, upper) = parse_interval(&value[..value_len])?;
+        (lower, upper, value_offset + value_len)
+    } else {
+        let (lower, upper) = parse_comparison(value)?;
+        (lower, upper, token.len())
+    };
+
+    let value_range = ValueRange::from_bounds(lower, upper, &schema_field.field_type)?;
+    Some((
+        field,
+        schema_field.field_type.clone(),
+        value_range,
+        clause_len,
+    ))
+}
+
+/// Parses `[start TO end]`, `{start TO end}` and mixed brackets.
+fn parse_interval(value: &str) -> Option<(ClauseBound<'_>, ClauseBound<'_>)> {
+    let lower_inclusive = value.starts_with('[');
+    let upper_inclusive = value.ends_with(']');
+    let mut parts = value[1..value.len() - 1].split_whitespace();
+    let (start, to, end) = (parts.next()?, parts.next()?, parts.next()?);
+    if to != "TO" || parts.next().is_some() {
+        return None;
+    }
+
+    let lower = (start != "*").then_some((start, lower_inclusive));
+    let upper = (end != "*").then_some((end, upper_inclusive));
+    Some((lower, upper))
+}
+
+/// Parses `>value`, `>=value`, `<value`, `<=value`, `start..end` and `value`.
+fn parse_comparison(value: &str) -> Option<(ClauseBound<'_>, ClauseBound<'_>)> {
+    let bounds = if let Some((start, end)) = value.split_once("..") {
+        if start == "*" && end == "*" {
+            return None;
+        }
+        (
+            (start != "*").then_some((start, true)),
+            (end != "*").then_some((end, true)),
+        )
+    } else if let Some(value) = value.strip_prefix(">=") {
+        (Some((value, true)), None)
+    } else if let Some(value) = value.strip_prefix('>') {
+        (Some((value, false)), None)
+    } else if let Some(value) = value.strip_prefix("<=") {
+        (None, Some((value, true)))
+    } else if let Some(value) = value.strip_prefix('<') {
+        (None, Some((value, false)))
+    } else {
+        (Some((value, true)), Some((value, true)))
+    };
+
+    match bounds {
+        (Some(("", _)), _) | (_, Some(("", _))) => None,
+        bounds => Some(bounds),
+    }
+}
+
+fn is_range_field_type(field_type: &FieldType) -> bool {
+    matches!(
+        field_type,
+        FieldType::U8
+            | FieldType::U16
+            | FieldType::U32
+            | FieldType::U64
+            | FieldType::I8
+            | FieldType::I16
+            | FieldType::I32
+            | FieldType::I64
+            | FieldType::Timestamp
+            | FieldType::F32
+            | FieldType::F64
+    )
+}
+
+/// Parses a single value into the value range it covers.
+fn parse_value(value: &str, field_type: &FieldType) -> Option<ValueRange> {
+    match field_type {
+        FieldType::Timestamp => parse_timestamp(value),
+        FieldType::F32 => {
+            let value = value
+                .parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite())?;
+            Some(ValueRange::Float(value as f64, value.next_up() as f64))
+        }
+        FieldType::F64 => {
+            let value = value
+                .parse::<f64>()
+                .ok()
+                .filter(|value| value.is_finite())?;
+            Some(ValueRange::Float(value, value.next_up()))
+        }
+        _ => {
+            let value = value.parse::<i128>().ok()?;
+            Some(ValueRange::Integer(value, value.saturating_add(1)))
+        }
+    }
+}
+
+/// Parses a Unix timestamp or an ISO-8601 date, date-time or RFC 3339 date-time (UTC if no offset is given).
+fn parse_timestamp(value: &str) -> Option<ValueRange> {
+    if let Ok(timestamp) = value.parse::<i64>() {
+        return Some(ValueRange::Integer(
+            timestamp as i128,
+            timestamp as i128 + 1,
+        ));
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let timestamp = date.and_hms_opt(0, 0, 0)?.and_utc().timestamp() as i128;
+        return Some(ValueRange::Integer(timestamp, timestamp + 86_400));
+    }
+
+    let timestamp = if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        date.timestamp()
+    } else {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+            .ok()?
+            .and_utc()
+            .timestamp()
+    } as i128;
+    Some(ValueRange::Integer(timestamp, timestamp + 1))
+}
+
+fn facet_filter_field(facet_filter: &FacetFilter) -> Option<&str> {
+    match facet_filter {
+        FacetFilter::U8 { field, .. }
+        | FacetFilter::U16 { field, .. }
+        | FacetFilter::U32 { field, .. }
+        | FacetFilter::U64 { field, .. }
+        | FacetFilter::I8 { field, .. }
+        | FacetFilter::I16 { field, .. }
+        | FacetFilter::I32 { field, .. }
+        | FacetFilter::I64 { field, .. }
+        | FacetFilter::Timestamp { field, .. }
+        | FacetFilter::F32 { field, .. }
+        | FacetFilter::F64 { field, .. } => Some(field),
+        _ => None,
+    }
+}
+
+fn facet_filter_range(facet_filter: &FacetFilter) -> Option<ValueRange> {
+    Some(match facet_filter {
+        FacetFilter::U8 { filter, .. } => {
+            ValueRange::Integer(filter.start as i128, filter.end as i128)
+        }
+        FacetFilter::U16 { filter, .. } => {
+            ValueRange::Integer(filter.start as i128, filter.end as i128)
+        }
+        FacetFilter::U32 { filter, .. } => {
+            ValueRange::Integer(filter.start as i128, filter.end as i128)
+        }
+        FacetFilter::U64 { filter, .. } => {
+            ValueRange::Integer(filter.start as i128, filter.end as i128)
+        }
+        FacetFilter::I8 { filter, .. } => {
+            ValueRange::Integer(filter.start as i128, filter.end as i128)
+        }
+        FacetFilter::I16 { filter, .. } => {
+            ValueRange::Integer(filter.start as i128, filter.end as i128)
+        }
+        FacetFilter::I32 { filter, .. } => {
+            ValueRange::Integer(filter.start as i128, filter.end as i128)
+        }
+        FacetFilter::I64 { filter, .. } | FacetFilter::Timestamp { filter, .. } => {
+            ValueRange::Integer(filter.start as i128, filter.end as i128)
+        }
+        FacetFilter::F32 { filter, .. } => {
+            ValueRange::Float(filter.start as f64, filter.end as f64)
+        }
+        FacetFilter::F64 { filter, .. } => ValueRange::Float(filter.start, filter.end),
+        _ => return None,
+    })
+}
+
+/// Converts a half-open i128 range into an inclusive range clamped to the value domain of the facet field type.
+/// An empty range or a range outside of the value domain becomes an empty inclusive range.
+fn integer_range<T>(start: i128, end: i128) -> RangeInclusive<T>
+where
+    T: Bounded + Into<i128> + TryFrom<i128>,
+{
+    let (min, max) = (T::min_value().into(), T::max_value().into());
+    if start >= end || start > max || end <= min {
+        return T::max_value()..=T::min_value();
+    }
+    let clamp = |value: i128| T::try_from(value.clamp(min, max)).unwrap_or_else(|_| T::min_value());
+    clamp(start)..=clamp(end - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+    [{"field":"title","field_type":"Text","store":true,"index_lexical":true},
+    {"field":"price","field_type":"U32","store":true,"index_lexical":false,"facet":true},
+    {"field":"level","field_type":"U8","store":true,"index_lexical":false,"facet":true},
+    {"field":"temperature","field_type":"I8","store":true,"index_lexical":false,"facet":true},
+    {"field":"rating","field_type":"F32","store":true,"index_lexical":false,"facet":true},
+    {"field":"date","field_type":"Timestamp","store":true,"index_lexical":false,"facet":true},
+    {"field":"views","field_type":"U32","store":true,"index_lexical":false,"facet":false}]"#;
+
+    fn extract(query_string: &str) -> (String, Vec<FilterSparse>) {
+        let (query, facet_filter, range_filters) = extract_with(query_string, Vec::new());
+        assert!(facet_filter.is_empty());
+        (query, range_filters)
+    }
+
+    fn extract_with(
+        query_string: &str,
+        mut facet_filter: Vec<FacetFilter>,
+    ) -> (String, Vec<FacetFilter>, Vec<FilterSparse>) {
+        let schema: Vec<SchemaField> = serde_json::from_str(SCHEMA).unwrap();
+        let schema_map = schema
+            .into_iter()
+            .map(|schema_field| (schema_field.field.clone(), schema_field))
+            .collect();
+        let (query, range_filters) =
+            extract_range_filters(query_string, &schema_map, &mut facet_filter);
+        (
+            query,
+            facet_filter,
+            range_filters
+                .iter()
+                .map(|range_filter| range_filter.filter_sparse())
+                .collect(),
+        )
+    }
+
+    fn price(filter: RangeInclusive<u32>) -> Vec<FilterSparse> {
+        vec![FilterSparse::U32(filter)]
+    }
+
+    #[test]
+    fn comparison_clauses() {
+        assert_eq!(
+            extract("apple price:>10"),
+            ("apple".into(), price(11..=u32::MAX))
+        );
+        assert_eq!(
+            extract("apple price:>=10"),
+            ("apple".into(), price(10..=u32::MAX))
+        );
+        assert_eq!(extract("apple price:<10"), ("apple".into(), price(0..=9)));
+        assert_eq!(extract("apple price:<=10"), ("apple".into(), price(0..=10)));
+        assert_eq!(extract("apple price:42"), ("apple".into(), price(42..=42)));
+        assert_eq!(
+            extract("+price:>10 apple price:<20"),
+            ("apple".into(), price(11..=19))
+        );
+    }
+
+    #[test]
+    fn interval_clauses() {
+        assert_eq!(extract("price:[10 TO 50]"), (String::new(), price(10..=50)));
+        assert_eq!(extract("price:{10 TO 50}"), (String::new(), price(11..=49)));
+        assert_eq!(extract("price:[10 TO 50}"), (String::new(), price(10..=49)));
+        assert_eq!(
+            extract("price:[10 TO *] apple"),
+            ("apple".into(), price(10..=u32::MAX))
+        );
+        assert_eq!(extract("price:10..50"), (String::new(), price(10..=50)));
+        assert_eq!(extract("price:*..50"), (String::new(), price(0..=50)));
+        assert_eq!(
+            extract("price:10..*"),
+            (String::new(), price(10..=u32::MAX))
+        );
+    }
+
+    #[test]
+    fn type_bounds() {
+        // the maximum value of the type is included
+        assert_eq!(extract("level:>=255").1, vec![FilterSparse::U8(255..=255)]);
+        assert_eq!(
+            extract("level:[200 TO 300]").1,
+            vec![FilterSparse::U8(200..=255)]
+        );
+
+        // ranges outside of the type domain are empty
+        let level_empty = vec![FilterSparse::U8(integer_range(1, 0))];
+        assert_eq!(extract("level:>255").1, level_empty);
+        assert_eq!(extract("level:<0").1, level_empty);
+        assert_eq!(
+            extract("temperature:<-128").1,
+            vec![FilterSparse::I8(integer_range(1, 0))]
+        );
+        assert_eq!(extract("price:[50 TO 10]").1, price(integer_range(1, 0)));
+
+        // an explicit facet filter keeps its half-open range: the range end is excluded
+        assert_eq!(
+            extract_with(
+                "level:>=200",
+                vec![FacetFilter::U8 {
+                    field: "level".into(),
+                    filter: 100..255
+                }]
+            ),
+            (String::new(), Vec::new(), vec![FilterSparse::U8(200..=254)])
+        );
+    }
+
+    #[test]
+    fn float_and_timestamp_clauses() {
+        assert_eq!(
+            extract("rating:>=4").1,
+            vec![FilterSparse::F32(4.0..f32::INFINITY)]
+        );
+        // a date without time covers the whole day
+        assert_eq!(
+            extract("date:[2024-01-01 TO 2024-01-31]").1,
+            vec![FilterSparse::Timestamp(1_704_067_200..=1_706_745_599)]
+        );
+        assert_eq!(
+            extract("date:>=2024-01-01T10:00:00+02:00").1,
+            vec![FilterSparse::Timestamp(1_704_096_000..=i64::MAX)]
+        );
+    }
+
+    #[test]
+    fn invalid_clauses() {
+        for query in [
+            "apple price:>abc",
+            "apple price:>",
+            "apple price:[10 TO]",
+            "apple price:[10 50]",
+            "apple price:*..*",
+            "apple rating:>inf",
+            "apple date:>2024-13-01",
+            "apple views:>10",
+            "apple unknown:>10",
+            "apple title:>10",
+            "\"apple price:>10\"",
+        ] {
+            assert_eq!(extract(query), (query.to_string(), Vec::new()), "{query}");
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::HashSet;
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[cfg(feature = "rerank")]
+pub use crate::cross_encoder::CrossEncoder;
+use crate::{index::Index, min_heap::Result};
+
+/// Scores (query, document) pairs for the reranking stage of a two-stage retrieval.
+/// The built-in implementation is CrossEncoder (feature "rerank"), a custom reranker (e.g. a remote reranking API) can be passed to Index::rerank.
+pub trait Reranker: Send + Sync {
+    /// Returns a relevance score per document, in the order of the documents: higher is more relevant.
+    /// Scores are logits, they are mapped to 0.0..1.0 with the sigmoid function for the blending with the retrieval score.
+    fn score<'a>(
+        &'a self,
+        query: &'a str,
+        documents: &'a [String],
+    ) -> BoxFuture<'a, std::result::Result<Vec<f32>, String>>;
+}
+
+/// Reranking of the top-ranked search results with a cross-encoder, after the fusion of lexical and vector results.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct Rerank {
+    /// Cross-encoder model: Hugging Face model id or local path to the model directory with config.json, tokenizer.json and model.safetensors,
+    /// e.g. "cross-encoder/ms-marco-MiniLM-L-6-v2". BERT and RoBERTa/XLM-RoBERTa sequence classification models are supported.
+    /// The SeekStorm server only accepts the models given with its rerank_models parameter, which are loaded once at startup.
+    #[schema(example = "cross-encoder/ms-marco-MiniLM-L-6-v2")]
+    pub model: String,
+    /// Stored text field that is scored together with the query string.
+    pub field: String,
+    /// Number of top-ranked results that are reranked (default=50): the search is executed with max(depth, offset+length) results,
+    /// and offset and length are applied to the reranked results.
+    #[serde(default = "depth_default")]
+    #[schema(required = false, minimum = 1, default = 50, example = 50)]
+    pub depth: usize,
+    /// Weight of the reranker score in the blended score (default=1.0): weight * sigmoid(reranker score) + (1 - weight) * retrieval score,
+    /// with the retrieval scores min-max normalized to 0.0..1.0 over the reranked results. 1.0 ranks by the reranker score only.
+    #[serde(default = "weight_default")]
+    #[schema(
+        required = false,
+        minimum = 0.0,
+        maximum = 1.0,
+        default = 1.0,
+        example = 0.8
+    )]
+    pub weight: f32,
+}
+
+fn depth_default() -> usize {
+    50
+}
+
+fn weight_default() -> f32 {
+    1.0
+}
+
+impl Index {
+    /// Reranks the top-ranked results with a reranker, e.g. a cross-encoder, and re-sorts them by the blended score.
+    /// The field text of each result is read from the document store: results without a stored value of the field, or with a NaN reranker score, get a reranker score of 0.0.
+    /// Results below the rerank depth keep their order and score, after the reranked results.
+    /// * `query`: Query string scored together with the field text.
+    /// * `results`: Search results, ordered by retrieval score.
+    /// * `rerank`: Rerank depth, field and score blending.
+    /// * `reranker`: Reranker, e.g. a CrossEncoder loaded with CrossEncoder::load(&rerank.model).
+    /// * `include_uncommitted`: Read the field text also of documents which have not yet been committed.
+    pub async fn rerank(
+        &self,
+        query: &str,
+        results: &mut [Result],
+        rerank: &Rerank,
+        reranker: &dyn Reranker,
+        include_uncommitted: bool,
+    ) -> std::result::Result<(), String> {
+        let depth = rerank.depth.min(results.len());
+        if depth == 0 {
+            return Ok(());
+        }
+
+        let field_filter = HashSet::from([rerank.field.clone()]);
+        let mut texts: Vec<Option<String>> = Vec::with_capacity(depth);
+        for result in results[..depth].iter() {
+            texts.push(
+                self.get_document(
+                    result.doc_id,
+                    include_uncommitted,
+                    &None,
+                    &field_filter,
+                    &[],
+                )
+                .await
+                .ok()
+                .and_then(|document| {
+                    document.get(&rerank.field).map(|value| {
+                        serde_json::from_value::<String>(value.clone()).unwrap_or(value.to_string())
+                    })
+                }),
+            );
+        }
+
+        let documents: Vec<String> = texts.iter().flatten().cloned().collect();
+        let mut scores = reranker.score(query, &documents).await?.into_iter();
+        if documents.len() != scores.len() {
+            return Err(format!(
+                "Reranker returned {} scores for {} documents",
+                scores.len(),
+                documents.len()
+            ));
+        }
+
+        let weight = rerank.weight.clamp(0.0, 1.0);
+        let (min_score, max_score) = results[..depth]
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(min, max), result| {
+                (min.min(result.score), max.max(result.score))
+            });
+        for (result, text) in results[..depth].iter_mut().zip(texts.iter()) {
+            // results without field text or with an invalid (NaN) reranker score get a reranker score of 0.0
+            let reranker_score = if text.is_some() {
+                let score = 1.0 / (1.0 + (-scores.next().unwrap()).exp());
+                if score.is_nan() { 0.0 } else { score }
+            } else {
+                0.0
+            };
+            let retrieval_score = if max_score > min_score {
+                (result.score - min_score) / (max_score - min_score)
+            } else {
+                1.0
+            };
+            result.score = weight * reranker_score + (1.0 - weight) * retrieval_score;
+        }
+        results[..depth].sort_by(|a, b| b.score.total_cmp(&a.score));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,303 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+};
+
+use ahash::AHashMap;
+use serde_json::{Value, json};
+
+use crate::{
+    commit::Commit,
+    index::{
+        COMPLETIONS_FILENAME, Close, DICTIONARY_FILENAME, DeleteDocuments, Document,
+        EMBEDDING_CACHE_FILENAME, FILE_PATH, FieldType, FileType, Index, IndexArc,
+        IndexDocumentDocid, SCHEMA_FILENAME, SYNONYMS_FILENAME, SchemaField, Synonym, create_index,
+        open_index,
+    },
+    search::FacetValue,
+    vector_field::VECTOR_FIELDS_PATH,
+};
+
+/// Result of Reshard::reshard.
+pub struct ReshardResult {
+    /// The resharded index, opened at the path of the original index.
+    pub index_arc: IndexArc,
+    /// Number of documents carried over into the resharded index, without deleted documents.
+    pub doc_count: usize,
+    /// Global document ids that changed: (document id in the original index, document id in the resharded index).
+    /// Empty for round-robin shard assignment, where the document ids are preserved. Documents that are not listed keep their document id.
+    pub doc_id_mapping: Vec<(u64, u64)>,
+}
+
+/// Resharding: rebuilds an index with a different number of shards.
+#[allow(async_fn_in_trait)]
+pub trait Reshard {
+    /// Rebuilds the index with shard_number shards, e.g. after moving it to a machine with more or fewer cores, and replaces the original index at its path.
+    /// The documents are re-indexed from the document store, therefore all indexed fields (lexical, vector, sparse vector, multi-vector) have to be stored,
+    /// otherwise an error is returned before the index is changed. Facet fields that are not indexed otherwise are restored from the facets if not stored.
+    /// Vectors are re-embedded from the stored fields (with the embedding cache of the original index).
+    /// Global document ids are preserved for round-robin shard assignment, deleted documents stay deleted.
+    /// With routing (SchemaField.routing) the documents are re-routed to the new shards and the changed document ids are returned in ReshardResult.doc_id_mapping.
+    /// Synonyms, the spelling correction dictionary and the completion list are kept, the ANN calibration is not carried over.
+    /// Resharding is offline: documents that are indexed or deleted during resharding are lost.
+    /// The original IndexArc is closed, use ReshardResult.index_arc instead.
+    /// * `shard_number`: number of shards of the resharded index.
+    async fn reshard(&self, shard_number: usize) -> Result<ReshardResult, String>;
+}
+
+impl Reshard for IndexArc {
+    async fn reshard(&self, shard_number: usize) -> Result<ReshardResult, String> {
+        if shard_number == 0 {
+            return Err("shard_number must be at least 1".to_string());
+        }
+
+        let index_path = PathBuf::from(&self.read().await.index_path_string);
+        let schema: Vec<SchemaField> = read_json(&index_path.join(SCHEMA_FILENAME))?;
+        let facet_fields = reindex_facet_fields(&schema)?;
+        let synonyms: Vec<Synonym> =
+            read_json(&index_path.join(SYNONYMS_FILENAME)).unwrap_or_default();
+
+        self.commit().await;
+
+        let index_ref = self.read().await;
+
+        let reshard_path = index_path.with_extension("reshard");
+        if reshard_path.exists() {
+            fs::remove_dir_all(&reshard_path).map_err(|e| e.to_string())?;
+        }
+        copy_embedding_caches(&index_path, &reshard_path)?;
+
+        let target_arc = create_index(
+            &reshard_path,
+            index_ref.meta.clone(),
+            &schema,
+            &synonyms,
+            index_ref.segment_number_bits1,
+            index_ref.mute,
+            Some(shard_number),
+        )
+        .await?;
+
+        // round-robin: documents are re-indexed in the order of their global document ids,
+        // with empty placeholders for deleted documents, to preserve the document ids
+        let preserve_doc_ids = index_ref.routing_field.is_none();
+        let doc_id_end = doc_id_end(&index_ref).await;
+
+        let mut target_doc_ids: AHashMap<u64, u64> = AHashMap::new();
+        let mut placeholder_doc_ids = Vec::new();
+        for doc_id in 0..doc_id_end {
+            if !is_live(&index_ref, doc_id).await {
+                if preserve_doc_ids {
+                    placeholder_doc_ids.push(
+                        target_arc
+                            .index_document_docid(Document::new(), FileType::None)
+                            .await as u64,
+                    );
+                }
+                continue;
+            }
+
+            let document = stored_document(&index_ref, doc_id, &facet_fields).await;
+            let target_doc_id = target_arc
+                .index_document_docid(document, FileType::None)
+                .await;
+            target_doc_ids.insert(doc_id as u64, target_doc_id as u64);
+        }
+
+        target_arc.commit().await;
+        target_arc.delete_documents(placeholder_doc_ids).await;
+        target_arc.close().await;
+        drop(target_arc);
+
+        copy_files(&index_path, &reshard_path, &target_doc_ids)?;
+        let mut doc_id_mapping: Vec<(u64, u64)> = target_doc_ids
+            .iter()
+            .filter(|(doc_id, target_doc_id)| doc_id != target_doc_id)
+            .map(|(doc_id, target_doc_id)| (*doc_id, *target_doc_id))
+            .collect();
+        doc_id_mapping.sort_unstable();
+
+        drop(index_ref);
+        self.close().await;
+
+        for filename in [DICTIONARY_FILENAME, COMPLETIONS_FILENAME] {
+            if index_path.join(filename).exists() {
+                fs::copy(index_path.join(filename), reshard_path.join(filename))
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+
+        let backup_path = index_path.with_extension("backup");
+        if backup_path.exists() {
+            fs::remove_dir_all(&backup_path).map_err(|e| e.to_string())?;
+        }
+        fs::rename(&index_path, &backup_path).map_err(|e| e.to_string())?;
+        if let Err(e) = fs::rename(&reshard_path, &index_path) {
+            let _ = fs::rename(&backup_path, &index_path);
+            return Err(e.to_string());
+        }
+        let index_arc = open_index(&index_path).await?;
+        let _ = fs::remove_dir_all(&backup_path);
+
+        Ok(ReshardResult {
+            index_arc,
+            doc_count: target_doc_ids.len(),
+            doc_id_mapping,
+        })
+    }
+}
+
+/// Non-stored facet fields, whose values are restored from the facets when re-indexing the stored documents.
+/// Returns an error if indexed fields are not stored and therefore can't be re-indexed.
+pub(crate) fn reindex_facet_fields(schema: &[SchemaField]) -> Result<Vec<String>, String> {
+    let unrecoverable_fields: Vec<&str> = schema
+        .iter()
+        .filter(|schema_field| {
+            !schema_field.store
+                && (schema_field.index_lexical
+                    || schema_field.index_vector
+                    || matches!(
+                        schema_field.field_type,
+                        FieldType::SparseVector | FieldType::MultiVector
+                    ))
+        })
+        .map(|schema_field| schema_field.field.as_str())
+        .collect();
+    if !unrecoverable_fields.is_empty() {
+        return Err(format!(
+            "re-indexing requires all indexed fields to be stored, indexed fields that are not stored: {}",
+            unrecoverable_fields.join(", ")
+        ));
+    }
+
+    Ok(schema
+        .iter()
+        .filter(|schema_field| schema_field.facet && !schema_field.store)
+        .map(|schema_field| schema_field.field.clone())
+        .collect())
+}
+
+/// End of the global document id range of the index (highest indexed document id + 1).
+pub(crate) async fn doc_id_end(index: &Index) -> usize {
+    let mut doc_id_end = 0;
+    for (shard_id, shard) in index.shard_vec.iter().enumerate() {
+        let indexed_doc_count = shard.read().await.indexed_doc_count;
+        if indexed_doc_count > 0 {
+            doc_id_end =
+                doc_id_end.max((indexed_doc_count - 1) * index.shard_number + shard_id + 1);
+        }
+    }
+    doc_id_end
+}
+
+/// True if the global document id is indexed and not deleted.
+pub(crate) async fn is_live(index: &Index, doc_id: usize) -> bool {
+    let shard_ref = index.shard_vec[doc_id % index.shard_number].read().await;
+    let doc_id_shard = doc_id / index.shard_number;
+    doc_id_shard < shard_ref.indexed_doc_count && !shard_ref.delete_hashset.contains(&doc_id_shard)
+}
+
+/// Stored document, with the values of the non-stored facet fields restored from the facets.
+pub(crate) async fn stored_document(
+    index: &Index,
+    doc_id: usize,
+    facet_fields: &[String],
+) -> Document {
+    let mut document = index
+        .get_document(doc_id, false, &None, &HashSet::new(), &[])
+        .await
+        .unwrap_or_default();
+    for field in facet_fields.iter() {
+        if let Some(value) = facet_value_json(index.get_facet_value(field, doc_id).await) {
+            document.insert(field.clone(), value);
+        }
+    }
+    document
+}
+
+pub(crate) fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|e| e.to_string())
+}
+
+/// Facet value as document field value, None for missing values.
+fn facet_value_json(facet_value: FacetValue) -> Option<Value> {
+    Some(match facet_value {
+        FacetValue::Bool(value) => json!(value),
+        FacetValue::U8(value) => json!(value),
+        FacetValue::U16(value) => json!(value),
+        FacetValue::U32(value) => json!(value),
+        FacetValue::U64(value) => json!(value),
+        FacetValue::I8(value) => json!(value),
+        FacetValue::I16(value) => json!(value),
+        FacetValue::I32(value) => json!(value),
+        FacetValue::I64(value) | FacetValue::Timestamp(value) => json!(value),
+        FacetValue::F32(value) => json!(value),
+        FacetValue::F64(value) => json!(value),
+        FacetValue::String(value) if !value.is_empty() => json!(value),
+        FacetValue::StringSet(value) if !value.is_empty() => json!(value),
+        FacetValue::Point(value) => json!(value),
+        FacetValue::String(_) | FacetValue::StringSet(_) | FacetValue::None => return None,
+    })
+}
+
+/// Appends the embedding caches of the index and its vector fields to those of the target index, so that the stored texts are not embedded again.
+pub(crate) fn copy_embedding_caches(index_path: &Path, target_path: &Path) -> Result<(), String> {
+    let mut cache_dirs = vec![PathBuf::new()];
+    if let Ok(entries) = fs::read_dir(index_path.join(VECTOR_FIELDS_PATH)) {
+        cache_dirs.extend(
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| Path::new(VECTOR_FIELDS_PATH).join(entry.file_name())),
+        );
+    }
+
+    for cache_dir in cache_dirs {
+        let cache_path = index_path.join(&cache_dir).join(EMBEDDING_CACHE_FILENAME);
+        if cache_path.exists() {
+            fs::create_dir_all(target_path.join(&cache_dir)).map_err(|e| e.to_string())?;
+            let mut target_file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(target_path.join(&cache_dir).join(EMBEDDING_CACHE_FILENAME))
+                .map_err(|e| e.to_string())?;
+            let mut source_file = File::open(cache_path).map_err(|e| e.to_string())?;
+            io::copy(&mut source_file, &mut target_file).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Copies the ingested files ({doc_id}.pdf) of the carried over documents, renamed to their document ids in the resharded index.
+pub(crate) fn copy_files(
+    index_path: &Path,
+    target_path: &Path,
+    target_doc_ids: &AHashMap<u64, u64>,
+) -> Result<(), String> {
+    let Ok(entries) = fs::read_dir(index_path.join(FILE_PATH)) else {
+        return Ok(());
+    };
+
+    fs::create_dir_all(target_path.join(FILE_PATH)).map_err(|e| e.to_string())?;
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let Some(doc_id) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        let Some(target_doc_id) = target_doc_ids.get(&doc_id) else {
+            continue;
+        };
+        let mut target_file_path = target_path.join(FILE_PATH).join(target_doc_id.to_string());
+        if let Some(extension) = path.extension() {
+            target_file_path.set_extension(extension);
+        }
+        fs::copy(&path, target_file_path).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
@@ -1,11 +1,20 @@
 use crate::INDEX_RUNTIME;
-use crate::geo_search::{decode_morton_2_d, point_distance_to_morton_range};
+use crate::geo_search::{
+    decode_morton_2_d, geo_grid_cell_key, point_distance_to_morton_range, validate_point,
+    validate_polygon,
+};
 use crate::index::{
-    DOCUMENT_LENGTH_COMPRESSION, DistanceUnit, Facet, FieldType, NgramType, ResultFacet, Shard,
-    ShardArc,
+    DOCUMENT_LENGTH_COMPRESSION, DistanceMethod, DistanceUnit, Facet, FieldType, GeoGridCellSum,
+    NgramType, ResultFacet, Shard, ShardArc,
 };
 use crate::iterator::{search_iterator_index, search_iterator_shard};
 use crate::min_heap::{Result, result_ordering_root};
+use crate::query_range::{RangeFilter, extract_range_filters};
+use crate::significant_terms::{
+    SHARD_CANDIDATE_FACTOR, SignificanceHeuristic, SignificantTerm, facet_term_counts,
+    rank_significant_terms, select_significant_terms,
+};
+use crate::sparse_vector::SparseVector;
 use crate::tokenizer::{tokenizer, tokenizer_lite};
 use crate::union::{union_docid_2, union_docid_3};
 use crate::utils::{
@@ -14,7 +23,8 @@ use crate::utils::{
 };
 #[cfg(feature = "vb")]
 use crate::vector::ResultSource;
-use crate::vector::{Embedding, Inference, Quantization, SearchVectorShard};
+use crate::vector::{ChunkAggregation, Embedding, Inference, Quantization, SearchVectorShard};
+use crate::vector_field::{VectorFieldIndex, embedding_dimensions};
 use crate::vector_similarity::{
     AnnMode, QuantizedVector, VectorSimilarity, normalize_f32, normalize_f32_simd,
     quantize_f32_to_i8, quantize_f32_to_i8_simd,
@@ -34,11 +44,11 @@ use crate::{
 
 use ahash::{AHashMap, AHashSet};
 use itertools::Itertools;
-use num::FromPrimitive;
+use num::{Bounded, CheckedSub, FromPrimitive, One};
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 use std::mem::discriminant;
-use std::ops::Range;
+use std::ops::{Range, RangeInclusive};
 use std::sync::{
     Arc,
     atomic::{AtomicUsize, Ordering},
@@ -82,6 +92,9 @@ pub enum SearchMode {
         similarity_threshold: Option<f32>,
         /// Specifies in which clusters to search for ANN results.
         ann_mode: AnnMode,
+        /// Specifies how the similarity scores of the chunks of a document are aggregated into the document score (default: Max).
+        #[serde(default)]
+        chunk_aggregation: ChunkAggregation,
     },
     /// Hybrid search mode: Search results are retrieved based on a combination of lexical and vector search.
     /// The relevance score of search results is calculated based on RRF (Reciprocal Rank Fusion) of the result positions in lexical and vector search.
@@ -92,9 +105,51 @@ pub enum SearchMode {
         similarity_threshold: Option<f32>,
         /// Specifies in which clusters to search for ANN results.
         ann_mode: AnnMode,
+        /// Specifies how the similarity scores of the chunks of a document are aggregated into the document score (default: Max).
+        #[serde(default)]
+        chunk_aggregation: ChunkAggregation,
+        /// Optional query sparse vector (term weights, e.g. from SPLADE or BM42): adds sparse vector search as third source to the RRF fusion.
+        #[serde(default)]
+        sparse_vector: Option<SparseVector>,
+    },
+    /// Sparse vector search mode: Search results are retrieved from the sparse vector fields (FieldType::SparseVector),
+    /// scored by the dot product of the query sparse vector and the document sparse vectors, using the block-max WAND of the inverted index.
+    /// Only committed documents are searched (include_uncommitted is ignored), of the query terms in the searched fields the 32 posting lists with the highest maximum scores are used.
+    /// field_filter selects the sparse vector fields to search (default: all).
+    Sparse {
+        /// Query sparse vector: terms with their weights, e.g. the output of a learned sparse retrieval model like SPLADE or BM42.
+        sparse_vector: SparseVector,
+    },
+    /// Late interaction search mode (ColBERT-style): Search results are retrieved from the multi-vector fields (FieldType::MultiVector),
+    /// scored by MaxSim: the sum over the query vectors of the maximum similarity to any of the document vectors.
+    /// The vector index, which stores the token vectors, serves as candidate generator: for each query vector the documents with the nearest token vectors
+    /// are retrieved from the clusters selected by ann_mode, and only those candidates are scored with MaxSim over all their token vectors.
+    /// field_filter selects the multi-vector fields to search (default: all).
+    LateInteraction {
+        /// Query token vectors, e.g. the output of a ColBERT query encoder, with identical dimensions as the document vectors.
+        query_vectors: Vec<Vec<f32>>,
+        /// Specifies in which clusters to search for candidates.
+        #[serde(default)]
+        ann_mode: AnnMode,
+        /// Number of candidate documents retrieved from the vector index per query vector and shard (default: 0 = max(100, offset+length)).
+        /// Higher values increase recall and latency.
+        #[serde(default)]
+        candidate_count: usize,
     },
 }
 
+impl SearchMode {
+    /// ANN mode of the vector search modes.
+    pub(crate) fn ann_mode_mut(&mut self) -> Option<&mut AnnMode> {
+        match self {
+            SearchMode::Vector { ann_mode, .. }
+            | SearchMode::Hybrid { ann_mode, .. }
+            | SearchMode::LateInteraction { ann_mode, .. } => Some(ann_mode),
+            SearchMode::Lexical | SearchMode::Sparse { .. } => None,
+        }
+    }
+}
+
 /// Specifies whether query rewriting is enabled or disabled
 #[derive(Default, PartialEq, Clone, Debug, Serialize, Deserialize, ToSchema)]
 pub enum QueryRewriting {
@@ -208,6 +263,10 @@ pub struct ResultObject {
     /// List of facet fields: field name and vector of unique values and their counts.
     /// Unique values and their counts are only accurate if result_type=TopkCount or ResultType=Count, but not for ResultType=Topk
     pub facets: AHashMap<String, Facet>,
+    /// List of geo grid facet fields: field name and vector of grid cells with their document counts and centroids.
+    pub geo_grids: AHashMap<String, Vec<GeoGridCell>>,
+    /// List of significant terms facet fields: field name and vector of significant terms with their significance scores and document counts.
+    pub significant_terms: AHashMap<String, Vec<SignificantTerm>>,
     ///Suggestions for auto complete and spelling correction.
     pub suggestions: Vec<String>,
 }
@@ -227,6 +286,42 @@ pub enum RangeType {
     CountBelowRange,
 }
 
+/// Specifies the sort order of string facet values
+#[derive(Debug, Clone, Deserialize, Serialize, Default, PartialEq, ToSchema)]
+pub enum FacetValueSort {
+    /// by document count, descending. Facet values with identical count are sorted alphabetically.
+    #[default]
+    CountDescending,
+    /// by document count, ascending. Facet values with identical count are sorted alphabetically.
+    CountAscending,
+    /// alphabetically by facet value, ascending
+    ValueAscending,
+    /// alphabetically by facet value, descending
+    ValueDescending,
+}
+
+/// Specifies the grid for bucketing Point facet field values into geo grid cells
+#[derive(Debug, Clone, Deserialize, Serialize, Default, PartialEq, ToSchema)]
+pub enum GeoGridType {
+    /// Geohash cells, precision = geohash length 1..12
+    #[default]
+    Geohash,
+    /// Slippy map tiles (z/x/y), precision = zoom level 0..29
+    Geotile,
+}
+
+/// Geo grid cell with the number of matching documents and the centroid of their locations.
+#[derive(Debug, Clone, Deserialize, Serialize, Default, PartialEq, ToSchema)]
+pub struct GeoGridCell {
+    /// cell key: geohash string for GeoGridType::Geohash, "zoom/x/y" for GeoGridType::Geotile
+    pub key: String,
+    /// number of matching documents located within the cell
+    pub count: usize,
+    /// centroid (latitude/lat, longitude/lon) of the locations of all matching documents within the cell
+    #[schema(value_type=Vec<f64>)]
+    pub centroid: Point,
+}
+
 /// Defines the query facets:
 /// - string facet field values
 /// - range segments for numerical facet field values
@@ -339,6 +434,15 @@ pub enum QueryFacet {
         prefix: String,
         /// maximum number of facet values to return
         length: u16,
+        /// number of facet values to skip, for paging through facet values
+        #[serde(default)]
+        offset: u16,
+        /// sort order of facet values: CountDescending (default), CountAscending, ValueAscending, ValueDescending
+        #[serde(default)]
+        sort: FacetValueSort,
+        /// minimum document count of facet values to return
+        #[serde(default)]
+        min_count: usize,
     },
     /// Facet field values of type string
     String32 {
@@ -348,6 +452,15 @@ pub enum QueryFacet {
         prefix: String,
         /// maximum number of facet values to return
         length: u32,
+        /// number of facet values to skip, for paging through facet values
+        #[serde(default)]
+        offset: u32,
+        /// sort order of facet values: CountDescending (default), CountAscending, ValueAscending, ValueDescending
+        #[serde(default)]
+        sort: FacetValueSort,
+        /// minimum document count of facet values to return
+        #[serde(default)]
+        min_count: usize,
     },
     /// Facet field values of type string set
     StringSet16 {
@@ -357,6 +470,15 @@ pub enum QueryFacet {
         prefix: String,
         /// maximum number of facet values to return
         length: u16,
+        /// number of facet values to skip, for paging through facet values
+        #[serde(default)]
+        offset: u16,
+        /// sort order of facet values: CountDescending (default), CountAscending, ValueAscending, ValueDescending
+        #[serde(default)]
+        sort: FacetValueSort,
+        /// minimum document count of facet values to return
+        #[serde(default)]
+        min_count: usize,
     },
     /// Facet field values of type string set
     StringSet32 {
@@ -366,6 +488,15 @@ pub enum QueryFacet {
         prefix: String,
         /// maximum number of facet values to return
         length: u32,
+        /// number of facet values to skip, for paging through facet values
+        #[serde(default)]
+        offset: u32,
+        /// sort order of facet values: CountDescending (default), CountAscending, ValueAscending, ValueDescending
+        #[serde(default)]
+        sort: FacetValueSort,
+        /// minimum document count of facet values to return
+        #[serde(default)]
+        min_count: usize,
     },
     /// Range segment definition for numerical facet field values of type Point (distance between base of type Point and facet field of type Point)
     Point {
@@ -380,6 +511,36 @@ pub enum QueryFacet {
         /// distance unit (kilometers/miles)
         unit: DistanceUnit,
     },
+    /// Geo grid aggregation of facet field values of type Point: document count and centroid per geohash cell or map tile.
+    /// Returned in ResultObject.geo_grids instead of ResultObject.facets.
+    GeoGrid {
+        /// field name
+        field: String,
+        /// grid type (Geohash, Geotile)
+        #[serde(default)]
+        grid_type: GeoGridType,
+        /// precision: geohash length 1..12 for Geohash, zoom level 0..29 for Geotile
+        precision: u8,
+        /// maximum number of grid cells to return, ordered by descending document count
+        length: u32,
+    },
+    /// Significant terms aggregation: the terms most over-represented in the foreground compared with the whole index (background).
+    /// For a stored, lexically indexed Text field the terms of the field text are scored: the foreground are the returned results,
+    /// the background count of a term is its posting count in the committed and uncommitted index.
+    /// For a facet field of type String16, String32, StringSet16 or StringSet32 the facet values are scored: the foreground are all documents matching the query.
+    /// Returned in ResultObject.significant_terms instead of ResultObject.facets.
+    SignificantTerms {
+        /// field name
+        field: String,
+        /// maximum number of significant terms to return, ordered by descending significance score
+        length: u32,
+        /// minimum number of documents matching the query a value has to occur in, to be considered significant
+        #[serde(default)]
+        min_count: usize,
+        /// significance heuristic: Jlh (default) or ChiSquare
+        #[serde(default)]
+        heuristic: SignificanceHeuristic,
+    },
     /// No query facet
     #[default]
     None,
@@ -412,6 +573,8 @@ pub enum Ranges {
     F64(RangeType, Vec<(String, f64)>),
     /// Proximity range filter: range type (CountWithinRange,CountBelowRange,CountAboveRange), range label, base point (longitude/lon, latitude/lat), distance unit
     Point(RangeType, Vec<(String, f64)>, Point, DistanceUnit),
+    /// Geo grid aggregation: grid type, precision
+    GeoGrid(GeoGridType, u8),
     #[default]
     /// No range filter
     None,
@@ -857,29 +1020,83 @@ pub enum FacetFilter {
         #[schema(value_type=(Point, RangeF64, DistanceUnit))]
         filter: (Point, Range<f64>, DistanceUnit),
     },
+    /// Point bounding box filter
+    PointBoundingBox {
+        /// field name
+        field: String,
+        /// filter: south-west corner (min latitude/lat, min longitude/lon), north-east corner (max latitude/lat, max longitude/lon).
+        /// If the south-west longitude is greater than the north-east longitude, the bounding box crosses the antimeridian.
+        #[schema(value_type=(Point, Point))]
+        filter: (Point, Point),
+    },
+    /// Point polygon filter
+    PointPolygon {
+        /// field name
+        field: String,
+        /// filter: polygon as array of rings of points (latitude/lat, longitude/lon). The first ring is the outer boundary, all subsequent rings are holes.
+        #[schema(value_type=Vec<Vec<Point>>)]
+        filter: Polygon,
+    },
+}
+
+impl FacetFilter {
+    /// Checks the points of Point, PointBoundingBox and PointPolygon filters, which need a latitude and a longitude,
+    /// and the south-west corner of a bounding box, which must not be north of the north-east corner.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        match self {
+            FacetFilter::Point { filter, .. } => validate_point(&filter.0),
+            FacetFilter::PointBoundingBox { filter, .. } => {
+                validate_point(&filter.0)?;
+                validate_point(&filter.1)?;
+                if filter.0[0] > filter.1[0] {
+                    return Err(format!(
+                        "bounding box south-west corner {:?} is north of north-east corner {:?}",
+                        filter.0, filter.1
+                    ));
+                }
+                Ok(())
+            }
+            FacetFilter::PointPolygon { filter, .. } => validate_polygon(filter),
+            _ => Ok(()),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default, PartialEq)]
 pub(crate) enum FilterSparse {
-    U8(Range<u8>),
-    U16(Range<u16>),
-    U32(Range<u32>),
-    U64(Range<u64>),
-    I8(Range<i8>),
-    I16(Range<i16>),
-    I32(Range<i32>),
-    I64(Range<i64>),
+    U8(RangeInclusive<u8>),
+    U16(RangeInclusive<u16>),
+    U32(RangeInclusive<u32>),
+    U64(RangeInclusive<u64>),
+    I8(RangeInclusive<i8>),
+    I16(RangeInclusive<i16>),
+    I32(RangeInclusive<i32>),
+    I64(RangeInclusive<i64>),
     /// Unix timestamp: the number of seconds since 1 January 1970
-    Timestamp(Range<i64>),
+    Timestamp(RangeInclusive<i64>),
     F32(Range<f32>),
     F64(Range<f64>),
     String16(Vec<u16>),
     String32(Vec<u32>),
     Point(Point, Range<f64>, DistanceUnit, Range<u64>),
+    BoundingBox(Point, Point),
+    Polygon(Polygon),
     #[default]
     None,
 }
 
+/// Converts the half-open range of an integer facet filter into an inclusive range.
+/// Range clauses of the query string are converted into inclusive ranges by RangeFilter, which can include the maximum value of the type.
+fn inclusive_range<T: Bounded + CheckedSub + One + PartialOrd + Copy>(
+    range: &Range<T>,
+) -> RangeInclusive<T> {
+    if let Some(end) = range.end.checked_sub(&T::one()) {
+        range.start..=end
+    } else {
+        T::max_value()..=T::min_value()
+    }
+}
+
 /// Specifies the sort order for the search results.
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, ToSchema)]
 pub enum SortOrder {
@@ -898,6 +1115,21 @@ pub struct ResultSort {
     pub order: SortOrder,
     /// Base value/point for (geo) proximity sorting
     pub base: FacetValue,
+    /// Distance method for (geo) proximity sorting: Equirectangular (default), Haversine or Vincenty
+    #[serde(default)]
+    pub distance_method: DistanceMethod,
+}
+
+impl ResultSort {
+    /// Sort by a facet field with the default distance method (Equirectangular) for geo proximity sorting.
+    pub fn new(field: String, order: SortOrder, base: FacetValue) -> Self {
+        ResultSort {
+            field,
+            order,
+            base,
+            distance_method: DistanceMethod::default(),
+        }
+    }
 }
 
 /// Specifies the sort order for the search results.
@@ -909,12 +1141,18 @@ pub(crate) struct ResultSortIndex<'a> {
     pub order: SortOrder,
     /// Base value/point for (geo) proximity sorting
     pub base: &'a FacetValue,
+    /// Distance method for (geo) proximity sorting
+    pub distance_method: &'a DistanceMethod,
 }
 
 /// latitude lat
 /// longitude lon
 pub type Point = Vec<f64>;
 
+/// Polygon: array of rings of points (latitude/lat, longitude/lon).
+/// The first ring is the outer boundary, all subsequent rings are holes.
+pub type Polygon = Vec<Vec<Point>>;
+
 #[allow(clippy::too_many_arguments)]
 #[allow(async_fn_in_trait)]
 /// Search the index for all indexed documents, both for committed and uncommitted documents.
@@ -922,6 +1160,7 @@ pub type Point = Vec<f64>;
 ///
 /// Arguments:
 /// * `query_string`: query string `+` `-` `""` search operators are recognized.
+///   Range clauses for numerical and timestamp facet fields `price:[10 TO 50]`, `price:{10 TO 50}`, `price:10..50`, `date:>2024-01-01`, `rating:>=4` are removed from the query string and applied as facet filters.
 /// * `query_type_default`: Specifiy default QueryType:
 ///   * **Union**, disjunction, OR,
 ///   * **Intersection**,  conjunction, AND, `+`,
@@ -981,6 +1220,7 @@ pub type Point = Vec<f64>;
 /// * `result_type`: type of search results to return: Count, Topk, TopkCount.
 /// * `include_uncommitted`: true realtime search: include indexed documents which where not yet committed into search results.
 /// * `field_filter`: Specify field names where to search at querytime, whereas SchemaField.indexed is set at indextime. If set to Vec::new() then all indexed fields are searched.
+///   In vector and hybrid search it selects the vector fields to search, the results of several vector fields with their own inference (SchemaField.inference) are fused with RRF.
 /// * `query_facets`: Must be set if facets should be returned in ResultObject. If set to Vec::new() then no facet fields are returned.
 ///   Facet fields are only collected, counted and returned for ResultType::Count and ResultType::TopkCount, but not for ResultType::Topk.
 ///   The prefix property of a QueryFacet allows at query time to filter the returned facet values to those matching a given prefix, if there are too many distinct values per facet field.
@@ -988,9 +1228,11 @@ pub type Point = Vec<f64>;
 ///   If the length property of a QueryFacet is set to 0 then no facet values for that facet are collected, counted and returned at query time. That decreases the query latency significantly.
 ///   The facet values are sorted by the frequency of the appearance of the value within the indexed documents matching the query in descending order.
 ///   Examples:
-///   query_facets = vec![QueryFacet::String16 {field: "language".into(),prefix: "ger".into(),length: 5},QueryFacet::String16 {field: "brand".into(),prefix: "a".into(),length: 5}];
+///   query_facets = vec![QueryFacet::String16 {field: "language".into(),prefix: "ger".into(),length: 5,offset: 0,sort: FacetValueSort::CountDescending,min_count: 0},QueryFacet::String16 {field: "brand".into(),prefix: "a".into(),length: 5,offset: 0,sort: FacetValueSort::CountDescending,min_count: 0}];
 ///   query_facets = vec![QueryFacet::U8 {field: "age".into(), range_type: RangeType::CountWithinRange, ranges: vec![("0-20".into(), 0),("20-40".into(), 20), ("40-60".into(), 40),("60-80".into(), 60), ("80-100".into(), 80)]}];
 ///   query_facets = vec![QueryFacet::Point {field: "location".into(),base:vec![38.8951, -77.0364],unit:DistanceUnit::Kilometers,range_type: RangeType::CountWithinRange,ranges: vec![ ("0-200".into(), 0.0),("200-400".into(), 200.0), ("400-600".into(), 400.0), ("600-800".into(), 600.0), ("800-1000".into(), 800.0)]}];
+///   query_facets = vec![QueryFacet::GeoGrid {field: "location".into(),grid_type: GeoGridType::Geohash,precision: 5,length: 100}];
+///   query_facets = vec![QueryFacet::SignificantTerms {field: "tags".into(),length: 10,min_count: 2,heuristic: SignificanceHeuristic::Jlh}];
 ///
 /// * `facet_filter`: Search results are filtered to documents matching specific string values or numerical ranges in the facet fields. If set to Vec::new() then result are not facet filtered.
 ///   The filter parameter filters the returned results to those documents both matching the query AND matching for all (boolean AND) stated facet filter fields at least one (boolean OR) of the stated values.
@@ -1000,6 +1242,8 @@ pub type Point = Vec<f64>;
 ///   facet_filter=vec![FacetFilter::String{field:"language".into(),filter:vec!["german".into()]},FacetFilter::String{field:"brand".into(),filter:vec!["apple".into(),"google".into()]}];
 ///   facet_filter=vec![FacetFilter::U8{field:"age".into(),filter: 21..65}];
 ///   facet_filter = vec![FacetFilter::Point {field: "location".into(),filter: (vec![38.8951, -77.0364], 0.0..1000.0, DistanceUnit::Kilometers)}];
+///   facet_filter = vec![FacetFilter::PointBoundingBox {field: "location".into(),filter: (vec![38.0, -78.0], vec![39.5, -76.5])}];
+///   facet_filter = vec![FacetFilter::PointPolygon {field: "location".into(),filter: vec![vec![vec![38.0, -78.0], vec![39.5, -78.0], vec![39.5, -76.5], vec![38.0, -76.5]]]}];
 ///
 /// * `result_sort`: Sort field and order: Search results are sorted by the specified facet field, either in ascending or descending order.
 ///   If no sort field is specified, then the search results are sorted by rank in descending order per default.
@@ -1009,8 +1253,8 @@ pub type Point = Vec<f64>;
 ///   A special _score field (BM25x), reflecting how relevant the result is for a given search query (phrase match, match in title etc.) can be combined with any of the other sort fields as primary, secondary or n-th search criterium.
 ///   Sort is only enabled on facet fields that are defined in schema at create_index!
 ///   Examples:
-///   result_sort = vec![ResultSort {field: "price".into(), order: SortOrder::Descending, base: FacetValue::None},ResultSort {field: "language".into(), order: SortOrder::Ascending, base: FacetValue::None}];
-///   result_sort = vec![ResultSort {field: "location".into(),order: SortOrder::Ascending, base: FacetValue::Point(vec![38.8951, -77.0364])}];
+///   result_sort = vec![ResultSort {field: "price".into(), order: SortOrder::Descending, base: FacetValue::None, distance_method: DistanceMethod::Equirectangular},ResultSort {field: "language".into(), order: SortOrder::Ascending, base: FacetValue::None, distance_method: DistanceMethod::Equirectangular}];
+///   result_sort = vec![ResultSort {field: "location".into(),order: SortOrder::Ascending, base: FacetValue::Point(vec![38.8951, -77.0364]), distance_method: DistanceMethod::Haversine}];
 ///  
 ///   If query_string is empty, then index facets (collected at index time) are returned, otherwise query facets (collected at query time) are returned.
 ///   Facets are defined in 3 different places:
@@ -1024,6 +1268,7 @@ pub trait Search {
     ///
     /// Arguments:
     /// * `query_string`: query string `+` `-` `""` search operators are recognized.
+    ///   Range clauses for numerical and timestamp facet fields `price:[10 TO 50]`, `price:{10 TO 50}`, `price:10..50`, `date:>2024-01-01`, `rating:>=4` are removed from the query string and applied as facet filters.
     /// * `query_type_default`: Specifiy default QueryType:
     ///   * **Union**, disjunction, OR,
     ///   * **Intersection**,  conjunction, AND, `+`,
@@ -1083,6 +1328,7 @@ pub trait Search {
     /// * `result_type`: type of search results to return: Count, Topk, TopkCount.
     /// * `include_uncommitted`: true realtime search: include indexed documents which where not yet committed into search results.
     /// * `field_filter`: Specify field names where to search at querytime, whereas SchemaField.indexed is set at indextime. If set to Vec::new() then all indexed fields are searched.
+    ///   In vector and hybrid search it selects the vector fields to search, the results of several vector fields with their own inference (SchemaField.inference) are fused with RRF.
     /// * `query_facets`: Must be set if facets should be returned in ResultObject. If set to Vec::new() then no facet fields are returned.
     ///   Facet fields are only collected, counted and returned for ResultType::Count and ResultType::TopkCount, but not for ResultType::Topk.
     ///   The prefix property of a QueryFacet allows at query time to filter the returned facet values to those matching a given prefix, if there are too many distinct values per facet field.
@@ -1090,9 +1336,11 @@ pub trait Search {
     ///   If the length property of a QueryFacet is set to 0 then no facet values for that facet are collected, counted and returned at query time. That decreases the query latency significantly.
     ///   The facet values are sorted by the frequency of the appearance of the value within the indexed documents matching the query in descending order.
     ///   Examples:
-    ///   query_facets = vec![QueryFacet::String16 {field: "language".into(),prefix: "ger".into(),length: 5},QueryFacet::String16 {field: "brand".into(),prefix: "a".into(),length: 5}];
+    ///   query_facets = vec![QueryFacet::String16 {field: "language".into(),prefix: "ger".into(),length: 5,offset: 0,sort: FacetValueSort::CountDescending,min_count: 0},QueryFacet::String16 {field: "brand".into(),prefix: "a".into(),length: 5,offset: 0,sort: FacetValueSort::CountDescending,min_count: 0}];
     ///   query_facets = vec![QueryFacet::U8 {field: "age".into(), range_type: RangeType::CountWithinRange, ranges: vec![("0-20".into(), 0),("20-40".into(), 20), ("40-60".into(), 40),("60-80".into(), 60), ("80-100".into(), 80)]}];
     ///   query_facets = vec![QueryFacet::Point {field: "location".into(),base:vec![38.8951, -77.0364],unit:DistanceUnit::Kilometers,range_type: RangeType::CountWithinRange,ranges: vec![ ("0-200".into(), 0.0),("200-400".into(), 200.0), ("400-600".into(), 400.0), ("600-800".into(), 600.0), ("800-1000".into(), 800.0)]}];
+    ///   query_facets = vec![QueryFacet::GeoGrid {field: "location".into(),grid_type: GeoGridType::Geohash,precision: 5,length: 100}];
+    ///   query_facets = vec![QueryFacet::SignificantTerms {field: "tags".into(),length: 10,min_count: 2,heuristic: SignificanceHeuristic::Jlh}];
     ///
     /// * `facet_filter`: Search results are filtered to documents matching specific string values or numerical ranges in the facet fields. If set to Vec::new() then result are not facet filtered.
     ///   The filter parameter filters the returned results to those documents both matching the query AND matching for all (boolean AND) stated facet filter fields at least one (boolean OR) of the stated values.
@@ -1102,6 +1350,8 @@ pub trait Search {
     ///   facet_filter=vec![FacetFilter::String{field:"language".into(),filter:vec!["german".into()]},FacetFilter::String{field:"brand".into(),filter:vec!["apple".into(),"google".into()]}];
     ///   facet_filter=vec![FacetFilter::U8{field:"age".into(),filter: 21..65}];
     ///   facet_filter = vec![FacetFilter::Point {field: "location".into(),filter: (vec![38.8951, -77.0364], 0.0..1000.0, DistanceUnit::Kilometers)}];
+    ///   facet_filter = vec![FacetFilter::PointBoundingBox {field: "location".into(),filter: (vec![38.0, -78.0], vec![39.5, -76.5])}];
+    ///   facet_filter = vec![FacetFilter::PointPolygon {field: "location".into(),filter: vec![vec![vec![38.0, -78.0], vec![39.5, -78.0], vec![39.5, -76.5], vec![38.0, -76.5]]]}];
     ///
     /// * `result_sort`: Sort field and order: Search results are sorted by the specified facet field, either in ascending or descending order.
     ///   If no sort field is specified, then the search results are sorted by rank in descending order per default.
@@ -1111,8 +1361,8 @@ pub trait Search {
     ///   A special _score field (BM25x), reflecting how relevant the result is for a given search query (phrase match, match in title etc.) can be combined with any of the other sort fields as primary, secondary or n-th search criterium.
     ///   Sort is only enabled on facet fields that are defined in schema at create_index!
     ///   Examples:
-    ///   result_sort = vec![ResultSort {field: "price".into(), order: SortOrder::Descending, base: FacetValue::None},ResultSort {field: "language".into(), order: SortOrder::Ascending, base: FacetValue::None}];
-    ///   result_sort = vec![ResultSort {field: "location".into(),order: SortOrder::Ascending, base: FacetValue::Point(vec![38.8951, -77.0364])}];
+    ///   result_sort = vec![ResultSort {field: "price".into(), order: SortOrder::Descending, base: FacetValue::None, distance_method: DistanceMethod::Equirectangular},ResultSort {field: "language".into(), order: SortOrder::Ascending, base: FacetValue::None, distance_method: DistanceMethod::Equirectangular}];
+    ///   result_sort = vec![ResultSort {field: "location".into(),order: SortOrder::Ascending, base: FacetValue::Point(vec![38.8951, -77.0364]), distance_method: DistanceMethod::Haversine}];
     ///
     /// * `query_rewriting`: Enables query rewriting features such as spelling correction and query auto-completion (QAC).
     ///   The spelling correction of multi-term query strings handles three cases:
@@ -1167,10 +1417,167 @@ impl Search for IndexArc {
         facet_filter: Vec<FacetFilter>,
         result_sort: Vec<ResultSort>,
         query_rewriting: QueryRewriting,
+    ) -> ResultObject {
+        self.search_shards(
+            query_string,
+            query_vector,
+            query_type_default,
+            search_mode,
+            enable_empty_query,
+            offset,
+            length,
+            result_type,
+            include_uncommitted,
+            field_filter,
+            query_facets,
+            facet_filter,
+            result_sort,
+            query_rewriting,
+            None,
+        )
+        .await
+    }
+}
+
+/// Search with routing: only the shard of a routing value is searched.
+#[allow(async_fn_in_trait)]
+#[allow(clippy::too_many_arguments)]
+pub trait SearchRouted {
+    /// Search the index like Search::search, but only the shard the documents with the routing value of the routing field (SchemaField.routing) are indexed into,
+    /// e.g. the shard of a tenant, instead of fanning out to all shards.
+    /// The shard may contain documents with other routing values: to return only the documents of the routing value, add a facet filter for the routing field.
+    /// If the schema has no routing field, then all shards are searched.
+    /// * `routing`: Value of the routing field, e.g. the tenant_id. String and numerical values are routed by their string representation.
+    async fn search_routed(
+        &self,
+        query_string: String,
+        query_vector: Option<Embedding>,
+        query_type_default: QueryType,
+        search_mode: SearchMode,
+        enable_empty_query: bool,
+        offset: usize,
+        length: usize,
+        result_type: ResultType,
+        include_uncommitted: bool,
+        field_filter: Vec<String>,
+        query_facets: Vec<QueryFacet>,
+        facet_filter: Vec<FacetFilter>,
+        result_sort: Vec<ResultSort>,
+        query_rewriting: QueryRewriting,
+        routing: &str,
+    ) -> ResultObject;
+}
+
+impl SearchRouted for IndexArc {
+    async fn search_routed(
+        &self,
+        query_string: String,
+        query_vector: Option<Embedding>,
+        query_type_default: QueryType,
+        search_mode: SearchMode,
+        enable_empty_query: bool,
+        offset: usize,
+        length: usize,
+        result_type: ResultType,
+        include_uncommitted: bool,
+        field_filter: Vec<String>,
+        query_facets: Vec<QueryFacet>,
+        facet_filter: Vec<FacetFilter>,
+        result_sort: Vec<ResultSort>,
+        query_rewriting: QueryRewriting,
+        routing: &str,
+    ) -> ResultObject {
+        let shard_filter = self.read().await.routing_shard_filter(routing);
+
+        self.search_shards(
+            query_string,
+            query_vector,
+            query_type_default,
+            search_mode,
+            enable_empty_query,
+            offset,
+            length,
+            result_type,
+            include_uncommitted,
+            field_filter,
+            query_facets,
+            facet_filter,
+            result_sort,
+            query_rewriting,
+            shard_filter,
+        )
+        .await
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[allow(async_fn_in_trait)]
+pub(crate) trait SearchShards {
+    /// Searches all shards, or only the shard of shard_filter.
+    async fn search_shards(
+        &self,
+        query_string: String,
+        query_vector: Option<Embedding>,
+        query_type_default: QueryType,
+        search_mode: SearchMode,
+        enable_empty_query: bool,
+        offset: usize,
+        length: usize,
+        result_type: ResultType,
+        include_uncommitted: bool,
+        field_filter: Vec<String>,
+        query_facets: Vec<QueryFacet>,
+        facet_filter: Vec<FacetFilter>,
+        result_sort: Vec<ResultSort>,
+        query_rewriting: QueryRewriting,
+        shard_filter: Option<usize>,
+    ) -> ResultObject;
+}
+
+impl SearchShards for IndexArc {
+    async fn search_shards(
+        &self,
+        query_string: String,
+        query_vector: Option<Embedding>,
+        query_type_default: QueryType,
+        search_mode: SearchMode,
+        enable_empty_query: bool,
+        offset: usize,
+        length: usize,
+        result_type: ResultType,
+        include_uncommitted: bool,
+        field_filter: Vec<String>,
+        query_facets: Vec<QueryFacet>,
+        facet_filter: Vec<FacetFilter>,
+        result_sort: Vec<ResultSort>,
+        query_rewriting: QueryRewriting,
+        shard_filter: Option<usize>,
     ) -> ResultObject {
         let index_ref = self.read().await;
         let original_query = query_string.clone();
 
+        let mut search_mode = search_mode;
+        let requested_ann_mode = search_mode.ann_mode_mut().map(|ann_mode| {
+            let requested_ann_mode = ann_mode.clone();
+            *ann_mode = index_ref.resolve_ann_mode(ann_mode);
+            requested_ann_mode
+        });
+
+        for filter in facet_filter.iter() {
+            if let Err(e) = filter.validate() {
+                println!("invalid facet filter: {}", e);
+                return ResultObject {
+                    original_query,
+                    query: query_string,
+                    ..Default::default()
+                };
+            }
+        }
+
+        let mut facet_filter = facet_filter;
+        let (query_string, range_filter) =
+            extract_range_filters(&query_string, &index_ref.schema_map, &mut facet_filter);
+
         let (edit_distance_max, term_length_threshold, correct, complete, suggestion_length) =
             match &query_rewriting {
                 QueryRewriting::SearchSuggest {
@@ -1413,12 +1820,18 @@ impl Search for IndexArc {
         if enable_empty_query
             && query_string.is_empty()
             && query_vector.is_none()
+            && !matches!(
+                search_mode,
+                SearchMode::Sparse { .. } | SearchMode::LateInteraction { .. }
+            )
             && query_facets.is_empty()
             && facet_filter.is_empty()
+            && range_filter.is_empty()
             && (result_sort.is_empty()
                 || (result_sort.len() == 1
                     && (result_sort.first().unwrap().field == "_id"
                         || result_sort.first().unwrap().field == "_score")))
+            && shard_filter.is_none()
         {
             return search_iterator_index(
                 self,
@@ -1432,6 +1845,11 @@ impl Search for IndexArc {
         }
 
         if index_ref.shard_number == 1 && matches!(search_mode, SearchMode::Lexical) {
+            let significant_terms_facets: Vec<QueryFacet> = query_facets
+                .iter()
+                .filter(|query_facet| matches!(query_facet, QueryFacet::SignificantTerms { .. }))
+                .cloned()
+                .collect();
             let mut result_object = index_ref.shard_vec[0]
                 .search_lexical_shard(
                     query_string.clone(),
@@ -1444,9 +1862,16 @@ impl Search for IndexArc {
                     field_filter,
                     query_facets,
                     facet_filter,
+                    range_filter,
                     result_sort,
                 )
                 .await;
+            let text_significant_terms = index_ref
+                .text_significant_terms(&significant_terms_facets, &result_object.results)
+                .await;
+            result_object
+                .significant_terms
+                .extend(text_significant_terms);
             result_object.original_query = original_query;
             result_object.query = query_string.clone();
             if let Some(suggestions) = suggestions.as_ref() {
@@ -1459,156 +1884,73 @@ impl Search for IndexArc {
         let shard_number = index_ref.shard_number;
         let aggregate_results = result_type != ResultType::Count;
 
-        let query_vector = if index_ref.is_vector_indexing {
-            Some(if let Some(mut qv) = query_vector {
-                if index_ref.vector_similarity == VectorSimilarity::Cosine
-                    && matches!(index_ref.meta.inference, Inference::External { .. })
-                    && let Embedding::F32(ref mut fvecs) = qv
+        let vector_field_indices: Vec<VectorFieldIndex> = if matches!(
+            search_mode,
+            SearchMode::Lexical | SearchMode::Sparse { .. } | SearchMode::LateInteraction { .. }
+        ) {
+            Vec::new()
+        } else {
+            index_ref
+                .vector_field_indices
+                .iter()
+                .filter(|vector_field_index| {
+                    field_filter.is_empty() || field_filter.contains(&vector_field_index.field)
+                })
+                .cloned()
+                .collect()
+        };
+        let vector_field_query_vector = if vector_field_indices.is_empty() {
+            None
+        } else {
+            query_vector.clone()
+        };
+        let query_vector = if vector_field_indices.is_empty() {
+            query_vector
+        } else {
+            query_vector.filter(|embedding| {
+                embedding_dimensions(embedding) == index_ref.vector_dimensions_original
+            })
+        };
+        let is_default_vector_search = field_filter.is_empty()
+            || field_filter.iter().any(|field| {
+                index_ref.schema_map.get(field).is_some_and(|schema_field| {
+                    schema_field.index_vector && schema_field.inference.is_none()
+                })
+            });
+
+        let query_vector = if index_ref.is_vector_indexing
+            && is_default_vector_search
+            && !matches!(
+                search_mode,
+                SearchMode::Sparse { .. } | SearchMode::LateInteraction { .. }
+            )
+            && (query_vector.is_some()
+                || index_ref.embedding_provider_option.is_some()
+                || vector_field_indices.is_empty())
+        {
+            Some(if let Some(qv) = query_vector {
+                prepare_query_vector(&index_ref, qv).await
+            } else if let Some(embedding_provider) = index_ref.embedding_provider_option.as_ref() {
+                let mut fvecs = match embedding_provider
+                    .embed(std::slice::from_ref(&query_string))
+                    .await
                 {
+                    Ok(mut embeddings) => embeddings.remove(0),
+                    Err(e) => {
+                        println!("Unable to embed query: {}", e);
+                        let result_object: ResultObject = Default::default();
+                        return result_object;
+                    }
+                };
+                if index_ref.vector_similarity == VectorSimilarity::Cosine {
                     if index_ref.is_simd {
                         unsafe {
-                            normalize_f32_simd(fvecs);
+                            normalize_f32_simd(&mut fvecs);
                         }
                     } else {
-                        normalize_f32(fvecs);
+                        normalize_f32(&mut fvecs);
                     }
-                };
-
-                if (index_ref.quantization == Quantization::ScalarQuantizationI8
-                    || index_ref.quantization == Quantization::TurboQuantI8)
-                    && let Embedding::F32(ref fvecs) = qv
-                {
-                    match (
-                        index_ref.vector_similarity,
-                        index_ref.quantization,
-                        index_ref.is_simd,
-                    ) {
-                        (VectorSimilarity::Cosine, Quantization::ScalarQuantizationI8, true) => {
-                            (unsafe { quantize_f32_to_i8_simd(fvecs) }, 1.0, 0.0, 0, 0)
-                        }
-                        (VectorSimilarity::Cosine, Quantization::ScalarQuantizationI8, false) => {
-                            (quantize_f32_to_i8(fvecs), 1.0, 0.0, 0, 0)
-                        }
-
-                        (VectorSimilarity::Dot, Quantization::ScalarQuantizationI8, true) => {
-                            let quantized_vector = QuantizedVector::new_scale_simd(fvecs);
-                            (
-                                Embedding::I8(quantized_vector.data),
-                                quantized_vector.scale,
-                                quantized_vector.norm,
-                                0,
-                                0,
-                            )
-                        }
-                        (VectorSimilarity::Dot, Quantization::ScalarQuantizationI8, false) => {
-                            let quantized_vector = QuantizedVector::new_scale(fvecs);
-                            (
-                                Embedding::I8(quantized_vector.data),
-                                quantized_vector.scale,
-                                quantized_vector.norm,
-                                0,
-                                0,
-                            )
-                        }
-                        (VectorSimilarity::Euclidean, Quantization::ScalarQuantizationI8, true) => {
-                            let non_affine =
-                                index_ref.shard_vec[0].read().await.max_vector_value == f32::MIN;
-                            if non_affine {
-                                let quantized_vector = QuantizedVector::new_scale_norm_simd(fvecs);
-                                (
-                                    Embedding::I8(quantized_vector.data),
-                                    quantized_vector.scale,
-                                    quantized_vector.norm,
-                                    0,
-                                    0,
-                                )
-                            } else {
-                                let mut min_vector_value =
-                                    index_ref.shard_vec[0].read().await.min_vector_value;
-                                let mut max_vector_value =
-                                    index_ref.shard_vec[0].read().await.max_vector_value;
-                                let quantized_vector = QuantizedVector::new_scale_norm_affine_simd(
-                                    &mut min_vector_value,
-                                    &mut max_vector_value,
-                                    fvecs,
-                                );
-                                (
-                                    Embedding::I8(quantized_vector.data),
-                                    quantized_vector.scale,
-                                    quantized_vector.norm,
-                                    quantized_vector.zero_point,
-                                    quantized_vector.sum_q,
-                                )
-                            }
-                        }
-
-                        (_, Quantization::TurboQuantI8, true) => {
-                            let quantized_vector =
-                                index_ref.turbo_quant.quantize_f32_i8_simd(fvecs);
-
-                            (
-                                Embedding::I8(quantized_vector.data),
-                                quantized_vector.scale,
-                                quantized_vector.norm,
-                                quantized_vector.zero_point,
-                                quantized_vector.sum_q,
-                            )
-                        }
-                        (
-                            VectorSimilarity::Euclidean,
-                            Quantization::ScalarQuantizationI8,
-                            false,
-                        ) => {
-                            let non_affine =
-                                index_ref.shard_vec[0].read().await.max_vector_value == f32::MIN;
-                            if non_affine {
-                                let quantized_vector = QuantizedVector::new_scale_norm(fvecs);
-                                (
-                                    Embedding::I8(quantized_vector.data),
-                                    quantized_vector.scale,
-                                    quantized_vector.norm,
-                                    0,
-                                    0,
-                                )
-                            } else {
-                                let mut min_vector_value =
-                                    index_ref.shard_vec[0].read().await.min_vector_value;
-                                let mut max_vector_value =
-                                    index_ref.shard_vec[0].read().await.max_vector_value;
-                                let quantized_vector = QuantizedVector::new_scale_norm_affine(
-                                    &mut min_vector_value,
-                                    &mut max_vector_value,
-                                    fvecs,
-                                );
-                                (
-                                    Embedding::I8(quantized_vector.data),
-                                    quantized_vector.scale,
-                                    quantized_vector.norm,
-                                    quantized_vector.zero_point,
-                                    quantized_vector.sum_q,
-                                )
-                            }
-                        }
-
-                        (_, Quantization::TurboQuantI8, false) => {
-                            let quantized_vector = index_ref.turbo_quant.quantize_f32_i8(fvecs);
-                            (
-                                Embedding::I8(quantized_vector.data),
-                                quantized_vector.scale,
-                                quantized_vector.norm,
-                                quantized_vector.zero_point,
-                                quantized_vector.sum_q,
-                            )
-                        }
-                        (_, Quantization::None, _) => (qv, 0.0, 0.0, 0, 0),
-                    }
-                } else {
-                    (qv, 0.0, 0.0, 0, 0)
                 }
-            } else if let Some(embedding_model) = index_ref.embedding_model_option.as_ref() {
-                let fvecs = embedding_model
-                    .encode(std::slice::from_ref(&query_string))
-                    .remove(0);
                 if index_ref.quantization == Quantization::ScalarQuantizationI8
                     || index_ref.quantization == Quantization::TurboQuantI8
                 {
@@ -1634,7 +1976,14 @@ impl Search for IndexArc {
             None
         };
 
-        for shard in index_ref.shard_vec.iter() {
+        for (_, shard) in index_ref
+            .shard_vec
+            .iter()
+            .enumerate()
+            .filter(|(shard_id, _)| {
+                shard_filter.is_none_or(|shard_filter| shard_filter == *shard_id)
+            })
+        {
             let query_string_clone = query_string.clone();
             let query_vector_clone = query_vector.clone();
             let shard_clone = shard.clone();
@@ -1644,6 +1993,7 @@ impl Search for IndexArc {
             let field_filter_clone = field_filter.clone();
             let query_facets_clone = query_facets.clone();
             let facet_filter_clone = facet_filter.clone();
+            let range_filter_clone = range_filter.clone();
             let result_sort_clone = result_sort.clone();
             let shard_id = shard.read().await.meta.id;
 
@@ -1662,6 +2012,7 @@ impl Search for IndexArc {
                                 field_filter_clone,
                                 query_facets_clone,
                                 facet_filter_clone,
+                                range_filter_clone,
                                 result_sort_clone,
                             )
                             .await;
@@ -1676,6 +2027,7 @@ impl Search for IndexArc {
                     SearchMode::Vector {
                         similarity_threshold,
                         ann_mode: cluster_search,
+                        chunk_aggregation,
                     } => {
                         let mut rlo_vector = shard_clone
                             .search_vector_shard(
@@ -1684,6 +2036,7 @@ impl Search for IndexArc {
                                 include_uncommitted,
                                 similarity_threshold,
                                 cluster_search,
+                                chunk_aggregation,
                                 field_filter_clone,
                             )
                             .await;
@@ -1698,6 +2051,8 @@ impl Search for IndexArc {
                     SearchMode::Hybrid {
                         similarity_threshold,
                         ann_mode,
+                        chunk_aggregation,
+                        sparse_vector: _,
                     } => {
                         let mut rlo_lexical = shard_clone
                             .search_lexical_shard(
@@ -1711,6 +2066,7 @@ impl Search for IndexArc {
                                 field_filter_clone.clone(),
                                 query_facets_clone,
                                 facet_filter_clone,
+                                range_filter_clone,
                                 result_sort_clone,
                             )
                             .await;
@@ -1727,6 +2083,7 @@ impl Search for IndexArc {
                                 include_uncommitted,
                                 similarity_threshold,
                                 ann_mode,
+                                chunk_aggregation,
                                 field_filter_clone,
                             )
                             .await;
@@ -1738,130 +2095,106 @@ impl Search for IndexArc {
                         }
                         (Some(rlo_lexical), Some(rlo_vector))
                     }
+                    SearchMode::Sparse { .. } | SearchMode::LateInteraction { .. } => (None, None),
                 }
             }));
         }
 
         let mut result_object: ResultObject = Default::default();
 
-        let mut result_facets: AHashMap<String, (AHashMap<String, usize>, u32)> = AHashMap::new();
+        let mut result_facets: AHashMap<String, (AHashMap<String, usize>, ResultFacet)> =
+            AHashMap::new();
         if result_type != ResultType::Topk {
             for query_facet in query_facets.iter() {
                 match query_facet {
                     QueryFacet::String16 {
                         field,
-                        prefix: _,
+                        prefix,
                         length,
-                    } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), *length as u32));
+                        offset,
+                        sort,
+                        min_count,
                     }
-                    QueryFacet::StringSet16 {
+                    | QueryFacet::StringSet16 {
                         field,
-                        prefix: _,
+                        prefix,
                         length,
+                        offset,
+                        sort,
+                        min_count,
                     } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), *length as u32));
+                        result_facets.insert(
+                            field.into(),
+                            (
+                                AHashMap::new(),
+                                ResultFacet {
+                                    field: field.clone(),
+                                    prefix: prefix.clone(),
+                                    length: *length as u32,
+                                    offset: *offset as u32,
+                                    sort: sort.clone(),
+                                    min_count: *min_count,
+                                    ..Default::default()
+                                },
+                            ),
+                        );
                     }
                     QueryFacet::String32 {
                         field,
-                        prefix: _,
+                        prefix,
                         length,
-                    } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), *length));
+                        offset,
+                        sort,
+                        min_count,
                     }
-                    QueryFacet::StringSet32 {
+                    | QueryFacet::StringSet32 {
                         field,
-                        prefix: _,
+                        prefix,
                         length,
+                        offset,
+                        sort,
+                        min_count,
                     } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), *length));
-                    }
-                    QueryFacet::Timestamp {
-                        field,
-                        range_type: _,
-                        ranges: _,
-                    } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), u16::MAX as u32));
+                        result_facets.insert(
+                            field.into(),
+                            (
+                                AHashMap::new(),
+                                ResultFacet {
+                                    field: field.clone(),
+                                    prefix: prefix.clone(),
+                                    length: *length,
+                                    offset: *offset,
+                                    sort: sort.clone(),
+                                    min_count: *min_count,
+                                    ..Default::default()
+                                },
+                            ),
+                        );
                     }
 
-                    QueryFacet::U8 {
-                        field,
-                        range_type: _,
-                        ranges: _,
-                    } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), u16::MAX as u32));
-                    }
-                    QueryFacet::U16 {
-                        field,
-                        range_type: _,
-                        ranges: _,
-                    } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), u16::MAX as u32));
-                    }
-                    QueryFacet::U32 {
-                        field,
-                        range_type: _,
-                        ranges: _,
-                    } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), u16::MAX as u32));
-                    }
-                    QueryFacet::U64 {
-                        field,
-                        range_type: _,
-                        ranges: _,
-                    } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), u16::MAX as u32));
-                    }
-                    QueryFacet::I8 {
-                        field,
-                        range_type: _,
-                        ranges: _,
-                    } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), u16::MAX as u32));
-                    }
-                    QueryFacet::I16 {
-                        field,
-                        range_type: _,
-                        ranges: _,
-                    } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), u16::MAX as u32));
-                    }
-                    QueryFacet::I32 {
-                        field,
-                        range_type: _,
-                        ranges: _,
-                    } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), u16::MAX as u32));
-                    }
-                    QueryFacet::I64 {
-                        field,
-                        range_type: _,
-                        ranges: _,
-                    } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), u16::MAX as u32));
-                    }
-                    QueryFacet::F32 {
-                        field,
-                        range_type: _,
-                        ranges: _,
-                    } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), u16::MAX as u32));
-                    }
-                    QueryFacet::F64 {
-                        field,
-                        range_type: _,
-                        ranges: _,
-                    } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), u16::MAX as u32));
-                    }
-                    QueryFacet::Point {
-                        field,
-                        range_type: _,
-                        ranges: _,
-                        base: _,
-                        unit: _,
-                    } => {
-                        result_facets.insert(field.into(), (AHashMap::new(), u16::MAX as u32));
+                    QueryFacet::Timestamp { field, .. }
+                    | QueryFacet::U8 { field, .. }
+                    | QueryFacet::U16 { field, .. }
+                    | QueryFacet::U32 { field, .. }
+                    | QueryFacet::U64 { field, .. }
+                    | QueryFacet::I8 { field, .. }
+                    | QueryFacet::I16 { field, .. }
+                    | QueryFacet::I32 { field, .. }
+                    | QueryFacet::I64 { field, .. }
+                    | QueryFacet::F32 { field, .. }
+                    | QueryFacet::F64 { field, .. }
+                    | QueryFacet::Point { field, .. } => {
+                        result_facets.insert(
+                            field.into(),
+                            (
+                                AHashMap::new(),
+                                ResultFacet {
+                                    field: field.clone(),
+                                    length: u16::MAX as u32,
+                                    ..Default::default()
+                                },
+                            ),
+                        );
                     }
 
                     _ => {}
@@ -1869,6 +2202,26 @@ impl Search for IndexArc {
             }
         }
 
+        let mut result_geo_grids: AHashMap<String, (AHashMap<String, GeoGridCellSum>, u32)> =
+            AHashMap::new();
+        if result_type != ResultType::Topk {
+            for query_facet in query_facets.iter() {
+                if let QueryFacet::GeoGrid { field, length, .. } = query_facet {
+                    result_geo_grids.insert(field.into(), (AHashMap::new(), *length));
+                }
+            }
+        }
+
+        let mut result_significant_terms: AHashMap<String, AHashMap<String, (usize, usize)>> =
+            AHashMap::new();
+        if result_type != ResultType::Topk {
+            for query_facet in query_facets.iter() {
+                if let QueryFacet::SignificantTerms { field, .. } = query_facet {
+                    result_significant_terms.insert(field.into(), AHashMap::new());
+                }
+            }
+        }
+
         let mut result_object_results_lexical: Vec<Result> = Vec::new();
         let mut result_object_results_vector: Vec<Result> = Vec::new();
 
@@ -1889,6 +2242,7 @@ impl Search for IndexArc {
                 SearchMode::Vector {
                     similarity_threshold: _,
                     ann_mode: _,
+                    chunk_aggregation: _,
                 } => {
                     let rlo_shard_vector = rlo_shard_hybrid_options.1.as_mut().unwrap();
                     if aggregate_results {
@@ -1909,6 +2263,8 @@ impl Search for IndexArc {
                 SearchMode::Hybrid {
                     similarity_threshold: _,
                     ann_mode: _,
+                    chunk_aggregation: _,
+                    sparse_vector: _,
                 } => {
                     let rlo_shard_lexical = rlo_shard_hybrid_options.0.as_mut().unwrap();
                     let rlo_shard_vector = rlo_shard_hybrid_options.1.as_mut().unwrap();
@@ -1925,10 +2281,9 @@ impl Search for IndexArc {
                         result_object.query_terms = rlo_shard_lexical.query_terms.clone()
                     };
                 }
+                SearchMode::Sparse { .. } | SearchMode::LateInteraction { .. } => {}
             };
-            if let Some(rlo_shard_lexical) = rlo_shard_hybrid_options.0
-                && !rlo_shard_lexical.facets.is_empty()
-            {
+            if let Some(rlo_shard_lexical) = rlo_shard_hybrid_options.0 {
                 for facet in rlo_shard_lexical.facets.iter() {
                     if let Some(existing) = result_facets.get_mut(facet.0) {
                         for (key, value) in facet.1.iter() {
@@ -1936,9 +2291,149 @@ impl Search for IndexArc {
                         }
                     };
                 }
+                for geo_grid in rlo_shard_lexical.geo_grids.iter() {
+                    if let Some(existing) = result_geo_grids.get_mut(geo_grid.0) {
+                        for cell in geo_grid.1.iter() {
+                            let entry = existing.0.entry(cell.key.clone()).or_insert((0, 0.0, 0.0));
+                            entry.0 += cell.count;
+                            entry.1 += cell.centroid[0] * cell.count as f64;
+                            entry.2 += cell.centroid[1] * cell.count as f64;
+                        }
+                    };
+                }
+                for significant_terms in rlo_shard_lexical.significant_terms.iter() {
+                    if let Some(existing) = result_significant_terms.get_mut(significant_terms.0) {
+                        for term in significant_terms.1.iter() {
+                            let entry = existing.entry(term.term.clone()).or_insert((0, 0));
+                            entry.0 += term.foreground_count;
+                            entry.1 += term.background_count;
+                        }
+                    };
+                }
             }
         }
 
+        let mut vector_field_results: Vec<Vec<Result>> = Vec::new();
+        if let SearchMode::Vector {
+            similarity_threshold,
+            ann_mode: _,
+            chunk_aggregation,
+        }
+        | SearchMode::Hybrid {
+            similarity_threshold,
+            ann_mode: _,
+            chunk_aggregation,
+            sparse_vector: _,
+        } = &search_mode
+            && let Some(requested_ann_mode) = requested_ann_mode.as_ref()
+        {
+            for vector_field_index in vector_field_indices.iter() {
+                let query_vector = vector_field_index
+                    .query_vector(&vector_field_query_vector)
+                    .await;
+                if query_vector.is_none() && query_string.is_empty() {
+                    continue;
+                }
+
+                let rlo_vector_field = Box::pin(vector_field_index.index_arc.search_shards(
+                    query_string.clone(),
+                    query_vector,
+                    query_type_default.clone(),
+                    SearchMode::Vector {
+                        similarity_threshold: *similarity_threshold,
+                        ann_mode: requested_ann_mode.clone(),
+                        chunk_aggregation: *chunk_aggregation,
+                    },
+                    false,
+                    0,
+                    offset + length,
+                    ResultType::Topk,
+                    include_uncommitted,
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                    QueryRewriting::SearchOnly,
+                    shard_filter,
+                ))
+                .await;
+
+                result_object.observed_vector_count += rlo_vector_field.observed_vector_count;
+                result_object.observed_cluster_count += rlo_vector_field.observed_cluster_count;
+                result_object.result_count_total += rlo_vector_field.result_count_total;
+
+                #[cfg(feature = "vb")]
+                let results = rlo_vector_field
+                    .results
+                    .into_iter()
+                    .map(|mut result| {
+                        result.field_id = vector_field_index.indexed_field_id as u32;
+                        result
+                    })
+                    .collect();
+                #[cfg(not(feature = "vb"))]
+                let results = rlo_vector_field.results;
+                vector_field_results.push(results);
+            }
+        }
+
+        let result_object_results_sparse = match &search_mode {
+            SearchMode::Sparse { sparse_vector }
+            | SearchMode::Hybrid {
+                sparse_vector: Some(sparse_vector),
+                ..
+            } => {
+                let rlo_sparse = index_ref
+                    .search_sparse(sparse_vector, &field_filter, offset + length, shard_filter)
+                    .await;
+                result_object.result_count_total = result_object
+                    .result_count_total
+                    .max(rlo_sparse.result_count_total);
+                rlo_sparse.results
+            }
+            _ => Vec::new(),
+        };
+
+        let result_object_results_late_interaction = if let SearchMode::LateInteraction {
+            query_vectors,
+            ann_mode,
+            candidate_count,
+        } = &search_mode
+        {
+            let fields = index_ref.multi_vector_fields(&field_filter).await;
+            // query vectors with other dimensions than the vector index don't match any document
+            let mut prepared_query_vectors = Vec::new();
+            if query_vectors
+                .iter()
+                .all(|query_vector| query_vector.len() == index_ref.vector_dimensions_original)
+            {
+                for query_vector in query_vectors.iter() {
+                    prepared_query_vectors.push(
+                        prepare_query_vector(&index_ref, Embedding::F32(query_vector.clone()))
+                            .await,
+                    );
+                }
+            }
+
+            let rlo_late_interaction = index_ref
+                .search_late_interaction(
+                    &prepared_query_vectors,
+                    ann_mode,
+                    *candidate_count,
+                    &fields,
+                    offset + length,
+                    include_uncommitted,
+                    shard_filter,
+                )
+                .await;
+            result_object.observed_vector_count += rlo_late_interaction.observed_vector_count;
+            result_object.observed_cluster_count += rlo_late_interaction.observed_cluster_count;
+            result_object.result_count_total += rlo_late_interaction.result_count_total;
+            rlo_late_interaction.results
+        } else {
+            Vec::new()
+        };
+
         if aggregate_results {
             match search_mode {
                 SearchMode::Lexical => {
@@ -1956,14 +2451,31 @@ impl Search for IndexArc {
                 SearchMode::Vector {
                     similarity_threshold: _,
                     ann_mode: _,
+                    chunk_aggregation: _,
                 } => {
-                    result_object.results = result_object_results_vector;
+                    if vector_field_results.is_empty() {
+                        result_object.results = result_object_results_vector;
+                    } else {
+                        if !result_object_results_vector.is_empty() {
+                            vector_field_results.push(result_object_results_vector);
+                        }
+                        if vector_field_results.len() == 1 {
+                            result_object.results = vector_field_results.remove(0);
+                        } else {
+                            let mut rrf_results: AHashMap<usize, Result> = AHashMap::new();
+                            for results in vector_field_results.iter() {
+                                rrf_fuse_vector_results(&mut rrf_results, results);
+                            }
+                            result_object.results = rrf_results.into_values().collect();
+                        }
+                    }
                 }
                 SearchMode::Hybrid {
                     similarity_threshold: _,
                     ann_mode: _,
+                    chunk_aggregation: _,
+                    sparse_vector: _,
                 } => {
-                    let k = 0.6;
                     let mut rrf_results: AHashMap<usize, Result> = AHashMap::new();
                     for (i, result) in result_object_results_lexical
                         .iter()
@@ -1974,7 +2486,7 @@ impl Search for IndexArc {
                             result.doc_id,
                             Result {
                                 doc_id: result.doc_id,
-                                score: 1.0 / (k + i as f32),
+                                score: 1.0 / (RRF_K + i as f32),
                                 #[cfg(feature = "vb")]
                                 lexical_score: result.score,
                                 #[cfg(feature = "vb")]
@@ -1983,71 +2495,91 @@ impl Search for IndexArc {
                             },
                         );
                     }
-                    for (i, result) in result_object_results_vector
-                        .iter()
-                        .sorted_by(|a, b| b.score.partial_cmp(&a.score).unwrap())
-                        .enumerate()
-                    {
-                        let rrf_score = 1.0 / (k + i as f32);
-
-                        #[cfg(feature = "vb")]
-                        rrf_results
-                            .entry(result.doc_id)
-                            .and_modify(|e| {
-                                e.score += rrf_score;
-
-                                e.field_id = result.field_id;
-                                e.chunk_id = result.chunk_id;
-                                e.level_id = result.level_id;
-                                e.shard_id = result.shard_id;
-                                e.cluster_id = result.cluster_id;
-                                e.cluster_score = result.cluster_score;
-                                e.vector_score = result.vector_score;
-                                e.source = ResultSource::Hybrid;
-                            })
-                            .or_insert(Result {
-                                doc_id: result.doc_id,
-                                score: rrf_score,
-
-                                field_id: result.field_id,
-                                chunk_id: result.chunk_id,
-                                level_id: result.level_id,
-                                shard_id: result.shard_id,
-                                cluster_id: result.cluster_id,
-                                cluster_score: result.cluster_score,
-                                vector_score: result.vector_score,
-                                lexical_score: 0.0,
-                                source: ResultSource::Vector,
-                            });
-
-                        #[cfg(not(feature = "vb"))]
-                        rrf_results
-                            .entry(result.doc_id)
-                            .and_modify(|e| {
-                                e.score += rrf_score;
-                            })
-                            .or_insert(Result {
-                                doc_id: result.doc_id,
-                                score: rrf_score,
-                            });
+                    rrf_fuse_vector_results(&mut rrf_results, &result_object_results_vector);
+                    for results in vector_field_results.iter() {
+                        rrf_fuse_vector_results(&mut rrf_results, results);
                     }
+                    rrf_fuse_sparse_results(&mut rrf_results, &result_object_results_sparse);
                     result_object.results = rrf_results.into_values().collect();
                 }
+                SearchMode::Sparse { ref sparse_vector } => {
+                    result_object.query_terms = sparse_vector.keys().cloned().collect();
+                    result_object.results = result_object_results_sparse;
+                }
+                SearchMode::LateInteraction { .. } => {
+                    result_object.results = result_object_results_late_interaction;
+                }
             };
         }
 
         for (key, value) in result_facets.iter_mut() {
-            let sum = value
+            // the facet values of a single shard are already paged
+            if index_ref.shard_number == 1 {
+                value.1.offset = 0;
+            }
+            result_object
+                .facets
+                .insert(key.clone(), value.1.page(value.0.drain()));
+        }
+
+        for (key, value) in result_geo_grids.iter_mut() {
+            let cells = value
                 .0
                 .iter()
-                .sorted_unstable_by(|a, b| b.1.cmp(a.1))
-                .map(|(a, c)| (a.clone(), *c))
+                .sorted_unstable_by(|a, b| b.1.0.cmp(&a.1.0))
                 .take(value.1 as usize)
+                .map(|(cell_key, (count, lat_sum, lon_sum))| GeoGridCell {
+                    key: cell_key.clone(),
+                    count: *count,
+                    centroid: vec![lat_sum / *count as f64, lon_sum / *count as f64],
+                })
                 .collect::<Vec<_>>();
-            result_object.facets.insert(key.clone(), sum);
+            result_object.geo_grids.insert(key.clone(), cells);
+        }
+
+        if !result_significant_terms.is_empty() {
+            let mut background_total = 0;
+            for (shard_id, shard) in index_ref.shard_vec.iter().enumerate() {
+                if shard_filter.is_none_or(|shard_filter| shard_filter == shard_id) {
+                    background_total += shard.read().await.indexed_doc_count;
+                }
+            }
+
+            for query_facet in query_facets.iter() {
+                if let QueryFacet::SignificantTerms {
+                    field,
+                    length,
+                    min_count,
+                    heuristic,
+                } = query_facet
+                    && let Some(counts) = result_significant_terms.remove(field)
+                {
+                    let significant_terms = rank_significant_terms(
+                        counts
+                            .into_iter()
+                            .map(|(term, (foreground_count, background_count))| {
+                                (term, foreground_count, background_count)
+                            }),
+                        result_object.result_count_total,
+                        background_total,
+                        heuristic,
+                    );
+                    result_object.significant_terms.insert(
+                        field.clone(),
+                        select_significant_terms(significant_terms, *min_count, *length as usize),
+                    );
+                }
+            }
         }
 
         if aggregate_results {
+            let is_empty_query = query_string.is_empty()
+                && query_vector.is_none()
+                && vector_field_query_vector.is_none()
+                && !matches!(
+                    search_mode,
+                    SearchMode::Sparse { .. } | SearchMode::LateInteraction { .. }
+                );
             let mut result_sort_index: Vec<ResultSortIndex> = Vec::new();
             if !result_sort.is_empty() {
                 for rs in result_sort.iter() {
@@ -2056,6 +2588,7 @@ impl Search for IndexArc {
                             idx: usize::MAX,
                             order: rs.order.clone(),
                             base: &rs.base,
+                            distance_method: &rs.distance_method,
                         });
                         continue;
                     }
@@ -2064,6 +2597,7 @@ impl Search for IndexArc {
                             idx: usize::MAX - 1,
                             order: rs.order.clone(),
                             base: &rs.base,
+                            distance_method: &rs.distance_method,
                         });
                         continue;
                     }
@@ -2078,6 +2612,7 @@ impl Search for IndexArc {
                             idx: *idx,
                             order: rs.order.clone(),
                             base: &rs.base,
+                            distance_method: &rs.distance_method,
                         });
                     }
                 }
@@ -2088,14 +2623,14 @@ impl Search for IndexArc {
                     result_ordering_root(
                         &shard_vec,
                         shard_number,
-                        query_string.is_empty() && query_vector.is_none(),
+                        is_empty_query,
                         &result_sort_index,
                         *b,
                         *a,
                     )
                 });
             } else {
-                if query_string.is_empty() && query_vector.is_none() {
+                if is_empty_query {
                     result_object
                         .results
                         .sort_by_key(|b| cmp::Reverse(b.doc_id));
@@ -2121,6 +2656,12 @@ impl Search for IndexArc {
             result_object.result_count = result_object.results.len();
         }
 
+        let text_significant_terms = index_ref
+            .text_significant_terms(&query_facets, &result_object.results)
+            .await;
+        result_object
+            .significant_terms
+            .extend(text_significant_terms);
         result_object.original_query = original_query;
         result_object.query = query_string.clone();
         if let Some(suggestions) = suggestions {
@@ -2131,6 +2672,154 @@ impl Search for IndexArc {
     }
 }
 
+/// Normalizes (cosine similarity) and quantizes a query vector to the precision and quantization of the index,
+/// returns the query embedding with its scale, norm, zero_point and sum_q for the similarity calculation.
+pub(crate) async fn prepare_query_vector(
+    index_ref: &Index,
+    qv: Embedding,
+) -> (Embedding, f32, f32, i16, i32) {
+    let mut qv = qv.widen();
+    if index_ref.vector_similarity == VectorSimilarity::Cosine
+        && matches!(index_ref.meta.inference, Inference::External { .. })
+        && let Embedding::F32(ref mut fvecs) = qv
+    {
+        if index_ref.is_simd {
+            unsafe {
+                normalize_f32_simd(fvecs);
+            }
+        } else {
+            normalize_f32(fvecs);
+        }
+    };
+
+    if (index_ref.quantization == Quantization::ScalarQuantizationI8
+        || index_ref.quantization == Quantization::TurboQuantI8)
+        && let Embedding::F32(ref fvecs) = qv
+    {
+        match (
+            index_ref.vector_similarity,
+            index_ref.quantization,
+            index_ref.is_simd,
+        ) {
+            (VectorSimilarity::Cosine, Quantization::ScalarQuantizationI8, true) => {
+                (unsafe { quantize_f32_to_i8_simd(fvecs) }, 1.0, 0.0, 0, 0)
+            }
+            (VectorSimilarity::Cosine, Quantization::ScalarQuantizationI8, false) => {
+                (quantize_f32_to_i8(fvecs), 1.0, 0.0, 0, 0)
+            }
+
+            (VectorSimilarity::Dot, Quantization::ScalarQuantizationI8, true) => {
+                let quantized_vector = QuantizedVector::new_scale_simd(fvecs);
+                (
+                    Embedding::I8(quantized_vector.data),
+                    quantized_vector.scale,
+                    quantized_vector.norm,
+                    0,
+                    0,
+                )
+            }
+            (VectorSimilarity::Dot, Quantization::ScalarQuantizationI8, false) => {
+                let quantized_vector = QuantizedVector::new_scale(fvecs);
+                (
+                    Embedding::I8(quantized_vector.data),
+                    quantized_vector.scale,
+                    quantized_vector.norm,
+                    0,
+                    0,
+                )
+            }
+            (VectorSimilarity::Euclidean, Quantization::ScalarQuantizationI8, true) => {
+                let non_affine = index_ref.shard_vec[0].read().await.max_vector_value == f32::MIN;
+                if non_affine {
+                    let quantized_vector = QuantizedVector::new_scale_norm_simd(fvecs);
+                    (
+                        Embedding::I8(quantized_vector.data),
+                        quantized_vector.scale,
+                        quantized_vector.norm,
+                        0,
+                        0,
+                    )
+                } else {
+                    let mut min_vector_value = index_ref.shard_vec[0].read().await.min_vector_value;
+                    let mut max_vector_value = index_ref.shard_vec[0].read().await.max_vector_value;
+                    let quantized_vector = QuantizedVector::new_scale_norm_affine_simd(
+                        &mut min_vector_value,
+                        &mut max_vector_value,
+                        fvecs,
+                    );
+                    (
+                        Embedding::I8(quantized_vector.data),
+                        quantized_vector.scale,
+                        quantized_vector.norm,
+                        quantized_vector.zero_point,
+                        quantized_vector.sum_q,
+                    )
+                }
+            }
+
+            (_, Quantization::TurboQuantI8, true) => {
+                let quantized_vector = index_ref.turbo_quant.quantize_f32_i8_simd(fvecs);
+
+                (
+                    Embedding::I8(quantized_vector.data),
+                    quantized_vector.scale,
+                    quantized_vector.norm,
+                    quantized_vector.zero_point,
+                    quantized_vector.sum_q,
+                )
+            }
+            (VectorSimilarity::Euclidean, Quantization::ScalarQuantizationI8, false) => {
+                let non_affine = index_ref.shard_vec[0].read().await.max_vector_value == f32::MIN;
+                if non_affine {
+                    let quantized_vector = QuantizedVector::new_scale_norm(fvecs);
+                    (
+                        Embedding::I8(quantized_vector.data),
+                        quantized_vector.scale,
+                        quantized_vector.norm,
+                        0,
+                        0,
+                    )
+                } else {
+                    let mut min_vector_value = index_ref.shard_vec[0].read().await.min_vector_value;
+                    let mut max_vector_value = index_ref.shard_vec[0].read().await.max_vector_value;
+                    let quantized_vector = QuantizedVector::new_scale_norm_affine(
+                        &mut min_vector_value,
+                        &mut max_vector_value,
+                        fvecs,
+                    );
+                    (
+                        Embedding::I8(quantized_vector.data),
+                        quantized_vector.scale,
+                        quantized_vector.norm,
+                        quantized_vector.zero_point,
+                        quantized_vector.sum_q,
+                    )
+                }
+            }
+
+            (_, Quantization::TurboQuantI8, false) => {
+                let quantized_vector = index_ref.turbo_quant.quantize_f32_i8(fvecs);
+                (
+                    Embedding::I8(quantized_vector.data),
+                    quantized_vector.scale,
+                    quantized_vector.norm,
+                    quantized_vector.zero_point,
+                    quantized_vector.sum_q,
+                )
+            }
+            (
+                _,
+                Quantization::None
+                | Quantization::ProductQuantization { .. }
+                | Quantization::Binary { .. },
+                _,
+            ) => (qv, 0.0, 0.0, 0, 0),
+        }
+    } else {
+        (qv, 0.0, 0.0, 0, 0)
+    }
+}
+
 /// Non-recursive binary search of non-consecutive u64 values in a slice of bytes
 #[inline(never)]
 pub(crate) fn binary_search(
@@ -2437,6 +3126,7 @@ pub(crate) trait SearchLexicalShard {
         field_filter: Vec<String>,
         query_facets: Vec<QueryFacet>,
         facet_filter: Vec<FacetFilter>,
+        range_filter: Vec<RangeFilter>,
         result_sort: Vec<ResultSort>,
     ) -> ResultObject;
 }
@@ -2454,6 +3144,7 @@ impl SearchLexicalShard for ShardArc {
         field_filter: Vec<String>,
         query_facets: Vec<QueryFacet>,
         facet_filter: Vec<FacetFilter>,
+        range_filter: Vec<RangeFilter>,
         result_sort: Vec<ResultSort>,
     ) -> ResultObject {
         let mut result_object: ResultObject = Default::default();
@@ -2502,6 +3193,7 @@ impl SearchLexicalShard for ShardArc {
                         idx: usize::MAX,
                         order: rs.order.clone(),
                         base: &rs.base,
+                        distance_method: &rs.distance_method,
                     });
                     continue;
                 }
@@ -2510,6 +3202,7 @@ impl SearchLexicalShard for ShardArc {
                         idx: usize::MAX - 1,
                         order: rs.order.clone(),
                         base: &rs.base,
+                        distance_method: &rs.distance_method,
                     });
                     continue;
                 }
@@ -2519,6 +3212,7 @@ impl SearchLexicalShard for ShardArc {
                         idx: *idx,
                         order: rs.order.clone(),
                         base: &rs.base,
+                        distance_method: &rs.distance_method,
                     });
                 }
             }
@@ -2541,71 +3235,79 @@ impl SearchLexicalShard for ShardArc {
         };
 
         let mut facet_filter_sparse: Vec<FilterSparse> = Vec::new();
-        if !facet_filter.is_empty() {
+        if !facet_filter.is_empty() || !range_filter.is_empty() {
             facet_filter_sparse = vec![FilterSparse::None; shard_ref.facets.len()];
+            for range_filter_item in range_filter.iter() {
+                if let Some(idx) = shard_ref.facets_map.get(&range_filter_item.field)
+                    && shard_ref.facets[*idx].field_type == range_filter_item.field_type
+                {
+                    facet_filter_sparse[*idx] = range_filter_item.filter_sparse()
+                }
+            }
             for facet_filter_item in facet_filter.iter() {
                 match &facet_filter_item {
                     FacetFilter::U8 { field, filter } => {
                         if let Some(idx) = shard_ref.facets_map.get(field)
                             && shard_ref.facets[*idx].field_type == FieldType::U8
                         {
-                            facet_filter_sparse[*idx] = FilterSparse::U8(filter.clone())
+                            facet_filter_sparse[*idx] = FilterSparse::U8(inclusive_range(filter))
                         }
                     }
                     FacetFilter::U16 { field, filter } => {
                         if let Some(idx) = shard_ref.facets_map.get(field)
                             && shard_ref.facets[*idx].field_type == FieldType::U16
                         {
-                            facet_filter_sparse[*idx] = FilterSparse::U16(filter.clone())
+                            facet_filter_sparse[*idx] = FilterSparse::U16(inclusive_range(filter))
                         }
                     }
                     FacetFilter::U32 { field, filter } => {
                         if let Some(idx) = shard_ref.facets_map.get(field)
                             && shard_ref.facets[*idx].field_type == FieldType::U32
                         {
-                            facet_filter_sparse[*idx] = FilterSparse::U32(filter.clone())
+                            facet_filter_sparse[*idx] = FilterSparse::U32(inclusive_range(filter))
                         }
                     }
                     FacetFilter::U64 { field, filter } => {
                         if let Some(idx) = shard_ref.facets_map.get(field)
                             && shard_ref.facets[*idx].field_type == FieldType::U64
                         {
-                            facet_filter_sparse[*idx] = FilterSparse::U64(filter.clone())
+                            facet_filter_sparse[*idx] = FilterSparse::U64(inclusive_range(filter))
                         }
                     }
                     FacetFilter::I8 { field, filter } => {
                         if let Some(idx) = shard_ref.facets_map.get(field)
                             && shard_ref.facets[*idx].field_type == FieldType::I8
                         {
-                            facet_filter_sparse[*idx] = FilterSparse::I8(filter.clone())
+                            facet_filter_sparse[*idx] = FilterSparse::I8(inclusive_range(filter))
                         }
                     }
                     FacetFilter::I16 { field, filter } => {
                         if let Some(idx) = shard_ref.facets_map.get(field)
                             && shard_ref.facets[*idx].field_type == FieldType::I16
                         {
-                            facet_filter_sparse[*idx] = FilterSparse::I16(filter.clone())
+                            facet_filter_sparse[*idx] = FilterSparse::I16(inclusive_range(filter))
                         }
                     }
                     FacetFilter::I32 { field, filter } => {
                         if let Some(idx) = shard_ref.facets_map.get(field)
                             && shard_ref.facets[*idx].field_type == FieldType::I32
                         {
-                            facet_filter_sparse[*idx] = FilterSparse::I32(filter.clone())
+                            facet_filter_sparse[*idx] = FilterSparse::I32(inclusive_range(filter))
                         }
                     }
                     FacetFilter::I64 { field, filter } => {
                         if let Some(idx) = shard_ref.facets_map.get(field)
                             && shard_ref.facets[*idx].field_type == FieldType::I64
                         {
-                            facet_filter_sparse[*idx] = FilterSparse::I64(filter.clone())
+                            facet_filter_sparse[*idx] = FilterSparse::I64(inclusive_range(filter))
                         }
                     }
                     FacetFilter::Timestamp { field, filter } => {
                         if let Some(idx) = shard_ref.facets_map.get(field)
                             && shard_ref.facets[*idx].field_type == FieldType::Timestamp
                         {
-                            facet_filter_sparse[*idx] = FilterSparse::Timestamp(filter.clone())
+                            facet_filter_sparse[*idx] =
+                                FilterSparse::Timestamp(inclusive_range(filter))
                         }
                     }
                     FacetFilter::F32 { field, filter } => {
@@ -2721,6 +3423,23 @@ impl SearchLexicalShard for ShardArc {
                             );
                         }
                     }
+
+                    FacetFilter::PointBoundingBox { field, filter } => {
+                        if let Some(idx) = shard_ref.facets_map.get(field)
+                            && shard_ref.facets[*idx].field_type == FieldType::Point
+                        {
+                            facet_filter_sparse[*idx] =
+                                FilterSparse::BoundingBox(filter.0.clone(), filter.1.clone());
+                        }
+                    }
+
+                    FacetFilter::PointPolygon { field, filter } => {
+                        if let Some(idx) = shard_ref.facets_map.get(field)
+                            && shard_ref.facets[*idx].field_type == FieldType::Point
+                        {
+                            facet_filter_sparse[*idx] = FilterSparse::Polygon(filter.clone());
+                        }
+                    }
                 }
             }
         }
@@ -2921,6 +3640,9 @@ impl SearchLexicalShard for ShardArc {
                         field,
                         prefix,
                         length,
+                        offset,
+                        sort,
+                        min_count,
                     } => {
                         if let Some(idx) = shard_ref.facets_map.get(field)
                             && shard_ref.facets[*idx].field_type == FieldType::String16
@@ -2929,6 +3651,9 @@ impl SearchLexicalShard for ShardArc {
                                 field: field.clone(),
                                 prefix: prefix.clone(),
                                 length: *length as u32,
+                                offset: *offset as u32,
+                                sort: sort.clone(),
+                                min_count: *min_count,
                                 ..Default::default()
                             }
                         }
@@ -2937,6 +3662,9 @@ impl SearchLexicalShard for ShardArc {
                         field,
                         prefix,
                         length,
+                        offset,
+                        sort,
+                        min_count,
                     } => {
                         if let Some(idx) = shard_ref.facets_map.get(field)
                             && shard_ref.facets[*idx].field_type == FieldType::StringSet16
@@ -2945,6 +3673,9 @@ impl SearchLexicalShard for ShardArc {
                                 field: field.clone(),
                                 prefix: prefix.clone(),
                                 length: *length as u32,
+                                offset: *offset as u32,
+                                sort: sort.clone(),
+                                min_count: *min_count,
                                 ..Default::default()
                             }
                         }
@@ -2954,6 +3685,9 @@ impl SearchLexicalShard for ShardArc {
                         field,
                         prefix,
                         length,
+                        offset,
+                        sort,
+                        min_count,
                     } => {
                         if let Some(idx) = shard_ref.facets_map.get(field)
                             && shard_ref.facets[*idx].field_type == FieldType::String32
@@ -2962,6 +3696,9 @@ impl SearchLexicalShard for ShardArc {
                                 field: field.clone(),
                                 prefix: prefix.clone(),
                                 length: *length,
+                                offset: *offset,
+                                sort: sort.clone(),
+                                min_count: *min_count,
                                 ..Default::default()
                             }
                         }
@@ -2970,6 +3707,9 @@ impl SearchLexicalShard for ShardArc {
                         field,
                         prefix,
                         length,
+                        offset,
+                        sort,
+                        min_count,
                     } => {
                         if let Some(idx) = shard_ref.facets_map.get(field)
                             && shard_ref.facets[*idx].field_type == FieldType::StringSet32
@@ -2978,6 +3718,9 @@ impl SearchLexicalShard for ShardArc {
                                 field: field.clone(),
                                 prefix: prefix.clone(),
                                 length: *length,
+                                offset: *offset,
+                                sort: sort.clone(),
+                                min_count: *min_count,
                                 ..Default::default()
                             }
                         }
@@ -3008,6 +3751,50 @@ impl SearchLexicalShard for ShardArc {
                         }
                     }
 
+                    QueryFacet::GeoGrid {
+                        field,
+                        grid_type,
+                        precision,
+                        length,
+                    } => {
+                        if let Some(idx) = shard_ref.facets_map.get(field)
+                            && shard_ref.facets[*idx].field_type == FieldType::Point
+                        {
+                            is_range_facet = true;
+                            search_result.query_facets[*idx] = ResultFacet {
+                                field: field.clone(),
+                                length: *length,
+                                ranges: Ranges::GeoGrid(grid_type.clone(), *precision),
+                                ..Default::default()
+                            };
+                        }
+                    }
+
+                    QueryFacet::SignificantTerms {
+                        field,
+                        length,
+                        min_count,
+                        heuristic,
+                    } => {
+                        if let Some(idx) = shard_ref.facets_map.get(field)
+                            && matches!(
+                                shard_ref.facets[*idx].field_type,
+                                FieldType::String16
+                                    | FieldType::String32
+                                    | FieldType::StringSet16
+                                    | FieldType::StringSet32
+                            )
+                        {
+                            search_result.query_facets[*idx] = ResultFacet {
+                                field: field.clone(),
+                                length: *length,
+                                min_count: *min_count,
+                                significance: Some(heuristic.clone()),
+                                ..Default::default()
+                            };
+                        }
+                    }
+
                     QueryFacet::None => {}
                 };
             }
@@ -3425,15 +4212,12 @@ impl SearchLexicalShard for ShardArc {
                                 continue;
                             }
 
-                            let v = stopword_result_object.facets[&facet.field]
-                                .iter()
-                                .sorted_unstable_by(|a, b| b.1.cmp(&a.1))
-                                .map(|(a, c)| (a.clone(), *c))
-                                .filter(|(a, _c)| {
-                                    facet.prefix.is_empty() || a.starts_with(&facet.prefix)
-                                })
-                                .take(facet.length.max(facet_cap) as usize)
-                                .collect::<Vec<_>>();
+                            let v = facet.page_shard(
+                                stopword_result_object.facets[&facet.field]
+                                    .iter()
+                                    .map(|(a, c)| (a.clone(), *c)),
+                                shard_ref.shard_number,
+                            );
                             if !v.is_empty() {
                                 facets.insert(facet.field.clone(), v);
                             }
@@ -3596,6 +4380,59 @@ impl SearchLexicalShard for ShardArc {
             + result_count_arc.load(Ordering::Relaxed);
 
         if !search_result.query_facets.is_empty() {
+            // geo grid cells are counted per matching document, also by the iterator of an empty query
+            for facet in search_result.query_facets.iter() {
+                if let Ranges::GeoGrid(grid_type, precision) = &facet.ranges
+                    && facet.length > 0
+                    && !facet.cells.is_empty()
+                {
+                    let cells = facet
+                        .cells
+                        .iter()
+                        .sorted_unstable_by(|a, b| b.1.0.cmp(&a.1.0))
+                        .take(facet.length.max(facet_cap) as usize)
+                        .map(|(cell, (count, lat_sum, lon_sum))| GeoGridCell {
+                            key: geo_grid_cell_key(*cell, grid_type, *precision),
+                            count: *count,
+                            centroid: vec![lat_sum / *count as f64, lon_sum / *count as f64],
+                        })
+                        .collect::<Vec<_>>();
+                    result_object.geo_grids.insert(facet.field.clone(), cells);
+                }
+            }
+
+            // significant terms: query facet counts (foreground) compared with index facet counts (background)
+            for (i, facet) in search_result.query_facets.iter().enumerate() {
+                let Some(heuristic) = &facet.significance else {
+                    continue;
+                };
+                if facet.length == 0 || facet.values.is_empty() {
+                    continue;
+                }
+
+                let significant_terms = rank_significant_terms(
+                    facet_term_counts(&shard_ref.facets[i], facet.values.iter()).into_iter(),
+                    result_object.result_count_total,
+                    shard_ref.indexed_doc_count,
+                    heuristic,
+                );
+                let significant_terms = if shard_ref.shard_number == 1 {
+                    select_significant_terms(
+                        significant_terms,
+                        facet.min_count,
+                        facet.length as usize,
+                    )
+                } else {
+                    significant_terms
+                        .into_iter()
+                        .take(facet.length as usize * SHARD_CANDIDATE_FACTOR)
+                        .collect()
+                };
+                result_object
+                    .significant_terms
+                    .insert(facet.field.clone(), significant_terms);
+            }
+
             result_object.facets = if result_object.query_terms.is_empty() {
                 shard_ref
                     .get_index_string_facets_shard(query_facets)
@@ -3603,6 +4440,13 @@ impl SearchLexicalShard for ShardArc {
             } else {
                 let mut facets: AHashMap<String, Facet> = AHashMap::new();
                 for (i, facet) in search_result.query_facets.iter_mut().enumerate() {
+                    if let Ranges::GeoGrid(..) = &facet.ranges {
+                        continue;
+                    }
+                    if facet.significance.is_some() {
+                        continue;
+                    }
+
                     if facet.length == 0 || facet.values.is_empty() {
                         continue;
                     }
@@ -3626,21 +4470,10 @@ impl SearchLexicalShard for ShardArc {
                                     *hash_map.entry(term.clone()).or_insert(0) += value.1;
                                 }
                             }
-                            hash_map
-                                .iter()
-                                .sorted_unstable_by(|a, b| b.1.cmp(a.1))
-                                .map(|(a, c)| (a.clone(), *c))
-                                .filter(|(a, _c)| {
-                                    facet.prefix.is_empty() || a.starts_with(&facet.prefix)
-                                })
-                                .take(facet.length.max(facet_cap) as usize)
-                                .collect::<Vec<_>>()
+                            facet.page_shard(hash_map.into_iter(), shard_ref.shard_number)
                         } else {
-                            facet
-                                .values
-                                .iter()
-                                .sorted_unstable_by(|a, b| b.1.cmp(a.1))
-                                .map(|(a, c)| {
+                            facet.page_shard(
+                                facet.values.iter().map(|(a, c)| {
                                     (
                                         shard_ref.facets[i]
                                             .values
@@ -3650,12 +4483,9 @@ impl SearchLexicalShard for ShardArc {
                                             .clone(),
                                         *c,
                                     )
-                                })
-                                .filter(|(a, _c)| {
-                                    facet.prefix.is_empty() || a.starts_with(&facet.prefix)
-                                })
-                                .take(facet.length.max(facet_cap) as usize)
-                                .collect::<Vec<_>>()
+                                }),
+                                shard_ref.shard_number,
+                            )
                         }
                     } else {
                         let range_type = match &facet.ranges {
@@ -3766,3 +4596,99 @@ impl SearchLexicalShard for ShardArc {
         result_object
     }
 }
+
+/// Reciprocal rank fusion constant of hybrid search and of the fusion of several vector fields.
+const RRF_K: f32 = 0.6;
+
+/// Adds the reciprocal rank fusion scores of a vector result list to the fused results.
+/// Documents already found by lexical search become hybrid results.
+pub(crate) fn rrf_fuse_vector_results(
+    rrf_results: &mut AHashMap<usize, Result>,
+    results: &[Result],
+) {
+    for (i, result) in results
+        .iter()
+        .sorted_by(|a, b| b.score.partial_cmp(&a.score).unwrap())
+        .enumerate()
+    {
+        let rrf_score = 1.0 / (RRF_K + i as f32);
+
+        #[cfg(feature = "vb")]
+        rrf_results
+            .entry(result.doc_id)
+            .and_modify(|e| {
+                e.score += rrf_score;
+
+                if matches!(e.source, ResultSource::Lexical) {
+                    e.field_id = result.field_id;
+                    e.chunk_id = result.chunk_id;
+                    e.level_id = result.level_id;
+                    e.shard_id = result.shard_id;
+                    e.cluster_id = result.cluster_id;
+                    e.cluster_score = result.cluster_score;
+                    e.vector_score = result.vector_score;
+                    e.source = ResultSource::Hybrid;
+                }
+            })
+            .or_insert(Result {
+                doc_id: result.doc_id,
+                score: rrf_score,
+
+                field_id: result.field_id,
+                chunk_id: result.chunk_id,
+                level_id: result.level_id,
+                shard_id: result.shard_id,
+                cluster_id: result.cluster_id,
+                cluster_score: result.cluster_score,
+                vector_score: result.vector_score,
+                lexical_score: 0.0,
+                source: ResultSource::Vector,
+            });
+
+        #[cfg(not(feature = "vb"))]
+        rrf_results
+            .entry(result.doc_id)
+            .and_modify(|e| {
+                e.score += rrf_score;
+            })
+            .or_insert(Result {
+                doc_id: result.doc_id,
+                score: rrf_score,
+            });
+    }
+}
+
+/// Adds the reciprocal rank fusion scores of the sparse vector results to the fused results.
+/// Documents already found by lexical or vector search become hybrid results.
+fn rrf_fuse_sparse_results(rrf_results: &mut AHashMap<usize, Result>, results: &[Result]) {
+    for (i, result) in results.iter().enumerate() {
+        let rrf_score = 1.0 / (RRF_K + i as f32);
+
+        #[cfg(feature = "vb")]
+        rrf_results
+            .entry(result.doc_id)
+            .and_modify(|e| {
+                e.score += rrf_score;
+                e.source = ResultSource::Hybrid;
+            })
+            .or_insert(Result {
+                doc_id: result.doc_id,
+                score: rrf_score,
+                shard_id: result.shard_id,
+                lexical_score: result.lexical_score,
+                source: ResultSource::Sparse,
+                ..Default::default()
+            });
+
+        #[cfg(not(feature = "vb"))]
+        rrf_results
+            .entry(result.doc_id)
+            .and_modify(|e| {
+                e.score += rrf_score;
+            })
+            .or_insert(Result {
+                doc_id: result.doc_id,
+                score: rrf_score,
+            });
+    }
+}
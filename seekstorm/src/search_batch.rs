@@ -0,0 +1,215 @@
+use ahash::AHashMap;
+
+use crate::{
+    INDEX_RUNTIME,
+    index::IndexArc,
+    min_heap::Result,
+    search::{ResultObject, prepare_query_vector, rrf_fuse_vector_results},
+    vector::{ChunkAggregation, Embedding, SearchVectorShard},
+    vector_field::embedding_dimensions,
+    vector_similarity::AnnMode,
+};
+
+/// Batch vector search: searches many query vectors at once, e.g. for offline evaluation or near-duplicate detection.
+#[allow(async_fn_in_trait)]
+#[allow(clippy::too_many_arguments)]
+pub trait SearchBatch {
+    /// Searches the vector index with several query vectors at once, with the same parameters as search with SearchMode::Vector.
+    /// Each selected cluster is scanned once for all queries that selected it: every stored vector is read once and scored against all those queries.
+    /// HNSW graphs (AnnMode::EfSearch) and compressed levels (product and binary quantization) are searched per query.
+    /// * `query_vectors`: Query vectors, with the dimensions of the index or of a vector field with its own inference (SchemaField.inference).
+    /// * `length`: Number of results to return per query.
+    /// * `similarity_threshold`: Minimum similarity score of the results, see SearchMode::Vector.
+    /// * `ann_mode`: Specifies the clusters to search, see SearchMode::Vector.
+    /// * `chunk_aggregation`: Aggregation of the chunk scores of a document, see SearchMode::Vector.
+    /// * `include_uncommitted`: Search also documents which have not yet been committed.
+    /// * `field_filter`: Vector fields to search, if empty all vector fields are searched. The results of several vector fields are fused with RRF.
+    ///
+    /// Returns a result object per query vector, in the order of the query vectors.
+    async fn search_batch(
+        &self,
+        query_vectors: Vec<Embedding>,
+        length: usize,
+        similarity_threshold: Option<f32>,
+        ann_mode: AnnMode,
+        chunk_aggregation: ChunkAggregation,
+        include_uncommitted: bool,
+        field_filter: Vec<String>,
+    ) -> Vec<ResultObject>;
+}
+
+impl SearchBatch for IndexArc {
+    async fn search_batch(
+        &self,
+        query_vectors: Vec<Embedding>,
+        length: usize,
+        similarity_threshold: Option<f32>,
+        ann_mode: AnnMode,
+        chunk_aggregation: ChunkAggregation,
+        include_uncommitted: bool,
+        field_filter: Vec<String>,
+    ) -> Vec<ResultObject> {
+        let index_ref = self.read().await;
+        let mut result_objects: Vec<ResultObject> =
+            query_vectors.iter().map(|_| Default::default()).collect();
+        let mut vector_results: Vec<Vec<Vec<Result>>> = vec![Vec::new(); query_vectors.len()];
+
+        let is_default_vector_search = field_filter.is_empty()
+            || field_filter.iter().any(|field| {
+                index_ref.schema_map.get(field).is_some_and(|schema_field| {
+                    schema_field.index_vector && schema_field.inference.is_none()
+                })
+            });
+
+        let mut query_ids = Vec::new();
+        let mut prepared_query_vectors = Vec::new();
+        if index_ref.is_vector_indexing && is_default_vector_search {
+            for (query_id, query_vector) in query_vectors.iter().enumerate() {
+                if embedding_dimensions(query_vector) == index_ref.vector_dimensions_original {
+                    query_ids.push(query_id);
+                    prepared_query_vectors
+                        .push(prepare_query_vector(&index_ref, query_vector.clone()).await);
+                }
+            }
+        }
+
+        if !prepared_query_vectors.is_empty() {
+            let shard_number = index_ref.shard_number;
+            let resolved_ann_mode = index_ref.resolve_ann_mode(&ann_mode);
+            // the queries are split into chunks per shard, to search them in parallel on all cores
+            let chunk_size = prepared_query_vectors
+                .len()
+                .div_ceil((num_cpus::get() / shard_number).max(1));
+            let mut shard_result_objects = Vec::new();
+            for shard in index_ref.shard_vec.iter() {
+                let shard_id = shard.read().await.meta.id as usize;
+                for (chunk_id, query_vectors_chunk) in
+                    prepared_query_vectors.chunks(chunk_size).enumerate()
+                {
+                    let shard_clone = shard.clone();
+                    let query_vectors_clone = query_vectors_chunk.to_vec();
+                    let ann_mode_clone = resolved_ann_mode.clone();
+                    let field_filter_clone = field_filter.clone();
+                    shard_result_objects.push((
+                        shard_id,
+                        chunk_id * chunk_size,
+                        INDEX_RUNTIME.handle().spawn(async move {
+                            shard_clone
+                                .search_vector_shard_batch(
+                                    query_vectors_clone,
+                                    length,
+                                    include_uncommitted,
+                                    similarity_threshold,
+                                    ann_mode_clone,
+                                    chunk_aggregation,
+                                    field_filter_clone,
+                                )
+                                .await
+                        }),
+                    ));
+                }
+            }
+
+            let mut shard_results: Vec<Vec<Result>> = vec![Vec::new(); query_vectors.len()];
+            for (shard_id, chunk_start, shard_result_object) in shard_result_objects {
+                for (query_id, rlo_shard) in query_ids[chunk_start..]
+                    .iter()
+                    .zip(shard_result_object.await.unwrap())
+                {
+                    let result_object = &mut result_objects[*query_id];
+                    result_object.observed_vector_count += rlo_shard.observed_vector_count;
+                    result_object.observed_cluster_count += rlo_shard.observed_cluster_count;
+                    result_object.result_count_total += rlo_shard.result_count_total;
+                    shard_results[*query_id].extend(rlo_shard.results.into_iter().map(
+                        |mut result| {
+                            result.doc_id = (result.doc_id * shard_number) + shard_id;
+                            result
+                        },
+                    ));
+                }
+            }
+            for (query_id, results) in shard_results.into_iter().enumerate() {
+                if !results.is_empty() {
+                    vector_results[query_id].push(results);
+                }
+            }
+        }
+
+        for vector_field_index in
+            index_ref
+                .vector_field_indices
+                .iter()
+                .filter(|vector_field_index| {
+                    field_filter.is_empty() || field_filter.contains(&vector_field_index.field)
+                })
+        {
+            let dimensions = vector_field_index
+                .index_arc
+                .read()
+                .await
+                .vector_dimensions_original;
+            let (field_query_ids, field_query_vectors): (Vec<usize>, Vec<Embedding>) =
+                query_vectors
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, query_vector)| embedding_dimensions(query_vector) == dimensions)
+                    .map(|(query_id, query_vector)| (query_id, query_vector.clone()))
+                    .unzip();
+            if field_query_vectors.is_empty() {
+                continue;
+            }
+
+            let rlo_vector_fields = Box::pin(vector_field_index.index_arc.search_batch(
+                field_query_vectors,
+                length,
+                similarity_threshold,
+                ann_mode.clone(),
+                chunk_aggregation,
+                include_uncommitted,
+                Vec::new(),
+            ))
+            .await;
+
+            for (query_id, rlo_vector_field) in field_query_ids.iter().zip(rlo_vector_fields) {
+                let result_object = &mut result_objects[*query_id];
+                result_object.observed_vector_count += rlo_vector_field.observed_vector_count;
+                result_object.observed_cluster_count += rlo_vector_field.observed_cluster_count;
+                result_object.result_count_total += rlo_vector_field.result_count_total;
+
+                #[cfg(feature = "vb")]
+                let results: Vec<Result> = rlo_vector_field
+                    .results
+                    .into_iter()
+                    .map(|mut result| {
+                        result.field_id = vector_field_index.indexed_field_id as u32;
+                        result
+                    })
+                    .collect();
+                #[cfg(not(feature = "vb"))]
+                let results = rlo_vector_field.results;
+                if !results.is_empty() {
+                    vector_results[*query_id].push(results);
+                }
+            }
+        }
+
+        for (result_object, mut results) in result_objects.iter_mut().zip(vector_results) {
+            result_object.results = if results.len() <= 1 {
+                results.pop().unwrap_or_default()
+            } else {
+                let mut rrf_results: AHashMap<usize, Result> = AHashMap::new();
+                for results in results.iter() {
+                    rrf_fuse_vector_results(&mut rrf_results, results);
+                }
+                rrf_results.into_values().collect()
+            };
+            result_object
+                .results
+                .sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+            result_object.results.truncate(length);
+            result_object.result_count = result_object.results.len();
+        }
+
+        result_objects
+    }
+}
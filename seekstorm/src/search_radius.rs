@@ -0,0 +1,199 @@
+use ahash::AHashMap;
+
+use crate::{
+    INDEX_RUNTIME,
+    index::{Clustering, IndexArc},
+    min_heap::Result,
+    search::prepare_query_vector,
+    vector::{ChunkAggregation, Embedding, SearchVectorShard, UNLIMITED_LENGTH},
+    vector_field::embedding_dimensions,
+    vector_similarity::{AnnMode, VectorSimilarity},
+};
+
+/// Result object of a radius vector search.
+#[derive(Debug, Clone, Default)]
+pub struct RadiusResultObject {
+    /// Number of returned search results. Identical to results.len()
+    pub result_count: usize,
+    /// Number of documents within the radius found by the search: exact for exhaustive search, a lower bound of the total for ANN search.
+    pub result_count_total: usize,
+    /// True if result_count_total is a lower bound: ANN search counts only the documents within the radius in the searched clusters (or visited HNSW nodes),
+    /// documents within the radius in the other clusters are neither counted nor returned. Exhaustive search (AnnMode::All, or indices without clustering) counts exactly.
+    pub result_count_lower_bound: bool,
+    /// Number of vectors observed during search
+    pub observed_vector_count: usize,
+    /// Number of clusters observed during search
+    pub observed_cluster_count: usize,
+    /// Documents within the radius from offset to offset+length, sorted by descending similarity (ascending distance), and by document id for equal scores.
+    /// For VectorSimilarity::Euclidean the score is the negative squared distance.
+    pub results: Vec<Result>,
+}
+
+/// Radius (range) vector search: all documents within a radius of the query vector, e.g. for deduplication and clustering jobs.
+#[allow(async_fn_in_trait)]
+#[allow(clippy::too_many_arguments)]
+pub trait SearchRadius {
+    /// Searches all documents with a vector within the radius of the query vector, without the top-k cap of search with SearchMode::Vector.
+    /// The results are sorted by similarity and paginated with offset and length, result_count_total is the number of all documents within the radius.
+    /// Vectors of the selected clusters are scored with full precision, product and binary quantization codes are not used.
+    /// The results are not cached: each page searches and sorts all documents within the radius again,
+    /// so paging through many pages costs a full radius search per page, while length usize::MAX returns all of them with a single search.
+    /// * `query_vector`: Query vector, with the dimensions of the index or of a vector field with its own inference (SchemaField.inference).
+    /// * `radius`: Maximum Euclidean distance for VectorSimilarity::Euclidean, minimum similarity between 0.0 and 1.0 for VectorSimilarity::Cosine and VectorSimilarity::Dot.
+    /// * `ann_mode`: Specifies the clusters to search, see SearchMode::Vector. AnnMode::All searches exhaustively and counts exactly,
+    ///   other modes search and count the documents within the radius only in the selected clusters (AnnMode::EfSearch: in the visited HNSW nodes),
+    ///   result_count_total is then a lower bound (result_count_lower_bound).
+    /// * `offset`: Offset of the returned results, to page through the documents within the radius.
+    /// * `length`: Number of results to return, usize::MAX returns all documents within the radius.
+    /// * `include_uncommitted`: Search also documents which have not yet been committed.
+    /// * `field_filter`: Vector fields to search, if empty all vector fields are searched. Documents within the radius of several vector fields are returned once, with their highest score.
+    async fn search_radius(
+        &self,
+        query_vector: Embedding,
+        radius: f32,
+        ann_mode: AnnMode,
+        offset: usize,
+        length: usize,
+        include_uncommitted: bool,
+        field_filter: Vec<String>,
+    ) -> RadiusResultObject;
+}
+
+impl SearchRadius for IndexArc {
+    async fn search_radius(
+        &self,
+        query_vector: Embedding,
+        radius: f32,
+        ann_mode: AnnMode,
+        offset: usize,
+        length: usize,
+        include_uncommitted: bool,
+        field_filter: Vec<String>,
+    ) -> RadiusResultObject {
+        let index_ref = self.read().await;
+        let mut result_object = RadiusResultObject::default();
+        let mut radius_results: AHashMap<usize, Result> = AHashMap::new();
+        let mut add_result = |result: Result| match radius_results.get_mut(&result.doc_id) {
+            Some(radius_result) if radius_result.score >= result.score => {}
+            Some(radius_result) => *radius_result = result,
+            None => {
+                radius_results.insert(result.doc_id, result);
+            }
+        };
+
+        let is_default_vector_search = field_filter.is_empty()
+            || field_filter.iter().any(|field| {
+                index_ref.schema_map.get(field).is_some_and(|schema_field| {
+                    schema_field.index_vector && schema_field.inference.is_none()
+                })
+            });
+
+        if index_ref.is_vector_indexing
+            && is_default_vector_search
+            && embedding_dimensions(&query_vector) == index_ref.vector_dimensions_original
+        {
+            let prepared_query_vector =
+                prepare_query_vector(&index_ref, query_vector.clone()).await;
+            let resolved_ann_mode = index_ref.resolve_ann_mode(&ann_mode);
+            // Euclidean scores are negative squared distances
+            let similarity_threshold = match index_ref.vector_similarity {
+                VectorSimilarity::Euclidean => radius * radius,
+                VectorSimilarity::Cosine | VectorSimilarity::Dot => radius,
+            };
+            result_object.result_count_lower_bound =
+                match (&resolved_ann_mode, &index_ref.meta.clustering) {
+                    (AnnMode::All, _) | (_, Clustering::None | Clustering::Fixed(0 | 1)) => false,
+                    (AnnMode::EfSearch(_), clustering) => {
+                        matches!(clustering, Clustering::Hnsw { .. })
+                    }
+                    (_, clustering) => !matches!(clustering, Clustering::Hnsw { .. }),
+                };
+
+            let shard_number = index_ref.shard_number;
+            let mut shard_result_objects = Vec::new();
+            for shard in index_ref.shard_vec.iter() {
+                let shard_id = shard.read().await.meta.id as usize;
+                let shard_clone = shard.clone();
+                let query_vector_clone = prepared_query_vector.clone();
+                let ann_mode_clone = resolved_ann_mode.clone();
+                let field_filter_clone = field_filter.clone();
+                shard_result_objects.push((
+                    shard_id,
+                    INDEX_RUNTIME.handle().spawn(async move {
+                        shard_clone
+                            .search_vector_shard(
+                                Some(query_vector_clone),
+                                UNLIMITED_LENGTH,
+                                include_uncommitted,
+                                Some(similarity_threshold),
+                                ann_mode_clone,
+                                ChunkAggregation::Max,
+                                field_filter_clone,
+                            )
+                            .await
+                    }),
+                ));
+            }
+
+            for (shard_id, shard_result_object) in shard_result_objects {
+                let rlo_shard = shard_result_object.await.unwrap();
+                result_object.observed_vector_count += rlo_shard.observed_vector_count;
+                result_object.observed_cluster_count += rlo_shard.observed_cluster_count;
+                for mut result in rlo_shard.results {
+                    result.doc_id = (result.doc_id * shard_number) + shard_id;
+                    add_result(result);
+                }
+            }
+        }
+
+        for vector_field_index in
+            index_ref
+                .vector_field_indices
+                .iter()
+                .filter(|vector_field_index| {
+                    field_filter.is_empty() || field_filter.contains(&vector_field_index.field)
+                })
+        {
+            if embedding_dimensions(&query_vector)
+                != vector_field_index
+                    .index_arc
+                    .read()
+                    .await
+                    .vector_dimensions_original
+            {
+                continue;
+            }
+
+            let rlo_vector_field = Box::pin(vector_field_index.index_arc.search_radius(
+                query_vector.clone(),
+                radius,
+                ann_mode.clone(),
+                0,
+                usize::MAX,
+                include_uncommitted,
+                Vec::new(),
+            ))
+            .await;
+
+            result_object.result_count_lower_bound |= rlo_vector_field.result_count_lower_bound;
+            result_object.observed_vector_count += rlo_vector_field.observed_vector_count;
+            result_object.observed_cluster_count += rlo_vector_field.observed_cluster_count;
+            for result in rlo_vector_field.results {
+                #[cfg(feature = "vb")]
+                let result = Result {
+                    field_id: vector_field_index.indexed_field_id as u32,
+                    ..result
+                };
+                add_result(result);
+            }
+        }
+
+        let mut results: Vec<Result> = radius_results.into_values().collect();
+        results.sort_unstable_by(|a, b| b.score.total_cmp(&a.score).then(a.doc_id.cmp(&b.doc_id)));
+        result_object.result_count_total = results.len();
+        result_object.results = results.into_iter().skip(offset).take(length).collect();
+        result_object.result_count = result_object.results.len();
+
+        result_object
+    }
+}
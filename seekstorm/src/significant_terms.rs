@@ -0,0 +1,321 @@
+use std::collections::HashSet;
+
+use ahash::{AHashMap, AHashSet};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    index::{AccessType, FacetField, FieldType, Index, MAX_POSITIONS_PER_TERM, NgramType, Shard},
+    min_heap::Result,
+    search::{QueryFacet, QueryType, decode_posting_list_counts},
+    tokenizer::tokenizer,
+};
+
+/// Number of candidate terms per shard, as multiple of the requested length, that are merged into the final significant terms of a multi-shard index.
+/// A term that is significant in the whole index, but not among the candidates of a shard, loses the counts of that shard.
+pub(crate) const SHARD_CANDIDATE_FACTOR: usize = 3;
+
+/// Significance heuristic: scores how much more frequent a term is in the result set (foreground) than in the whole index (background).
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub enum SignificanceHeuristic {
+    /// JLH score: absolute change in popularity multiplied by relative change in popularity. Favors rare terms with a strong uplift.
+    #[default]
+    Jlh,
+    /// Chi-square test of independence between term occurrence and result set membership. Favors more common terms.
+    ChiSquare,
+}
+
+/// Significant term with its significance score and the counts it was derived from.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct SignificantTerm {
+    /// term: term of a text field or facet field value
+    pub term: String,
+    /// significance score
+    pub score: f64,
+    /// number of documents of the foreground containing the term: documents matching the query (facet fields) or returned results (text fields)
+    pub foreground_count: usize,
+    /// number of documents in the whole index (background) containing the term
+    pub background_count: usize,
+}
+
+/// Foreground and background counts of the values of a string facet field within a shard.
+/// The foreground counts are the query facet counts collected during search (value id, number of matching documents),
+/// the background counts are the facet value counts collected at index time.
+/// For StringSet16 and StringSet32 fields the counts of each value of the set are summed up.
+pub(crate) fn facet_term_counts<'a>(
+    facet_field: &FacetField,
+    foreground: impl Iterator<Item = (&'a u32, &'a usize)>,
+) -> Vec<(String, usize, usize)> {
+    let is_set = matches!(
+        facet_field.field_type,
+        FieldType::StringSet16 | FieldType::StringSet32
+    );
+
+    let mut counts: AHashMap<String, (usize, usize)> = AHashMap::new();
+    for (value_id, count) in foreground {
+        let Some((value, (terms, _))) = facet_field.values.get_index(*value_id as usize) else {
+            continue;
+        };
+        if is_set {
+            for term in terms.iter() {
+                counts.entry(term.clone()).or_default().0 += count;
+            }
+        } else {
+            counts.entry(value.clone()).or_default().0 += count;
+        }
+    }
+
+    for (value, (terms, count)) in facet_field.values.iter() {
+        if is_set {
+            for term in terms.iter() {
+                if let Some(term_counts) = counts.get_mut(term) {
+                    term_counts.1 += count;
+                }
+            }
+        } else if let Some(term_counts) = counts.get_mut(value) {
+            term_counts.1 += count;
+        }
+    }
+
+    counts
+        .into_iter()
+        .map(|(term, (foreground_count, background_count))| {
+            (term, foreground_count, background_count)
+        })
+        .collect()
+}
+
+impl Index {
+    /// Significant terms of the text fields of the significant terms query facets: the foreground are the returned results,
+    /// whose stored field text is tokenized with the index tokenizer, the background count of a term is its posting count in the committed and uncommitted index.
+    /// Query facets of fields that are not stored, lexically indexed text fields are skipped.
+    pub(crate) async fn text_significant_terms(
+        &self,
+        query_facets: &[QueryFacet],
+        results: &[Result],
+    ) -> AHashMap<String, Vec<SignificantTerm>> {
+        let mut text_significant_terms = AHashMap::new();
+        if results.is_empty() {
+            return text_significant_terms;
+        }
+
+        let mut background_total = 0;
+        for shard in self.shard_vec.iter() {
+            background_total += shard.read().await.indexed_doc_count;
+        }
+
+        for query_facet in query_facets.iter() {
+            let QueryFacet::SignificantTerms {
+                field,
+                length,
+                min_count,
+                heuristic,
+            } = query_facet
+            else {
+                continue;
+            };
+            if !self.schema_map.get(field).is_some_and(|schema_field| {
+                schema_field.store
+                    && schema_field.index_lexical
+                    && schema_field.field_type == FieldType::Text
+            }) {
+                continue;
+            }
+
+            let fields_hashset: HashSet<String> = HashSet::from([field.clone()]);
+            let mut foreground_counts: AHashMap<String, (usize, u32, u64)> = AHashMap::new();
+            let mut foreground_total = 0;
+            for result in results.iter() {
+                let Ok(doc) = self
+                    .get_document(result.doc_id, true, &None, &fields_hashset, &[])
+                    .await
+                else {
+                    continue;
+                };
+                foreground_total += 1;
+                let Some(text) = doc.get(field).and_then(|value| value.as_str()) else {
+                    continue;
+                };
+
+                let shard_ref = self.shard_vec[result.doc_id % self.shard_number]
+                    .read()
+                    .await;
+                let mut doc_terms: AHashSet<String> = AHashSet::new();
+                for (term, key0, key_hash, _) in unique_single_terms(&shard_ref, text).await {
+                    if doc_terms.insert(term.clone()) {
+                        foreground_counts
+                            .entry(term)
+                            .or_insert((0, key0, key_hash))
+                            .0 += 1;
+                    }
+                }
+            }
+
+            let mut counts = Vec::new();
+            for (term, (foreground_count, key0, key_hash)) in foreground_counts {
+                if foreground_count >= *min_count {
+                    let background_count = background_count(self, &term, key0, key_hash).await;
+                    counts.push((term, foreground_count, background_count));
+                }
+            }
+
+            let significant_terms = rank_significant_terms(
+                counts.into_iter(),
+                foreground_total,
+                background_total,
+                heuristic,
+            );
+            text_significant_terms.insert(
+                field.clone(),
+                select_significant_terms(significant_terms, *min_count, *length as usize),
+            );
+        }
+        text_significant_terms
+    }
+}
+
+/// Scores the terms by significance, and returns them ordered by descending significance score.
+/// * `counts`: term, foreground count, background count.
+/// * `foreground_total`: number of documents matching the query.
+/// * `background_total`: number of documents in the index.
+pub(crate) fn rank_significant_terms(
+    counts: impl Iterator<Item = (String, usize, usize)>,
+    foreground_total: usize,
+    background_total: usize,
+    heuristic: &SignificanceHeuristic,
+) -> Vec<SignificantTerm> {
+    if foreground_total == 0 || background_total == 0 {
+        return Vec::new();
+    }
+
+    let mut significant_terms: Vec<SignificantTerm> = counts
+        .filter(|(_, foreground_count, _)| *foreground_count > 0)
+        .map(|(term, foreground_count, background_count)| {
+            let background_count = background_count.max(foreground_count);
+            SignificantTerm {
+                score: significance_score(
+                    foreground_count,
+                    foreground_total,
+                    background_count,
+                    background_total.max(foreground_total),
+                    heuristic,
+                ),
+                term,
+                foreground_count,
+                background_count,
+            }
+        })
+        .collect();
+
+    significant_terms.sort_unstable_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.term.cmp(&b.term))
+    });
+    significant_terms
+}
+
+/// Returns the first `length` terms with a positive significance score and at least `min_count` foreground documents.
+pub(crate) fn select_significant_terms(
+    significant_terms: Vec<SignificantTerm>,
+    min_count: usize,
+    length: usize,
+) -> Vec<SignificantTerm> {
+    significant_terms
+        .into_iter()
+        .filter(|term| term.score > 0.0 && term.foreground_count >= min_count)
+        .take(length)
+        .collect()
+}
+
+/// computes the significance score of a term from its foreground (result set) and background (whole index) document counts
+fn significance_score(
+    foreground_count: usize,
+    foreground_total: usize,
+    background_count: usize,
+    background_total: usize,
+    heuristic: &SignificanceHeuristic,
+) -> f64 {
+    let foreground_rate = foreground_count as f64 / foreground_total as f64;
+    let background_rate = background_count as f64 / background_total as f64;
+    if foreground_rate <= background_rate {
+        return 0.0;
+    }
+
+    match heuristic {
+        SignificanceHeuristic::Jlh => {
+            (foreground_rate - background_rate) * (foreground_rate / background_rate)
+        }
+        SignificanceHeuristic::ChiSquare => {
+            // the background is a superset of the foreground
+            let n11 = foreground_count as f64;
+            let n10 = (foreground_total - foreground_count) as f64;
+            let n01 = (background_count - foreground_count) as f64;
+            let n00 = ((background_total - foreground_total) as f64 - n01).max(0.0);
+            let n = n11 + n10 + n01 + n00;
+            let denominator = (n11 + n01) * (n11 + n10) * (n10 + n00) * (n01 + n00);
+            if denominator <= 0.0 {
+                return 0.0;
+            }
+            n * (n11 * n00 - n10 * n01).powi(2) / denominator
+        }
+    }
+}
+
+/// number of documents of the committed and uncommitted index containing the term
+pub(crate) async fn background_count(index: &Index, term: &str, key0: u32, key_hash: u64) -> usize {
+    let mut background_count = 0;
+    for shard in index.shard_vec.iter() {
+        let shard_ref = shard.read().await;
+        if let Some(segment) = shard_ref.segments_index.get(key0 as usize) {
+            background_count += if shard_ref.meta.access_type == AccessType::Mmap {
+                decode_posting_list_counts(segment, &shard_ref, key_hash)
+                    .map_or(0, |posting_counts| posting_counts.0 as usize)
+            } else {
+                segment
+                    .segment
+                    .get(&key_hash)
+                    .map_or(0, |posting_list| posting_list.posting_count as usize)
+            };
+        }
+        background_count += shard_ref.get_posting_count_uncommitted(term);
+    }
+    background_count
+}
+
+/// tokenizes a text with the index tokenizer, stemmer and stop words, and returns the unique single terms with their index keys and term frequency
+pub(crate) async fn unique_single_terms(
+    shard: &Shard,
+    text: &str,
+) -> Vec<(String, u32, u64, usize)> {
+    let mut unique_terms = AHashMap::new();
+    let mut non_unique_terms = Vec::new();
+    let mut nonunique_terms_count = 0u32;
+    let mut query_type = QueryType::Union;
+    tokenizer(
+        shard,
+        text,
+        &mut unique_terms,
+        &mut non_unique_terms,
+        shard.meta.tokenizer,
+        shard.segment_number_mask1,
+        &mut nonunique_terms_count,
+        u16::MAX as u32,
+        MAX_POSITIONS_PER_TERM,
+        true,
+        &mut query_type,
+        0,
+        0,
+        1,
+    )
+    .await;
+
+    unique_terms
+        .into_values()
+        .filter(|term| term.ngram_type == NgramType::SingleTerm)
+        .map(|term| {
+            let term_frequency = term.field_positions_vec[0].len();
+            (term.term, term.key0, term.key_hash, term_frequency)
+        })
+        .collect()
+}
@@ -38,6 +38,7 @@ pub(crate) async fn single_docid<'a>(
 
     let filtered = !not_query_list.is_empty()
         || !field_filter_set.is_empty()
+        || !shard.delete_hashset.is_empty()
         || !search_result.topk_candidates.result_sort.is_empty()
         || (!search_result.query_facets.is_empty() || !facet_filter.is_empty())
             && result_type != &ResultType::Topk;
@@ -159,6 +160,7 @@ pub(crate) async fn single_docid<'a>(
         idf_ngram2: query_list_item_mut.idf_ngram2,
         idf_ngram3: query_list_item_mut.idf_ngram3,
         ngram_type: query_list_item_mut.ngram_type.clone(),
+        is_sparse: query_list_item_mut.is_sparse,
     };
 
     match compression_type {
@@ -0,0 +1,314 @@
+use std::{
+    mem,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use ahash::{AHashMap, AHashSet};
+use indexmap::IndexMap;
+use serde_json::{Map, Value};
+
+use crate::{
+    index::{
+        AccessType, BlockObjectIndex, FieldType, Index, NgramType, PostingListObjectQuery, Shard,
+        TermObject, hash32, hash64,
+    },
+    min_heap::MinHeap,
+    search::{ResultObject, ResultType, SearchResult, decode_posting_list_object},
+    single::single_blockid,
+    union::union_blockid,
+};
+
+#[cfg(feature = "vb")]
+use crate::vector::ResultSource;
+
+/// Sparse vector: map of terms to weights, e.g. the output of a learned sparse retrieval model like SPLADE or BM42.
+pub type SparseVector = IndexMap<String, f32>;
+
+/// Quantization scale of the document term weights: a weight w is indexed as term frequency round(w * SPARSE_WEIGHT_SCALE),
+/// i.e. with a resolution of 1/16.
+pub(crate) const SPARSE_WEIGHT_SCALE: f32 = 16.0;
+
+/// Maximum quantized weight (term frequency) of a document term, corresponds to a weight of 64.
+const SPARSE_WEIGHT_MAX: f32 = 1023.0;
+
+/// Maximum number of posting lists (query terms times searched sparse vector fields) of a sparse vector query:
+/// union_scan_32 scores up to 32 posting lists per block, the posting lists with the highest maximum scores are kept.
+const SPARSE_QUERY_TERM_MAX: usize = 32;
+
+/// Sparse vector field of the schema (FieldType::SparseVector).
+#[derive(Clone)]
+pub(crate) struct SparseField {
+    pub field: String,
+    pub indexed_field_id: usize,
+    pub boost: f32,
+}
+
+/// Term of a sparse vector field in the inverted index: the term prefixed with the indexed field id and control characters,
+/// which keeps the posting lists of the sparse vector fields apart and can't be produced by the tokenizer of a lexical query.
+pub(crate) fn sparse_term(indexed_field_id: usize, term: &str) -> String {
+    format!("\u{1}{indexed_field_id}\u{1}{term}")
+}
+
+/// Quantized weight of a document term, indexed as term frequency. None for weights <= 0.0 or non-finite weights.
+/// Positive weights below the resolution are rounded up to the smallest quantized weight.
+pub(crate) fn quantize_sparse_weight(weight: f32) -> Option<u16> {
+    (weight > 0.0 && weight.is_finite()).then(|| {
+        (weight * SPARSE_WEIGHT_SCALE)
+            .round()
+            .clamp(1.0, SPARSE_WEIGHT_MAX) as u16
+    })
+}
+
+/// Adds the terms of a sparse vector field of a document (JSON object with terms as keys and weights as values) to the unique terms of the document.
+/// Each term is indexed with its quantized weight as term frequency into the posting list of the field, terms with a weight <= 0.0 are ignored.
+pub(crate) fn tokenize_sparse_vector(
+    terms: &Map<String, Value>,
+    indexed_field_id: usize,
+    indexed_field_number: usize,
+    segment_number_mask1: u32,
+    unique_terms: &mut AHashMap<String, TermObject>,
+) {
+    for (term, value) in terms.iter() {
+        let Some(term_frequency) = value
+            .as_f64()
+            .and_then(|weight| quantize_sparse_weight(weight as f32))
+        else {
+            continue;
+        };
+
+        let term_string = sparse_term(indexed_field_id, term);
+        let term_bytes = term_string.as_bytes();
+        let mut field_positions_vec = vec![Vec::new(); indexed_field_number];
+        field_positions_vec[indexed_field_id] = (0..term_frequency).collect();
+        unique_terms.insert(
+            term_string.clone(),
+            TermObject {
+                key0: hash32(term_bytes) & segment_number_mask1,
+                key_hash: hash64(term_bytes),
+                term: term_string,
+                field_positions_vec,
+                ngram_type: NgramType::SingleTerm,
+                ..Default::default()
+            },
+        );
+    }
+}
+
+/// Committed posting list of a query term within a sparse vector field, with the block scores weighted with the query term weight and the field boost.
+struct SparsePostingList {
+    term: String,
+    key0: u32,
+    weight: f32,
+    posting_count: u32,
+    max_list_score: f32,
+    blocks: Vec<BlockObjectIndex>,
+}
+
+impl Shard {
+    /// Top-k documents by the dot product of the query sparse vector and the document sparse vectors.
+    /// The sparse vector terms are posting lists of the committed inverted index, with the quantized document weights as term frequencies,
+    /// retrieved with the block-max WAND of the lexical union: only blocks and documents whose maximum score sum exceeds the current top-k threshold are scored.
+    /// Documents are searchable after commit.
+    pub(crate) async fn search_sparse_shard(
+        &self,
+        sparse_vector: &SparseVector,
+        fields: &[SparseField],
+        length: usize,
+    ) -> ResultObject {
+        let mut result_object: ResultObject = Default::default();
+        let top_k = length.min(self.indexed_doc_count);
+        if top_k == 0 || self.segments_index.is_empty() {
+            return result_object;
+        }
+
+        let mut posting_lists: Vec<SparsePostingList> = Vec::new();
+        for (term, query_weight) in sparse_vector.iter() {
+            if !(*query_weight > 0.0 && query_weight.is_finite()) {
+                continue;
+            }
+            for field in fields.iter() {
+                let term = sparse_term(field.indexed_field_id, term);
+                let key0 = hash32(term.as_bytes()) & self.segment_number_mask1;
+                let key_hash = hash64(term.as_bytes());
+                let posting_list = if self.meta.access_type == AccessType::Mmap {
+                    decode_posting_list_object(
+                        &self.segments_index[key0 as usize],
+                        self,
+                        key_hash,
+                        true,
+                    )
+                    .map(|plo| (plo.posting_count, plo.max_list_score, plo.blocks))
+                } else {
+                    self.segments_index[key0 as usize]
+                        .segment
+                        .get(&key_hash)
+                        .map(|plo| (plo.posting_count, plo.max_list_score, plo.blocks.clone()))
+                };
+
+                if let Some((posting_count, max_list_score, mut blocks)) = posting_list {
+                    let weight = query_weight * field.boost;
+                    for block in blocks.iter_mut() {
+                        block.max_block_score *= weight;
+                    }
+                    posting_lists.push(SparsePostingList {
+                        term,
+                        key0,
+                        weight,
+                        posting_count,
+                        max_list_score: max_list_score * weight,
+                        blocks,
+                    });
+                }
+            }
+        }
+        posting_lists.sort_unstable_by(|a, b| b.max_list_score.total_cmp(&a.max_list_score));
+        posting_lists.truncate(SPARSE_QUERY_TERM_MAX);
+
+        let mut query_list: Vec<PostingListObjectQuery> = posting_lists
+            .iter()
+            .enumerate()
+            .map(|(term_index_unique, posting_list)| PostingListObjectQuery {
+                posting_count: posting_list.posting_count,
+                max_list_score: posting_list.max_list_score,
+                blocks: &posting_list.blocks,
+                p_block_max: posting_list.blocks.len() as i32,
+                term: posting_list.term.clone(),
+                key0: posting_list.key0,
+                term_index_unique,
+                idf: posting_list.weight,
+                ngram_type: NgramType::SingleTerm,
+                is_sparse: true,
+                ..Default::default()
+            })
+            .collect();
+
+        let result_sort_index = Vec::new();
+        let mut search_result = SearchResult {
+            topk_candidates: MinHeap::new(top_k, self, false, &result_sort_index),
+            query_facets: Vec::new(),
+            skip_facet_count: false,
+        };
+        let result_count_arc = Arc::new(AtomicUsize::new(0));
+        let field_filter_set = AHashSet::new();
+
+        match query_list.len() {
+            0 => return result_object,
+            1 => {
+                single_blockid(
+                    self,
+                    &mut [],
+                    &mut query_list,
+                    &mut [],
+                    &result_count_arc,
+                    &mut search_result,
+                    top_k,
+                    &ResultType::TopkCount,
+                    &field_filter_set,
+                    &[],
+                    &mut 0,
+                )
+                .await
+            }
+            _ => {
+                union_blockid(
+                    self,
+                    &mut Vec::new(),
+                    &mut query_list,
+                    &mut [],
+                    &result_count_arc,
+                    &mut search_result,
+                    top_k,
+                    &ResultType::TopkCount,
+                    &field_filter_set,
+                    &[],
+                )
+                .await
+            }
+        }
+
+        let result_count = search_result.topk_candidates.current_heap_size;
+        let mut results = mem::take(&mut search_result.topk_candidates._elements);
+        results.truncate(result_count);
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        #[cfg(feature = "vb")]
+        for result in results.iter_mut() {
+            result.shard_id = self.meta.id as u32;
+            result.lexical_score = result.score;
+            result.source = ResultSource::Sparse;
+        }
+
+        result_object.results = results;
+        result_object.result_count = result_count;
+        result_object.result_count_total = result_count_arc.load(Ordering::Relaxed);
+        result_object
+    }
+}
+
+impl Index {
+    /// Sparse vector fields to search: the sparse vector fields in field_filter, or all sparse vector fields if field_filter contains none.
+    pub(crate) async fn sparse_fields(&self, field_filter: &[String]) -> Vec<SparseField> {
+        let fields: Vec<SparseField> = self.shard_vec[0]
+            .read()
+            .await
+            .indexed_schema_vec
+            .iter()
+            .filter(|schema_field| schema_field.field_type == FieldType::SparseVector)
+            .map(|schema_field| SparseField {
+                field: schema_field.field.clone(),
+                indexed_field_id: schema_field.indexed_field_id,
+                boost: schema_field.boost,
+            })
+            .collect();
+        let filtered_fields: Vec<SparseField> = fields
+            .iter()
+            .filter(|field| field_filter.contains(&field.field))
+            .cloned()
+            .collect();
+        if filtered_fields.is_empty() {
+            fields
+        } else {
+            filtered_fields
+        }
+    }
+
+    /// Searches the sparse vector fields of all shards (or only the shard of shard_filter), returns the top-k results with global document ids.
+    /// Only committed documents are searched.
+    pub(crate) async fn search_sparse(
+        &self,
+        sparse_vector: &SparseVector,
+        field_filter: &[String],
+        length: usize,
+        shard_filter: Option<usize>,
+    ) -> ResultObject {
+        let fields = self.sparse_fields(field_filter).await;
+        let shard_number = self.shard_number;
+
+        let mut result_object: ResultObject = Default::default();
+        for (shard_id, shard) in self.shard_vec.iter().enumerate().filter(|(shard_id, _)| {
+            shard_filter.is_none_or(|shard_filter| shard_filter == *shard_id)
+        }) {
+            let rlo_shard = shard
+                .read()
+                .await
+                .search_sparse_shard(sparse_vector, &fields, length)
+                .await;
+            result_object.result_count_total += rlo_shard.result_count_total;
+            result_object
+                .results
+                .extend(rlo_shard.results.into_iter().map(|mut result| {
+                    result.doc_id = (result.doc_id * shard_number) + shard_id;
+                    result
+                }));
+        }
+
+        result_object
+            .results
+            .sort_unstable_by(|a, b| b.score.total_cmp(&a.score));
+        result_object.results.truncate(length);
+        result_object.result_count = result_object.results.len();
+        result_object
+    }
+}
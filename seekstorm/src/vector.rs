@@ -1,23 +1,32 @@
 use crate::vector_similarity::VectorSimilarity;
 use crate::{
+    binary_quantization::BinaryLevel,
     clustering::{ClusterHeader, Medoid, ParentMedoid},
-    index::{Clustering, Document, FieldType, IS_SYSTEM_LE, Shard, ShardArc},
+    hnsw::{Candidate, HnswGraph},
+    index::{Clustering, Document, FieldType, IS_SYSTEM_LE, Index, Shard, ShardArc},
     min_heap,
+    product_quantization::PqLevel,
     search::ResultObject,
     utils::decode_bytes_from_base64_string,
+    vector_merge::vector_doc_id,
     vector_similarity::{
         AnnMode, QuantizedVector, QuerySimd, normalize_f32, normalize_f32_simd, quantize_f32_to_i8,
         quantize_f32_to_i8_simd, similarity_embedding, similarity_embedding_simd,
         similarity_embedding_view, similarity_embedding_view_simd,
     },
 };
-use ahash::AHashSet;
+use ahash::{AHashMap, AHashSet};
 use bytemuck::{Pod, Zeroable, bytes_of, cast_slice, from_bytes, try_cast_slice};
 use chunk::chunk;
+use half::{bf16, f16};
 use memmap2::Mmap;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+#[cfg(feature = "vb")]
+use std::collections::HashSet;
 use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
     fmt,
     io::{Seek, SeekFrom, Write},
 };
@@ -28,6 +37,9 @@ use utoipa::ToSchema;
 /// which occurs when both vectors are identical and all values are 127. The dot product in that case is 64 * 127^2 = 16129.0.
 pub const SIMILARITY_NORMALIZATION_64_I8: f32 = 1.0 / 16129.0;
 
+/// Result length of a radius search: all documents above the similarity threshold are returned, without top-k cap.
+pub(crate) const UNLIMITED_LENGTH: usize = usize::MAX;
+
 /// Vector precision
 #[repr(u8)]
 #[derive(Clone, Copy, PartialEq, Deserialize, Serialize, ToSchema, Debug)]
@@ -36,6 +48,10 @@ pub enum Precision {
     None = 0,
     /// 32-bit floating point vector embedding
     F32 = 1,
+    /// 16-bit IEEE 754 half precision floating point vector embedding: half the memory of F32, with 11 bit mantissa (3 decimal digits) and a range of ±65504.
+    F16 = 2,
+    /// 16-bit brain floating point vector embedding: half the memory of F32, with the range of F32 but only 8 bit mantissa (2 decimal digits).
+    BF16 = 3,
     /// 8-bit integer vector embedding
     I8 = 4,
 }
@@ -45,6 +61,8 @@ impl fmt::Display for Precision {
         match *self {
             Precision::None => write!(f, "None"),
             Precision::F32 => write!(f, "F32"),
+            Precision::F16 => write!(f, "F16"),
+            Precision::BF16 => write!(f, "BF16"),
             Precision::I8 => write!(f, "I8"),
         }
     }
@@ -55,10 +73,56 @@ impl fmt::Display for Precision {
 pub enum Embedding {
     /// 32-bit floating point vector embedding
     F32(Vec<f32>),
+    /// 16-bit half precision floating point vector embedding
+    F16(Vec<f16>),
+    /// 16-bit brain floating point vector embedding
+    BF16(Vec<bf16>),
     /// 8-bit integer vector embedding
     I8(Vec<i8>),
 }
 
+impl Embedding {
+    pub(crate) fn view(&self) -> EmbeddingView<'_> {
+        match self {
+            Embedding::F32(e) => EmbeddingView::F32(e.as_slice()),
+            Embedding::F16(e) => EmbeddingView::F16(e.as_slice()),
+            Embedding::BF16(e) => EmbeddingView::BF16(e.as_slice()),
+            Embedding::I8(e) => EmbeddingView::I8(e.as_slice()),
+        }
+    }
+
+    /// Values converted to f32, e.g. to normalize, quantize or average half precision embeddings.
+    pub(crate) fn to_f32(&self) -> Vec<f32> {
+        match self {
+            Embedding::F32(e) => e.clone(),
+            Embedding::F16(e) => e.iter().map(|v| v.to_f32()).collect(),
+            Embedding::BF16(e) => e.iter().map(|v| v.to_f32()).collect(),
+            Embedding::I8(e) => e.iter().map(|v| *v as f32).collect(),
+        }
+    }
+
+    /// Half precision embeddings are widened to F32, other embeddings are returned unchanged.
+    pub(crate) fn widen(self) -> Embedding {
+        match self {
+            Embedding::F16(_) | Embedding::BF16(_) => Embedding::F32(self.to_f32()),
+            _ => self,
+        }
+    }
+
+    /// F32 embeddings are narrowed to the stored half precision, other embeddings are returned unchanged.
+    pub(crate) fn narrow(self, precision: Precision) -> Embedding {
+        match (self, precision) {
+            (Embedding::F32(e), Precision::F16) => {
+                Embedding::F16(e.iter().map(|v| f16::from_f32(*v)).collect())
+            }
+            (Embedding::F32(e), Precision::BF16) => {
+                Embedding::BF16(e.iter().map(|v| bf16::from_f32(*v)).collect())
+            }
+            (embedding, _) => embedding,
+        }
+    }
+}
+
 #[repr(C)]
 #[repr(packed)]
 #[derive(Pod, Zeroable, Clone, Copy)]
@@ -75,6 +139,8 @@ pub(crate) struct VectorHeader {
 #[derive(Clone, Copy, Debug)]
 pub(crate) enum EmbeddingView<'a> {
     F32(&'a [f32]),
+    F16(&'a [f16]),
+    BF16(&'a [bf16]),
     I8(&'a [i8]),
 }
 
@@ -87,6 +153,8 @@ pub(crate) struct VectorRecordView<'a> {
 pub fn embedding_to_json(embedding: Embedding) -> Value {
     match embedding {
         Embedding::F32(v) => Value::Array(v.iter().map(|v| Value::from(*v)).collect()),
+        Embedding::F16(v) => Value::Array(v.iter().map(|v| Value::from(v.to_f32())).collect()),
+        Embedding::BF16(v) => Value::Array(v.iter().map(|v| Value::from(v.to_f32())).collect()),
         Embedding::I8(v) => Value::Array(v.iter().map(|v| Value::from(*v)).collect()),
     }
 }
@@ -99,6 +167,16 @@ pub fn embedding_to_bytes_be(embedding: &Embedding) -> Vec<u8> {
             byte_array.extend(v.iter().flat_map(|v| v.to_be_bytes()));
             byte_array
         }
+        Embedding::F16(v) => {
+            let mut byte_array = Vec::with_capacity(size_of_val(v));
+            byte_array.extend(v.iter().flat_map(|v| v.to_be_bytes()));
+            byte_array
+        }
+        Embedding::BF16(v) => {
+            let mut byte_array = Vec::with_capacity(size_of_val(v));
+            byte_array.extend(v.iter().flat_map(|v| v.to_be_bytes()));
+            byte_array
+        }
         Embedding::I8(v) => {
             let mut byte_array = Vec::with_capacity(size_of_val(v));
             byte_array.extend(v.iter().flat_map(|v| v.to_be_bytes()));
@@ -136,6 +214,46 @@ pub fn embedding_from_bytes_be(
                 None
             }
         }
+        (Precision::F16, true) => {
+            if bytes.len() == dimensions * 2 {
+                let chunks = bytes.chunks_exact(2);
+                let vector = chunks
+                    .map(|chunk| f16::from_be_bytes(chunk.try_into().unwrap()))
+                    .collect();
+                Some(Embedding::F16(vector))
+            } else {
+                None
+            }
+        }
+        (Precision::F16, false) => {
+            if let Ok(vector) = try_cast_slice(bytes)
+                && vector.len() == dimensions
+            {
+                Some(Embedding::F16(vector.to_vec()))
+            } else {
+                None
+            }
+        }
+        (Precision::BF16, true) => {
+            if bytes.len() == dimensions * 2 {
+                let chunks = bytes.chunks_exact(2);
+                let vector = chunks
+                    .map(|chunk| bf16::from_be_bytes(chunk.try_into().unwrap()))
+                    .collect();
+                Some(Embedding::BF16(vector))
+            } else {
+                None
+            }
+        }
+        (Precision::BF16, false) => {
+            if let Ok(vector) = try_cast_slice(bytes)
+                && vector.len() == dimensions
+            {
+                Some(Embedding::BF16(vector.to_vec()))
+            } else {
+                None
+            }
+        }
         (Precision::I8, _) => {
             if let Ok(vector) = try_cast_slice(bytes)
                 && vector.len() == dimensions
@@ -149,6 +267,26 @@ pub fn embedding_from_bytes_be(
     }
 }
 
+/// Convert the JSON value of a vector field to its embeddings: a single vector (FieldType::Json),
+/// or the token-level vectors of a multi-vector (FieldType::MultiVector), whose position is used as chunk_id.
+pub(crate) fn embeddings_from_json(
+    value: &Value,
+    field_type: &FieldType,
+    vector_type: Precision,
+    dimensions: usize,
+) -> Vec<Embedding> {
+    match (field_type, value) {
+        (FieldType::MultiVector, Value::Array(vectors)) => vectors
+            .iter()
+            .filter_map(|vector| embedding_from_json(vector, vector_type, dimensions))
+            .collect(),
+        (FieldType::MultiVector, _) => Vec::new(),
+        _ => embedding_from_json(value, vector_type, dimensions)
+            .into_iter()
+            .collect(),
+    }
+}
+
 /// Convert a JSON value to an embedding based on the specified vector type and dimensions.
 pub fn embedding_from_json(
     value: &Value,
@@ -165,6 +303,28 @@ pub fn embedding_from_json(
                 None
             }
         }
+        Precision::F16 => {
+            if let Ok(vector) = serde_json::from_value::<Vec<f32>>(value.clone())
+                && vector.len() == dimensions
+            {
+                Some(Embedding::F16(
+                    vector.iter().map(|v| f16::from_f32(*v)).collect(),
+                ))
+            } else {
+                None
+            }
+        }
+        Precision::BF16 => {
+            if let Ok(vector) = serde_json::from_value::<Vec<f32>>(value.clone())
+                && vector.len() == dimensions
+            {
+                Some(Embedding::BF16(
+                    vector.iter().map(|v| bf16::from_f32(*v)).collect(),
+                ))
+            } else {
+                None
+            }
+        }
         Precision::I8 => {
             if let Ok(vector) = serde_json::from_value::<Vec<i8>>(value.clone())
                 && vector.len() == dimensions
@@ -212,6 +372,18 @@ pub(crate) fn read_record(
 
             EmbeddingView::F32(vec_slice)
         }
+        Precision::F16 => {
+            let record_len = size_of::<VectorHeader>() + (dimensions * 2);
+            let vec_offset = size_of::<VectorHeader>();
+            let vec_bytes = &bytes[vec_offset..record_len];
+            EmbeddingView::F16(cast_slice(vec_bytes))
+        }
+        Precision::BF16 => {
+            let record_len = size_of::<VectorHeader>() + (dimensions * 2);
+            let vec_offset = size_of::<VectorHeader>();
+            let vec_bytes = &bytes[vec_offset..record_len];
+            EmbeddingView::BF16(cast_slice(vec_bytes))
+        }
         Precision::I8 => {
             let record_len = size_of::<VectorHeader>() + dimensions;
             let vec_offset = size_of::<VectorHeader>();
@@ -244,6 +416,25 @@ pub enum Quantization {
     /// For SIFT1M, SeekStorms affine scalar quantization (recall@10=100%) is better than TurboQuant (recall@10=97.26%).
     /// Those vectors have less than 256 distinct values, which SeekStorms affine Scalar Quantization preserves distortionlessly, while TurboQuant introduces additional distortion due to the random rotations.
     TurboQuantI8,
+    /// Product Quantization (PQ): each f32 vector is split into `subvectors` subvectors, each encoded as one byte,
+    /// the index of the nearest of up to 256 centroids of a per level k-means trained codebook (e.g. 128 dimensions * 4 bytes = 512 bytes -> 16 subvectors = 16 bytes).
+    /// The search scores the codes via a per query lookup table (asymmetric distance computation),
+    /// and re-ranks the `length * rerank_factor` best candidates with the full precision vectors, which are kept on disk (memory mapped).
+    /// Requires Precision::F32. The codebooks are trained at commit, therefore PQ is more costly to index than Scalar Quantization or TurboQuant.
+    ProductQuantization {
+        /// Number of subvectors (code bytes) per vector, at most the number of dimensions. Should be a divisor of the number of dimensions, e.g. dimensions/4 or dimensions/8.
+        subvectors: usize,
+        /// Number of candidates per requested result that are re-ranked with the full precision vectors, e.g. 10. Higher values increase recall at the cost of latency.
+        rerank_factor: usize,
+    },
+    /// Binary Quantization (BQ): 1 bit per dimension, the sign of each dimension relative to the mean vector of the level (e.g. 1024 dimensions * 4 bytes = 4096 bytes -> 128 bytes).
+    /// The search scans the bit vectors with popcount based Hamming distance (AVX2/NEON),
+    /// and rescores the `length * oversampling` best candidates with the stored vectors (Precision::F32, F16, BF16 or I8), which are kept on disk (memory mapped).
+    /// Works best with high-dimensional embeddings (>= 512 dimensions) of modern embedding models, which tolerate binary quantization well.
+    Binary {
+        /// Number of candidates per requested result that are rescored with the F32 or I8 vectors, e.g. 4..10. Higher values increase recall at the cost of latency.
+        oversampling: usize,
+    },
     /// no quantization, keep f32
     #[default]
     None,
@@ -255,6 +446,17 @@ impl fmt::Display for Quantization {
             Quantization::None => write!(f, "None"),
             Quantization::ScalarQuantizationI8 => write!(f, "ScalarQuantizationI8"),
             Quantization::TurboQuantI8 => write!(f, "TurboQuantI8"),
+            Quantization::ProductQuantization {
+                subvectors,
+                rerank_factor,
+            } => write!(
+                f,
+                "ProductQuantization(subvectors: {}, rerank_factor: {})",
+                subvectors, rerank_factor
+            ),
+            Quantization::Binary { oversampling } => {
+                write!(f, "Binary(oversampling: {})", oversampling)
+            }
         }
     }
 }
@@ -279,7 +481,7 @@ pub enum Model {
 }
 
 /// Inference type, to transform input text into vector embeddings.  
-/// This can be a predefined model2vec model, a custom model2vec model, an external inference, or no inference.
+/// This can be a predefined model2vec model, a custom model2vec model, an OpenAI-compatible embeddings endpoint, an external inference, or no inference.
 #[derive(Clone, Default, Debug, PartialEq, Deserialize, Serialize, ToSchema)]
 pub enum Inference {
     /// Predefined model2vec models, already normalized + dot product = cosine similarity, use the same similarity metric that was used during the training of the embedding model.
@@ -300,6 +502,35 @@ pub enum Inference {
         /// Quantization method for embeddings.
         quantization: Quantization,
     },
+    /// OpenAI-compatible /v1/embeddings endpoint, e.g. a local llama.cpp server (`llama-server --embeddings`), Ollama, vLLM, or text-embeddings-inference.
+    /// Text fields are embedded at ingest and the query string at search time, in batches, with retries and an on-disk embedding cache in the index directory.
+    OpenAiCompatible {
+        /// URL of the embeddings endpoint, e.g. "http://localhost:11434/v1/embeddings" (Ollama) or "http://localhost:8080/v1/embeddings" (llama.cpp).
+        /// Only plain http is supported: use a local endpoint or a TLS-terminating proxy.
+        url: String,
+        /// Model name sent with each request, e.g. "nomic-embed-text".
+        model: String,
+        /// Number of dimensions of the embeddings returned by the model.
+        dimensions: usize,
+        /// Chunk size for splitting input text, e.g. 1000 characters. Chunks must fit into the context window of the embedding model.
+        chunk_size: usize,
+        /// Quantization method for embeddings.
+        quantization: Quantization,
+        /// Similarity metric to use for comparing embeddings, use the same similarity metric that was used during the training of the embedding model.
+        similarity: VectorSimilarity,
+        /// Bearer token sent with each request. If None, the environment variable OPENAI_API_KEY is used, if set.
+        #[serde(default)]
+        api_key: Option<String>,
+        /// Maximum number of texts per request.
+        #[serde(default = "embedding_batch_size_default")]
+        batch_size: usize,
+        /// Number of retries of a failed request (connection error, HTTP 429 or 5xx), with exponential backoff.
+        #[serde(default = "embedding_max_retries_default")]
+        max_retries: usize,
+        /// Cache embeddings on disk, so that texts which were embedded before (re-indexed documents, repeated queries) are not sent to the endpoint again.
+        #[serde(default = "embedding_cache_default")]
+        cache: bool,
+    },
     /// External inference
     External {
         /// Number of dimensions for the embeddings.
@@ -317,6 +548,18 @@ pub enum Inference {
     None,
 }
 
+fn embedding_batch_size_default() -> usize {
+    64
+}
+
+fn embedding_max_retries_default() -> usize {
+    3
+}
+
+fn embedding_cache_default() -> bool {
+    true
+}
+
 impl fmt::Display for Inference {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -330,6 +573,16 @@ impl fmt::Display for Inference {
                 chunk_size,
                 quantization: _,
             } => write!(f, "Model2VecCustom: {}, chunks: {} byte", path, chunk_size),
+            Inference::OpenAiCompatible {
+                url,
+                model,
+                chunk_size,
+                ..
+            } => write!(
+                f,
+                "OpenAiCompatible: {} {}, chunks: {} byte",
+                url, model, chunk_size
+            ),
             Inference::External {
                 dimensions: _,
                 precision: _,
@@ -361,6 +614,12 @@ pub(crate) struct TopK {
     result_count_total: usize,
     observed_vector_count: usize,
     lowest_similarity_score: f32,
+    vector_similarity: VectorSimilarity,
+    /// Factor mapping raw Dot/Cosine scores to -1.0..1.0: SIMILARITY_NORMALIZATION_64_I8 for i8 vectors, 1.0 for float vectors
+    score_normalization: f32,
+    chunk_aggregation: ChunkAggregation,
+    /// ChunkAggregation other than Max: best chunk and all chunk scores per observed document
+    doc_chunks: AHashMap<usize, (Item, Vec<f32>)>,
 }
 
 impl TopK {
@@ -368,6 +627,8 @@ impl TopK {
         k: usize,
         similarity_threshold_option: Option<f32>,
         vector_similarity: VectorSimilarity,
+        score_normalization: f32,
+        chunk_aggregation: ChunkAggregation,
     ) -> Self {
         Self {
             items: vec![
@@ -380,7 +641,7 @@ impl TopK {
                     cluster_score: f32::MIN,
                     score: f32::MIN
                 };
-                k
+                if k == UNLIMITED_LENGTH { 0 } else { k }
             ],
             len: 0,
             k,
@@ -390,10 +651,10 @@ impl TopK {
             {
                 match vector_similarity {
                     VectorSimilarity::Dot => {
-                        ((similarity_threshold * 2.0) - 1.0) / SIMILARITY_NORMALIZATION_64_I8
+                        ((similarity_threshold * 2.0) - 1.0) / score_normalization
                     }
                     VectorSimilarity::Cosine => {
-                        ((similarity_threshold * 2.0) - 1.0) / SIMILARITY_NORMALIZATION_64_I8
+                        ((similarity_threshold * 2.0) - 1.0) / score_normalization
                     }
                     VectorSimilarity::Euclidean => -similarity_threshold,
                 }
@@ -402,6 +663,10 @@ impl TopK {
             },
             observed_vector_count: 0,
             lowest_similarity_score: f32::MIN,
+            vector_similarity,
+            score_normalization,
+            chunk_aggregation,
+            doc_chunks: AHashMap::new(),
         }
     }
 
@@ -420,14 +685,39 @@ impl TopK {
     ) -> bool {
         self.observed_vector_count += 1;
 
+        if self.chunk_aggregation != ChunkAggregation::Max || self.k == UNLIMITED_LENGTH {
+            if score < self.similarity_threshold_precalculated {
+                return false;
+            }
+            let item = Item {
+                doc_id,
+                field_id,
+                chunk_id,
+                cluster_id,
+                level_id,
+                cluster_score,
+                score,
+            };
+            match self.doc_chunks.get_mut(&doc_id) {
+                Some((best, scores)) => {
+                    if score > best.score {
+                        *best = item;
+                    }
+                    scores.push(score);
+                }
+                None => {
+                    self.doc_chunks.insert(doc_id, (item, vec![score]));
+                }
+            }
+            return true;
+        }
+
         if score < self.similarity_threshold_precalculated
             || (self.len == self.k && score <= self.lowest_similarity_score)
         {
             return false;
         }
 
-        self.result_count_total += 1;
-
         if self.len < self.k {
             let new_item = Item {
                 doc_id,
@@ -454,6 +744,7 @@ impl TopK {
 
             self.items[self.len] = new_item;
             self.len += 1;
+            self.result_count_total += 1;
             return true;
         }
 
@@ -479,6 +770,7 @@ impl TopK {
         }
 
         if score > min_v {
+            self.result_count_total += 1;
             self.lowest_similarity_score = min_v;
             self.items[min_i] = Item {
                 doc_id,
@@ -494,19 +786,90 @@ impl TopK {
             false
         }
     }
+
+    /// Aggregates the chunk scores per document (ChunkAggregation other than Max, or radius search) and selects the top-k documents,
+    /// each with its best matching chunk.
+    fn aggregate_chunks(&mut self) {
+        if self.chunk_aggregation == ChunkAggregation::Max && self.k != UNLIMITED_LENGTH {
+            return;
+        }
+
+        let mut documents: Vec<Item> = self
+            .doc_chunks
+            .drain()
+            .map(|(_, (mut item, mut scores))| {
+                item.score = match self.chunk_aggregation {
+                    ChunkAggregation::MeanTopN(n) => {
+                        scores.sort_unstable_by(|a, b| b.partial_cmp(a).unwrap());
+                        scores.truncate(n.max(1));
+                        scores.iter().sum::<f32>() / scores.len() as f32
+                    }
+                    ChunkAggregation::Sum => match self.vector_similarity {
+                        VectorSimilarity::Euclidean => scores.iter().sum(),
+                        _ => {
+                            let sum = scores
+                                .iter()
+                                .map(|score| ((score * self.score_normalization) + 1.0) * 0.5)
+                                .sum::<f32>();
+                            ((sum * 2.0) - 1.0) / self.score_normalization
+                        }
+                    },
+                    ChunkAggregation::Max => item.score,
+                };
+                item
+            })
+            .collect();
+
+        self.result_count_total = documents.len();
+        documents.sort_unstable_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+        documents.truncate(self.k);
+        self.len = documents.len();
+        self.items = documents;
+    }
 }
 
 impl Shard {
     pub(crate) async fn embed_vector_shard(&mut self) {
-        let index = self.index_option.as_ref().unwrap().read().await;
-        let model = if let Some(model) = index.embedding_model_option.as_ref() {
-            model
+        let embedding_provider = if let Some(embedding_provider) = self
+            .index_option
+            .as_ref()
+            .unwrap()
+            .read()
+            .await
+            .embedding_provider_option
+            .clone()
+        {
+            embedding_provider
         } else {
             return;
         };
 
-        let embeddings = model.encode(&self.chunks_string);
-        for (i, embedding) in embeddings.iter().enumerate() {
+        let mut embeddings = match embedding_provider.embed(&self.chunks_string).await {
+            Ok(embeddings) => embeddings,
+            Err(e) => {
+                println!(
+                    "Unable to embed {} chunks, vectors not indexed: {}",
+                    self.chunks_string.len(),
+                    e
+                );
+                self.chunks_meta.clear();
+                self.chunks_string.clear();
+                return;
+            }
+        };
+
+        let is_normalize = self.vector_similarity == VectorSimilarity::Cosine;
+        for (i, embedding) in embeddings.iter_mut().enumerate() {
+            if is_normalize {
+                if self.is_simd {
+                    unsafe {
+                        normalize_f32_simd(embedding);
+                    }
+                } else {
+                    normalize_f32(embedding);
+                }
+            }
+
             let embedding = if self.quantization == Quantization::ScalarQuantizationI8
                 || self.quantization == Quantization::TurboQuantI8
             {
@@ -524,6 +887,7 @@ impl Shard {
                 similarity: 0.0,
                 is_medoid: false,
 
+                level_id: self.committed_level_id() as u32,
                 doc_id: self.chunks_meta[i].0,
                 field_id: self.chunks_meta[i].1,
                 chunk_id: self.chunks_meta[i].2,
@@ -551,6 +915,7 @@ impl Shard {
         let schema = self.indexed_schema_vec.clone();
         for schema_field in schema.iter() {
             if schema_field.index_vector
+                && schema_field.inference.is_none()
                 && let Some(field_value) = document.get(&schema_field.field)
             {
                 match schema_field.field_type {
@@ -558,10 +923,7 @@ impl Shard {
                         let text = serde_json::from_value::<String>(field_value.clone())
                             .unwrap_or(field_value.to_string());
                         let field_id = schema_field.indexed_field_id as u32;
-                        let chunks: Vec<&[u8]> = chunk(text.as_bytes())
-                            .delimiters(b"\n.?!")
-                            .size(self.chunk_size)
-                            .collect();
+                        let chunks = chunk_text(&text, self.chunk_size);
 
                         for (chunk_id, chunk) in chunks.iter().enumerate() {
                             let chunk_text = String::from_utf8_lossy(chunk).to_string();
@@ -576,12 +938,17 @@ impl Shard {
                         }
                     }
 
-                    FieldType::Json => {
-                        if let Some(mut embedding) = embedding_from_json(
+                    FieldType::Json | FieldType::MultiVector => {
+                        for (chunk_id, embedding) in embeddings_from_json(
                             field_value,
+                            &schema_field.field_type,
                             self.vector_precision,
                             self.vector_dimensions_original,
-                        ) {
+                        )
+                        .into_iter()
+                        .enumerate()
+                        {
+                            let mut embedding = embedding.widen();
                             if self.vector_similarity == VectorSimilarity::Cosine
                                 && matches!(self.meta.inference, Inference::External { .. })
                                 && let Embedding::F32(ref mut fvecs) = embedding
@@ -739,7 +1106,13 @@ impl Shard {
                                             quantized_vector.sum_q,
                                         )
                                     }
-                                    (_, Quantization::None, _) => (0.0, 0.0, 0, 0),
+                                    (
+                                        _,
+                                        Quantization::None
+                                        | Quantization::ProductQuantization { .. }
+                                        | Quantization::Binary { .. },
+                                        _,
+                                    ) => (0.0, 0.0, 0, 0),
                                 }
                             } else {
                                 (0.0, 0.0, 0, 0)
@@ -750,14 +1123,15 @@ impl Shard {
                                 similarity: 0.0,
                                 is_medoid: false,
 
+                                level_id: self.committed_level_id() as u32,
                                 doc_id,
                                 field_id: schema_field.indexed_field_id as u32,
-                                chunk_id: 0,
+                                chunk_id: chunk_id as u32,
                                 scale,
                                 norm,
                                 zero_point,
                                 sum_q,
-                                embedding,
+                                embedding: embedding.narrow(self.vector_precision),
                             };
                             self.block_vector_buffer.push(record);
                             self.indexed_vector_count += 1;
@@ -768,13 +1142,14 @@ impl Shard {
                         if let Ok(string_base64) =
                             serde_json::from_value::<String>(field_value.clone())
                             && let Ok(bytes) = decode_bytes_from_base64_string(&string_base64)
-                            && let Some(mut embedding) = embedding_from_bytes_be(
+                            && let Some(embedding) = embedding_from_bytes_be(
                                 &bytes,
                                 self.vector_precision,
                                 self.vector_dimensions_original,
                                 *IS_SYSTEM_LE,
                             )
                         {
+                            let mut embedding = embedding.widen();
                             if self.vector_similarity == VectorSimilarity::Cosine
                                 && matches!(self.meta.inference, Inference::External { .. })
                                 && let Embedding::F32(ref mut fvecs) = embedding
@@ -935,7 +1310,13 @@ impl Shard {
                                             quantized_vector.sum_q,
                                         )
                                     }
-                                    (_, Quantization::None, _) => (0.0, 0.0, 0, 0),
+                                    (
+                                        _,
+                                        Quantization::None
+                                        | Quantization::ProductQuantization { .. }
+                                        | Quantization::Binary { .. },
+                                        _,
+                                    ) => (0.0, 0.0, 0, 0),
                                 }
                             } else {
                                 (0.0, 0.0, 0, 0)
@@ -946,6 +1327,7 @@ impl Shard {
                                 similarity: 0.0,
                                 is_medoid: false,
 
+                                level_id: self.committed_level_id() as u32,
                                 doc_id,
                                 field_id: schema_field.indexed_field_id as u32,
                                 chunk_id: 0,
@@ -953,7 +1335,7 @@ impl Shard {
                                 norm,
                                 zero_point,
                                 sum_q,
-                                embedding,
+                                embedding: embedding.narrow(self.vector_precision),
                             };
                             self.block_vector_buffer.push(record);
                             self.indexed_vector_count += 1;
@@ -966,6 +1348,71 @@ impl Shard {
         }
     }
 
+    /// level id of the level that is currently committed: the incomplete last level is committed again, otherwise a new level is appended.
+    pub(crate) fn committed_level_id(&self) -> usize {
+        if self.is_last_level_incomplete {
+            self.level_index.len() - 1
+        } else {
+            self.level_index.len()
+        }
+    }
+
+    /// Clusters the vectors in the block_vector_buffer according to the clustering of the index, and returns the medoids.
+    /// Without clustering (or below 100 vectors) all vectors form a single cluster.
+    pub(crate) async fn cluster_block_vector_buffer(&mut self) -> Vec<Medoid> {
+        let enable_clustering = if let Clustering::Fixed(size) = self.meta.clustering {
+            size > 1 && self.block_vector_buffer.len() >= 100
+        } else if let Clustering::None | Clustering::Hnsw { .. } = self.meta.clustering {
+            false
+        } else {
+            self.block_vector_buffer.len() >= 100
+        };
+
+        if enable_clustering {
+            self.cluster_vector_shard(true).await
+        } else {
+            vec![Medoid {
+                medoid_index: 0,
+                child_count: self.block_vector_buffer.len(),
+            }]
+        }
+    }
+
+    /// Serializes the vectors in the block_vector_buffer as vector file section of a level: number of clusters, child count per cluster and the vector records.
+    pub(crate) fn vector_section_bytes(&self, medoids: &[Medoid]) -> Vec<u8> {
+        let (_, vector_size) = self.vector_record_size();
+        let mut bytes = Vec::with_capacity(
+            4 + medoids.len() * 4 + self.block_vector_buffer.len() * vector_size,
+        );
+        bytes.extend_from_slice(&(medoids.len() as u32).to_le_bytes());
+        for medoid in medoids.iter() {
+            bytes.extend_from_slice(&(medoid.child_count as u32).to_le_bytes());
+        }
+
+        for record in self.block_vector_buffer.iter() {
+            let vec_bytes: &[u8] = match &record.embedding {
+                Embedding::F32(v) => cast_slice(v.as_slice()),
+                Embedding::F16(v) => cast_slice(v.as_slice()),
+                Embedding::BF16(v) => cast_slice(v.as_slice()),
+                Embedding::I8(v) => cast_slice(v.as_slice()),
+            };
+
+            let header = VectorHeader {
+                doc_id: record.doc_id,
+                field_id: record.field_id,
+                chunk_id: record.chunk_id,
+                scale: record.scale,
+                norm: record.norm,
+                zero_point: record.zero_point,
+                sum_q: record.sum_q,
+            };
+
+            bytes.extend_from_slice(bytes_of(&header));
+            bytes.extend_from_slice(vec_bytes);
+        }
+        bytes
+    }
+
     pub(crate) async fn commit_vector_shard(&mut self) {
         if self.is_last_level_incomplete {
             let vector_dimensions = self.vector_dimensions;
@@ -978,6 +1425,7 @@ impl Shard {
                 + (vector_dimensions
                     * match vector_type {
                         Precision::F32 => 4,
+                        Precision::F16 | Precision::BF16 => 2,
                         Precision::I8 => 1,
                         Precision::None => 0,
                     });
@@ -1019,6 +1467,7 @@ impl Shard {
                         similarity: 0.0,
                         is_medoid: false,
 
+                        level_id: self.committed_level_id() as u32,
                         doc_id: record.header.doc_id,
                         field_id: record.header.field_id,
                         chunk_id: record.header.chunk_id,
@@ -1029,6 +1478,8 @@ impl Shard {
                         embedding: match record.embedding {
                             EmbeddingView::I8(e) => Embedding::I8(e.to_vec()),
                             EmbeddingView::F32(e) => Embedding::F32(e.to_vec()),
+                            EmbeddingView::F16(e) => Embedding::F16(e.to_vec()),
+                            EmbeddingView::BF16(e) => Embedding::BF16(e.to_vec()),
                         },
                     });
                 }
@@ -1045,52 +1496,22 @@ impl Shard {
             self.embed_vector_shard().await;
         }
 
-        let enable_clustering = if let Clustering::Fixed(size) = self.meta.clustering {
-            size > 1 && self.block_vector_buffer.len() >= 100
-        } else if let Clustering::None = self.meta.clustering {
-            false
-        } else {
-            self.block_vector_buffer.len() >= 100
-        };
-
-        let medoids = if enable_clustering {
-            self.cluster_vector_shard(true).await
-        } else {
-            vec![Medoid {
-                medoid_index: 0,
-                child_count: self.block_vector_buffer.len(),
-            }]
-        };
+        let medoids = self.cluster_block_vector_buffer().await;
         self.indexed_cluster_count += medoids.len();
 
-        let mut header = [0u8; 4];
-        header[..4].copy_from_slice(&(medoids.len() as u32).to_le_bytes());
-        let _ = self.vector_file.write_all(&header);
+        let _ = self
+            .vector_file
+            .write_all(&self.vector_section_bytes(&medoids));
 
-        for medoid in medoids.iter() {
-            header[..4].copy_from_slice(&(medoid.child_count as u32).to_le_bytes());
-            let _ = self.vector_file.write_all(&header);
+        if let Clustering::Hnsw { m, ef_construction } = self.meta.clustering {
+            self.commit_hnsw_graph(m, ef_construction);
         }
-
-        for record in self.block_vector_buffer.iter() {
-            let vec_bytes: &[u8] = match &record.embedding {
-                Embedding::F32(v) => cast_slice(v.as_slice()),
-                Embedding::I8(v) => cast_slice(v.as_slice()),
-            };
-
-            let header = VectorHeader {
-                doc_id: record.doc_id,
-                field_id: record.field_id,
-                chunk_id: record.chunk_id,
-                scale: record.scale,
-                norm: record.norm,
-                zero_point: record.zero_point,
-                sum_q: record.sum_q,
-            };
-
-            let header_bytes = bytes_of(&header);
-            let _ = self.vector_file.write_all(header_bytes);
-            let _ = self.vector_file.write_all(vec_bytes);
+        match self.quantization {
+            Quantization::ProductQuantization { subvectors, .. } => {
+                self.commit_product_quantization(subvectors)
+            }
+            Quantization::Binary { .. } => self.commit_binary_quantization(),
+            _ => {}
         }
         self.block_vector_buffer.clear();
 
@@ -1110,8 +1531,51 @@ pub(crate) trait SearchVectorShard {
         include_uncommitted: bool,
         similarity_threshold: Option<f32>,
         cluster_search: AnnMode,
+        chunk_aggregation: ChunkAggregation,
         field_filter: Vec<String>,
     ) -> ResultObject;
+
+    async fn search_vector_shard_batch(
+        &self,
+        query_vectors: Vec<(Embedding, f32, f32, i16, i32)>,
+        length: usize,
+        include_uncommitted: bool,
+        similarity_threshold: Option<f32>,
+        ann_mode: AnnMode,
+        chunk_aggregation: ChunkAggregation,
+        field_filter: Vec<String>,
+    ) -> Vec<ResultObject>;
+}
+
+/// Compressed vectors of a level with the query prepared for them,
+/// scanned to select the candidates that are rescored with the stored vectors.
+enum CompressedLevel<'a> {
+    /// product quantization codes with the query similarity lookup table
+    Product(PqLevel<'a>, Vec<f32>),
+    /// bit vectors with the query bit vector
+    Binary(BinaryLevel<'a>, Vec<u64>),
+}
+
+impl CompressedLevel<'_> {
+    #[inline(always)]
+    fn similarity(&self, vector_id: usize, is_simd: bool) -> f32 {
+        match self {
+            CompressedLevel::Product(pq_level, similarity_table) => {
+                pq_level.similarity(similarity_table, vector_id)
+            }
+            CompressedLevel::Binary(binary_level, query_bits) => {
+                binary_level.similarity(query_bits, vector_id, is_simd)
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn doc_field_id(&self, vector_id: usize) -> (u16, u16) {
+        match self {
+            CompressedLevel::Product(pq_level, _) => pq_level.doc_field_id(vector_id),
+            CompressedLevel::Binary(binary_level, _) => binary_level.doc_field_id(vector_id),
+        }
+    }
 }
 
 /// Defines the source of search results, which can be lexical, vector-based, or a hybrid of both.
@@ -1124,6 +1588,109 @@ pub enum ResultSource {
     Vector,
     /// Results obtained from a combination of both lexical and vector-based search methods.
     Hybrid,
+    /// Results obtained from sparse vector search (learned sparse retrieval), the dot product is returned as lexical_score.
+    Sparse,
+}
+
+/// Specifies how the similarity scores of the chunks of a document are aggregated into the document score.
+/// The chunk with the highest similarity is always returned as matched chunk (Result.field_id, Result.chunk_id).
+#[derive(Default, PartialEq, Clone, Copy, Debug, Serialize, Deserialize, ToSchema)]
+pub enum ChunkAggregation {
+    /// Score of the best matching chunk (default).
+    #[default]
+    Max,
+    /// Mean of the scores of the n best matching chunks: favors documents with several relevant passages over documents with a single one.
+    MeanTopN(usize),
+    /// Sum of the chunk similarities, normalized to 0.0..1.0 for dot product and cosine similarity: favors documents with many matching chunks.
+    /// For Euclidean distance the negative distances are summed, which favors documents with few chunks; use Max or MeanTopN instead.
+    Sum,
+}
+
+/// Text span of the chunk that matched the query vector, e.g. as passage for RAG pipelines.
+#[derive(Clone, Debug, Serialize, Deserialize, ToSchema)]
+pub struct MatchedChunk {
+    /// Name of the field the chunk was taken from
+    pub field: String,
+    /// Chunk number within the field
+    pub chunk_id: u32,
+    /// Start byte offset of the chunk within the field text
+    pub start: usize,
+    /// End byte offset (exclusive) of the chunk within the field text
+    pub end: usize,
+    /// Chunk text
+    pub text: String,
+}
+
+/// Splits text into chunks that respect sentence boundaries, identical at indexing and for matched chunk retrieval.
+pub(crate) fn chunk_text(text: &str, chunk_size: usize) -> Vec<&[u8]> {
+    chunk(text.as_bytes())
+        .delimiters(b"\n.?!")
+        .size(chunk_size)
+        .collect()
+}
+
+impl Index {
+    /// Get the text span of the chunk that matched the query vector for a vector or hybrid search result.
+    ///
+    /// Arguments:
+    /// * `result`: Search result with field_id and chunk_id, as returned by search with SearchMode::Vector or SearchMode::Hybrid.
+    /// * `include_uncommitted`: Return also chunks of documents which have not yet been committed.
+    ///
+    /// Returns None if the index uses external embeddings (no source text), if the field is not stored, or the document is not found.
+    #[cfg(feature = "vb")]
+    pub async fn get_matched_chunk(
+        &self,
+        result: &min_heap::Result,
+        include_uncommitted: bool,
+    ) -> Option<MatchedChunk> {
+        let schema_field = self.schema_map.values().find(|schema_field| {
+            schema_field.index_vector && schema_field.indexed_field_id == result.field_id as usize
+        })?;
+        let field = schema_field.field.clone();
+        let (inference, chunk_size) = match self
+            .vector_field_indices
+            .iter()
+            .find(|vector_field_index| vector_field_index.field == field)
+        {
+            Some(vector_field_index) => {
+                let index_ref = vector_field_index.index_arc.read().await;
+                (index_ref.meta.inference.clone(), index_ref.chunk_size)
+            }
+            None => (self.meta.inference.clone(), self.chunk_size),
+        };
+        if !matches!(
+            inference,
+            Inference::Model2Vec { .. }
+                | Inference::Model2VecCustom { .. }
+                | Inference::OpenAiCompatible { .. }
+        ) {
+            return None;
+        }
+
+        let document = self
+            .get_document(
+                result.doc_id,
+                include_uncommitted,
+                &None,
+                &HashSet::from([field.clone()]),
+                &[],
+            )
+            .await
+            .ok()?;
+        let field_value = document.get(&field)?;
+        let text = serde_json::from_value::<String>(field_value.clone())
+            .unwrap_or(field_value.to_string());
+
+        let chunk = *chunk_text(&text, chunk_size).get(result.chunk_id as usize)?;
+        let start = chunk.as_ptr() as usize - text.as_ptr() as usize;
+        Some(MatchedChunk {
+            field,
+            chunk_id: result.chunk_id,
+            start,
+            end: start + chunk.len(),
+            text: String::from_utf8_lossy(chunk).to_string(),
+        })
+    }
 }
 
 impl Shard {
@@ -1140,7 +1707,7 @@ impl Shard {
         field_filter_set: &AHashSet<u16>,
         top_k: &mut TopK,
     ) {
-        let level_id = self.level_index.len();
+        let level_id = self.committed_level_id();
         let enable_scale = self.quantization != Quantization::None
             && self.vector_similarity != VectorSimilarity::Cosine;
 
@@ -1198,6 +1765,79 @@ impl Shard {
         }
     }
 }
+impl Shard {
+    /// Indexed field ids of the field filter, to filter the vectors by field_id.
+    fn vector_field_filter_set(&self, field_filter: &[String]) -> AHashSet<u16> {
+        let mut field_filter_set: AHashSet<u16> = AHashSet::new();
+        for item in field_filter.iter() {
+            match self.schema_map.get(item) {
+                Some(value) => {
+                    if value.index_lexical || value.index_vector {
+                        field_filter_set.insert(value.indexed_field_id as u16);
+                    }
+                }
+                None => {
+                    println!("field not found: {}", item)
+                }
+            }
+        }
+        field_filter_set
+    }
+
+    /// Aggregates the chunks of the top-k vector results and converts them into a result object.
+    fn result_object_from_top_k(
+        &self,
+        mut top_k: TopK,
+        observed_cluster_count: usize,
+    ) -> ResultObject {
+        let mut result_object: ResultObject = Default::default();
+
+        top_k.aggregate_chunks();
+        top_k.items[..top_k.len].sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+
+        for item in top_k.items[..top_k.len].iter() {
+            let result = min_heap::Result {
+                doc_id: item.doc_id,
+                score: item.score,
+                #[cfg(feature = "vb")]
+                field_id: item.field_id,
+                #[cfg(feature = "vb")]
+                chunk_id: item.chunk_id,
+                #[cfg(feature = "vb")]
+                level_id: item.level_id,
+                #[cfg(feature = "vb")]
+                shard_id: self.meta.id as u32,
+                #[cfg(feature = "vb")]
+                cluster_id: item.cluster_id,
+                #[cfg(feature = "vb")]
+                cluster_score: if self.vector_similarity == VectorSimilarity::Euclidean {
+                    -item.cluster_score
+                } else {
+                    ((item.cluster_score * top_k.score_normalization) + 1.0) * 0.5
+                },
+                #[cfg(feature = "vb")]
+                vector_score: if self.vector_similarity == VectorSimilarity::Euclidean {
+                    -item.score
+                } else {
+                    ((item.score * top_k.score_normalization) + 1.0) * 0.5
+                },
+                #[cfg(feature = "vb")]
+                lexical_score: 0.0,
+                #[cfg(feature = "vb")]
+                source: ResultSource::Vector,
+            };
+            result_object.results.push(result);
+        }
+
+        result_object.result_count = top_k.len;
+        result_object.result_count_total = top_k.result_count_total;
+        result_object.observed_vector_count = top_k.observed_vector_count;
+        result_object.observed_cluster_count = observed_cluster_count;
+
+        result_object
+    }
+}
+
 impl SearchVectorShard for ShardArc {
     async fn search_vector_shard(
         &self,
@@ -1206,9 +1846,10 @@ impl SearchVectorShard for ShardArc {
         include_uncommitted: bool,
         similarity_threshold: Option<f32>,
         ann_mode: AnnMode,
+        chunk_aggregation: ChunkAggregation,
         field_filter: Vec<String>,
     ) -> ResultObject {
-        let mut result_object: ResultObject = Default::default();
+        let result_object: ResultObject = Default::default();
 
         if include_uncommitted && !self.read().await.chunks_string.is_empty() {
             self.write().await.embed_vector_shard().await;
@@ -1223,19 +1864,7 @@ impl SearchVectorShard for ShardArc {
             return result_object;
         }
 
-        let mut field_filter_set: AHashSet<u16> = AHashSet::new();
-        for item in field_filter.iter() {
-            match shard_ref.schema_map.get(item) {
-                Some(value) => {
-                    if value.index_lexical {
-                        field_filter_set.insert(value.indexed_field_id as u16);
-                    }
-                }
-                None => {
-                    println!("field not found: {}", item)
-                }
-            }
-        }
+        let field_filter_set = shard_ref.vector_field_filter_set(&field_filter);
 
         let vector_similarity = shard_ref.vector_similarity;
         let vector_dimensions = shard_ref.vector_dimensions;
@@ -1248,15 +1877,24 @@ impl SearchVectorShard for ShardArc {
             + (vector_dimensions
                 * match vector_type {
                     Precision::F32 => 4,
+                    Precision::F16 | Precision::BF16 => 2,
                     Precision::I8 => 1,
                     Precision::None => 0,
                 });
 
-        let query_embedding = query_vector.unwrap();
+        let Some(query_embedding) = query_vector else {
+            return result_object;
+        };
 
         let query_simd = unsafe { QuerySimd::new(&query_embedding.0) };
 
-        let mut top_k = TopK::new(length, similarity_threshold, vector_similarity);
+        let mut top_k = TopK::new(
+            length,
+            similarity_threshold,
+            vector_similarity,
+            shard_ref.score_normalization(),
+            chunk_aggregation,
+        );
 
         if include_uncommitted && shard_ref.uncommitted && !shard_ref.block_vector_buffer.is_empty()
         {
@@ -1277,6 +1915,7 @@ impl SearchVectorShard for ShardArc {
 
         let mut offset = 0;
         for level_id in 0..shard_ref.level_index.len() {
+            let merged_vector_level = shard_ref.merged_vector_levels.get(&level_id);
             let cluster_number_bytes = &shard_ref.vector_file_mmap[offset..offset + 4];
             let cluster_number =
                 u32::from_le_bytes(cluster_number_bytes.try_into().unwrap()) as usize;
@@ -1297,8 +1936,116 @@ impl SearchVectorShard for ShardArc {
                 level_vectors_count += cluster_header.child_count;
             }
 
+            let enable_scale = shard_ref.quantization != Quantization::None
+                && shard_ref.vector_similarity != VectorSimilarity::Cosine;
+
+            if let AnnMode::EfSearch(ef_search) = ann_mode
+                && let Some(graph_bytes) = shard_ref.hnsw_file.level(level_id)
+                && let Ok(graph) = HnswGraph::new(graph_bytes)
+                && graph.node_count == level_vectors_count as usize
+            {
+                let level_bytes = &shard_ref.vector_file_mmap[offset..];
+                let mut score = |node: u32| {
+                    let record = read_record(
+                        &level_bytes[node as usize * vector_size..],
+                        vector_dimensions,
+                        vector_type,
+                    );
+                    let scale_norm = if enable_scale {
+                        Some((
+                            query_embedding.1,
+                            query_embedding.2,
+                            query_embedding.3,
+                            query_embedding.4,
+                            record.header.scale,
+                            record.header.norm,
+                            record.header.zero_point,
+                            record.header.sum_q,
+                        ))
+                    } else {
+                        None
+                    };
+                    if shard_ref.is_simd {
+                        unsafe {
+                            similarity_embedding_view_simd(
+                                &query_simd,
+                                &record.embedding,
+                                scale_norm,
+                                vector_similarity,
+                                shard_ref.quantization,
+                                non_affine,
+                            )
+                        }
+                    } else {
+                        similarity_embedding_view(
+                            &query_embedding.0,
+                            &record.embedding,
+                            scale_norm,
+                            vector_similarity,
+                            shard_ref.quantization,
+                            non_affine,
+                        )
+                    }
+                };
+
+                let ef_search = if length == UNLIMITED_LENGTH {
+                    ef_search
+                } else {
+                    ef_search.max(length)
+                };
+                // deleted documents and filtered fields are skipped during the graph traversal, so that length results are returned
+                let mut accept = |node: u32| {
+                    let record = read_record(
+                        &level_bytes[node as usize * vector_size..],
+                        vector_dimensions,
+                        vector_type,
+                    );
+                    (field_filter_set.is_empty()
+                        || field_filter_set.contains(&(record.header.field_id as u16)))
+                        && (shard_ref.delete_hashset.is_empty()
+                            || !shard_ref.delete_hashset.contains(&vector_doc_id(
+                                merged_vector_level,
+                                level_id,
+                                node as usize,
+                                record.header.doc_id,
+                            )))
+                };
+                let (candidates, evaluated_count) =
+                    graph.search(ef_search, &mut score, &mut accept);
+                observed_cluster_count += 1;
+                top_k.observed_vector_count += evaluated_count.saturating_sub(candidates.len());
+                for candidate in candidates.iter() {
+                    let record = read_record(
+                        &level_bytes[candidate.node as usize * vector_size..],
+                        vector_dimensions,
+                        vector_type,
+                    );
+                    let doc_id = vector_doc_id(
+                        merged_vector_level,
+                        level_id,
+                        candidate.node as usize,
+                        record.header.doc_id,
+                    );
+                    top_k.push(
+                        doc_id,
+                        record.header.field_id,
+                        record.header.chunk_id,
+                        0,
+                        level_id as u32,
+                        0.0,
+                        candidate.score,
+                        shard_ref.meta.id,
+                    );
+                }
+
+                offset += level_vectors_count as usize * vector_size;
+                continue;
+            }
+
             let (n_probe, cluster_similarity_threshold) = match ann_mode {
-                AnnMode::All => (clusters.len(), None),
+                AnnMode::All | AnnMode::EfSearch(_) | AnnMode::TargetRecall(_) => {
+                    (clusters.len(), None)
+                }
                 AnnMode::Similaritythreshold(threshold) => (clusters.len(), Some(threshold)),
                 AnnMode::Nprobe(n_probe) => (n_probe.min(clusters.len()), None),
                 AnnMode::NprobeSimilaritythreshold(n_probe, threshold) => {
@@ -1306,13 +2053,22 @@ impl SearchVectorShard for ShardArc {
                 }
             };
 
-            let enable_scale = shard_ref.quantization != Quantization::None
-                && shard_ref.vector_similarity != VectorSimilarity::Cosine;
-            let selected_clusters: Vec<(u32, u32, f32, ClusterHeader)> = if ann_mode != AnnMode::All
-            {
-                let mut top_k_medoid =
-                    TopK::new(n_probe, cluster_similarity_threshold, vector_similarity);
+            let selected_clusters: Vec<(u32, u32, f32, ClusterHeader)> = if !matches!(
+                ann_mode,
+                AnnMode::All | AnnMode::EfSearch(_) | AnnMode::TargetRecall(_)
+            ) {
+                let mut top_k_medoid = TopK::new(
+                    n_probe,
+                    cluster_similarity_threshold,
+                    vector_similarity,
+                    shard_ref.score_normalization(),
+                    ChunkAggregation::Max,
+                );
                 for (cluster_id, cluster) in clusters.iter().enumerate() {
+                    // a level without vectors has a single empty cluster, without a medoid
+                    if cluster.child_count == 0 {
+                        continue;
+                    }
                     let medoid_offset = offset + cluster.start_index as usize * vector_size;
                     let medoid_record = read_record(
                         &shard_ref.vector_file_mmap[medoid_offset..],
@@ -1387,12 +2143,133 @@ impl SearchVectorShard for ShardArc {
             } else {
                 clusters
                     .iter()
-                    .map(|cluster| (0, level_id as u32, 0.0, *cluster))
+                    .enumerate()
+                    .map(|(cluster_id, cluster)| {
+                        (cluster_id as u32, level_id as u32, 0.0, *cluster)
+                    })
                     .collect()
             };
 
             observed_cluster_count += selected_clusters.len();
 
+            // radius search scores all vectors of the selected clusters with full precision
+            let compressed_level = match shard_ref.quantization {
+                _ if length == UNLIMITED_LENGTH => None,
+                Quantization::ProductQuantization { rerank_factor, .. } => {
+                    if let Embedding::F32(query) = &query_embedding.0
+                        && let Some(pq_bytes) = shard_ref.pq_file.level(level_id)
+                        && let Some(pq_level) = PqLevel::new(pq_bytes)
+                        && pq_level.vector_count == level_vectors_count as usize
+                    {
+                        let similarity_table = pq_level.similarity_table(query, vector_similarity);
+                        Some((
+                            CompressedLevel::Product(pq_level, similarity_table),
+                            rerank_factor,
+                        ))
+                    } else {
+                        None
+                    }
+                }
+                Quantization::Binary { oversampling } => {
+                    if let Some(binary_bytes) = shard_ref.binary_file.level(level_id)
+                        && let Some(binary_level) = BinaryLevel::new(binary_bytes)
+                        && binary_level.vector_count == level_vectors_count as usize
+                    {
+                        let query_bits = binary_level.binarize_query(&query_embedding.0);
+                        Some((
+                            CompressedLevel::Binary(binary_level, query_bits),
+                            oversampling,
+                        ))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+
+            if let Some((compressed_level, rescore_factor)) = compressed_level {
+                let candidate_count = length.saturating_mul(rescore_factor.max(1));
+                let mut candidates: BinaryHeap<Reverse<(Candidate, usize)>> =
+                    BinaryHeap::with_capacity(candidate_count + 1);
+                let mut scored_count = 0;
+                for (cluster_index, (_, _, _, cluster)) in selected_clusters.iter().enumerate() {
+                    let start_index = cluster.start_index as usize;
+                    for vector_id in start_index..start_index + cluster.child_count as usize {
+                        let (doc_id, field_id) = compressed_level.doc_field_id(vector_id);
+                        let doc_id =
+                            vector_doc_id(merged_vector_level, level_id, vector_id, doc_id);
+                        if (field_filter_set.is_empty() || field_filter_set.contains(&field_id))
+                            && (shard_ref.delete_hashset.is_empty()
+                                || !shard_ref.delete_hashset.contains(&doc_id))
+                        {
+                            scored_count += 1;
+                            let candidate = Candidate {
+                                score: compressed_level.similarity(vector_id, shard_ref.is_simd),
+                                node: vector_id as u32,
+                            };
+                            if candidates.len() < candidate_count {
+                                candidates.push(Reverse((candidate, cluster_index)));
+                            } else if let Some(Reverse((worst, _))) = candidates.peek()
+                                && candidate > *worst
+                            {
+                                candidates.pop();
+                                candidates.push(Reverse((candidate, cluster_index)));
+                            }
+                        }
+                    }
+                }
+
+                top_k.observed_vector_count += scored_count - candidates.len();
+                let level_bytes = &shard_ref.vector_file_mmap[offset..];
+                for Reverse((candidate, cluster_index)) in candidates.into_iter() {
+                    let record = read_record(
+                        &level_bytes[candidate.node as usize * vector_size..],
+                        vector_dimensions,
+                        vector_type,
+                    );
+                    let similarity = if shard_ref.is_simd {
+                        unsafe {
+                            similarity_embedding_view_simd(
+                                &query_simd,
+                                &record.embedding,
+                                None,
+                                vector_similarity,
+                                shard_ref.quantization,
+                                non_affine,
+                            )
+                        }
+                    } else {
+                        similarity_embedding_view(
+                            &query_embedding.0,
+                            &record.embedding,
+                            None,
+                            vector_similarity,
+                            shard_ref.quantization,
+                            non_affine,
+                        )
+                    };
+                    let (cluster_id, _, cluster_score, _) = selected_clusters[cluster_index];
+                    top_k.push(
+                        vector_doc_id(
+                            merged_vector_level,
+                            level_id,
+                            candidate.node as usize,
+                            record.header.doc_id,
+                        ),
+                        record.header.field_id,
+                        record.header.chunk_id,
+                        cluster_id,
+                        level_id as u32,
+                        cluster_score,
+                        similarity,
+                        shard_ref.meta.id,
+                    );
+                }
+
+                offset += level_vectors_count as usize * vector_size;
+                continue;
+            }
+
             let _zero_hit_count = 0;
             for (cluster_id, _level_id2, cluster_score, cluster) in selected_clusters.iter() {
                 let cluster_vectors_count = cluster.child_count as usize;
@@ -1445,7 +2322,12 @@ impl SearchVectorShard for ShardArc {
                                 )
                             };
 
-                            let doc_id = (level_id << 16) | (record.header.doc_id as usize);
+                            let doc_id = vector_doc_id(
+                                merged_vector_level,
+                                level_id,
+                                cluster.start_index as usize + i,
+                                record.header.doc_id,
+                            );
 
                             if shard_ref.delete_hashset.is_empty()
                                 || !shard_ref.delete_hashset.contains(&doc_id)
@@ -1469,47 +2351,508 @@ impl SearchVectorShard for ShardArc {
             offset += level_vectors_count as usize * vector_size;
         }
 
-        top_k.items[..top_k.len].sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap());
+        shard_ref.result_object_from_top_k(top_k, observed_cluster_count)
+    }
 
-        for item in top_k.items[..top_k.len].iter() {
-            let result = min_heap::Result {
-                doc_id: item.doc_id,
-                score: item.score,
-                #[cfg(feature = "vb")]
-                field_id: item.field_id,
-                #[cfg(feature = "vb")]
-                chunk_id: item.chunk_id,
-                #[cfg(feature = "vb")]
-                level_id: item.level_id,
-                #[cfg(feature = "vb")]
-                shard_id: shard_ref.meta.id as u32,
-                #[cfg(feature = "vb")]
-                cluster_id: item.cluster_id,
-                #[cfg(feature = "vb")]
-                cluster_score: if shard_ref.vector_similarity == VectorSimilarity::Euclidean {
-                    -item.cluster_score
-                } else {
-                    ((item.cluster_score * SIMILARITY_NORMALIZATION_64_I8) + 1.0) * 0.5
-                },
-                #[cfg(feature = "vb")]
-                vector_score: if shard_ref.vector_similarity == VectorSimilarity::Euclidean {
-                    -item.score
-                } else {
-                    ((item.score * SIMILARITY_NORMALIZATION_64_I8) + 1.0) * 0.5
-                },
-                #[cfg(feature = "vb")]
-                lexical_score: 0.0,
-                #[cfg(feature = "vb")]
-                source: ResultSource::Vector,
+    /// Searches several query vectors at once: the medoids and the vectors of each selected cluster are read once
+    /// and scored against all queries that selected the cluster.
+    /// HNSW graphs (AnnMode::EfSearch) and compressed levels (product and binary quantization) are searched per query.
+    async fn search_vector_shard_batch(
+        &self,
+        query_vectors: Vec<(Embedding, f32, f32, i16, i32)>,
+        length: usize,
+        include_uncommitted: bool,
+        similarity_threshold: Option<f32>,
+        ann_mode: AnnMode,
+        chunk_aggregation: ChunkAggregation,
+        field_filter: Vec<String>,
+    ) -> Vec<ResultObject> {
+        if include_uncommitted && !self.read().await.chunks_string.is_empty() {
+            self.write().await.embed_vector_shard().await;
+        }
+
+        let shard_ref = self.read().await;
+        if !shard_ref.is_vector_indexing || shard_ref.indexed_vector_count == 0 {
+            return query_vectors.iter().map(|_| Default::default()).collect();
+        }
+
+        if (matches!(ann_mode, AnnMode::EfSearch(_))
+            && matches!(shard_ref.meta.clustering, Clustering::Hnsw { .. }))
+            || matches!(
+                shard_ref.quantization,
+                Quantization::ProductQuantization { .. } | Quantization::Binary { .. }
+            )
+        {
+            drop(shard_ref);
+            let mut result_objects = Vec::with_capacity(query_vectors.len());
+            for query_vector in query_vectors {
+                result_objects.push(
+                    self.search_vector_shard(
+                        Some(query_vector),
+                        length,
+                        include_uncommitted,
+                        similarity_threshold,
+                        ann_mode.clone(),
+                        chunk_aggregation,
+                        field_filter.clone(),
+                    )
+                    .await,
+                );
+            }
+            return result_objects;
+        }
+
+        let field_filter_set = shard_ref.vector_field_filter_set(&field_filter);
+        let vector_similarity = shard_ref.vector_similarity;
+        let vector_dimensions = shard_ref.vector_dimensions;
+        let (vector_type, vector_size) = shard_ref.vector_record_size();
+        let non_affine = shard_ref.max_vector_value == f32::MIN;
+        let enable_scale = shard_ref.quantization != Quantization::None
+            && shard_ref.vector_similarity != VectorSimilarity::Cosine;
+
+        let query_simds: Vec<QuerySimd> = query_vectors
+            .iter()
+            .map(|query_vector| unsafe { QuerySimd::new(&query_vector.0) })
+            .collect();
+        let mut top_ks: Vec<TopK> = query_vectors
+            .iter()
+            .map(|_| {
+                TopK::new(
+                    length,
+                    similarity_threshold,
+                    vector_similarity,
+                    shard_ref.score_normalization(),
+                    chunk_aggregation,
+                )
+            })
+            .collect();
+        let mut observed_cluster_counts = vec![0; query_vectors.len()];
+
+        let similarity = |query_index: usize, record: &VectorRecordView| -> f32 {
+            let query_vector = &query_vectors[query_index];
+            let scale_norm = if enable_scale {
+                Some((
+                    query_vector.1,
+                    query_vector.2,
+                    query_vector.3,
+                    query_vector.4,
+                    record.header.scale,
+                    record.header.norm,
+                    record.header.zero_point,
+                    record.header.sum_q,
+                ))
+            } else {
+                None
             };
-            result_object.results.push(result);
+            if shard_ref.is_simd {
+                unsafe {
+                    similarity_embedding_view_simd(
+                        &query_simds[query_index],
+                        &record.embedding,
+                        scale_norm,
+                        vector_similarity,
+                        shard_ref.quantization,
+                        non_affine,
+                    )
+                }
+            } else {
+                similarity_embedding_view(
+                    &query_vector.0,
+                    &record.embedding,
+                    scale_norm,
+                    vector_similarity,
+                    shard_ref.quantization,
+                    non_affine,
+                )
+            }
+        };
+
+        if include_uncommitted && shard_ref.uncommitted && !shard_ref.block_vector_buffer.is_empty()
+        {
+            for (query_index, top_k) in top_ks.iter_mut().enumerate() {
+                let query_vector = &query_vectors[query_index];
+                shard_ref
+                    .search_vector_shard_uncommitted(
+                        &query_simds[query_index],
+                        &query_vector.0,
+                        query_vector.1,
+                        query_vector.2,
+                        query_vector.3,
+                        query_vector.4,
+                        &vector_similarity,
+                        &field_filter_set,
+                        top_k,
+                    )
+                    .await;
+            }
         }
 
-        result_object.result_count = top_k.len;
-        result_object.result_count_total = top_k.result_count_total;
-        result_object.observed_vector_count = top_k.observed_vector_count;
-        result_object.observed_cluster_count = observed_cluster_count;
+        let mut offset = 0;
+        for level_id in 0..shard_ref.level_index.len() {
+            let merged_vector_level = shard_ref.merged_vector_levels.get(&level_id);
+            let cluster_number = u32::from_le_bytes(
+                shard_ref.vector_file_mmap[offset..offset + 4]
+                    .try_into()
+                    .unwrap(),
+            ) as usize;
+            offset += 4;
 
-        result_object
+            let mut clusters = Vec::with_capacity(cluster_number);
+            let mut start_index = 0;
+            for _i in 0..cluster_number {
+                let cluster_header = ClusterHeader {
+                    start_index,
+                    child_count: u32::from_le_bytes(
+                        shard_ref.vector_file_mmap[offset..offset + 4]
+                            .try_into()
+                            .unwrap(),
+                    ),
+                };
+                offset += 4;
+                start_index += cluster_header.child_count;
+                clusters.push(cluster_header);
+            }
+
+            // queries (with cluster score) per cluster
+            let mut cluster_queries: Vec<Vec<(usize, f32)>> = vec![Vec::new(); clusters.len()];
+            match ann_mode {
+                AnnMode::All | AnnMode::EfSearch(_) | AnnMode::TargetRecall(_) => {
+                    for queries in cluster_queries.iter_mut() {
+                        *queries = (0..query_vectors.len())
+                            .map(|query_index| (query_index, 0.0))
+                            .collect();
+                    }
+                    for observed_cluster_count in observed_cluster_counts.iter_mut() {
+                        *observed_cluster_count += clusters.len();
+                    }
+                }
+                _ => {
+                    let (n_probe, cluster_similarity_threshold) = match ann_mode {
+                        AnnMode::Similaritythreshold(threshold) => {
+                            (clusters.len(), Some(threshold))
+                        }
+                        AnnMode::Nprobe(n_probe) => (n_probe.min(clusters.len()), None),
+                        AnnMode::NprobeSimilaritythreshold(n_probe, threshold) => {
+                            (n_probe.min(clusters.len()), Some(threshold))
+                        }
+                        _ => (clusters.len(), None),
+                    };
+                    let mut top_k_medoids: Vec<TopK> = query_vectors
+                        .iter()
+                        .map(|_| {
+                            TopK::new(
+                                n_probe,
+                                cluster_similarity_threshold,
+                                vector_similarity,
+                                shard_ref.score_normalization(),
+                                ChunkAggregation::Max,
+                            )
+                        })
+                        .collect();
+                    for (cluster_id, cluster) in clusters.iter().enumerate() {
+                        if cluster.child_count == 0 {
+                            continue;
+                        }
+                        let medoid_record = read_record(
+                            &shard_ref.vector_file_mmap
+                                [offset + cluster.start_index as usize * vector_size..],
+                            vector_dimensions,
+                            vector_type,
+                        );
+                        for (query_index, top_k_medoid) in top_k_medoids.iter_mut().enumerate() {
+                            let similarity = similarity(query_index, &medoid_record);
+                            top_k_medoid.push(
+                                cluster_id,
+                                0,
+                                0,
+                                cluster_id as u32,
+                                level_id as u32,
+                                similarity,
+                                similarity,
+                                shard_ref.meta.id,
+                            );
+                        }
+                    }
+                    for (query_index, top_k_medoid) in top_k_medoids.iter().enumerate() {
+                        for item in top_k_medoid.items[..top_k_medoid.len].iter() {
+                            cluster_queries[item.doc_id].push((query_index, item.cluster_score));
+                        }
+                        observed_cluster_counts[query_index] += top_k_medoid.len;
+                    }
+                }
+            }
+
+            for (cluster_id, (cluster, queries)) in
+                clusters.iter().zip(cluster_queries.iter()).enumerate()
+            {
+                if queries.is_empty() {
+                    continue;
+                }
+                let cluster_offset = offset + cluster.start_index as usize * vector_size;
+                for i in 0..cluster.child_count as usize {
+                    let record = read_record(
+                        &shard_ref.vector_file_mmap[cluster_offset + i * vector_size..],
+                        vector_dimensions,
+                        vector_type,
+                    );
+                    let doc_id = vector_doc_id(
+                        merged_vector_level,
+                        level_id,
+                        cluster.start_index as usize + i,
+                        record.header.doc_id,
+                    );
+                    if (!field_filter_set.is_empty()
+                        && !field_filter_set.contains(&(record.header.field_id as u16)))
+                        || (!shard_ref.delete_hashset.is_empty()
+                            && shard_ref.delete_hashset.contains(&doc_id))
+                    {
+                        continue;
+                    }
+                    for (query_index, cluster_score) in queries.iter() {
+                        top_ks[*query_index].push(
+                            doc_id,
+                            record.header.field_id,
+                            record.header.chunk_id,
+                            cluster_id as u32,
+                            level_id as u32,
+                            *cluster_score,
+                            similarity(*query_index, &record),
+                            shard_ref.meta.id,
+                        );
+                    }
+                }
+            }
+
+            offset += start_index as usize * vector_size;
+        }
+
+        top_ks
+            .into_iter()
+            .zip(observed_cluster_counts)
+            .map(|(top_k, observed_cluster_count)| {
+                shard_ref.result_object_from_top_k(top_k, observed_cluster_count)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        index::IS_SIMD,
+        vector_similarity::{QuerySimd, similarity_embedding_view_simd, similarity_float},
+    };
+
+    /// maximum relative rounding error of f16 (11 bit mantissa) and bf16 (8 bit mantissa)
+    const F16_EPSILON: f32 = 1.0 / 2048.0;
+    const BF16_EPSILON: f32 = 1.0 / 256.0;
+
+    /// Deterministic pseudo-random vectors with components in [-1.0, 1.0).
+    fn test_vectors(count: usize, dimensions: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut state = seed;
+        (0..count)
+            .map(|_| {
+                (0..dimensions)
+                    .map(|_| {
+                        state = state
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        ((state >> 40) as f32 / (1u64 << 23) as f32) - 1.0
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn half_precision_round_trip() {
+        let mut values: Vec<f32> = test_vectors(1, 64, 8)[0].clone();
+        values.extend([1000.123, -0.001234, 3.0e-3, 65000.0]);
+        let dimensions = values.len();
+        let json = serde_json::json!(values);
+
+        for (precision, epsilon) in [
+            (Precision::F16, F16_EPSILON),
+            (Precision::BF16, BF16_EPSILON),
+        ] {
+            let embedding = embedding_from_json(&json, precision, dimensions).unwrap();
+            for (value, half) in values.iter().zip(embedding.to_f32().iter()) {
+                assert!(
+                    (value - half).abs() <= value.abs() * epsilon,
+                    "{precision}: {value} {half}"
+                );
+            }
+
+            // the same rounding as narrowing the F32 embedding, the JSON and byte exchange formats are lossless
+            let narrowed = Embedding::F32(values.clone()).narrow(precision);
+            assert_eq!(narrowed.to_f32(), embedding.to_f32());
+            let from_json =
+                embedding_from_json(&embedding_to_json(embedding.clone()), precision, dimensions)
+                    .unwrap();
+            assert_eq!(from_json.to_f32(), embedding.to_f32());
+            let from_bytes = embedding_from_bytes_be(
+                &embedding_to_bytes_be(&embedding),
+                precision,
+                dimensions,
+                true,
+            )
+            .unwrap();
+            assert_eq!(from_bytes.to_f32(), embedding.to_f32());
+            assert_eq!(embedding.clone().widen().to_f32(), embedding.to_f32());
+
+            assert!(embedding_from_json(&json, precision, dimensions + 1).is_none());
+        }
+
+        // f16 range: values above 65504 overflow to infinity, bf16 keeps the f32 range
+        let large = serde_json::json!([1.0e6]);
+        let f16_large = embedding_from_json(&large, Precision::F16, 1).unwrap();
+        assert!(f16_large.to_f32()[0].is_infinite());
+        let bf16_large = embedding_from_json(&large, Precision::BF16, 1).unwrap();
+        assert!((bf16_large.to_f32()[0] - 1.0e6).abs() <= 1.0e6 * BF16_EPSILON);
+    }
+
+    #[test]
+    fn half_precision_similarity() {
+        let vectors = test_vectors(50, 64, 9);
+        let query = &test_vectors(1, 64, 10)[0];
+        let query_embedding = Embedding::F32(query.clone());
+        let query_simd = unsafe { QuerySimd::new(&query_embedding) };
+
+        for (precision, epsilon) in [
+            (Precision::F16, F16_EPSILON),
+            (Precision::BF16, BF16_EPSILON),
+        ] {
+            for vector in vectors.iter() {
+                let half = Embedding::F32(vector.clone()).narrow(precision);
+                for vector_similarity in [VectorSimilarity::Dot, VectorSimilarity::Euclidean] {
+                    let exact = similarity_float(
+                        &query_embedding.view(),
+                        &EmbeddingView::F32(vector),
+                        vector_similarity,
+                    );
+                    let scalar =
+                        similarity_float(&query_embedding.view(), &half.view(), vector_similarity);
+
+                    // error bound from the rounding error of each dimension of the stored vector
+                    let bound = match vector_similarity {
+                        VectorSimilarity::Euclidean => query
+                            .iter()
+                            .zip(vector.iter())
+                            .map(|(q, v)| 2.0 * (q - v).abs() * v.abs() * epsilon)
+                            .sum::<f32>(),
+                        _ => query
+                            .iter()
+                            .zip(vector.iter())
+                            .map(|(q, v)| (q * v).abs() * epsilon)
+                            .sum::<f32>(),
+                    } + 1e-4;
+                    assert!(
+                        (exact - scalar).abs() <= bound,
+                        "{precision} {vector_similarity:?}: {exact} {scalar}"
+                    );
+
+                    if *IS_SIMD {
+                        let simd = unsafe {
+                            similarity_embedding_view_simd(
+                                &query_simd,
+                                &half.view(),
+                                None,
+                                vector_similarity,
+                                Quantization::None,
+                                false,
+                            )
+                        };
+                        assert!((simd - scalar).abs() <= 1e-4 * scalar.abs().max(1.0));
+                    }
+                }
+            }
+        }
+    }
+
+    /// doc_id, chunk_id and rounded score of the aggregated top-k items
+    fn top_k_items(top_k: &mut TopK) -> Vec<(usize, u32, f32)> {
+        top_k.aggregate_chunks();
+        top_k.items[..top_k.len].sort_by(|a, b| b.score.total_cmp(&a.score));
+        top_k.items[..top_k.len]
+            .iter()
+            .map(|item| {
+                (
+                    item.doc_id,
+                    item.chunk_id,
+                    (item.score * 1000.0).round() / 1000.0,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chunk_aggregation() {
+        // doc_id, chunk_id, negative squared euclidean distance
+        let chunks = [
+            (1, 0, -1.0),
+            (1, 1, -2.0),
+            (1, 2, -9.0),
+            (2, 0, -1.6),
+            (2, 1, -1.5),
+            (3, 0, -0.5),
+        ];
+        let aggregate = |chunk_aggregation: ChunkAggregation, k: usize| {
+            let mut top_k = TopK::new(
+                k,
+                None,
+                VectorSimilarity::Euclidean,
+                SIMILARITY_NORMALIZATION_64_I8,
+                chunk_aggregation,
+            );
+            for (doc_id, chunk_id, score) in chunks {
+                top_k.push(doc_id, 0, chunk_id, 0, 0, 0.0, score, 0);
+            }
+            top_k
+        };
+
+        let mut top_k = aggregate(ChunkAggregation::MeanTopN(2), 10);
+        assert_eq!(
+            top_k_items(&mut top_k),
+            vec![(3, 0, -0.5), (1, 0, -1.5), (2, 1, -1.55)]
+        );
+        assert_eq!(top_k.result_count_total, 3);
+
+        let mut top_k = aggregate(ChunkAggregation::Sum, 2);
+        assert_eq!(top_k_items(&mut top_k), vec![(3, 0, -0.5), (2, 1, -3.1)]);
+        assert_eq!(top_k.result_count_total, 3);
+
+        let mut top_k = aggregate(ChunkAggregation::Max, 10);
+        assert_eq!(
+            top_k_items(&mut top_k),
+            vec![(3, 0, -0.5), (1, 0, -1.0), (2, 1, -1.5)]
+        );
+        // the chunks of a document are counted once
+        assert_eq!(top_k.result_count_total, 3);
+
+        // dot product: the normalized chunk similarities (0.0..1.0) are summed
+        let raw = |similarity: f32| ((similarity * 2.0) - 1.0) / SIMILARITY_NORMALIZATION_64_I8;
+        let mut top_k = TopK::new(
+            10,
+            None,
+            VectorSimilarity::Dot,
+            SIMILARITY_NORMALIZATION_64_I8,
+            ChunkAggregation::Sum,
+        );
+        top_k.push(1, 0, 0, 0, 0, 0.0, raw(0.6), 0);
+        top_k.push(1, 0, 1, 0, 0, 0.0, raw(0.6), 0);
+        top_k.push(2, 0, 0, 0, 0, 0.0, raw(0.9), 0);
+        top_k.aggregate_chunks();
+        let normalized: Vec<(usize, f32)> = top_k.items[..top_k.len]
+            .iter()
+            .map(|item| {
+                (
+                    item.doc_id,
+                    (((item.score * SIMILARITY_NORMALIZATION_64_I8) + 1.0) * 0.5 * 1000.0).round()
+                        / 1000.0,
+                )
+            })
+            .collect();
+        assert_eq!(normalized, vec![(1, 1.2), (2, 0.9)]);
     }
 }
@@ -0,0 +1,144 @@
+use std::path::Path;
+
+use crate::{
+    INDEX_RUNTIME,
+    index::{
+        Document, FileType, IndexArc, IndexDocumentShard, SchemaField, create_index, open_index,
+    },
+    vector::Embedding,
+};
+
+/// Subdirectory of the index directory that contains the vector indices of the vector fields with their own inference.
+pub(crate) const VECTOR_FIELDS_PATH: &str = "vector_fields";
+
+/// Vector field with its own inference (SchemaField.inference), indexed into its own vector-only index within the index directory.
+/// Its document IDs are identical to the document IDs of the parent index.
+#[derive(Clone)]
+pub(crate) struct VectorFieldIndex {
+    /// name of the vector field
+    pub field: String,
+    /// indexed field id of the vector field in the parent index, reported as Result.field_id
+    #[cfg_attr(not(feature = "vb"), allow(dead_code))]
+    pub indexed_field_id: usize,
+    pub index_arc: IndexArc,
+}
+
+/// Creates (serialize_schema=true) or opens the vector indices of all vector fields with their own inference.
+pub(crate) async fn create_vector_field_indices(
+    index_arc: &IndexArc,
+    index_path: &Path,
+    serialize_schema: bool,
+    segment_number_bits1: usize,
+) -> Result<(), String> {
+    let (meta, shard_number, mut schema) = {
+        let index_ref = index_arc.read().await;
+        (
+            index_ref.meta.clone(),
+            index_ref.shard_number,
+            index_ref
+                .schema_map
+                .values()
+                .filter(|schema_field| {
+                    schema_field.index_vector && schema_field.inference.is_some()
+                })
+                .cloned()
+                .collect::<Vec<SchemaField>>(),
+        )
+    };
+    schema.sort_by_key(|schema_field| schema_field.field_id);
+
+    let mut vector_field_indices = Vec::new();
+    for schema_field in schema {
+        let vector_field_path = index_path
+            .join(VECTOR_FIELDS_PATH)
+            .join(schema_field.field_id.to_string());
+
+        let vector_field_index_arc = if serialize_schema {
+            let mut vector_field_meta = meta.clone();
+            vector_field_meta.name = format!("{} {}", meta.name, schema_field.field);
+            vector_field_meta.inference = schema_field.inference.clone().unwrap();
+            vector_field_meta.spelling_correction = None;
+            vector_field_meta.query_completion = None;
+
+            let vector_field_schema = vec![SchemaField {
+                store: false,
+                index_lexical: false,
+                facet: false,
+                longest: false,
+                dictionary_source: false,
+                completion_source: false,
+                inference: None,
+                routing: false,
+                ..schema_field.clone()
+            }];
+
+            Box::pin(create_index(
+                &vector_field_path,
+                vector_field_meta,
+                &vector_field_schema,
+                &Vec::new(),
+                segment_number_bits1,
+                true,
+                Some(shard_number),
+            ))
+            .await?
+        } else {
+            Box::pin(open_index(&vector_field_path)).await?
+        };
+        vector_field_index_arc.write().await.mute = true;
+
+        vector_field_indices.push(VectorFieldIndex {
+            field: schema_field.field.clone(),
+            indexed_field_id: schema_field.indexed_field_id,
+            index_arc: vector_field_index_arc,
+        });
+    }
+
+    index_arc.write().await.vector_field_indices = vector_field_indices;
+    Ok(())
+}
+
+impl VectorFieldIndex {
+    /// Indexes the vector field of a document with the document id assigned by the parent index,
+    /// documents without the field are indexed empty to keep the document ids aligned.
+    pub(crate) async fn index_document(&self, document: &Document, docid_global: usize) {
+        let mut vector_field_document = Document::new();
+        if let Some(value) = document.get(&self.field) {
+            vector_field_document.insert(self.field.clone(), value.clone());
+        }
+
+        let index_ref = self.index_arc.read().await;
+        let shard_arc = index_ref.shard_vec[docid_global % index_ref.shard_number].clone();
+        *index_ref.docid_global.write().await = docid_global + 1;
+        drop(index_ref);
+
+        let semaphore = shard_arc.read().await.semaphore.clone();
+        let permit = semaphore.acquire_owned().await.unwrap();
+        INDEX_RUNTIME.handle().spawn(async move {
+            shard_arc
+                .index_document_shard(vector_field_document, FileType::None, docid_global)
+                .await;
+            drop(permit);
+        });
+    }
+
+    /// The query vector is used only if it matches the dimensions of the vector field,
+    /// otherwise the query string is embedded with the model of the vector field (if any).
+    pub(crate) async fn query_vector(&self, query_vector: &Option<Embedding>) -> Option<Embedding> {
+        let dimensions = self.index_arc.read().await.vector_dimensions_original;
+        query_vector
+            .as_ref()
+            .filter(|embedding| embedding_dimensions(embedding) == dimensions)
+            .cloned()
+    }
+}
+
+/// Number of dimensions of an embedding.
+pub(crate) fn embedding_dimensions(embedding: &Embedding) -> usize {
+    match embedding {
+        Embedding::F32(vector) => vector.len(),
+        Embedding::F16(vector) => vector.len(),
+        Embedding::BF16(vector) => vector.len(),
+        Embedding::I8(vector) => vector.len(),
+    }
+}
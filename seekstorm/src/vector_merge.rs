@@ -0,0 +1,528 @@
+use std::{
+    io::{Seek, SeekFrom, Write},
+    mem,
+    path::Path,
+};
+
+use ahash::AHashMap;
+use memmap2::{Mmap, MmapOptions};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    ann_calibration::calibrate,
+    clustering::ParentMedoid,
+    index::{Clustering, IndexArc, MERGED_VECTOR_LEVELS_FILENAME, Shard},
+    level_file::LevelFile,
+    utils::read_u32,
+    vector::{Embedding, EmbeddingView, Quantization, read_record},
+};
+
+/// Vector file section with the vectors of consecutive merged levels, stored in the section of the last of these levels.
+/// The sections of the other merged levels are empty.
+pub(crate) struct MergedVectorLevel {
+    /// first of the merged levels
+    pub(crate) first_level_id: usize,
+    /// level of the document of each vector of the section, in the order of the vectors
+    pub(crate) level_ids: Vec<u32>,
+}
+
+/// Local document id of the vector at vector_index of the vector file section of level_id:
+/// the level of the document is the level of the section, or for merged levels the level stored per vector.
+#[inline(always)]
+pub(crate) fn vector_doc_id(
+    merged_vector_level: Option<&MergedVectorLevel>,
+    level_id: usize,
+    vector_index: usize,
+    doc_id: u16,
+) -> usize {
+    let level_id = merged_vector_level.map_or(level_id, |merged_vector_level| {
+        merged_vector_level.level_ids[vector_index] as usize
+    });
+    (level_id << 16) | doc_id as usize
+}
+
+/// Loads the merged vector levels of a shard.
+/// File format per merged level: level id of the section, first level id, vector count, level id per vector (all u32).
+pub(crate) fn load_merged_vector_levels(index_path: &Path) -> AHashMap<usize, MergedVectorLevel> {
+    let mut merged_vector_levels = AHashMap::new();
+    let Ok(bytes) = std::fs::read(index_path.join(MERGED_VECTOR_LEVELS_FILENAME)) else {
+        return merged_vector_levels;
+    };
+
+    let mut offset = 0;
+    while offset + 12 <= bytes.len() {
+        let level_id = read_u32(&bytes, offset) as usize;
+        let first_level_id = read_u32(&bytes, offset + 4) as usize;
+        let vector_count = read_u32(&bytes, offset + 8) as usize;
+        offset += 12;
+        if offset + vector_count * 4 > bytes.len() {
+            break;
+        }
+        let level_ids = (0..vector_count)
+            .map(|i| read_u32(&bytes, offset + i * 4))
+            .collect();
+        offset += vector_count * 4;
+        merged_vector_levels.insert(
+            level_id,
+            MergedVectorLevel {
+                first_level_id,
+                level_ids,
+            },
+        );
+    }
+    merged_vector_levels
+}
+
+/// Serializes the merged vector levels of a shard in the file format of load_merged_vector_levels.
+pub(crate) fn merged_vector_levels_bytes(
+    merged_vector_levels: &AHashMap<usize, MergedVectorLevel>,
+) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for (level_id, merged_vector_level) in merged_vector_levels.iter() {
+        bytes.extend_from_slice(&(*level_id as u32).to_le_bytes());
+        bytes.extend_from_slice(&(merged_vector_level.first_level_id as u32).to_le_bytes());
+        bytes.extend_from_slice(&(merged_vector_level.level_ids.len() as u32).to_le_bytes());
+        for level_id in merged_vector_level.level_ids.iter() {
+            bytes.extend_from_slice(&level_id.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+/// Result of MergeVectorLevels::merge_vector_levels, summed over all shards and vector field indices.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct MergeVectorLevelsResult {
+    /// Number of levels that contained vectors before the merge.
+    pub level_count_before: usize,
+    /// Number of levels that contain vectors after the merge.
+    pub level_count_after: usize,
+    /// Number of vectors that were re-clustered into merged levels.
+    pub merged_vector_count: usize,
+    /// Number of vectors of deleted documents that were purged.
+    pub purged_vector_count: usize,
+}
+
+impl MergeVectorLevelsResult {
+    fn add(&mut self, other: &MergeVectorLevelsResult) {
+        self.level_count_before += other.level_count_before;
+        self.level_count_after += other.level_count_after;
+        self.merged_vector_count += other.merged_vector_count;
+        self.purged_vector_count += other.purged_vector_count;
+    }
+}
+
+/// Vector level merging: every commit clusters the vectors of its level separately, and ANN search latency grows linearly with the number of levels.
+/// Merging combines the vectors of consecutive committed levels, re-clusters them with fresh medoids into fewer, larger levels,
+/// and purges the vectors of deleted documents. Lexical levels and document ids are not changed.
+#[allow(async_fn_in_trait)]
+pub trait MergeVectorLevels {
+    /// Merges the vectors of consecutive committed levels of each shard into levels of up to max_level_vectors (non-deleted) vectors,
+    /// re-clustered according to the clustering of the index, with rebuilt HNSW graphs and product or binary quantization codes.
+    /// Levels that contain vectors of deleted documents are rewritten without them, even if they are not merged with other levels.
+    /// The incomplete last level (less than 65_536 documents) is not merged, as it is re-clustered by every commit.
+    /// Can be invoked on demand or periodically from a background task: each shard is locked only while its levels are merged.
+    /// A calibrated index is re-calibrated, vector fields with their own inference are merged as well.
+    /// * `max_level_vectors`: maximum number of vectors per merged level, e.g. 1_000_000. Larger levels reduce the per-level overhead of ANN search, but take longer to cluster.
+    async fn merge_vector_levels(&self, max_level_vectors: usize) -> MergeVectorLevelsResult;
+}
+
+impl MergeVectorLevels for IndexArc {
+    async fn merge_vector_levels(&self, max_level_vectors: usize) -> MergeVectorLevelsResult {
+        let mut merge_result = MergeVectorLevelsResult::default();
+
+        let shard_vec = self.read().await.shard_vec.clone();
+        for shard in shard_vec.iter() {
+            let shard_result = shard
+                .write()
+                .await
+                .merge_vector_levels_shard(max_level_vectors)
+                .await;
+            merge_result.add(&shard_result);
+        }
+
+        let recalibration = self
+            .read()
+            .await
+            .ann_calibration
+            .as_ref()
+            .map(|ann_calibration| (ann_calibration.sample_size, ann_calibration.length));
+        if let Some((sample_size, length)) = recalibration
+            && (merge_result.merged_vector_count > 0 || merge_result.purged_vector_count > 0)
+        {
+            calibrate(self, sample_size, length).await;
+        }
+
+        let vector_field_indices = self.read().await.vector_field_indices.clone();
+        for vector_field_index in vector_field_indices.iter() {
+            let field_result = Box::pin(
+                vector_field_index
+                    .index_arc
+                    .merge_vector_levels(max_level_vectors),
+            )
+            .await;
+            merge_result.add(&field_result);
+        }
+
+        merge_result
+    }
+}
+
+/// Consecutive levels that are merged into the vector file section of their last level:
+/// a single level, or levels that were merged before and are only merged again as a whole.
+struct LevelRange {
+    first_level_id: usize,
+    level_id: usize,
+    vector_count: usize,
+    live_vector_count: usize,
+}
+
+/// Rewrites the blocks of a level file from first_level_id on: the new blocks of the merged levels, the existing blocks of the other levels.
+fn rewrite_level_blocks(
+    level_file: &mut LevelFile,
+    first_level_id: usize,
+    mut blocks: AHashMap<usize, Vec<u8>>,
+) {
+    let level_count = level_file.level_offsets.len();
+    if level_count <= first_level_id {
+        return;
+    }
+
+    let level_blocks: Vec<Vec<u8>> = (first_level_id..level_count)
+        .map(|level_id| {
+            blocks.remove(&level_id).unwrap_or_else(|| {
+                let start = level_file.level_offsets[level_id];
+                let end = level_file
+                    .level_offsets
+                    .get(level_id + 1)
+                    .copied()
+                    .unwrap_or(level_file.mmap.len());
+                level_file.mmap[start..end].to_vec()
+            })
+        })
+        .collect();
+
+    for (i, block) in level_blocks.iter().enumerate() {
+        level_file.write_level(first_level_id + i, block);
+    }
+}
+
+impl Shard {
+    /// Vector file section that stores the vectors of the documents of a level: the level itself, or the section the level was merged into.
+    #[cfg(feature = "vb")]
+    pub(crate) fn vector_section_id(&self, level_id: usize) -> usize {
+        self.merged_vector_levels
+            .iter()
+            .find(|(section_id, merged_vector_level)| {
+                (merged_vector_level.first_level_id..=**section_id).contains(&level_id)
+            })
+            .map_or(level_id, |(section_id, _)| *section_id)
+    }
+
+    fn save_merged_vector_levels(&self) {
+        if let Err(e) = std::fs::write(
+            Path::new(&self.index_path_string).join(MERGED_VECTOR_LEVELS_FILENAME),
+            merged_vector_levels_bytes(&self.merged_vector_levels),
+        ) {
+            println!("Unable to write merged vector levels {:?}", e)
+        }
+    }
+
+    pub(crate) fn clear_merged_vector_levels(&mut self) {
+        if !self.merged_vector_levels.is_empty() {
+            self.merged_vector_levels.clear();
+            self.save_merged_vector_levels();
+        }
+    }
+
+    /// Non-deleted vectors of the vector file section of a level, with the level of their document.
+    fn live_vectors(
+        &self,
+        level_id: usize,
+        offset: usize,
+        vector_count: usize,
+    ) -> Vec<ParentMedoid> {
+        let (vector_type, vector_size) = self.vector_record_size();
+        let merged_vector_level = self.merged_vector_levels.get(&level_id);
+        let mut vectors = Vec::new();
+        for i in 0..vector_count {
+            let record = read_record(
+                &self.vector_file_mmap[offset + i * vector_size..],
+                self.vector_dimensions,
+                vector_type,
+            );
+            let doc_id = vector_doc_id(merged_vector_level, level_id, i, record.header.doc_id);
+            if self.delete_hashset.contains(&doc_id) {
+                continue;
+            }
+            vectors.push(ParentMedoid {
+                medoid_index: 0,
+                similarity: 0.0,
+                is_medoid: false,
+
+                level_id: (doc_id >> 16) as u32,
+                doc_id: record.header.doc_id,
+                field_id: record.header.field_id,
+                chunk_id: record.header.chunk_id,
+                scale: record.header.scale,
+                norm: record.header.norm,
+                zero_point: record.header.zero_point,
+                sum_q: record.header.sum_q,
+                embedding: match record.embedding {
+                    EmbeddingView::I8(e) => Embedding::I8(e.to_vec()),
+                    EmbeddingView::F32(e) => Embedding::F32(e.to_vec()),
+                    EmbeddingView::F16(e) => Embedding::F16(e.to_vec()),
+                    EmbeddingView::BF16(e) => Embedding::BF16(e.to_vec()),
+                },
+            });
+        }
+        vectors
+    }
+
+    /// Number of non-deleted vectors of the vector file section of a level.
+    fn live_vector_count(&self, level_id: usize, offset: usize, vector_count: usize) -> usize {
+        if self.delete_hashset.is_empty() {
+            return vector_count;
+        }
+        let (vector_type, vector_size) = self.vector_record_size();
+        let merged_vector_level = self.merged_vector_levels.get(&level_id);
+        (0..vector_count)
+            .filter(|i| {
+                let record = read_record(
+                    &self.vector_file_mmap[offset + i * vector_size..],
+                    self.vector_dimensions,
+                    vector_type,
+                );
+                !self.delete_hashset.contains(&vector_doc_id(
+                    merged_vector_level,
+                    level_id,
+                    *i,
+                    record.header.doc_id,
+                ))
+            })
+            .count()
+    }
+
+    /// Merges the vectors of consecutive complete levels of the shard into the vector file section of the last of these levels.
+    pub(crate) async fn merge_vector_levels_shard(
+        &mut self,
+        max_level_vectors: usize,
+    ) -> MergeVectorLevelsResult {
+        let mut merge_result = MergeVectorLevelsResult::default();
+        if !self.is_vector_indexing || self.vector_file_mmap.is_empty() {
+            return merge_result;
+        }
+
+        let levels = self.vector_levels();
+        let (_, vector_size) = self.vector_record_size();
+        let section_start = |level_id: usize| {
+            let (offset, cluster_number, _) = levels[level_id];
+            offset - 4 - cluster_number * 4
+        };
+        let section_end = |level_id: usize| {
+            let (offset, _, vector_count) = levels[level_id];
+            offset + vector_count * vector_size
+        };
+        merge_result.level_count_before = levels.iter().filter(|level| level.2 > 0).count();
+
+        // the incomplete last level is re-clustered by every commit
+        let level_count = levels.len().min(
+            self.level_index
+                .len()
+                .saturating_sub(self.is_last_level_incomplete as usize),
+        );
+
+        let mut level_ranges = Vec::new();
+        let mut first_level_id = 0;
+        while first_level_id < level_count {
+            let level_id = self
+                .merged_vector_levels
+                .iter()
+                .find(|(_, merged_vector_level)| {
+                    merged_vector_level.first_level_id == first_level_id
+                })
+                .map_or(first_level_id, |(level_id, _)| *level_id);
+            if level_id >= level_count {
+                break;
+            }
+            let (offset, _, vector_count) = levels[level_id];
+            level_ranges.push(LevelRange {
+                first_level_id,
+                level_id,
+                vector_count,
+                live_vector_count: self.live_vector_count(level_id, offset, vector_count),
+            });
+            first_level_id = level_id + 1;
+        }
+
+        let mut groups: Vec<Vec<LevelRange>> = Vec::new();
+        let mut group_vector_count = 0;
+        for level_range in level_ranges {
+            match groups.last_mut() {
+                Some(group)
+                    if group_vector_count + level_range.live_vector_count <= max_level_vectors =>
+                {
+                    group_vector_count += level_range.live_vector_count;
+                    group.push(level_range);
+                }
+                _ => {
+                    group_vector_count = level_range.live_vector_count;
+                    groups.push(vec![level_range]);
+                }
+            }
+        }
+
+        let is_merged = |group: &[LevelRange]| {
+            group
+                .iter()
+                .filter(|level_range| level_range.vector_count > 0)
+                .count()
+                > 1
+                || group
+                    .iter()
+                    .any(|level_range| level_range.live_vector_count < level_range.vector_count)
+        };
+        let Some(first_merged_level_id) = groups
+            .iter()
+            .find(|group| is_merged(group))
+            .map(|group| group[0].first_level_id)
+        else {
+            merge_result.level_count_after = merge_result.level_count_before;
+            return merge_result;
+        };
+
+        // the uncommitted vectors are restored after the merge
+        let uncommitted_vectors = mem::take(&mut self.block_vector_buffer);
+
+        let mut bytes = Vec::new();
+        let mut hnsw_blocks = AHashMap::new();
+        let mut pq_blocks = AHashMap::new();
+        let mut binary_blocks = AHashMap::new();
+        for group in groups.iter() {
+            if group[0].first_level_id < first_merged_level_id {
+                continue;
+            }
+            let first_level_id = group[0].first_level_id;
+            let last_level_id = group[group.len() - 1].level_id;
+            if !is_merged(group) {
+                bytes.extend_from_slice(
+                    &self.vector_file_mmap
+                        [section_start(first_level_id)..section_end(last_level_id)],
+                );
+                continue;
+            }
+
+            let mut vectors = Vec::new();
+            for level_range in group.iter() {
+                let (offset, cluster_number, vector_count) = levels[level_range.level_id];
+                vectors.extend(self.live_vectors(level_range.level_id, offset, vector_count));
+                merge_result.purged_vector_count +=
+                    level_range.vector_count - level_range.live_vector_count;
+                self.indexed_cluster_count -= cluster_number;
+            }
+            merge_result.merged_vector_count += vectors.len();
+
+            for level_id in first_level_id..=last_level_id {
+                self.block_vector_buffer = if level_id == last_level_id {
+                    mem::take(&mut vectors)
+                } else {
+                    Vec::new()
+                };
+
+                let medoids = if self.block_vector_buffer.is_empty() {
+                    Vec::new()
+                } else {
+                    self.cluster_block_vector_buffer().await
+                };
+                self.indexed_cluster_count += medoids.len();
+                bytes.extend_from_slice(&self.vector_section_bytes(&medoids));
+
+                if let Clustering::Hnsw { m, ef_construction } = self.meta.clustering
+                    && level_id < self.hnsw_file.level_offsets.len()
+                {
+                    hnsw_blocks.insert(level_id, self.build_hnsw_graph(m, ef_construction));
+                }
+                match self.quantization {
+                    Quantization::ProductQuantization { subvectors, .. }
+                        if level_id < self.pq_file.level_offsets.len() =>
+                    {
+                        let block = self.encode_product_quantization(subvectors);
+                        if !block.is_empty() {
+                            pq_blocks.insert(level_id, block);
+                        }
+                    }
+                    Quantization::Binary { .. }
+                        if level_id < self.binary_file.level_offsets.len() =>
+                    {
+                        binary_blocks.insert(level_id, self.encode_binary_quantization());
+                    }
+                    _ => {}
+                }
+            }
+
+            self.merged_vector_levels
+                .retain(|level_id, _| !(first_level_id..=last_level_id).contains(level_id));
+            if first_level_id < last_level_id && !self.block_vector_buffer.is_empty() {
+                self.merged_vector_levels.insert(
+                    last_level_id,
+                    MergedVectorLevel {
+                        first_level_id,
+                        level_ids: self
+                            .block_vector_buffer
+                            .iter()
+                            .map(|vector| vector.level_id)
+                            .collect(),
+                    },
+                );
+            }
+        }
+        self.block_vector_buffer = uncommitted_vectors;
+
+        // sections of the levels that are not merged, including the incomplete last level
+        let start = section_start(first_merged_level_id);
+        let last_level_start = if level_count < levels.len() {
+            let last_level_start =
+                start + bytes.len() + section_start(levels.len() - 1) - section_start(level_count);
+            bytes.extend_from_slice(&self.vector_file_mmap[section_start(level_count)..]);
+            Some(last_level_start)
+        } else {
+            None
+        };
+        self.vector_file_mmap = unsafe {
+            MmapOptions::new()
+                .len(0)
+                .map(&self.vector_file)
+                .expect("Unable to create Mmap")
+        };
+        let _ = self.vector_file.seek(SeekFrom::Start(start as u64));
+        let _ = self.vector_file.write_all(&bytes);
+        if let Err(e) = self.vector_file.set_len((start + bytes.len()) as u64) {
+            println!(
+                "Unable to vector_file.set_len in merge_vector_levels {:?}",
+                e
+            )
+        }
+        self.vector_file.flush().expect("Unable to flush Mmap");
+        self.vector_file_mmap =
+            unsafe { Mmap::map(&self.vector_file).expect("Unable to create Mmap") };
+        if let Some(last_level_start) = last_level_start
+            && self.is_last_level_incomplete
+        {
+            self.last_level_vector_file_start_pos = last_level_start as u64;
+        }
+        self.indexed_vector_count -= merge_result.purged_vector_count;
+
+        rewrite_level_blocks(&mut self.hnsw_file, first_merged_level_id, hnsw_blocks);
+        rewrite_level_blocks(&mut self.pq_file, first_merged_level_id, pq_blocks);
+        rewrite_level_blocks(&mut self.binary_file, first_merged_level_id, binary_blocks);
+
+        self.save_merged_vector_levels();
+
+        merge_result.level_count_after = self
+            .vector_levels()
+            .iter()
+            .filter(|level| level.2 > 0)
+            .count();
+        merge_result
+    }
+}
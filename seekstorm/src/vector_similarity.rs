@@ -1,4 +1,7 @@
 #[cfg(target_arch = "aarch64")]
+use half::slice::HalfFloatSliceExt;
+use half::{bf16, f16};
+#[cfg(target_arch = "aarch64")]
 use std::arch::aarch64::*;
 #[cfg(target_arch = "x86_64")]
 use std::arch::x86_64::*;
@@ -46,8 +49,8 @@ pub enum AnnMode {
     All,
     /// Search only in the clusters with the highest similarity scores to the query vector.
     /// The number of clusters to search is specified by the n-probe parameter.
-    /// You cannot directly set a specific, guaranteed recall number (e.g., "always give me 95% recall@10"). There is no one-fits-all, there is no automatism.
-    /// Instead, you manually tune parameters that control the tradeoff between query latency and accuracy.
+    /// You cannot directly set a specific, guaranteed recall number (e.g., "always give me 95% recall@10"). There is no one-fits-all.
+    /// Instead, you manually tune parameters that control the tradeoff between query latency and accuracy, or calibrate the index and use AnnMode::TargetRecall.
     /// Because recall depends heavily on the structure of your specific data (distribution, dimensionality, and clustering) and queries,
     /// there is always a trial-and-error (benchmarking) phase required to determine the right settings for your data.
     /// Examples:
@@ -64,6 +67,14 @@ pub enum AnnMode {
     /// For dot product similarity, the similarity threshold should be between 0.0 and 1.0, where higher values indicate higher similarity (identical=1.0).
     /// For Euclidean distance similarity, the similarity threshold should be between 0.0 and infinity, where lower values indicate higher similarity (identical=0.0).
     NprobeSimilaritythreshold(usize, f32),
+    /// HNSW graph search with the specified candidate list width (ef_search), for indices created with `Clustering::Hnsw`.
+    /// Higher values increase recall and latency, ef_search is at least the number of requested results (length).
+    /// For indices without HNSW graph, all vectors are searched exhaustively.
+    EfSearch(usize),
+    /// Search with the lowest n-probe (or ef_search for HNSW indices) that reached the specified target recall (between 0.0 and 1.0, e.g. 0.95) in the ANN calibration.
+    /// The recall curve is measured with `CalibrateAnn::calibrate_ann` and re-calibrated after commits that add levels.
+    /// Without calibration, or if the target recall was not reached, all clusters are searched.
+    TargetRecall(f32),
 }
 
 #[inline(always)]
@@ -180,7 +191,13 @@ pub(crate) fn similarity_embedding_view(
             }
         }
 
-        (Embedding::I8(a), VectorSimilarity::Dot, Quantization::None) => {
+        (
+            Embedding::I8(a),
+            VectorSimilarity::Dot,
+            Quantization::None
+            | Quantization::ProductQuantization { .. }
+            | Quantization::Binary { .. },
+        ) => {
             if let EmbeddingView::I8(b) = b {
                 dot_i8(a, b) as f32
             } else {
@@ -192,7 +209,7 @@ pub(crate) fn similarity_embedding_view(
             if let EmbeddingView::F32(b) = b {
                 dot_f32(a, b)
             } else {
-                panic!("dot_f32 only supports f32 embeddings")
+                similarity_float(&EmbeddingView::F32(a), b, vector_similarity)
             }
         }
         (Embedding::I8(a), VectorSimilarity::Cosine, Quantization::ScalarQuantizationI8) => {
@@ -239,7 +256,13 @@ pub(crate) fn similarity_embedding_view(
             }
         }
 
-        (Embedding::I8(a), VectorSimilarity::Cosine, Quantization::None) => {
+        (
+            Embedding::I8(a),
+            VectorSimilarity::Cosine,
+            Quantization::None
+            | Quantization::ProductQuantization { .. }
+            | Quantization::Binary { .. },
+        ) => {
             if let EmbeddingView::I8(b) = b {
                 dot_i8(a, b) as f32
             } else {
@@ -251,7 +274,7 @@ pub(crate) fn similarity_embedding_view(
             if let EmbeddingView::F32(b) = b {
                 dot_f32(a, b)
             } else {
-                panic!("dot_f32 only supports f32 embeddings")
+                similarity_float(&EmbeddingView::F32(a), b, vector_similarity)
             }
         }
         (Embedding::I8(a), VectorSimilarity::Euclidean, Quantization::ScalarQuantizationI8) => {
@@ -326,7 +349,13 @@ pub(crate) fn similarity_embedding_view(
             }
         }
 
-        (Embedding::I8(a), VectorSimilarity::Euclidean, Quantization::None) => {
+        (
+            Embedding::I8(a),
+            VectorSimilarity::Euclidean,
+            Quantization::None
+            | Quantization::ProductQuantization { .. }
+            | Quantization::Binary { .. },
+        ) => {
             if let EmbeddingView::I8(b) = b {
                 -euclidean_i8(a, b)
             } else {
@@ -338,9 +367,13 @@ pub(crate) fn similarity_embedding_view(
             if let EmbeddingView::F32(b) = b {
                 -euclidean_f32(a, b)
             } else {
-                panic!("euclidean_f32 only supports f32 embeddings")
+                similarity_float(&EmbeddingView::F32(a), b, vector_similarity)
             }
         }
+
+        (Embedding::F16(_) | Embedding::BF16(_), _, _) => {
+            similarity_float(&a.view(), b, vector_similarity)
+        }
     }
 }
 
@@ -399,7 +432,13 @@ pub(crate) fn similarity_embedding(
             }
         }
 
-        (Embedding::I8(a), VectorSimilarity::Dot, Quantization::None) => {
+        (
+            Embedding::I8(a),
+            VectorSimilarity::Dot,
+            Quantization::None
+            | Quantization::ProductQuantization { .. }
+            | Quantization::Binary { .. },
+        ) => {
             if let Embedding::I8(b) = b {
                 dot_i8(a, b) as f32
             } else {
@@ -411,7 +450,7 @@ pub(crate) fn similarity_embedding(
             if let Embedding::F32(b) = b {
                 dot_f32(a, b)
             } else {
-                panic!("dot_f32 only supports f32 embeddings")
+                similarity_float(&EmbeddingView::F32(a), &b.view(), vector_similarity)
             }
         }
 
@@ -471,7 +510,7 @@ pub(crate) fn similarity_embedding(
             if let Embedding::F32(b) = b {
                 dot_f32(a, b)
             } else {
-                panic!("dot_f32 only supports f32 embeddings")
+                similarity_float(&EmbeddingView::F32(a), &b.view(), vector_similarity)
             }
         }
         (Embedding::I8(a), VectorSimilarity::Euclidean, Quantization::ScalarQuantizationI8) => {
@@ -545,7 +584,13 @@ pub(crate) fn similarity_embedding(
                 panic!("euclidean_i8 only supports i8 embeddings")
             }
         }
-        (Embedding::I8(a), VectorSimilarity::Euclidean, Quantization::None) => {
+        (
+            Embedding::I8(a),
+            VectorSimilarity::Euclidean,
+            Quantization::None
+            | Quantization::ProductQuantization { .. }
+            | Quantization::Binary { .. },
+        ) => {
             if let Embedding::I8(b) = b {
                 -euclidean_i8(a, b)
             } else {
@@ -556,9 +601,13 @@ pub(crate) fn similarity_embedding(
             if let Embedding::F32(b) = b {
                 -euclidean_f32(a, b)
             } else {
-                panic!("euclidean_f32 only supports f32 embeddings")
+                similarity_float(&EmbeddingView::F32(a), &b.view(), vector_similarity)
             }
         }
+
+        (Embedding::F16(_) | Embedding::BF16(_), _, _) => {
+            similarity_float(&a.view(), &b.view(), vector_similarity)
+        }
     }
 }
 
@@ -611,9 +660,13 @@ pub(crate) unsafe fn similarity_embedding_view_avx2(
                 }
             }
 
-            (EmbeddingView::I8(e), VectorSimilarity::Dot, Quantization::None) => {
-                dot_i8_avx2(query, e) as f32
-            }
+            (
+                EmbeddingView::I8(e),
+                VectorSimilarity::Dot,
+                Quantization::None
+                | Quantization::ProductQuantization { .. }
+                | Quantization::Binary { .. },
+            ) => dot_i8_avx2(query, e) as f32,
 
             (EmbeddingView::F32(e), VectorSimilarity::Dot, _) => dot_f32_avx2(query, e),
 
@@ -657,9 +710,13 @@ pub(crate) unsafe fn similarity_embedding_view_avx2(
                 }
             }
 
-            (EmbeddingView::I8(e), VectorSimilarity::Cosine, Quantization::None) => {
-                dot_i8_avx2(query, e) as f32
-            }
+            (
+                EmbeddingView::I8(e),
+                VectorSimilarity::Cosine,
+                Quantization::None
+                | Quantization::ProductQuantization { .. }
+                | Quantization::Binary { .. },
+            ) => dot_i8_avx2(query, e) as f32,
 
             (EmbeddingView::F32(e), VectorSimilarity::Cosine, _) => dot_f32_avx2(query, e),
             (
@@ -729,12 +786,24 @@ pub(crate) unsafe fn similarity_embedding_view_avx2(
                     -euclidean_i8_avx2(query, e) as f32
                 }
             }
-            (EmbeddingView::I8(e), VectorSimilarity::Euclidean, Quantization::None) => {
-                -euclidean_i8_avx2(query, e) as f32
-            }
+            (
+                EmbeddingView::I8(e),
+                VectorSimilarity::Euclidean,
+                Quantization::None
+                | Quantization::ProductQuantization { .. }
+                | Quantization::Binary { .. },
+            ) => -euclidean_i8_avx2(query, e) as f32,
             (EmbeddingView::F32(e), VectorSimilarity::Euclidean, _) => {
                 -euclidean_f32_avx2(query, e)
             }
+            (EmbeddingView::F16(e), VectorSimilarity::Euclidean, _) => {
+                -euclidean_half_avx2(query, e, load_f16_avx2)
+            }
+            (EmbeddingView::F16(e), _, _) => dot_half_avx2(query, e, load_f16_avx2),
+            (EmbeddingView::BF16(e), VectorSimilarity::Euclidean, _) => {
+                -euclidean_half_avx2(query, e, load_bf16_avx2)
+            }
+            (EmbeddingView::BF16(e), _, _) => dot_half_avx2(query, e, load_bf16_avx2),
         }
     }
 }
@@ -788,9 +857,13 @@ pub(crate) unsafe fn similarity_embedding_avx2(
                 }
             }
 
-            (Embedding::I8(e), VectorSimilarity::Dot, Quantization::None) => {
-                dot_i8_avx2(query, e) as f32
-            }
+            (
+                Embedding::I8(e),
+                VectorSimilarity::Dot,
+                Quantization::None
+                | Quantization::ProductQuantization { .. }
+                | Quantization::Binary { .. },
+            ) => dot_i8_avx2(query, e) as f32,
 
             (Embedding::F32(e), VectorSimilarity::Dot, _) => dot_f32_avx2(query, e),
 
@@ -830,9 +903,13 @@ pub(crate) unsafe fn similarity_embedding_avx2(
                 }
             }
 
-            (Embedding::I8(e), VectorSimilarity::Cosine, Quantization::None) => {
-                dot_i8_avx2(query, e) as f32
-            }
+            (
+                Embedding::I8(e),
+                VectorSimilarity::Cosine,
+                Quantization::None
+                | Quantization::ProductQuantization { .. }
+                | Quantization::Binary { .. },
+            ) => dot_i8_avx2(query, e) as f32,
 
             (Embedding::F32(e), VectorSimilarity::Cosine, _) => dot_f32_avx2(query, e),
 
@@ -900,10 +977,22 @@ pub(crate) unsafe fn similarity_embedding_avx2(
                     -euclidean_i8_avx2(query, e) as f32
                 }
             }
-            (Embedding::I8(e), VectorSimilarity::Euclidean, Quantization::None) => {
-                -euclidean_i8_avx2(query, e) as f32
-            }
+            (
+                Embedding::I8(e),
+                VectorSimilarity::Euclidean,
+                Quantization::None
+                | Quantization::ProductQuantization { .. }
+                | Quantization::Binary { .. },
+            ) => -euclidean_i8_avx2(query, e) as f32,
             (Embedding::F32(e), VectorSimilarity::Euclidean, _) => -euclidean_f32_avx2(query, e),
+            (Embedding::F16(e), VectorSimilarity::Euclidean, _) => {
+                -euclidean_half_avx2(query, e, load_f16_avx2)
+            }
+            (Embedding::F16(e), _, _) => dot_half_avx2(query, e, load_f16_avx2),
+            (Embedding::BF16(e), VectorSimilarity::Euclidean, _) => {
+                -euclidean_half_avx2(query, e, load_bf16_avx2)
+            }
+            (Embedding::BF16(e), _, _) => dot_half_avx2(query, e, load_bf16_avx2),
         }
     }
 }
@@ -1015,6 +1104,116 @@ pub(crate) fn dot_i8(a: &[i8], b: &[i8]) -> i32 {
         .sum()
 }
 
+/// Dot product of two vectors of any float precision (f32, f16, bf16), computed in f32.
+#[inline(always)]
+pub(crate) fn dot_float<A: Copy + Into<f32>, B: Copy + Into<f32>>(a: &[A], b: &[B]) -> f32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(&x, &y)| x.into() * y.into())
+        .sum()
+}
+
+/// Squared euclidean distance of two vectors of any float precision (f32, f16, bf16), computed in f32.
+#[inline(always)]
+pub(crate) fn euclidean_float<A: Copy + Into<f32>, B: Copy + Into<f32>>(a: &[A], b: &[B]) -> f32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(&x, &y)| {
+            let diff = x.into() - y.into();
+            diff * diff
+        })
+        .sum()
+}
+
+/// Dot product or negative squared euclidean distance of two float embeddings (F32, F16, BF16) of any combination of precisions.
+/// Half precision values are converted to f32.
+pub(crate) fn similarity_float(
+    a: &EmbeddingView,
+    b: &EmbeddingView,
+    vector_similarity: VectorSimilarity,
+) -> f32 {
+    fn similarity<A: Copy + Into<f32>>(
+        a: &[A],
+        b: &EmbeddingView,
+        vector_similarity: VectorSimilarity,
+    ) -> f32 {
+        match (b, vector_similarity) {
+            (EmbeddingView::F32(b), VectorSimilarity::Euclidean) => -euclidean_float(a, b),
+            (EmbeddingView::F16(b), VectorSimilarity::Euclidean) => -euclidean_float(a, b),
+            (EmbeddingView::BF16(b), VectorSimilarity::Euclidean) => -euclidean_float(a, b),
+            (EmbeddingView::F32(b), _) => dot_float(a, b),
+            (EmbeddingView::F16(b), _) => dot_float(a, b),
+            (EmbeddingView::BF16(b), _) => dot_float(a, b),
+            (EmbeddingView::I8(_), _) => panic!("similarity_float only supports float embeddings"),
+        }
+    }
+
+    match a {
+        EmbeddingView::F32(a) => similarity(a, b, vector_similarity),
+        EmbeddingView::F16(a) => similarity(a, b, vector_similarity),
+        EmbeddingView::BF16(a) => similarity(a, b, vector_similarity),
+        EmbeddingView::I8(_) => panic!("similarity_float only supports float embeddings"),
+    }
+}
+
+/// Hamming distance of two bit vectors: number of differing bits.
+#[inline(always)]
+pub(crate) fn hamming_distance(a: &[u64], b: &[u64]) -> u32 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(x, y)| (x ^ y).count_ones())
+        .sum()
+}
+
+/// Hamming distance of two bit vectors, with AVX2 on x86_64 and NEON on aarch64.
+#[inline(always)]
+pub(crate) unsafe fn hamming_distance_simd(a: &[u64], b: &[u64]) -> u32 {
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        hamming_distance_avx2(a, b)
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        hamming_distance_neon(a, b)
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        hamming_distance(a, b)
+    }
+}
+
+/// Popcount of 256 bit blocks via nibble lookup table (vpshufb), summed with vpsadbw.
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+pub(crate) unsafe fn hamming_distance_avx2(a: &[u64], b: &[u64]) -> u32 {
+    unsafe {
+        let lookup = _mm256_setr_epi8(
+            0, 1, 1, 2, 1, 2, 2, 3, 1, 2, 2, 3, 2, 3, 3, 4, 0, 1, 1, 2, 1, 2, 2, 3, 1, 2, 2, 3, 2,
+            3, 3, 4,
+        );
+        let low_mask = _mm256_set1_epi8(0x0f);
+        let mut acc = _mm256_setzero_si256();
+        let blocks = a.len() / 4;
+        for i in 0..blocks {
+            let va = _mm256_loadu_si256(a.as_ptr().add(i * 4) as *const __m256i);
+            let vb = _mm256_loadu_si256(b.as_ptr().add(i * 4) as *const __m256i);
+            let x = _mm256_xor_si256(va, vb);
+            let low = _mm256_shuffle_epi8(lookup, _mm256_and_si256(x, low_mask));
+            let high =
+                _mm256_shuffle_epi8(lookup, _mm256_and_si256(_mm256_srli_epi16(x, 4), low_mask));
+            acc = _mm256_add_epi64(
+                acc,
+                _mm256_sad_epu8(_mm256_add_epi8(low, high), _mm256_setzero_si256()),
+            );
+        }
+
+        let mut tmp = [0u64; 4];
+        _mm256_storeu_si256(tmp.as_mut_ptr() as *mut __m256i, acc);
+
+        tmp.iter().sum::<u64>() as u32 + hamming_distance(&a[blocks * 4..], &b[blocks * 4..])
+    }
+}
+
 #[cfg(target_arch = "x86_64")]
 pub(crate) type QuerySimdLaneI = __m256i;
 #[cfg(target_arch = "x86_64")]
@@ -1063,6 +1262,7 @@ impl QuerySimd {
             match query {
                 Embedding::I8(e) => QuerySimd::I(e.clone()),
                 Embedding::F32(e) => QuerySimd::F(e.clone()),
+                Embedding::F16(_) | Embedding::BF16(_) => QuerySimd::F(query.to_f32()),
             }
         }
     }
@@ -1087,6 +1287,9 @@ impl QuerySimd {
 
                     QuerySimd::F(q)
                 }
+                Embedding::F16(_) | Embedding::BF16(_) => {
+                    Self::new_avx2(&Embedding::F32(query.to_f32()))
+                }
             }
         }
     }
@@ -1110,6 +1313,9 @@ impl QuerySimd {
                     }
                     QuerySimd::F(q)
                 }
+                Embedding::F16(_) | Embedding::BF16(_) => {
+                    Self::new_neon(&Embedding::F32(query.to_f32()))
+                }
             }
         }
     }
@@ -1141,6 +1347,85 @@ pub(crate) unsafe fn dot_f32_avx2(query: &QuerySimd, emb: &[f32]) -> f32 {
     }
 }
 
+/// Loads 8 f16 values and converts them to f32 (F16C).
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+pub(crate) unsafe fn load_f16_avx2(ptr: *const f16) -> __m256 {
+    unsafe { _mm256_cvtph_ps(_mm_loadu_si128(ptr as *const __m128i)) }
+}
+
+/// Loads 8 bf16 values and converts them to f32: a bf16 value is the upper half of the f32 value.
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+pub(crate) unsafe fn load_bf16_avx2(ptr: *const bf16) -> __m256 {
+    unsafe {
+        _mm256_castsi256_ps(_mm256_slli_epi32(
+            _mm256_cvtepu16_epi32(_mm_loadu_si128(ptr as *const __m128i)),
+            16,
+        ))
+    }
+}
+
+/// Dot product of the f32 query with a half precision (f16 or bf16) embedding, converted to f32 by `load`.
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+pub(crate) unsafe fn dot_half_avx2<T>(
+    query: &QuerySimd,
+    emb: &[T],
+    load: unsafe fn(*const T) -> __m256,
+) -> f32 {
+    unsafe {
+        let query = match query {
+            QuerySimd::F(e) => e,
+            _ => {
+                println!("{:?}", query);
+                panic!("dot_half only supports f32 queries")
+            }
+        };
+
+        let mut sum = _mm256_setzero_ps();
+
+        for (i, q) in query.iter().enumerate() {
+            let vb = load(emb.as_ptr().add(i * 8));
+            sum = _mm256_fmadd_ps(*q, vb, sum);
+        }
+
+        let mut tmp = [0.0f32; 8];
+        _mm256_storeu_ps(tmp.as_mut_ptr(), sum);
+        tmp.iter().sum()
+    }
+}
+
+/// Squared euclidean distance of the f32 query to a half precision (f16 or bf16) embedding, converted to f32 by `load`.
+#[cfg(target_arch = "x86_64")]
+#[inline(always)]
+pub(crate) unsafe fn euclidean_half_avx2<T>(
+    query: &QuerySimd,
+    emb: &[T],
+    load: unsafe fn(*const T) -> __m256,
+) -> f32 {
+    unsafe {
+        let query = match query {
+            QuerySimd::F(e) => e,
+            _ => {
+                println!("{:?}", query);
+                panic!("euclidean_half only supports f32 queries")
+            }
+        };
+
+        let mut sum = _mm256_setzero_ps();
+
+        for (i, q) in query.iter().enumerate() {
+            let diff = _mm256_sub_ps(*q, load(emb.as_ptr().add(i * 8)));
+            sum = _mm256_fmadd_ps(diff, diff, sum);
+        }
+
+        let mut tmp = [0.0f32; 8];
+        _mm256_storeu_ps(tmp.as_mut_ptr(), sum);
+        tmp.iter().sum()
+    }
+}
+
 #[cfg(target_arch = "x86_64")]
 #[inline(always)]
 pub(crate) unsafe fn dot_i8_avx2(query: &QuerySimd, emb: &[i8]) -> i32 {
@@ -2178,6 +2463,68 @@ pub(crate) unsafe fn dot_f32_neon(query: &QuerySimd, emb: &[f32]) -> f32 {
     }
 }
 
+/// Loads 4 f16 values and converts them to f32.
+/// The conversion uses the fp16 hardware instructions via the half crate, as the fp16 NEON intrinsics are not yet stable.
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+pub(crate) unsafe fn load_f16_neon(ptr: *const f16) -> float32x4_t {
+    unsafe {
+        let mut values = [0f32; 4];
+        std::slice::from_raw_parts(ptr, 4).convert_to_f32_slice(&mut values);
+        vld1q_f32(values.as_ptr())
+    }
+}
+
+/// Loads 4 bf16 values and converts them to f32: a bf16 value is the upper half of the f32 value.
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+pub(crate) unsafe fn load_bf16_neon(ptr: *const bf16) -> float32x4_t {
+    unsafe { vreinterpretq_f32_u32(vshll_n_u16(vld1_u16(ptr as *const u16), 16)) }
+}
+
+/// Dot product of the f32 query with a half precision (f16 or bf16) embedding, converted to f32 by `load`.
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+pub(crate) unsafe fn dot_half_neon<T>(
+    query: &QuerySimd,
+    emb: &[T],
+    load: unsafe fn(*const T) -> float32x4_t,
+) -> f32 {
+    unsafe {
+        let query = match query {
+            QuerySimd::F(e) => e,
+            _ => panic!("dot_half_neon only supports f32 queries"),
+        };
+        let mut sum = vdupq_n_f32(0.0);
+        for (i, q) in query.iter().enumerate() {
+            sum = vfmaq_f32(sum, *q, load(emb.as_ptr().add(i * 4)));
+        }
+        vaddvq_f32(sum)
+    }
+}
+
+/// Squared euclidean distance of the f32 query to a half precision (f16 or bf16) embedding, converted to f32 by `load`.
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+pub(crate) unsafe fn euclidean_half_neon<T>(
+    query: &QuerySimd,
+    emb: &[T],
+    load: unsafe fn(*const T) -> float32x4_t,
+) -> f32 {
+    unsafe {
+        let query = match query {
+            QuerySimd::F(e) => e,
+            _ => panic!("euclidean_half_neon only supports f32 queries"),
+        };
+        let mut sum = vdupq_n_f32(0.0);
+        for (i, q) in query.iter().enumerate() {
+            let diff = vsubq_f32(*q, load(emb.as_ptr().add(i * 4)));
+            sum = vfmaq_f32(sum, diff, diff);
+        }
+        vaddvq_f32(sum)
+    }
+}
+
 #[cfg(target_arch = "aarch64")]
 #[inline(always)]
 pub(crate) unsafe fn dot_i8_neon(query: &QuerySimd, emb: &[i8]) -> i32 {
@@ -2198,6 +2545,22 @@ pub(crate) unsafe fn dot_i8_neon(query: &QuerySimd, emb: &[i8]) -> i32 {
     }
 }
 
+/// Popcount of 128 bit blocks via vcntq_u8.
+#[cfg(target_arch = "aarch64")]
+#[inline(always)]
+pub(crate) unsafe fn hamming_distance_neon(a: &[u64], b: &[u64]) -> u32 {
+    unsafe {
+        let mut sum = 0u32;
+        let blocks = a.len() / 2;
+        for i in 0..blocks {
+            let va = vld1q_u8(a.as_ptr().add(i * 2) as *const u8);
+            let vb = vld1q_u8(b.as_ptr().add(i * 2) as *const u8);
+            sum += vaddlvq_u8(vcntq_u8(veorq_u8(va, vb))) as u32;
+        }
+        sum + hamming_distance(&a[blocks * 2..], &b[blocks * 2..])
+    }
+}
+
 #[cfg(target_arch = "aarch64")]
 #[inline(always)]
 pub(crate) unsafe fn euclidean_f32_neon(query: &QuerySimd, b: &[f32]) -> f32 {
@@ -2505,9 +2868,13 @@ pub(crate) unsafe fn similarity_embedding_view_neon(
                     dot_i8_neon(query, e) as f32
                 }
             }
-            (EmbeddingView::I8(e), VectorSimilarity::Dot, Quantization::None) => {
-                dot_i8_neon(query, e) as f32
-            }
+            (
+                EmbeddingView::I8(e),
+                VectorSimilarity::Dot,
+                Quantization::None
+                | Quantization::ProductQuantization { .. }
+                | Quantization::Binary { .. },
+            ) => dot_i8_neon(query, e) as f32,
             (EmbeddingView::F32(e), VectorSimilarity::Dot, _) => dot_f32_neon(query, e),
             (
                 EmbeddingView::I8(e),
@@ -2527,9 +2894,13 @@ pub(crate) unsafe fn similarity_embedding_view_neon(
                     dot_i8_neon(query, e) as f32
                 }
             }
-            (EmbeddingView::I8(e), VectorSimilarity::Cosine, Quantization::None) => {
-                dot_i8_neon(query, e) as f32
-            }
+            (
+                EmbeddingView::I8(e),
+                VectorSimilarity::Cosine,
+                Quantization::None
+                | Quantization::ProductQuantization { .. }
+                | Quantization::Binary { .. },
+            ) => dot_i8_neon(query, e) as f32,
             (EmbeddingView::F32(e), VectorSimilarity::Cosine, _) => dot_f32_neon(query, e),
             (
                 EmbeddingView::I8(e),
@@ -2555,12 +2926,24 @@ pub(crate) unsafe fn similarity_embedding_view_neon(
                     -euclidean_i8_neon(query, e) as f32
                 }
             }
-            (EmbeddingView::I8(e), VectorSimilarity::Euclidean, Quantization::None) => {
-                -euclidean_i8_neon(query, e) as f32
-            }
+            (
+                EmbeddingView::I8(e),
+                VectorSimilarity::Euclidean,
+                Quantization::None
+                | Quantization::ProductQuantization { .. }
+                | Quantization::Binary { .. },
+            ) => -euclidean_i8_neon(query, e) as f32,
             (EmbeddingView::F32(e), VectorSimilarity::Euclidean, _) => {
                 -euclidean_f32_neon(query, e)
             }
+            (EmbeddingView::F16(e), VectorSimilarity::Euclidean, _) => {
+                -euclidean_half_neon(query, e, load_f16_neon)
+            }
+            (EmbeddingView::F16(e), _, _) => dot_half_neon(query, e, load_f16_neon),
+            (EmbeddingView::BF16(e), VectorSimilarity::Euclidean, _) => {
+                -euclidean_half_neon(query, e, load_bf16_neon)
+            }
+            (EmbeddingView::BF16(e), _, _) => dot_half_neon(query, e, load_bf16_neon),
         }
     }
 }
@@ -2576,14 +2959,10 @@ pub(crate) unsafe fn similarity_embedding_neon(
     quantization: Quantization,
     non_affine: bool,
 ) -> f32 {
-    let view = match emb {
-        Embedding::I8(e) => EmbeddingView::I8(e.as_slice()),
-        Embedding::F32(e) => EmbeddingView::F32(e.as_slice()),
-    };
     unsafe {
         similarity_embedding_view_neon(
             query,
-            &view,
+            &emb.view(),
             scale_norm,
             vector_similarity,
             quantization,
@@ -3046,6 +3425,44 @@ mod neon_parity_tests {
         assert_eq!(neon, scalar);
     }
 
+    #[test]
+    fn dot_half_neon_matches_scalar() {
+        let a = make_f32(128);
+        let b = make_f32(128);
+        let b16: Vec<f16> = b.iter().map(|v| f16::from_f32(*v)).collect();
+        let bb16: Vec<bf16> = b.iter().map(|v| bf16::from_f32(*v)).collect();
+        let q = unsafe { QuerySimd::new(&Embedding::F32(a.clone())) };
+        let neon = unsafe { dot_half_neon(&q, &b16, load_f16_neon) };
+        let scalar = dot_float(&a, &b16);
+        assert!(
+            (neon - scalar).abs() < 1e-3,
+            "neon {} scalar {}",
+            neon,
+            scalar
+        );
+        let neon = unsafe { euclidean_half_neon(&q, &bb16, load_bf16_neon) };
+        let scalar = euclidean_float(&a, &bb16);
+        assert!(
+            (neon - scalar).abs() < 1e-3,
+            "neon {} scalar {}",
+            neon,
+            scalar
+        );
+    }
+
+    #[test]
+    fn hamming_distance_neon_matches_scalar() {
+        let a: Vec<u64> = (0..7u64)
+            .map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+            .collect();
+        let b: Vec<u64> = (0..7u64)
+            .map(|i| i.wrapping_mul(0xbf58_476d_1ce4_e5b9))
+            .collect();
+        let neon = unsafe { hamming_distance_neon(&a, &b) };
+        let scalar = hamming_distance(&a, &b);
+        assert_eq!(neon, scalar);
+    }
+
     #[test]
     fn euclidean_f32_neon_matches_scalar() {
         let a = make_f32(128);
@@ -0,0 +1,1072 @@
+use std::{
+    fs::{self, File},
+    io::{Seek, SeekFrom, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use memmap2::Mmap;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    binary_quantization::{BINARY_HEADER_SIZE, binary_level_size},
+    commit::Commit,
+    hnsw::{HNSW_HEADER_SIZE, hnsw_level_size},
+    index::{
+        BINARY_FILENAME, CHECKSUM_FILENAME, DELETE_FILENAME, DOCSTORE_FILENAME, DeleteDocuments,
+        Document, FACET_FILENAME, FileType, HNSW_FILENAME, INDEX_FILENAME, INDEX_HEADER_SIZE,
+        Index, IndexArc, IndexDocumentShard, MERGED_VECTOR_LEVELS_FILENAME, PQ_FILENAME,
+        ROARING_BLOCK_SIZE, SCHEMA_FILENAME, SchemaField, Shard, VECTOR_FILENAME, facet_size,
+        open_index,
+    },
+    product_quantization::{PQ_HEADER_SIZE, pq_level_size},
+    utils::{read_u32, read_u64},
+    vector::{Precision, Quantization, VectorHeader},
+    vector_field::VECTOR_FIELDS_PATH,
+};
+
+/// Size of a level record in checksum.bin: index.bin start and end, index.bin segment head table start and end, docstore.bin start and end,
+/// indexed document count, vector record size, index.bin checksum and docstore.bin checksum of the level (u64 each).
+const LEVEL_CHECKSUM_SIZE: usize = 80;
+
+/// Checksum record of a committed level of a shard, one per level in checksum.bin.
+/// The record of an incomplete last level is replaced when the level is committed again.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct LevelChecksum {
+    index_start: u64,
+    index_end: u64,
+    index_head_start: u64,
+    index_head_end: u64,
+    docstore_start: u64,
+    docstore_end: u64,
+    indexed_doc_count: u64,
+    vector_size: u64,
+    index_checksum: u64,
+    docstore_checksum: u64,
+}
+
+impl LevelChecksum {
+    fn to_bytes(self) -> [u8; LEVEL_CHECKSUM_SIZE] {
+        let mut bytes = [0u8; LEVEL_CHECKSUM_SIZE];
+        for (i, value) in [
+            self.index_start,
+            self.index_end,
+            self.index_head_start,
+            self.index_head_end,
+            self.docstore_start,
+            self.docstore_end,
+            self.indexed_doc_count,
+            self.vector_size,
+            self.index_checksum,
+            self.docstore_checksum,
+        ]
+        .iter()
+        .enumerate()
+        {
+            bytes[i * 8..(i + 1) * 8].copy_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> LevelChecksum {
+        LevelChecksum {
+            index_start: read_u64(bytes, 0),
+            index_end: read_u64(bytes, 8),
+            index_head_start: read_u64(bytes, 16),
+            index_head_end: read_u64(bytes, 24),
+            docstore_start: read_u64(bytes, 32),
+            docstore_end: read_u64(bytes, 40),
+            indexed_doc_count: read_u64(bytes, 48),
+            vector_size: read_u64(bytes, 56),
+            index_checksum: read_u64(bytes, 64),
+            docstore_checksum: read_u64(bytes, 72),
+        }
+    }
+
+    /// Record of a level at the given ranges of index.bin and docstore.bin, with the checksums of the buffers fed to level_hasher.
+    pub(crate) fn new(
+        index_range: Range<u64>,
+        docstore_range: Range<u64>,
+        indexed_doc_count: usize,
+        vector_size: usize,
+        level_hasher: &LevelHasher,
+    ) -> LevelChecksum {
+        LevelChecksum {
+            index_start: index_range.start,
+            index_end: index_range.end,
+            index_head_start: level_hasher.index_head_start,
+            index_head_end: level_hasher.index_head_start + level_hasher.index_head.len() as u64,
+            docstore_start: docstore_range.start,
+            docstore_end: docstore_range.end,
+            indexed_doc_count: indexed_doc_count as u64,
+            vector_size: vector_size as u64,
+            index_checksum: level_hasher.index_checksum(),
+            docstore_checksum: if docstore_range.is_empty() {
+                0
+            } else {
+                level_hasher
+                    .docstore_checksum((docstore_range.end - docstore_range.start - 4) as u32)
+            },
+        }
+    }
+
+    /// Levels committed before checksum.bin existed have an empty record.
+    fn is_recorded(&self) -> bool {
+        self.index_end != 0
+    }
+}
+
+/// Incremental SHA-256 of the last level, fed with the buffers as they are written to index.bin and docstore.bin at commit,
+/// so that the level doesn't have to be read again for its checksum.
+/// The segment head table of index.bin is written after the blocks, it is hashed last.
+/// The docstore pointers and documents are hashed separately, as each commit of an incomplete last level extends both.
+#[derive(Clone, Default)]
+pub(crate) struct LevelHasher {
+    index: Sha256,
+    index_head_start: u64,
+    index_head: Vec<u8>,
+    docstore_pointers: Sha256,
+    docstore_docs: Sha256,
+    /// False if the docstore hashes don't cover the committed documents of the last level, e.g. after open_index.
+    pub(crate) is_docstore_level_hashed: bool,
+}
+
+impl LevelHasher {
+    /// Starts the index.bin hash of a level: each commit writes the whole level, also for an incomplete last level.
+    pub(crate) fn start_index_level(&mut self) {
+        self.index = Sha256::new();
+        self.index_head_start = 0;
+        self.index_head.clear();
+    }
+
+    pub(crate) fn update_index(&mut self, bytes: &[u8]) {
+        self.index.update(bytes);
+    }
+
+    /// Sets the segment head table of the level, written at position of index.bin.
+    pub(crate) fn set_index_head(&mut self, position: u64, bytes: &[u8]) {
+        self.index_head_start = position;
+        self.index_head = bytes.to_vec();
+    }
+
+    /// Starts the docstore.bin hashes of a new level.
+    pub(crate) fn start_docstore_level(&mut self) {
+        self.docstore_pointers = Sha256::new();
+        self.docstore_docs = Sha256::new();
+        self.is_docstore_level_hashed = true;
+    }
+
+    /// Adds the pointers and the documents of newly committed documents.
+    pub(crate) fn update_docstore(&mut self, pointers: &[u8], docs: &[u8]) {
+        self.docstore_pointers.update(pointers);
+        self.docstore_docs.update(docs);
+    }
+
+    /// Hashes the pointers of the first doc_count documents and the documents of the docstore.bin level in bytes[level_start..level_end].
+    pub(crate) fn hash_docstore_level(
+        &mut self,
+        bytes: &[u8],
+        level_start: usize,
+        level_end: usize,
+        doc_count: usize,
+    ) {
+        self.start_docstore_level();
+        self.update_docstore(
+            &bytes[level_start + 4..level_start + 4 + doc_count * 4],
+            &bytes[level_start + 4 + ROARING_BLOCK_SIZE * 4..level_end],
+        );
+    }
+
+    fn index_checksum(&self) -> u64 {
+        let mut hasher = self.index.clone();
+        hasher.update(&self.index_head);
+        truncated_checksum(hasher)
+    }
+
+    /// Checksum of the docstore level with the size in its first 4 bytes.
+    fn docstore_checksum(&self, size: u32) -> u64 {
+        let mut hasher = Sha256::new();
+        hasher.update(self.docstore_pointers.clone().finalize());
+        hasher.update(self.docstore_docs.clone().finalize());
+        hasher.update(size.to_le_bytes());
+        truncated_checksum(hasher)
+    }
+}
+
+/// Result of the integrity verification of a shard.
+#[derive(Debug, Clone, Default)]
+pub struct ShardVerifyResult {
+    /// Path of the shard directory.
+    pub path: String,
+    /// Number of committed levels of the shard.
+    pub level_count: usize,
+    /// Number of levels from the start that are consistent: the levels a repair would keep.
+    pub consistent_level_count: usize,
+    /// Number of levels with a checksum. Levels committed before checksums were introduced can only be verified structurally.
+    pub checksum_level_count: usize,
+    /// Inconsistencies found in the shard, empty if the shard is consistent.
+    pub errors: Vec<String>,
+}
+
+/// Result of the integrity verification of an index.
+#[derive(Debug, Clone, Default)]
+pub struct VerifyResult {
+    /// True if no inconsistencies were found in any shard.
+    pub is_consistent: bool,
+    /// Results per shard, including the shards of vector fields with their own inference (SchemaField.inference).
+    pub shard_results: Vec<ShardVerifyResult>,
+}
+
+/// Result of the repair of a shard.
+#[derive(Debug, Clone, Default)]
+pub struct ShardRepairResult {
+    /// Path of the shard directory.
+    pub path: String,
+    /// Number of levels before the repair.
+    pub level_count: usize,
+    /// Number of levels after the repair.
+    pub repaired_level_count: usize,
+    /// Number of documents removed with the rolled back levels. They have to be indexed again.
+    pub removed_doc_count: usize,
+}
+
+/// Result of repair_index.
+pub struct RepairResult {
+    /// The repaired index, opened at its path.
+    pub index_arc: IndexArc,
+    /// Results per shard, including the shards of vector fields with their own inference (SchemaField.inference).
+    pub shard_results: Vec<ShardRepairResult>,
+    /// Document ids of deleted placeholder documents, indexed to fill the gaps in the round-robin document ids of shards that were rolled back further than others.
+    pub placeholder_doc_ids: Vec<u64>,
+}
+
+impl Shard {
+    /// Writes the checksum record of the committed last level to checksum.bin, replacing the records of the level and the levels after it.
+    pub(crate) fn write_level_checksum(&self, indexed_doc_count: usize) {
+        let shard_path = Path::new(&self.index_path_string);
+        let level_id = self.level_index.len() - 1;
+        let index_end = self
+            .index_file
+            .metadata()
+            .map_or(0, |metadata| metadata.len());
+        let docstore_end = self
+            .docstore_file
+            .metadata()
+            .map_or(0, |metadata| metadata.len());
+
+        let level_checksum = LevelChecksum::new(
+            self.last_level_index_file_start_pos..index_end,
+            self.last_level_docstore_file_start_pos..docstore_end,
+            indexed_doc_count,
+            if self.is_vector_indexing {
+                self.vector_size()
+            } else {
+                0
+            },
+            &self.level_hasher,
+        );
+
+        match File::options()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(shard_path.join(CHECKSUM_FILENAME))
+        {
+            Ok(mut file) => {
+                let position = (level_id * LEVEL_CHECKSUM_SIZE) as u64;
+                let _ = file.set_len(position);
+                let _ = file.seek(SeekFrom::Start(position));
+                if let Err(e) = file
+                    .write_all(&level_checksum.to_bytes())
+                    .and_then(|_| file.flush())
+                {
+                    println!("Unable to write checksum file {:?}", e)
+                }
+            }
+            Err(e) => println!("Unable to open checksum file {:?}", e),
+        }
+    }
+
+    /// Size of a vector record in vector.bin: header and embedding.
+    fn vector_size(&self) -> usize {
+        let vector_type = match self.quantization {
+            Quantization::ScalarQuantizationI8 | Quantization::TurboQuantI8 => Precision::I8,
+            _ => self.vector_precision,
+        };
+        size_of::<VectorHeader>()
+            + (self.vector_dimensions
+                * match vector_type {
+                    Precision::F32 => 4,
+                    Precision::F16 | Precision::BF16 => 2,
+                    Precision::I8 => 1,
+                    Precision::None => 0,
+                })
+    }
+
+    /// Verifies the committed files of the shard: level structure of index.bin, docstore offsets, facet.bin size, vector files and level checksums.
+    fn verify_shard(&self) -> ShardVerifyResult {
+        let shard_path = Path::new(&self.index_path_string);
+        let level_count = self.level_index.len();
+        let mut errors = Vec::new();
+
+        let (level_checksums, checksum_consistent_level_count, checksum_errors) =
+            verify_level_checksums(shard_path);
+        errors.extend(checksum_errors);
+        if level_checksums.len() > level_count {
+            errors.push(format!(
+                "{} has {} level records, but the shard has {} levels",
+                CHECKSUM_FILENAME,
+                level_checksums.len(),
+                level_count
+            ));
+        }
+
+        let index_level_count = match map_file(&shard_path.join(INDEX_FILENAME)) {
+            Some(index_mmap) => self.verify_index_file(&index_mmap, &mut errors),
+            None => {
+                errors.push(format!("{} is missing", INDEX_FILENAME));
+                0
+            }
+        };
+
+        let docstore_level_count = if self.stored_field_names.is_empty() {
+            level_count
+        } else {
+            match map_file(&shard_path.join(DOCSTORE_FILENAME)) {
+                Some(docstore_mmap) => self.verify_docstore_file(&docstore_mmap, &mut errors),
+                None => {
+                    errors.push(format!("{} is missing", DOCSTORE_FILENAME));
+                    0
+                }
+            }
+        };
+
+        if !self.facets.is_empty() {
+            let facet_file_size = fs::metadata(shard_path.join(FACET_FILENAME))
+                .map_or(0, |metadata| metadata.len() as usize);
+            if facet_file_size < self.facets_size_sum * self.committed_doc_count {
+                errors.push(format!(
+                    "{} size {} is smaller than the facet values of {} documents ({} bytes)",
+                    FACET_FILENAME,
+                    facet_file_size,
+                    self.committed_doc_count,
+                    self.facets_size_sum * self.committed_doc_count
+                ));
+            }
+        }
+
+        let mut vector_level_count = level_count;
+        if self.is_vector_indexing
+            && let Some(vector_mmap) = map_file(&shard_path.join(VECTOR_FILENAME))
+            && !vector_mmap.is_empty()
+        {
+            let (section_ends, is_complete) =
+                vector_section_ends(&vector_mmap, self.vector_size(), level_count);
+            vector_level_count = section_ends.len();
+            if section_ends.len() < level_count {
+                errors.push(format!(
+                    "{} has {} complete level sections, but the shard has {} levels",
+                    VECTOR_FILENAME,
+                    section_ends.len(),
+                    level_count
+                ));
+            } else if !is_complete {
+                errors.push(format!(
+                    "{} has data after the section of the last level {}",
+                    VECTOR_FILENAME,
+                    level_count - 1
+                ));
+            }
+        }
+
+        for (filename, header_size, level_size) in LEVEL_FILES {
+            if let Some(level_mmap) = map_file(&shard_path.join(filename)) {
+                let (block_ends, is_complete) =
+                    level_block_ends(&level_mmap, header_size, level_size);
+                if !is_complete {
+                    errors.push(format!(
+                        "{} has an incomplete level block after level {}",
+                        filename,
+                        block_ends.len()
+                    ));
+                }
+                if block_ends.len() > level_count {
+                    errors.push(format!(
+                        "{} has {} level blocks, but the shard has {} levels",
+                        filename,
+                        block_ends.len(),
+                        level_count
+                    ));
+                }
+            }
+        }
+
+        let consistent_level_count = checksum_consistent_level_count
+            .min(index_level_count)
+            .min(docstore_level_count)
+            .min(vector_level_count)
+            .min(level_count);
+
+        ShardVerifyResult {
+            path: self.index_path_string.clone(),
+            level_count,
+            consistent_level_count,
+            checksum_level_count: level_checksums
+                .iter()
+                .filter(|level_checksum| level_checksum.is_recorded())
+                .count(),
+            errors,
+        }
+    }
+
+    /// Walks the levels of index.bin: document lengths, level header and segment blocks. Returns the number of complete levels.
+    fn verify_index_file(&self, bytes: &[u8], errors: &mut Vec<String>) -> usize {
+        let mut position = INDEX_HEADER_SIZE as usize;
+        let mut level_id = 0;
+        let mut indexed_doc_count = 0;
+        while position < bytes.len() {
+            let level_start = position;
+            if level_id == 0 {
+                position += 2;
+            }
+            position += self.indexed_field_vec.len() * ROARING_BLOCK_SIZE + 16;
+            if position + self.segment_number1 * 8 > bytes.len() {
+                errors.push(format!(
+                    "{} level {} is truncated at the level header (offset {})",
+                    INDEX_FILENAME, level_id, level_start
+                ));
+                return level_id;
+            }
+
+            let level_doc_count = read_u64(bytes, position - 16) as usize;
+            if level_doc_count <= level_id * ROARING_BLOCK_SIZE
+                || level_doc_count > (level_id + 1) * ROARING_BLOCK_SIZE
+            {
+                errors.push(format!(
+                    "{} level {} has an invalid document count {}",
+                    INDEX_FILENAME, level_id, level_doc_count
+                ));
+                return level_id;
+            }
+
+            let mut blocks_size = 0;
+            for key0 in 0..self.segment_number1 {
+                let block_length = read_u32(bytes, position + key0 * 8) as usize;
+                let key_count = read_u32(bytes, position + key0 * 8 + 4) as usize;
+                if key_count * self.key_head_size > block_length {
+                    errors.push(format!(
+                        "{} level {} segment {} has an invalid block header",
+                        INDEX_FILENAME, level_id, key0
+                    ));
+                    return level_id;
+                }
+                blocks_size += block_length;
+            }
+            position += self.segment_number1 * 8 + blocks_size;
+            if position > bytes.len() {
+                errors.push(format!(
+                    "{} level {} is truncated: {} of {} bytes",
+                    INDEX_FILENAME,
+                    level_id,
+                    bytes.len() - level_start,
+                    position - level_start
+                ));
+                return level_id;
+            }
+
+            indexed_doc_count = level_doc_count;
+            level_id += 1;
+        }
+
+        if level_id != self.level_index.len() {
+            errors.push(format!(
+                "{} has {} levels, but the shard has {} levels",
+                INDEX_FILENAME,
+                level_id,
+                self.level_index.len()
+            ));
+        }
+        if indexed_doc_count != self.committed_doc_count {
+            errors.push(format!(
+                "{} has {} documents, but the shard has {} committed documents",
+                INDEX_FILENAME, indexed_doc_count, self.committed_doc_count
+            ));
+        }
+        level_id
+    }
+
+    /// Walks the levels of docstore.bin and checks that the document offsets are ascending and within the level. Returns the number of complete levels.
+    fn verify_docstore_file(&self, bytes: &[u8], errors: &mut Vec<String>) -> usize {
+        let mut position = 0;
+        let mut level_id = 0;
+        while position < bytes.len() {
+            if position + 4 > bytes.len() {
+                errors.push(format!(
+                    "{} level {} is truncated at the level header",
+                    DOCSTORE_FILENAME, level_id
+                ));
+                return level_id;
+            }
+            let level_size = read_u32(bytes, position) as usize;
+            position += 4;
+            if level_size < ROARING_BLOCK_SIZE * 4 || position + level_size > bytes.len() {
+                errors.push(format!(
+                    "{} level {} is truncated: {} of {} bytes",
+                    DOCSTORE_FILENAME,
+                    level_id,
+                    bytes.len() - position,
+                    level_size
+                ));
+                return level_id;
+            }
+
+            let level = &bytes[position..position + level_size];
+            let level_doc_count = self
+                .committed_doc_count
+                .saturating_sub(level_id * ROARING_BLOCK_SIZE)
+                .min(ROARING_BLOCK_SIZE);
+            let mut previous_pointer = ROARING_BLOCK_SIZE * 4;
+            for doc_id_local in 0..level_doc_count {
+                let pointer = read_u32(level, doc_id_local * 4) as usize;
+                if pointer < previous_pointer || pointer > level_size {
+                    errors.push(format!(
+                        "{} level {} has an invalid offset {} for document {}",
+                        DOCSTORE_FILENAME,
+                        level_id,
+                        pointer,
+                        level_id * ROARING_BLOCK_SIZE + doc_id_local
+                    ));
+                    return level_id;
+                }
+                previous_pointer = pointer;
+            }
+
+            position += level_size;
+            level_id += 1;
+        }
+
+        if level_id != self.level_index.len() {
+            errors.push(format!(
+                "{} has {} levels, but the shard has {} levels",
+                DOCSTORE_FILENAME,
+                level_id,
+                self.level_index.len()
+            ));
+        }
+        level_id
+    }
+}
+
+/// Size of the level block at the start of the bytes of a level file, from its header.
+pub(crate) type LevelSize = fn(&[u8]) -> usize;
+
+/// Level files with one block per level: file name, block header size and block size function.
+pub(crate) const LEVEL_FILES: [(&str, usize, LevelSize); 3] = [
+    (HNSW_FILENAME, HNSW_HEADER_SIZE, hnsw_level_size),
+    (PQ_FILENAME, PQ_HEADER_SIZE, pq_level_size),
+    (BINARY_FILENAME, BINARY_HEADER_SIZE, binary_level_size),
+];
+
+impl Index {
+    /// Verifies the integrity of the committed index files, e.g. after the process was killed during a commit:
+    /// the level structure of index.bin, the document offsets of docstore.bin, the size of facet.bin against the committed document count,
+    /// the level sections of the vector files, and the level checksums written at commit.
+    /// Uncommitted documents are not verified. Inconsistent indices can be rolled back to their last consistent level with repair_index.
+    pub async fn verify(&self) -> VerifyResult {
+        let mut shard_results = Vec::new();
+        for shard in self.shard_vec.iter() {
+            shard_results.push(shard.read().await.verify_shard());
+        }
+        for vector_field_index in self.vector_field_indices.iter() {
+            let vector_field_result =
+                Box::pin(async { vector_field_index.index_arc.read().await.verify().await }).await;
+            shard_results.extend(vector_field_result.shard_results);
+        }
+
+        VerifyResult {
+            is_consistent: shard_results
+                .iter()
+                .all(|shard_result| shard_result.errors.is_empty()),
+            shard_results,
+        }
+    }
+}
+
+/// Repairs a closed index by rolling back each shard to its last consistent level, e.g. after the process was killed during a commit,
+/// and opens the repaired index.
+/// Levels are consistent if their checksums (written at commit) match and the level sections of the vector files are complete.
+/// index.bin, docstore.bin, the vector files and the deleted documents are truncated to the last consistent level.
+/// The documents of the rolled back levels are lost and have to be indexed again, ShardRepairResult.removed_doc_count reports their number.
+/// The shards of vector fields with their own inference (SchemaField.inference) are rolled back to the same level as the shards of the index.
+/// With round-robin shard assignment, the gaps in the document ids of shards that were rolled back further than others are filled with deleted placeholder documents.
+/// Indices created before checksums were introduced can only be repaired for levels committed afterwards, earlier levels are kept.
+/// * `index_path`: path of the index, which must not be open.
+pub async fn repair_index(index_path: &Path) -> Result<RepairResult, String> {
+    let mut index_paths = vec![index_path.to_path_buf()];
+    if let Ok(entries) = fs::read_dir(index_path.join(VECTOR_FIELDS_PATH)) {
+        let mut vector_field_paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.join("shards").is_dir())
+            .collect();
+        vector_field_paths.sort();
+        index_paths.extend(vector_field_paths);
+    }
+
+    let shard_count = shard_count(index_path);
+    if shard_count == 0 {
+        return Err(format!("no shards found in {}", index_path.display()));
+    }
+
+    // shards of vector fields hold the same documents as the shards of the index, they are rolled back to the same level
+    let mut repaired_level_counts = vec![usize::MAX; shard_count];
+    for path in index_paths.iter() {
+        for (shard_id, repaired_level_count) in repaired_level_counts.iter_mut().enumerate() {
+            let shard_path = path.join("shards").join(shard_id.to_string());
+            *repaired_level_count =
+                (*repaired_level_count).min(consistent_level_count(&shard_path));
+        }
+    }
+
+    let mut shard_results = Vec::new();
+    for path in index_paths.iter() {
+        let schema: Vec<SchemaField> = fs::read(path.join(SCHEMA_FILENAME))
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        let facets_size_sum = schema
+            .iter()
+            .filter(|schema_field| schema_field.facet)
+            .map(|schema_field| facet_size(&schema_field.field_type))
+            .sum();
+        for (shard_id, repaired_level_count) in repaired_level_counts.iter().enumerate() {
+            let shard_path = path.join("shards").join(shard_id.to_string());
+            shard_results.push(truncate_shard(
+                &shard_path,
+                *repaired_level_count,
+                facets_size_sum,
+            )?);
+        }
+    }
+
+    let index_arc = open_index(index_path).await?;
+
+    let placeholder_doc_ids = fill_doc_id_gaps(&index_arc).await;
+
+    Ok(RepairResult {
+        index_arc,
+        shard_results,
+        placeholder_doc_ids,
+    })
+}
+
+/// Fills the gaps in the round-robin document ids of shards with fewer documents than others with deleted placeholder documents,
+/// so that new documents are assigned to the shards in turn again. Indices with a routing field (SchemaField.routing) are not changed.
+/// Returns the document ids of the placeholder documents.
+pub(crate) async fn fill_doc_id_gaps(index_arc: &IndexArc) -> Vec<u64> {
+    let mut placeholder_doc_ids = Vec::new();
+    let index_ref = index_arc.read().await;
+    if index_ref.routing_field.is_none() {
+        let shard_number = index_ref.shard_number;
+        let mut shard_doc_counts = Vec::new();
+        for shard in index_ref.shard_vec.iter() {
+            shard_doc_counts.push(shard.read().await.indexed_doc_count);
+        }
+        let doc_id_end = shard_doc_counts
+            .iter()
+            .enumerate()
+            .filter(|(_, doc_count)| **doc_count > 0)
+            .map(|(shard_id, doc_count)| (doc_count - 1) * shard_number + shard_id + 1)
+            .max()
+            .unwrap_or(0);
+
+        for (shard_id, shard) in index_ref.shard_vec.iter().enumerate() {
+            for doc_id_local in shard_doc_counts[shard_id]
+                ..(doc_id_end + shard_number - 1 - shard_id) / shard_number
+            {
+                let doc_id = doc_id_local * shard_number + shard_id;
+                for vector_field_index in index_ref.vector_field_indices.iter() {
+                    vector_field_index
+                        .index_document(&Document::new(), doc_id)
+                        .await;
+                }
+                shard
+                    .index_document_shard(Document::new(), FileType::None, doc_id)
+                    .await;
+                placeholder_doc_ids.push(doc_id as u64);
+            }
+        }
+        *index_ref.docid_global.write().await = doc_id_end;
+    }
+    drop(index_ref);
+
+    if !placeholder_doc_ids.is_empty() {
+        index_arc.commit().await;
+        index_arc
+            .delete_documents(placeholder_doc_ids.clone())
+            .await;
+    }
+
+    placeholder_doc_ids
+}
+
+/// Number of shard directories of an index.
+fn shard_count(index_path: &Path) -> usize {
+    (0..)
+        .take_while(|shard_id: &usize| {
+            index_path
+                .join("shards")
+                .join(shard_id.to_string())
+                .is_dir()
+        })
+        .count()
+}
+
+/// Writes the level records of a closed shard to checksum.bin, replacing existing records.
+pub(crate) fn write_level_checksums(
+    shard_path: &Path,
+    level_checksums: &[LevelChecksum],
+) -> Result<(), String> {
+    let mut bytes = Vec::with_capacity(level_checksums.len() * LEVEL_CHECKSUM_SIZE);
+    for level_checksum in level_checksums.iter() {
+        bytes.extend_from_slice(&level_checksum.to_bytes());
+    }
+    fs::write(shard_path.join(CHECKSUM_FILENAME), bytes).map_err(|e| e.to_string())
+}
+
+/// Reads the level checksum records of a shard and verifies the checksums of index.bin and docstore.bin.
+/// Returns the records, the number of consistent levels from the start, and the inconsistencies found.
+fn verify_level_checksums(shard_path: &Path) -> (Vec<LevelChecksum>, usize, Vec<String>) {
+    let mut errors = Vec::new();
+    let bytes = fs::read(shard_path.join(CHECKSUM_FILENAME)).unwrap_or_default();
+    let level_checksums: Vec<LevelChecksum> = bytes
+        .chunks_exact(LEVEL_CHECKSUM_SIZE)
+        .map(LevelChecksum::from_bytes)
+        .collect();
+    if !bytes.len().is_multiple_of(LEVEL_CHECKSUM_SIZE) {
+        errors.push(format!(
+            "{} has an incomplete level record",
+            CHECKSUM_FILENAME
+        ));
+    }
+
+    let index_path = shard_path.join(INDEX_FILENAME);
+    let docstore_path = shard_path.join(DOCSTORE_FILENAME);
+    let index_mmap = map_file(&index_path);
+    let docstore_mmap = map_file(&docstore_path);
+    let mut consistent_level_count = usize::MAX;
+    for (level_id, level_checksum) in level_checksums.iter().enumerate() {
+        if !level_checksum.is_recorded() {
+            continue;
+        }
+
+        let error = if index_mmap
+            .as_ref()
+            .and_then(|index_mmap| index_level_checksum(index_mmap, level_checksum))
+            != Some(level_checksum.index_checksum)
+        {
+            Some(INDEX_FILENAME)
+        } else if docstore_level_checksum(
+            docstore_mmap.as_deref().unwrap_or_default(),
+            level_checksum,
+        ) != Some(level_checksum.docstore_checksum)
+        {
+            Some(DOCSTORE_FILENAME)
+        } else {
+            None
+        };
+
+        if let Some(filename) = error {
+            errors.push(format!("{} level {} checksum mismatch", filename, level_id));
+            consistent_level_count = consistent_level_count.min(level_id);
+        }
+    }
+
+    if let Some(last_level_checksum) = level_checksums.last()
+        && last_level_checksum.is_recorded()
+    {
+        for (path, level_end) in [
+            (&index_path, last_level_checksum.index_end),
+            (&docstore_path, last_level_checksum.docstore_end),
+        ] {
+            let file_size = fs::metadata(path).map_or(0, |metadata| metadata.len());
+            if file_size > level_end {
+                errors.push(format!(
+                    "{} has {} bytes after the last checksummed level {}",
+                    path.file_name().unwrap_or_default().to_string_lossy(),
+                    file_size - level_end,
+                    level_checksums.len() - 1
+                ));
+            }
+        }
+    }
+
+    (level_checksums, consistent_level_count, errors)
+}
+
+/// Number of levels of a closed shard that a repair keeps: levels with matching checksums and complete vector sections.
+/// usize::MAX for shards without checksum records, which are kept unchanged.
+fn consistent_level_count(shard_path: &Path) -> usize {
+    let (level_checksums, checksum_consistent_level_count, _) = verify_level_checksums(shard_path);
+    if level_checksums.is_empty() {
+        return usize::MAX;
+    }
+    let mut consistent_level_count = checksum_consistent_level_count.min(level_checksums.len());
+
+    let vector_size = level_checksums
+        .last()
+        .map_or(0, |level_checksum| level_checksum.vector_size as usize);
+    if vector_size > 0
+        && let Some(vector_mmap) = map_file(&shard_path.join(VECTOR_FILENAME))
+        && !vector_mmap.is_empty()
+    {
+        let (section_ends, _) =
+            vector_section_ends(&vector_mmap, vector_size, consistent_level_count);
+        consistent_level_count = consistent_level_count.min(section_ends.len());
+    }
+
+    consistent_level_count
+}
+
+/// Truncates the files of a closed shard to the first level_count levels.
+/// Shards without checksum records are kept unchanged.
+/// * `facets_size_sum`: size of the facet values of a document in facet.bin, 0 if the schema has no facet fields.
+fn truncate_shard(
+    shard_path: &Path,
+    level_count: usize,
+    facets_size_sum: usize,
+) -> Result<ShardRepairResult, String> {
+    let (level_checksums, _, _) = verify_level_checksums(shard_path);
+    let mut shard_repair_result = ShardRepairResult {
+        path: shard_path.to_string_lossy().to_string(),
+        level_count: level_checksums.len(),
+        repaired_level_count: level_checksums.len(),
+        removed_doc_count: 0,
+    };
+    if level_checksums.is_empty() {
+        return Ok(shard_repair_result);
+    }
+    let level_count = level_count.min(level_checksums.len());
+
+    let (index_end, docstore_end, doc_count) = match level_count.checked_sub(1) {
+        Some(last_level_id) => (
+            level_checksums[last_level_id].index_end,
+            level_checksums[last_level_id].docstore_end,
+            level_checksums[last_level_id].indexed_doc_count as usize,
+        ),
+        None => (INDEX_HEADER_SIZE, 0, 0),
+    };
+    if level_count > 0 && !level_checksums[level_count - 1].is_recorded() {
+        return Ok(shard_repair_result);
+    }
+
+    let vector_size = level_checksums
+        .last()
+        .map_or(0, |level_checksum| level_checksum.vector_size as usize);
+    let vector_end = map_file(&shard_path.join(VECTOR_FILENAME)).and_then(|vector_mmap| {
+        (vector_size > 0 && !vector_mmap.is_empty()).then(|| {
+            vector_section_ends(&vector_mmap, vector_size, level_count)
+                .0
+                .last()
+                .copied()
+                .unwrap_or(0)
+        })
+    });
+
+    truncate_file(&shard_path.join(INDEX_FILENAME), index_end)?;
+    truncate_file(&shard_path.join(DOCSTORE_FILENAME), docstore_end)?;
+    if let Some(vector_end) = vector_end {
+        truncate_file(&shard_path.join(VECTOR_FILENAME), vector_end as u64)?;
+    }
+    for (filename, header_size, level_size) in LEVEL_FILES {
+        if let Some(level_mmap) = map_file(&shard_path.join(filename)) {
+            let (block_ends, _) = level_block_ends(&level_mmap, header_size, level_size);
+            let level_end = block_ends
+                .get(level_count.min(block_ends.len()).wrapping_sub(1))
+                .copied()
+                .unwrap_or(0);
+            drop(level_mmap);
+            truncate_file(&shard_path.join(filename), level_end as u64)?;
+        }
+    }
+    truncate_file(
+        &shard_path.join(CHECKSUM_FILENAME),
+        (level_count * LEVEL_CHECKSUM_SIZE) as u64,
+    )?;
+    if facets_size_sum > 0 && shard_path.join(FACET_FILENAME).exists() {
+        // facet.bin holds the levels and the values of the next level: the values of the rolled back documents are cleared
+        let facet_level_size = (facets_size_sum * ROARING_BLOCK_SIZE) as u64;
+        truncate_file(
+            &shard_path.join(FACET_FILENAME),
+            facet_level_size * level_count as u64,
+        )?;
+        File::options()
+            .write(true)
+            .open(shard_path.join(FACET_FILENAME))
+            .and_then(|file| file.set_len(facet_level_size * (level_count as u64 + 1)))
+            .map_err(|e| e.to_string())?;
+    }
+
+    filter_records(&shard_path.join(DELETE_FILENAME), |bytes| {
+        (bytes.len() >= 8).then(|| (8, read_u64(bytes, 0) < doc_count as u64))
+    })?;
+    filter_records(&shard_path.join(MERGED_VECTOR_LEVELS_FILENAME), |bytes| {
+        (bytes.len() >= 12).then(|| {
+            let vector_count = read_u32(bytes, 8) as usize;
+            (
+                12 + vector_count * 4,
+                (read_u32(bytes, 0) as usize) < level_count,
+            )
+        })
+    })?;
+
+    shard_repair_result.repaired_level_count = level_count;
+    shard_repair_result.removed_doc_count = level_checksums
+        .last()
+        .map_or(0, |level_checksum| {
+            level_checksum.indexed_doc_count as usize
+        })
+        .saturating_sub(doc_count);
+    Ok(shard_repair_result)
+}
+
+/// Ends of the complete level sections of vector.bin (cluster count, cluster headers and vector records), for up to level_count levels.
+/// Returns true if the file ends after the last section.
+pub(crate) fn vector_section_ends(
+    bytes: &[u8],
+    vector_size: usize,
+    level_count: usize,
+) -> (Vec<usize>, bool) {
+    let mut section_ends = Vec::new();
+    let mut offset = 0;
+    while section_ends.len() < level_count && offset + 4 <= bytes.len() {
+        let cluster_number = read_u32(bytes, offset) as usize;
+        let mut section_end = offset + 4 + cluster_number * 4;
+        if section_end > bytes.len() {
+            break;
+        }
+        let mut vector_count = 0;
+        for cluster_id in 0..cluster_number {
+            vector_count += read_u32(bytes, offset + 4 + cluster_id * 4) as usize;
+        }
+        section_end += vector_count * vector_size;
+        if section_end > bytes.len() {
+            break;
+        }
+        section_ends.push(section_end);
+        offset = section_end;
+    }
+    (section_ends, offset == bytes.len())
+}
+
+/// Ends of the complete level blocks of a level file. Returns true if the file ends after the last block.
+pub(crate) fn level_block_ends(
+    bytes: &[u8],
+    header_size: usize,
+    level_size: LevelSize,
+) -> (Vec<usize>, bool) {
+    let mut block_ends = Vec::new();
+    let mut offset = 0;
+    while offset + header_size <= bytes.len() {
+        let block_end = offset + level_size(&bytes[offset..]);
+        if block_end > bytes.len() {
+            break;
+        }
+        block_ends.push(block_end);
+        offset = block_end;
+    }
+    (block_ends, offset == bytes.len())
+}
+
+/// Checksum of the index.bin level of a record, in the order of LevelHasher. None if the level is truncated.
+fn index_level_checksum(bytes: &[u8], level_checksum: &LevelChecksum) -> Option<u64> {
+    let [start, head_start, head_end, end] = [
+        level_checksum.index_start,
+        level_checksum.index_head_start,
+        level_checksum.index_head_end,
+        level_checksum.index_end,
+    ]
+    .map(|position| position as usize);
+    if start > head_start || head_start > head_end || head_end > end || end > bytes.len() {
+        return None;
+    }
+
+    let mut level_hasher = LevelHasher::default();
+    level_hasher.update_index(&bytes[start..head_start]);
+    level_hasher.update_index(&bytes[head_end..end]);
+    level_hasher.set_index_head(head_start as u64, &bytes[head_start..head_end]);
+    Some(level_hasher.index_checksum())
+}
+
+/// Checksum of the docstore.bin level of a record, in the order of LevelHasher. None if the level is truncated.
+fn docstore_level_checksum(bytes: &[u8], level_checksum: &LevelChecksum) -> Option<u64> {
+    let (start, end) = (
+        level_checksum.docstore_start as usize,
+        level_checksum.docstore_end as usize,
+    );
+    if start == end {
+        return Some(0);
+    }
+    if start + 4 + ROARING_BLOCK_SIZE * 4 > end || end > bytes.len() {
+        return None;
+    }
+
+    let doc_count = match level_checksum.indexed_doc_count as usize {
+        0 => 0,
+        indexed_doc_count => ((indexed_doc_count - 1) % ROARING_BLOCK_SIZE) + 1,
+    };
+    let mut level_hasher = LevelHasher::default();
+    level_hasher.hash_docstore_level(bytes, start, end, doc_count);
+    Some(level_hasher.docstore_checksum(read_u32(bytes, start)))
+}
+
+/// SHA-256 truncated to u64.
+fn truncated_checksum(hasher: Sha256) -> u64 {
+    let hash = hasher.finalize();
+    u64::from_le_bytes(hash[..8].try_into().unwrap())
+}
+
+/// Memory maps a file for reading, None if the file doesn't exist.
+pub(crate) fn map_file(path: &Path) -> Option<Mmap> {
+    let file = File::open(path).ok()?;
+    unsafe { Mmap::map(&file).ok() }
+}
+
+fn truncate_file(path: &Path, size: u64) -> Result<(), String> {
+    let file = File::options()
+        .write(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    if file.metadata().map_err(|e| e.to_string())?.len() > size {
+        file.set_len(size).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Rewrites a file of variable size records, keeping the records for which record returns true.
+/// record returns the size of the record at the start of bytes and whether to keep it, None for an incomplete record, which is removed with the rest of the file.
+fn filter_records(
+    path: &Path,
+    record: impl Fn(&[u8]) -> Option<(usize, bool)>,
+) -> Result<(), String> {
+    let Ok(bytes) = fs::read(path) else {
+        return Ok(());
+    };
+
+    let mut filtered = Vec::with_capacity(bytes.len());
+    let mut offset = 0;
+    while let Some((size, keep)) = record(&bytes[offset..]) {
+        if offset + size > bytes.len() {
+            break;
+        }
+        if keep {
+            filtered.extend_from_slice(&bytes[offset..offset + size]);
+        }
+        offset += size;
+    }
+
+    if filtered.len() != bytes.len() {
+        fs::write(path, filtered).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
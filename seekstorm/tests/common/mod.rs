@@ -0,0 +1,130 @@
+//! Shared fixtures of the integration tests: test index meta objects, deterministic test vectors, index creation and document indexing.
+
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+
+use seekstorm::{
+    commit::Commit,
+    index::{
+        AccessType, Clustering, Document, DocumentCompression, FrequentwordType, IndexArc,
+        IndexDocuments, IndexMetaObject, LexicalSimilarity, NgramSet, StemmerType, StopwordType,
+        TokenizerType, create_index,
+    },
+    vector::{Inference, Precision, Quantization},
+    vector_similarity::VectorSimilarity,
+};
+
+/// Index meta object for tests: BM25F, single terms only, no stemming, stop words or frequent words.
+pub fn test_meta() -> IndexMetaObject {
+    IndexMetaObject {
+        id: 0,
+        name: "test_index".into(),
+        lexical_similarity: LexicalSimilarity::Bm25f,
+        tokenizer: TokenizerType::UnicodeAlphanumeric,
+        stemmer: StemmerType::None,
+        stop_words: StopwordType::None,
+        frequent_words: FrequentwordType::None,
+        ngram_indexing: NgramSet::SingleTerm as u8,
+        document_compression: DocumentCompression::Snappy,
+        access_type: AccessType::Mmap,
+        spelling_correction: None,
+        query_completion: None,
+        clustering: Clustering::None,
+        inference: Inference::None,
+    }
+}
+
+/// Index meta object for vector tests: external F32 vectors with the given dimensions, similarity, quantization and clustering.
+/// The dimensions must be a multiple of 8, the block size of the AVX2 similarity kernels.
+pub fn test_vector_meta(
+    dimensions: usize,
+    similarity: VectorSimilarity,
+    quantization: Quantization,
+    clustering: Clustering,
+) -> IndexMetaObject {
+    IndexMetaObject {
+        clustering,
+        inference: Inference::External {
+            dimensions,
+            precision: Precision::F32,
+            quantization,
+            similarity,
+        },
+        ..test_meta()
+    }
+}
+
+/// Deterministic pseudo-random vectors with components in [-1.0, 1.0).
+pub fn test_vectors(count: usize, dimensions: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut state = seed
+        .wrapping_mul(6364136223846793005)
+        .wrapping_add(1442695040888963407);
+    (0..count)
+        .map(|_| {
+            (0..dimensions)
+                .map(|_| {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    ((state >> 40) as f32 / (1u64 << 23) as f32) - 1.0
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Path of a test index below the temp dir, e.g. to reopen it.
+pub fn test_index_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join("seekstorm_test").join(name)
+}
+
+/// Path of a test index below the temp dir, removed before it is returned.
+pub fn test_index_path(name: &str) -> PathBuf {
+    let index_path = test_index_dir(name);
+    let _ = std::fs::remove_dir_all(&index_path);
+    index_path
+}
+
+/// Creates a test index with the given meta, schema (JSON) and number of shards.
+pub async fn create_test_index_meta(
+    name: &str,
+    meta: IndexMetaObject,
+    schema_json: &str,
+    shard_number: usize,
+) -> IndexArc {
+    let schema = serde_json::from_str(schema_json).unwrap();
+    create_index(
+        &test_index_path(name),
+        meta,
+        &schema,
+        &Vec::new(),
+        11,
+        true,
+        Some(shard_number),
+    )
+    .await
+    .unwrap()
+}
+
+/// Creates a test index with test_meta(), the given schema (JSON) and number of shards.
+pub async fn create_test_index(name: &str, schema_json: &str, shard_number: usize) -> IndexArc {
+    create_test_index_meta(name, test_meta(), schema_json, shard_number).await
+}
+
+/// Indexes the documents (JSON array) and commits them.
+pub async fn index_test_documents(index_arc: &IndexArc, documents_json: &str) {
+    let documents: Vec<Document> = serde_json::from_str(documents_json).unwrap();
+    index_arc.index_documents(documents).await;
+    index_arc.commit().await;
+}
+
+/// Indexes one document per vector into the field "vector" and commits them.
+pub async fn index_test_vectors(index_arc: &IndexArc, vectors: &[Vec<f32>]) {
+    let documents: Vec<Document> = vectors
+        .iter()
+        .map(|vector| Document::from([("vector".to_string(), serde_json::json!(vector))]))
+        .collect();
+    index_arc.index_documents(documents).await;
+    index_arc.commit().await;
+}
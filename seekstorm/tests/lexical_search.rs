@@ -0,0 +1,993 @@
+//! Lexical search tests: facets, significant terms, range queries in the query string, geo grids, routing,
+//! query expansion and reranking. Every test creates its own index below the temp dir.
+//! Use: cargo test --test lexical_search
+
+mod common;
+
+use common::{create_test_index, index_test_documents, test_index_dir};
+use seekstorm::commit::Commit;
+use seekstorm::index::{Close, Document, FileType, IndexArc, IndexDocument, open_index};
+use seekstorm::query_expansion::{QueryExpansion, SearchExpanded};
+use seekstorm::rerank::{Rerank, Reranker};
+use seekstorm::search::{
+    FacetFilter, FacetValueSort, GeoGridType, QueryFacet, QueryRewriting, QueryType, ResultType,
+    Search, SearchMode, SearchRouted,
+};
+use seekstorm::significant_terms::{SignificanceHeuristic, SignificantTerm};
+use std::collections::HashSet;
+
+/// Lexical search: doc_id and score of the results.
+async fn search_lexical(
+    index_arc: &IndexArc,
+    query: &str,
+    query_type: QueryType,
+    length: usize,
+) -> Vec<(usize, f32)> {
+    index_arc
+        .search(
+            query.into(),
+            None,
+            query_type,
+            SearchMode::Lexical,
+            false,
+            0,
+            length,
+            ResultType::Topk,
+            false,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            QueryRewriting::SearchOnly,
+        )
+        .await
+        .results
+        .iter()
+        .map(|result| (result.doc_id, result.score))
+        .collect()
+}
+
+fn doc_ids(results: &[(usize, f32)]) -> Vec<usize> {
+    results.iter().map(|(doc_id, _)| *doc_id).collect()
+}
+
+const FACET_SCHEMA: &str = r#"
+[{"field":"title","field_type":"Text","store":true,"index_lexical":true},
+{"field":"color","field_type":"String16","store":true,"index_lexical":false,"facet":true}]"#;
+
+async fn search_color_facet(
+    index_arc: &IndexArc,
+    query: &str,
+    offset: u16,
+    length: u16,
+    sort: FacetValueSort,
+    min_count: usize,
+) -> Vec<(String, usize)> {
+    let result_object = index_arc
+        .search(
+            query.into(),
+            None,
+            QueryType::Intersection,
+            SearchMode::Lexical,
+            true,
+            0,
+            10,
+            ResultType::TopkCount,
+            false,
+            Vec::new(),
+            vec![QueryFacet::String16 {
+                field: "color".into(),
+                prefix: String::new(),
+                length,
+                offset,
+                sort,
+                min_count,
+            }],
+            Vec::new(),
+            Vec::new(),
+            QueryRewriting::SearchOnly,
+        )
+        .await;
+    result_object
+        .facets
+        .get("color")
+        .cloned()
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+/// facet paging with offset, sort order and minimum count, for query facets and index facets
+async fn test_01_facet_paging() {
+    // color counts: a 5, b 4, c 3, d 2, e 1
+    let documents = [("a", 5), ("b", 4), ("c", 3), ("d", 2), ("e", 1)]
+        .iter()
+        .flat_map(|(color, count)| {
+            std::iter::repeat_n(format!(r#"{{"title":"item","color":"{color}"}}"#), *count)
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    for shard_number in [1, 3] {
+        let index_arc = create_test_index(
+            &format!("facet_paging_{shard_number}"),
+            FACET_SCHEMA,
+            shard_number,
+        )
+        .await;
+        index_test_documents(&index_arc, &format!("[{documents}]")).await;
+
+        // query facets (query) and index facets (empty query)
+        for query in ["item", ""] {
+            assert_eq!(
+                search_color_facet(&index_arc, query, 1, 2, FacetValueSort::CountDescending, 0)
+                    .await,
+                vec![("b".to_string(), 4), ("c".to_string(), 3)]
+            );
+            assert_eq!(
+                search_color_facet(&index_arc, query, 0, 2, FacetValueSort::CountAscending, 2)
+                    .await,
+                vec![("d".to_string(), 2), ("c".to_string(), 3)]
+            );
+            assert_eq!(
+                search_color_facet(&index_arc, query, 2, 2, FacetValueSort::ValueDescending, 0)
+                    .await,
+                vec![("c".to_string(), 3), ("b".to_string(), 4)]
+            );
+            assert_eq!(
+                search_color_facet(&index_arc, query, 4, 2, FacetValueSort::ValueAscending, 0)
+                    .await,
+                vec![("e".to_string(), 1)]
+            );
+        }
+        index_arc.close().await;
+    }
+}
+
+fn significant_term_scores(
+    significant_terms: &[SignificantTerm],
+) -> Vec<(&str, usize, usize, f64)> {
+    significant_terms
+        .iter()
+        .map(|term| {
+            (
+                term.term.as_str(),
+                term.foreground_count,
+                term.background_count,
+                (term.score * 1e6).round() / 1e6,
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+/// significant terms of facet fields and text fields: foreground and background counts and scores
+async fn test_02_significant_terms() {
+    const SCHEMA: &str = r#"
+    [{"field":"title","field_type":"Text","store":true,"index_lexical":true},
+    {"field":"category","field_type":"String16","store":true,"index_lexical":false,"facet":true},
+    {"field":"tags","field_type":"StringSet16","store":true,"index_lexical":false,"facet":true}]"#;
+
+    // foreground (query "rust"): 4 documents, background: 10 documents
+    // category: a 3/4, b 1/5
+    // tags: x 3/3, y 2/4, z 1/3, w 0/2
+    // title: rust 4/4, fast 3/3, safe 2/4, one 1/2, go 0/6
+    const DOCUMENTS: &str = r#"
+    [{"title":"rust fast one","category":"a","tags":["x","y"]},
+    {"title":"rust fast","category":"a","tags":["x"]},
+    {"title":"rust fast safe","category":"a","tags":["x","z"]},
+    {"title":"rust safe","category":"b","tags":["y"]},
+    {"title":"go one","category":"a","tags":["y"]},
+    {"title":"go safe","category":"b","tags":["y"]},
+    {"title":"go safe","category":"b","tags":["z"]},
+    {"title":"go","category":"b","tags":["z"]},
+    {"title":"go","category":"b","tags":["w"]},
+    {"title":"go","category":"c","tags":["w"]}]"#;
+
+    let title_significant_terms = async |index_arc: &IndexArc| {
+        index_arc
+            .search(
+                "rust".into(),
+                None,
+                QueryType::Intersection,
+                SearchMode::Lexical,
+                false,
+                0,
+                10,
+                ResultType::Topk,
+                false,
+                Vec::new(),
+                vec![QueryFacet::SignificantTerms {
+                    field: "title".into(),
+                    length: 10,
+                    min_count: 2,
+                    heuristic: SignificanceHeuristic::Jlh,
+                }],
+                Vec::new(),
+                Vec::new(),
+                QueryRewriting::SearchOnly,
+            )
+            .await
+            .significant_terms
+            .remove("title")
+            .unwrap()
+    };
+
+    for shard_number in [1, 2] {
+        let index_arc = create_test_index(
+            &format!("significant_terms_{shard_number}"),
+            SCHEMA,
+            shard_number,
+        )
+        .await;
+        index_test_documents(&index_arc, DOCUMENTS).await;
+
+        let result_object = index_arc
+            .search(
+                "rust".into(),
+                None,
+                QueryType::Intersection,
+                SearchMode::Lexical,
+                false,
+                0,
+                10,
+                ResultType::TopkCount,
+                false,
+                Vec::new(),
+                vec![
+                    QueryFacet::SignificantTerms {
+                        field: "category".into(),
+                        length: 10,
+                        min_count: 0,
+                        heuristic: SignificanceHeuristic::ChiSquare,
+                    },
+                    QueryFacet::SignificantTerms {
+                        field: "tags".into(),
+                        length: 10,
+                        min_count: 2,
+                        heuristic: SignificanceHeuristic::Jlh,
+                    },
+                ],
+                Vec::new(),
+                Vec::new(),
+                QueryRewriting::SearchOnly,
+            )
+            .await;
+        assert_eq!(result_object.result_count_total, 4);
+        assert!(result_object.facets.is_empty());
+
+        // chi-square: n=10, n11=3, n10=1, n01=1, n00=5: 10*(3*5-1*1)^2/(4*4*6*6)
+        assert_eq!(
+            significant_term_scores(&result_object.significant_terms["category"]),
+            vec![("a", 3, 4, 3.402778)]
+        );
+        // jlh: (0.75-0.3)*(0.75/0.3), (0.5-0.4)*(0.5/0.4); z is under-represented
+        assert_eq!(
+            significant_term_scores(&result_object.significant_terms["tags"]),
+            vec![("x", 3, 3, 1.125), ("y", 2, 4, 0.125)]
+        );
+
+        // text field: the foreground are the returned results, the background counts are the posting counts
+        // jlh: (1.0-0.4)*(1.0/0.4), (0.75-0.3)*(0.75/0.3), (0.5-0.4)*(0.5/0.4); one is below min_count
+        assert_eq!(
+            significant_term_scores(&title_significant_terms(&index_arc).await),
+            vec![
+                ("rust", 4, 4, 1.5),
+                ("fast", 3, 3, 1.125),
+                ("safe", 2, 4, 0.125)
+            ]
+        );
+
+        // the posting counts of uncommitted documents are part of the background: 11 documents
+        // jlh: (1.0-4/11)*(11/4), (0.75-4/11)*(0.75*11/4), (0.5-5/11)*(0.5*11/5)
+        index_arc
+            .index_document(
+                Document::from([("title".to_string(), serde_json::json!("go fast safe"))]),
+                FileType::None,
+            )
+            .await;
+        assert_eq!(
+            significant_term_scores(&title_significant_terms(&index_arc).await),
+            vec![
+                ("rust", 4, 4, 1.75),
+                ("fast", 3, 4, 0.796875),
+                ("safe", 2, 5, 0.05)
+            ]
+        );
+        index_arc.close().await;
+    }
+}
+
+#[tokio::test]
+/// range clauses in the query string match the maximum and minimum value of the field type, explicit facet filters are half-open
+async fn test_03_query_range_type_maximum() {
+    let schema = r#"
+    [{"field":"title","field_type":"Text","store":true,"index_lexical":true},
+    {"field":"level","field_type":"U8","store":true,"index_lexical":false,"facet":true}]"#;
+    let index_arc = create_test_index("query_range_type_maximum", schema, 1).await;
+    index_test_documents(
+        &index_arc,
+        r#"[{"title":"item","level":254},{"title":"item","level":255},{"title":"item","level":0}]"#,
+    )
+    .await;
+
+    for (query, count) in [
+        ("item level:>=255", 1),
+        ("item level:>=254", 2),
+        ("item level:<=0", 1),
+        ("item level:>255", 0),
+    ] {
+        let result_object = index_arc
+            .search(
+                query.into(),
+                None,
+                QueryType::Intersection,
+                SearchMode::Lexical,
+                false,
+                0,
+                10,
+                ResultType::Count,
+                false,
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                QueryRewriting::SearchOnly,
+            )
+            .await;
+        assert_eq!(result_object.result_count_total, count, "{query}");
+    }
+
+    // explicit facet filters are half-open: the range end is excluded, also at the maximum value of the type
+    for (query, filter, count) in [
+        ("item", 250..255, 1),
+        ("item", 0..255, 2),
+        ("item level:>=200", 0..255, 1),
+    ] {
+        let result_object = index_arc
+            .search(
+                query.into(),
+                None,
+                QueryType::Intersection,
+                SearchMode::Lexical,
+                false,
+                0,
+                10,
+                ResultType::Count,
+                false,
+                Vec::new(),
+                Vec::new(),
+                vec![FacetFilter::U8 {
+                    field: "level".into(),
+                    filter: filter.clone(),
+                }],
+                Vec::new(),
+                QueryRewriting::SearchOnly,
+            )
+            .await;
+        assert_eq!(
+            result_object.result_count_total, count,
+            "{query} {filter:?}"
+        );
+    }
+    index_arc.close().await;
+}
+
+const GEO_SCHEMA: &str = r#"
+[{"field":"title","field_type":"Text","store":true,"index_lexical":true},
+{"field":"location","field_type":"Point","store":true,"index_lexical":false,"facet":true}]"#;
+
+const GEO_DOCUMENTS: &str = r#"
+[{"title":"white house","location":[38.8977,-77.0365]},
+{"title":"capitol","location":[38.8899,-77.0091]},
+{"title":"lincoln memorial","location":[38.8893,-77.0502]},
+{"title":"inner harbor","location":[39.2854,-76.6122]},
+{"title":"eiffel tower","location":[48.8584,2.2945]}]"#;
+
+#[tokio::test]
+/// an empty query with a bounding box filter returns the geo grid cells of the viewport
+async fn test_04_empty_query_returns_geo_grids() {
+    for shard_number in [1, 2] {
+        let index_arc = create_test_index(
+            &format!("geo_grid_{shard_number}"),
+            GEO_SCHEMA,
+            shard_number,
+        )
+        .await;
+        index_test_documents(&index_arc, GEO_DOCUMENTS).await;
+
+        let result_object = index_arc
+            .search(
+                String::new(),
+                None,
+                QueryType::Intersection,
+                SearchMode::Lexical,
+                true,
+                0,
+                10,
+                ResultType::TopkCount,
+                false,
+                Vec::new(),
+                vec![QueryFacet::GeoGrid {
+                    field: "location".into(),
+                    grid_type: GeoGridType::Geohash,
+                    precision: 4,
+                    length: 10,
+                }],
+                vec![FacetFilter::PointBoundingBox {
+                    field: "location".into(),
+                    filter: (vec![38.0, -78.0], vec![39.5, -76.5]),
+                }],
+                Vec::new(),
+                QueryRewriting::SearchOnly,
+            )
+            .await;
+        assert_eq!(result_object.result_count_total, 4);
+
+        let cells = &result_object.geo_grids["location"];
+        assert_eq!(cells.len(), 2);
+        assert_eq!(cells[0].key, "dqcj");
+        assert_eq!(cells[0].count, 3);
+        assert!((cells[0].centroid[0] - 38.8923).abs() < 1e-4);
+        assert_eq!(cells[1].key, "dqcx");
+        assert_eq!(cells[1].count, 1);
+        index_arc.close().await;
+    }
+}
+
+/// Expected routed document ids: docid_local * shard_number + shard_id, documents without tenant go to the shard with the fewest documents.
+fn routed_doc_ids(
+    tenants: &[Option<&str>],
+    tenant_shard: &dyn Fn(&str) -> usize,
+    shard_docids: &mut [usize],
+) -> Vec<usize> {
+    let shard_number = shard_docids.len();
+    tenants
+        .iter()
+        .map(|tenant| {
+            let shard_id = match tenant {
+                Some(tenant) => tenant_shard(tenant),
+                None => (0..shard_number)
+                    .min_by_key(|shard_id| shard_docids[*shard_id])
+                    .unwrap(),
+            };
+            shard_docids[shard_id] += 1;
+            (shard_docids[shard_id] - 1) * shard_number + shard_id
+        })
+        .collect()
+}
+
+/// Document with the title "common doc{i}" and the tenant, if any.
+fn tenant_document(i: usize, tenant: Option<&str>) -> Document {
+    let mut document = Document::from([(
+        "title".to_string(),
+        serde_json::json!(format!("common doc{i}")),
+    )]);
+    if let Some(tenant) = tenant {
+        document.insert("tenant".to_string(), serde_json::json!(tenant));
+    }
+    document
+}
+
+/// Document ids of the documents "common doc{i}".
+async fn tenant_doc_ids(index_arc: &IndexArc, range: std::ops::Range<usize>) -> Vec<usize> {
+    let mut doc_ids = Vec::new();
+    for i in range {
+        let results =
+            search_lexical(index_arc, &format!("doc{i}"), QueryType::Intersection, 10).await;
+        assert_eq!(results.len(), 1, "doc{i}");
+        doc_ids.push(results[0].0);
+    }
+    doc_ids
+}
+
+#[tokio::test]
+/// routed document ids, routed search, and query expansion with routing
+async fn test_05_routed_doc_ids_and_search() {
+    const ROUTING_SCHEMA: &str = r#"
+    [{"field":"title","field_type":"Text","store":true,"index_lexical":true},
+    {"field":"tenant","field_type":"String16","store":true,"index_lexical":false,"facet":true,"routing":true}]"#;
+
+    let shard_number = 3;
+    let index_arc = create_test_index("routing", ROUTING_SCHEMA, shard_number).await;
+    let tenants: Vec<Option<&str>> = (0..30)
+        .map(|i| match i % 5 {
+            0 | 1 => Some("a"),
+            2 => Some("b"),
+            3 => Some("c"),
+            _ => None,
+        })
+        .collect();
+    for (i, tenant) in tenants.iter().enumerate() {
+        index_arc
+            .index_document(tenant_document(i, *tenant), FileType::None)
+            .await;
+    }
+    index_arc.commit().await;
+    let doc_ids = tenant_doc_ids(&index_arc, 0..tenants.len()).await;
+
+    // all documents of a tenant are in the same shard
+    let tenant_shard = |tenant: &str| {
+        let i = tenants.iter().position(|t| *t == Some(tenant)).unwrap();
+        doc_ids[i] % shard_number
+    };
+    for (doc_id, tenant) in doc_ids.iter().zip(tenants.iter()) {
+        if let Some(tenant) = tenant {
+            assert_eq!(doc_id % shard_number, tenant_shard(tenant));
+        }
+        let stored = index_arc
+            .read()
+            .await
+            .get_document(*doc_id, false, &None, &HashSet::new(), &[])
+            .await
+            .unwrap();
+        assert_eq!(
+            stored.get("tenant").and_then(|value| value.as_str()),
+            *tenant
+        );
+    }
+    // the document ids are not contiguous
+    let mut shard_docids = vec![0; shard_number];
+    assert_eq!(
+        doc_ids,
+        routed_doc_ids(&tenants, &tenant_shard, &mut shard_docids)
+    );
+    assert_eq!(
+        index_arc.read().await.indexed_doc_count().await,
+        tenants.len()
+    );
+    assert!(*doc_ids.iter().max().unwrap() >= tenants.len());
+
+    let search_routed = |routing: &str, facet_filter: Vec<FacetFilter>| {
+        let index_arc = index_arc.clone();
+        let routing = routing.to_string();
+        async move {
+            let result_object = index_arc
+                .search_routed(
+                    "common".into(),
+                    None,
+                    QueryType::Intersection,
+                    SearchMode::Lexical,
+                    false,
+                    0,
+                    100,
+                    ResultType::TopkCount,
+                    false,
+                    Vec::new(),
+                    Vec::new(),
+                    facet_filter,
+                    Vec::new(),
+                    QueryRewriting::SearchOnly,
+                    &routing,
+                )
+                .await;
+            let mut doc_ids: Vec<usize> = result_object
+                .results
+                .iter()
+                .map(|result| result.doc_id)
+                .collect();
+            doc_ids.sort_unstable();
+            assert_eq!(result_object.result_count_total, doc_ids.len());
+            doc_ids
+        }
+    };
+
+    // the routed search returns the documents of the shard of the routing value only
+    let shard_a = tenant_shard("a");
+    let mut shard_a_doc_ids: Vec<usize> = doc_ids
+        .iter()
+        .copied()
+        .filter(|doc_id| doc_id % shard_number == shard_a)
+        .collect();
+    shard_a_doc_ids.sort_unstable();
+    assert_eq!(search_routed("a", Vec::new()).await, shard_a_doc_ids);
+    // with a facet filter for the routing field only those of the routing value
+    let mut tenant_a: Vec<usize> = doc_ids
+        .iter()
+        .zip(tenants.iter())
+        .filter(|(_, tenant)| **tenant == Some("a"))
+        .map(|(doc_id, _)| *doc_id)
+        .collect();
+    tenant_a.sort_unstable();
+    let tenant_filter = vec![FacetFilter::String16 {
+        field: "tenant".into(),
+        filter: vec!["a".into()],
+    }];
+    assert_eq!(search_routed("a", tenant_filter).await, tenant_a);
+
+    // without routing all shards are searched
+    let mut all_doc_ids = doc_ids.clone();
+    all_doc_ids.sort_unstable();
+    let mut results = doc_ids_of(&index_arc, "common").await;
+    results.sort_unstable();
+    assert_eq!(results, all_doc_ids);
+
+    // the routing hint applies to the initial retrieval and the re-run of query expansion
+    let result_object = index_arc
+        .search_expanded(
+            "common".into(),
+            None,
+            QueryType::Intersection,
+            SearchMode::Lexical,
+            false,
+            0,
+            100,
+            ResultType::TopkCount,
+            false,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            QueryRewriting::SearchOnly,
+            &QueryExpansion::default(),
+            Some("a"),
+        )
+        .await;
+    assert_ne!(result_object.query, "common");
+    assert!(!result_object.results.is_empty());
+    assert!(
+        result_object
+            .results
+            .iter()
+            .all(|result| result.doc_id % shard_number == shard_a)
+    );
+
+    // after reopening, the local document ids continue per shard
+    index_arc.close().await;
+    let index_arc = open_index(&test_index_dir("routing")).await.unwrap();
+    let more_tenants = [Some("b"), None, Some("a")];
+    let expected_doc_ids = routed_doc_ids(&more_tenants, &tenant_shard, &mut shard_docids);
+    for (i, tenant) in more_tenants.iter().enumerate() {
+        index_arc
+            .index_document(tenant_document(30 + i, *tenant), FileType::None)
+            .await;
+    }
+    index_arc.commit().await;
+    assert_eq!(
+        tenant_doc_ids(&index_arc, 30..30 + more_tenants.len()).await,
+        expected_doc_ids
+    );
+    index_arc.close().await;
+
+    // without routing field all shards are searched
+    let index_arc = create_test_index("routing_none", GEO_SCHEMA, shard_number).await;
+    index_test_documents(&index_arc, GEO_DOCUMENTS).await;
+    let result_object = index_arc
+        .search_routed(
+            String::new(),
+            None,
+            QueryType::Intersection,
+            SearchMode::Lexical,
+            true,
+            0,
+            10,
+            ResultType::TopkCount,
+            false,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            QueryRewriting::SearchOnly,
+            "a",
+        )
+        .await;
+    assert_eq!(result_object.result_count_total, 5);
+    index_arc.close().await;
+}
+
+async fn doc_ids_of(index_arc: &IndexArc, query: &str) -> Vec<usize> {
+    doc_ids(&search_lexical(index_arc, query, QueryType::Intersection, 100).await)
+}
+
+/// Max-normalized interpolation of the initial and feedback scores, in descending order.
+fn interpolate(
+    initial: &[(usize, f32)],
+    feedback: &[(usize, f32)],
+    weight: f32,
+) -> Vec<(usize, f32)> {
+    let max = |results: &[(usize, f32)]| {
+        results
+            .iter()
+            .map(|(_, score)| *score)
+            .fold(f32::MIN_POSITIVE, f32::max)
+    };
+    let (initial_max, feedback_max) = (max(initial), max(feedback));
+    let mut interpolated: Vec<(usize, f32)> = Vec::new();
+    for (doc_id, score) in initial.iter() {
+        interpolated.push((*doc_id, (1.0 - weight) * score / initial_max));
+    }
+    for (doc_id, score) in feedback.iter() {
+        let score = weight * score / feedback_max;
+        match interpolated.iter_mut().find(|(id, _)| id == doc_id) {
+            Some((_, interpolated_score)) => *interpolated_score += score,
+            None => interpolated.push((*doc_id, score)),
+        }
+    }
+    interpolated.sort_by(|a, b| b.1.total_cmp(&a.1));
+    interpolated
+}
+
+#[tokio::test]
+/// RM3 query expansion: expansion terms, interpolated scores of the initial and the feedback retrieval
+async fn test_06_rm3_expansion() {
+    // "engine" occurs in 3 of 5 documents, "speed" in 2 of 5
+    const DOCUMENTS: &str = r#"
+    [{"title":"jaguar engine engine"},
+    {"title":"jaguar engine speed"},
+    {"title":"engine repair"},
+    {"title":"cat forest"},
+    {"title":"speed limit"}]"#;
+
+    let schema = r#"[{"field":"title","field_type":"Text","store":true,"index_lexical":true}]"#;
+    for shard_number in [1, 2] {
+        let index_arc = create_test_index(
+            &format!("query_expansion_{shard_number}"),
+            schema,
+            shard_number,
+        )
+        .await;
+        index_test_documents(&index_arc, DOCUMENTS).await;
+
+        let expanded = |length: usize, query_expansion: QueryExpansion| {
+            let index_arc = index_arc.clone();
+            async move {
+                index_arc
+                    .search_expanded(
+                        "jaguar".to_string(),
+                        None,
+                        QueryType::Intersection,
+                        SearchMode::Lexical,
+                        false,
+                        0,
+                        length,
+                        ResultType::TopkCount,
+                        false,
+                        Vec::new(),
+                        Vec::new(),
+                        Vec::new(),
+                        Vec::new(),
+                        QueryRewriting::SearchOnly,
+                        &query_expansion,
+                        None,
+                    )
+                    .await
+            }
+        };
+
+        let initial = search_lexical(&index_arc, "jaguar", QueryType::Intersection, 10).await;
+        assert_eq!(doc_ids(&initial), vec![0, 1]);
+
+        // engine 0.5 * (2/3 + 1/3) * ln(1 + 2.5/3.5) = 0.269 > speed 0.5 * 1/3 * ln(1 + 3.5/2.5) = 0.146,
+        // the query term jaguar and the terms of documents outside the feedback (repair, limit) are not added
+        let query_expansion = QueryExpansion {
+            depth: 2,
+            weight: 0.5,
+            terms: 2,
+            ..Default::default()
+        };
+        let result_object = expanded(10, query_expansion.clone()).await;
+        assert_eq!(result_object.query, "jaguar engine speed");
+        assert!(
+            result_object.query_terms.contains(&"engine".to_string())
+                && result_object.query_terms.contains(&"speed".to_string())
+        );
+        // result_count_total is that of the initial retrieval
+        assert_eq!(result_object.result_count_total, 2);
+        let result_object_one_term = expanded(
+            10,
+            QueryExpansion {
+                terms: 1,
+                ..query_expansion.clone()
+            },
+        )
+        .await;
+        assert_eq!(result_object_one_term.query, "jaguar engine");
+
+        // the expansion terms find the documents 2 and 4, which don't contain the query term,
+        // the feedback documents keep the highest scores: 0.5 + 0.5 * feedback score > 0.5 * feedback score
+        let feedback = search_lexical(&index_arc, "engine speed", QueryType::Union, 10).await;
+        let interpolated = interpolate(&initial, &feedback, 0.5);
+        let results: Vec<(usize, f32)> = result_object
+            .results
+            .iter()
+            .map(|result| (result.doc_id, result.score))
+            .collect();
+        assert_eq!(doc_ids(&results), doc_ids(&interpolated));
+        assert_eq!(result_object.result_count, 4);
+        assert!(!doc_ids(&results).contains(&3));
+        let mut top = doc_ids(&results[..2]);
+        top.sort_unstable();
+        assert_eq!(top, vec![0, 1]);
+        for ((_, score), (_, interpolated_score)) in results.iter().zip(interpolated.iter()) {
+            assert!((score - interpolated_score).abs() < 1e-6);
+        }
+
+        // the initial retrieval returns max(depth, offset+length) results, only the top length results are returned
+        let result_object = expanded(1, query_expansion.clone()).await;
+        assert_eq!(result_object.results.len(), 1);
+        assert_eq!(result_object.results[0].doc_id, interpolated[0].0);
+
+        // terms 0 disables lexical expansion: the results of the initial retrieval are returned
+        let result_object = expanded(
+            10,
+            QueryExpansion {
+                terms: 0,
+                ..query_expansion
+            },
+        )
+        .await;
+        assert_eq!(result_object.query, "jaguar");
+        let results: Vec<usize> = result_object
+            .results
+            .iter()
+            .map(|result| result.doc_id)
+            .collect();
+        assert_eq!(results, vec![0, 1]);
+
+        index_arc.close().await;
+    }
+}
+
+/// Reranker with fixed scores per document text.
+struct FixedReranker(Vec<(&'static str, f32)>);
+
+impl Reranker for FixedReranker {
+    fn score<'a>(
+        &'a self,
+        _query: &'a str,
+        documents: &'a [String],
+    ) -> futures::future::BoxFuture<'a, Result<Vec<f32>, String>> {
+        Box::pin(async move {
+            Ok(documents
+                .iter()
+                .map(|document| {
+                    self.0
+                        .iter()
+                        .find(|(text, _)| text == document)
+                        .map_or(0.0, |(_, score)| *score)
+                })
+                .collect())
+        })
+    }
+}
+
+#[tokio::test]
+/// reranking blends the reranker and retrieval scores within the rerank depth
+async fn test_07_rerank_order() {
+    let index_arc = create_test_index(
+        "rerank",
+        r#"[{"field":"title","field_type":"Text","store":true,"index_lexical":true}]"#,
+        1,
+    )
+    .await;
+    index_test_documents(
+        &index_arc,
+        r#"[{"title":"a"},{"title":"b"},{"title":"c"},{"title":"d"},{}]"#,
+    )
+    .await;
+    // a NaN score ranks like a reranker score of 0.0 instead of panicking, document 4 has no title
+    let reranker = FixedReranker(vec![("a", -2.0), ("b", f32::NAN), ("c", 4.0), ("d", 1.0)]);
+
+    for (weight, depth, expected) in [
+        (1.0, 50, vec![2, 3, 0, 1, 4]),
+        (0.5, 50, vec![2, 0, 3, 1, 4]),
+        (1.0, 2, vec![0, 1, 2, 3, 4]),
+        (1.0, 3, vec![2, 0, 1, 3, 4]),
+    ] {
+        // all documents, with descending retrieval scores in the order of the document ids
+        let mut results = index_arc
+            .search(
+                String::new(),
+                None,
+                QueryType::Intersection,
+                SearchMode::Lexical,
+                true,
+                0,
+                10,
+                ResultType::Topk,
+                false,
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                QueryRewriting::SearchOnly,
+            )
+            .await
+            .results;
+        assert_eq!(results.len(), 5);
+        results.sort_by_key(|result| result.doc_id);
+        for result in results.iter_mut() {
+            result.score = 5.0 - result.doc_id as f32;
+        }
+
+        index_arc
+            .read()
+            .await
+            .rerank(
+                "query",
+                &mut results,
+                &Rerank {
+                    model: String::new(),
+                    field: "title".into(),
+                    depth,
+                    weight,
+                },
+                &reranker,
+                false,
+            )
+            .await
+            .unwrap();
+        let doc_ids: Vec<usize> = results.iter().map(|result| result.doc_id).collect();
+        assert_eq!(doc_ids, expected, "weight {weight} depth {depth}");
+        assert!(results.iter().all(|result| !result.score.is_nan()));
+    }
+
+    index_arc.close().await;
+}
+
+#[tokio::test]
+/// facet paging with more distinct values than the values per shard: consecutive pages line up with the exact facet counts
+async fn test_08_facet_paging_shards() {
+    // 60 colors with counts 1..60, every color on a single shard of 3: 20 colors and 610 documents per shard
+    let color = |count: usize| format!("v{:02}", count * 7 % 61);
+    let mut shard_colors = vec![Vec::new(); 3];
+    for count in 1..=60 {
+        shard_colors[(count.min(61 - count) - 1) % 3]
+            .extend(std::iter::repeat_n(color(count), count));
+    }
+    // documents are assigned to the shards round robin
+    let documents = (0..610)
+        .flat_map(|i| shard_colors.iter().map(move |colors| colors[i].clone()))
+        .map(|color| format!(r#"{{"title":"item","color":"{color}"}}"#))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    for shard_number in [1, 3] {
+        let index_arc = create_test_index(
+            &format!("facet_paging_shards_{shard_number}"),
+            FACET_SCHEMA,
+            shard_number,
+        )
+        .await;
+        index_test_documents(&index_arc, &format!("[{documents}]")).await;
+
+        for query in ["item", ""] {
+            for sort in [
+                FacetValueSort::CountDescending,
+                FacetValueSort::CountAscending,
+                FacetValueSort::ValueAscending,
+                FacetValueSort::ValueDescending,
+            ] {
+                for min_count in [0, 25] {
+                    let mut expected: Vec<(String, usize)> = (1..=60)
+                        .filter(|count| *count >= min_count)
+                        .map(|count| (color(count), count))
+                        .collect();
+                    expected.sort_by(|a, b| match sort {
+                        FacetValueSort::CountDescending => b.1.cmp(&a.1),
+                        FacetValueSort::CountAscending => a.1.cmp(&b.1),
+                        FacetValueSort::ValueAscending => a.0.cmp(&b.0),
+                        FacetValueSort::ValueDescending => b.0.cmp(&a.0),
+                    });
+
+                    // pages of 2 values: up to 8 values per shard for count sorts
+                    let mut pages = Vec::new();
+                    for offset in (0..60).step_by(2) {
+                        pages.extend(
+                            search_color_facet(
+                                &index_arc,
+                                query,
+                                offset,
+                                2,
+                                sort.clone(),
+                                min_count,
+                            )
+                            .await,
+                        );
+                    }
+                    assert_eq!(pages, expected, "{query:?} {sort:?} {min_count}");
+                }
+            }
+        }
+        index_arc.close().await;
+    }
+}
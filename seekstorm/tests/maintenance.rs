@@ -0,0 +1,626 @@
+//! Index maintenance tests: merge, reshard, verify, repair, reopening, commits, deletes, documents without stored fields and format versions. Every test creates its own index below the temp dir.
+//! Use: cargo test --test maintenance
+
+mod common;
+
+use ahash::AHashMap;
+use common::{create_test_index, index_test_documents, test_index_dir, test_index_path, test_meta};
+use seekstorm::commit::Commit;
+use seekstorm::index::{
+    Close, DeleteDocument, Document, FileType, INDEX_FORMAT_VERSION_MINOR, IndexArc, IndexDocument,
+    IndexDocuments, ROARING_BLOCK_SIZE, create_index, open_index,
+};
+use seekstorm::merge::merge_indices;
+use seekstorm::reshard::Reshard;
+use seekstorm::search::{
+    FacetValue, FacetValueSort, QueryFacet, QueryRewriting, QueryType, ResultObject, ResultType,
+    Search, SearchMode,
+};
+use seekstorm::verify::repair_index;
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SCHEMA: &str = r#"[{"field":"title","field_type":"Text","store":true,"index_lexical":true},
+    {"field":"category","field_type":"String16","store":false,"index_lexical":false,"facet":true},
+    {"field":"tags","field_type":"StringSet16","store":false,"index_lexical":false,"facet":true}]"#;
+
+async fn search_common(index_arc: &IndexArc, query: &str) -> ResultObject {
+    index_arc
+        .search(
+            query.into(),
+            None,
+            QueryType::Intersection,
+            SearchMode::Lexical,
+            false,
+            0,
+            1000,
+            ResultType::TopkCount,
+            false,
+            Vec::new(),
+            vec![QueryFacet::String16 {
+                field: "category".into(),
+                prefix: String::new(),
+                length: 10,
+                offset: 0,
+                sort: FacetValueSort::ValueAscending,
+                min_count: 0,
+            }],
+            Vec::new(),
+            Vec::new(),
+            QueryRewriting::SearchOnly,
+        )
+        .await
+}
+
+/// Sorted doc ids of the results.
+async fn search_doc_ids(index_arc: &IndexArc, query: &str) -> Vec<usize> {
+    let mut doc_ids: Vec<usize> = search_common(index_arc, query)
+        .await
+        .results
+        .iter()
+        .map(|result| result.doc_id)
+        .collect();
+    doc_ids.sort_unstable();
+    doc_ids
+}
+
+/// Title, category and tags of a document.
+async fn document_values(index_arc: &IndexArc, doc_id: usize) -> (Value, FacetValue, FacetValue) {
+    let index_ref = index_arc.read().await;
+    let document = index_ref
+        .get_document(doc_id, false, &None, &HashSet::new(), &[])
+        .await
+        .unwrap();
+    (
+        document["title"].clone(),
+        index_ref.get_facet_value("category", doc_id).await,
+        index_ref.get_facet_value("tags", doc_id).await,
+    )
+}
+
+#[tokio::test]
+/// merge: documents, stored and facet values are found under their mapped document ids
+async fn test_01_merge_preserves_documents_and_facets() {
+    // facet value dictionaries that differ per source, a deleted document, and a source with an empty shard
+    let source_categories = [
+        vec!["red", "green"],
+        vec!["blue", "red", "yellow"],
+        vec!["green"],
+    ];
+    let source_doc_counts = [5, 6, 1];
+    let mut sources = Vec::new();
+    for (source_id, (categories, doc_count)) in
+        source_categories.iter().zip(source_doc_counts).enumerate()
+    {
+        let source = create_test_index(&format!("merge_source_{source_id}"), SCHEMA, 2).await;
+        let documents: Vec<Value> = (0..doc_count)
+            .map(|i| {
+                json!({
+                    "title": format!("common src{source_id} doc{i}"),
+                    "category": categories[i % categories.len()],
+                    "tags": [format!("t{}", i % 2), format!("s{source_id}")],
+                })
+            })
+            .collect();
+        index_test_documents(&source, &serde_json::to_string(&documents).unwrap()).await;
+        sources.push(source);
+    }
+    let deleted_doc_id = search_common(&sources[1], "doc2").await.results[0].doc_id;
+    sources[1].delete_document(deleted_doc_id as u64).await;
+
+    let mut expected_values = Vec::new();
+    for source in sources.iter() {
+        let mut source_values = Vec::new();
+        for result in search_common(source, "common").await.results {
+            source_values.push((result.doc_id, document_values(source, result.doc_id).await));
+        }
+        expected_values.push(source_values);
+    }
+
+    let target_path = test_index_path("merge_target");
+    let merge_result = merge_indices(&sources, &target_path).await.unwrap();
+    let index_arc = merge_result.index_arc;
+    assert_eq!(merge_result.doc_count, 11);
+    assert_eq!(index_arc.read().await.current_doc_count().await, 11);
+    assert!(index_arc.read().await.verify().await.is_consistent);
+
+    // the documents are found under their mapped document ids, with the same stored and facet values
+    let mut target_doc_ids = Vec::new();
+    for (source_values, source_doc_id_mapping) in expected_values
+        .iter()
+        .zip(merge_result.doc_id_mapping.iter())
+    {
+        assert_eq!(source_values.len(), source_doc_id_mapping.len());
+        let doc_id_mapping: AHashMap<u64, u64> = source_doc_id_mapping.iter().copied().collect();
+        for (doc_id, values) in source_values.iter() {
+            let target_doc_id = doc_id_mapping[&(*doc_id as u64)] as usize;
+            assert_eq!(&document_values(&index_arc, target_doc_id).await, values);
+            target_doc_ids.push(target_doc_id);
+        }
+    }
+    target_doc_ids.sort_unstable();
+    assert_eq!(search_doc_ids(&index_arc, "common").await, target_doc_ids);
+    assert_eq!(search_common(&index_arc, "src1").await.results.len(), 5);
+    assert_eq!(search_common(&index_arc, "doc2").await.results.len(), 1);
+
+    // the facet counts of the merged dictionaries
+    assert_eq!(
+        search_common(&index_arc, "common").await.facets["category"],
+        vec![
+            ("blue".to_string(), 2),
+            ("green".to_string(), 3),
+            ("red".to_string(), 5),
+            ("yellow".to_string(), 1)
+        ]
+    );
+
+    // new documents are appended after the merged documents
+    index_arc
+        .index_document(
+            serde_json::from_value(
+                json!({"title": "common new", "category": "yellow", "tags": ["t9"]}),
+            )
+            .unwrap(),
+            FileType::None,
+        )
+        .await;
+    index_arc.commit().await;
+    let results = search_common(&index_arc, "new").await.results;
+    assert_eq!(results.len(), 1);
+    assert!(!target_doc_ids.contains(&results[0].doc_id));
+    assert_eq!(
+        document_values(&index_arc, results[0].doc_id).await.1,
+        FacetValue::String("yellow".to_string())
+    );
+    assert_eq!(search_common(&index_arc, "common").await.results.len(), 12);
+    index_arc.close().await;
+
+    let index_arc = open_index(&target_path).await.unwrap();
+    assert_eq!(index_arc.read().await.current_doc_count().await, 12);
+    assert_eq!(search_common(&index_arc, "common").await.results.len(), 12);
+    index_arc.close().await;
+}
+
+#[tokio::test]
+/// merge: the sources must have identical segment number bits and shard numbers
+async fn test_02_merge_requires_identical_segment_number_bits_and_shards() {
+    let schema = serde_json::from_str(SCHEMA).unwrap();
+    let source = create_test_index("merge_bits_source", SCHEMA, 2).await;
+    for (name, segment_number_bits1, shard_number, error) in [
+        ("merge_bits", 10, 2, "segment number bits 10 of source 1"),
+        ("merge_shards", 11, 3, "source 1 has 3 shards"),
+    ] {
+        let other = create_index(
+            &test_index_path(name),
+            test_meta(),
+            &schema,
+            &Vec::new(),
+            segment_number_bits1,
+            true,
+            Some(shard_number),
+        )
+        .await
+        .unwrap();
+        let result = merge_indices(
+            &[source.clone(), other],
+            &test_index_path(&format!("{name}_target")),
+        )
+        .await;
+        assert!(result.is_err_and(|e| e.starts_with(error)), "{name}");
+    }
+}
+
+/// Title and tenant (stored, or the facet value if not stored) of a document.
+async fn tenant_document_values(index_arc: &IndexArc, doc_id: usize) -> (Value, FacetValue) {
+    let index_ref = index_arc.read().await;
+    let document = index_ref
+        .get_document(doc_id, false, &None, &HashSet::new(), &[])
+        .await
+        .unwrap();
+    (
+        document["title"].clone(),
+        index_ref.get_facet_value("tenant", doc_id).await,
+    )
+}
+
+#[tokio::test]
+/// reshard: the same documents, with the same values, are found under their (mapped) document ids
+async fn test_03_reshard_preserves_documents() {
+    // round-robin with a non-stored facet field, and routing by tenant
+    for (name, tenant_field) in [
+        (
+            "reshard",
+            r#"{"field":"tenant","field_type":"String16","store":false,"index_lexical":false,"facet":true}"#,
+        ),
+        (
+            "reshard_routing",
+            r#"{"field":"tenant","field_type":"String16","store":true,"index_lexical":false,"facet":true,"routing":true}"#,
+        ),
+    ] {
+        let schema = format!(
+            r#"[{{"field":"title","field_type":"Text","store":true,"index_lexical":true}},{tenant_field}]"#
+        );
+        let index_arc = create_test_index(name, &schema, 2).await;
+        let documents: Vec<Value> = (0..40)
+            .map(|i| json!({"title": format!("common w{} doc{i}", i % 7), "tenant": format!("t{}", i % 3)}))
+            .collect();
+        index_test_documents(&index_arc, &serde_json::to_string(&documents).unwrap()).await;
+
+        assert_eq!(search_doc_ids(&index_arc, "common").await.len(), 40);
+        for query in ["doc5", "doc17"] {
+            let doc_id = search_doc_ids(&index_arc, query).await[0];
+            index_arc.delete_document(doc_id as u64).await;
+        }
+        let queries = ["common", "w3", "doc12", "doc5"];
+        let mut before = Vec::new();
+        for query in queries {
+            let mut results = Vec::new();
+            for doc_id in search_doc_ids(&index_arc, query).await {
+                results.push((doc_id, tenant_document_values(&index_arc, doc_id).await));
+            }
+            before.push(results);
+        }
+        assert_eq!(before[0].len(), 38);
+        assert!(before[3].is_empty());
+
+        let reshard_result = index_arc.reshard(3).await.unwrap();
+        let index_arc = reshard_result.index_arc;
+        assert_eq!(reshard_result.doc_count, 38);
+        assert_eq!(index_arc.read().await.shard_count().await, 3);
+        let is_round_robin = name == "reshard";
+        // round-robin keeps the deleted documents as deleted placeholders, to preserve the document ids
+        assert_eq!(
+            index_arc.read().await.indexed_doc_count().await,
+            if is_round_robin { 40 } else { 38 }
+        );
+        let doc_id_mapping: AHashMap<usize, usize> = reshard_result
+            .doc_id_mapping
+            .iter()
+            .map(|(doc_id, target_doc_id)| (*doc_id as usize, *target_doc_id as usize))
+            .collect();
+        // round-robin preserves the document ids, routing re-routes the documents to the new shards
+        assert_eq!(doc_id_mapping.is_empty(), is_round_robin);
+
+        for (query, results) in queries.iter().zip(before.iter()) {
+            let mut expected: Vec<(usize, (Value, FacetValue))> = results
+                .iter()
+                .map(|(doc_id, values)| {
+                    (
+                        *doc_id_mapping.get(doc_id).unwrap_or(doc_id),
+                        values.clone(),
+                    )
+                })
+                .collect();
+            expected.sort_unstable_by_key(|(doc_id, _)| *doc_id);
+            let mut after = Vec::new();
+            for doc_id in search_doc_ids(&index_arc, query).await {
+                after.push((doc_id, tenant_document_values(&index_arc, doc_id).await));
+            }
+            assert_eq!(after, expected, "{name} {query}");
+        }
+
+        index_arc.close().await;
+    }
+}
+
+#[tokio::test]
+/// reshard: indexed fields that are not stored can't be re-indexed, the index is not changed
+async fn test_04_reshard_requires_stored_fields() {
+    let schema = r#"[{"field":"title","field_type":"Text","store":true,"index_lexical":true},
+    {"field":"body","field_type":"Text","store":false,"index_lexical":true}]"#;
+    let index_arc = create_test_index("reshard_not_stored", schema, 2).await;
+    index_test_documents(
+        &index_arc,
+        r#"[{"title":"a","body":"common"},{"title":"b","body":"common"}]"#,
+    )
+    .await;
+
+    let Err(error) = index_arc.reshard(3).await else {
+        panic!("reshard of non-stored indexed fields");
+    };
+    assert!(error.contains("body"));
+    assert_eq!(index_arc.read().await.shard_count().await, 2);
+    assert_eq!(search_doc_ids(&index_arc, "common").await, vec![0, 1]);
+    index_arc.close().await;
+}
+
+const VERIFY_SCHEMA: &str = r#"[{"field":"title","field_type":"Text","store":true,"index_lexical":true},
+    {"field":"category","field_type":"String16","store":false,"index_lexical":false,"facet":true}]"#;
+
+async fn index_documents(index_arc: &IndexArc, count: usize, title: &str, category: &str) {
+    let documents = (0..count)
+        .map(|_| {
+            Document::from([
+                ("title".into(), json!(title)),
+                ("category".into(), json!(category)),
+            ])
+        })
+        .collect();
+    index_arc.index_documents(documents).await;
+    index_arc.commit().await;
+}
+
+async fn search_count(index_arc: &IndexArc, query: &str) -> usize {
+    index_arc
+        .search(
+            query.into(),
+            None,
+            QueryType::Intersection,
+            SearchMode::Lexical,
+            false,
+            0,
+            10,
+            ResultType::Count,
+            false,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            QueryRewriting::SearchOnly,
+        )
+        .await
+        .result_count_total
+}
+
+/// Directory of a shard of the index.
+fn shard_path(index_path: &Path, shard_id: usize) -> PathBuf {
+    index_path.join("shards").join(shard_id.to_string())
+}
+
+#[tokio::test]
+/// repair rolls back a level with a corrupted document to the last consistent level
+async fn test_05_repair_rolls_back_corrupted_level() {
+    let index_arc = create_test_index("verify_corrupted_level", VERIFY_SCHEMA, 1).await;
+    let index_path = test_index_dir("verify_corrupted_level");
+    let shard_path = shard_path(&index_path, 0);
+
+    // a complete level, and an incomplete level committed several times, also after reopening the index
+    index_documents(&index_arc, ROARING_BLOCK_SIZE, "first", "red").await;
+    index_documents(&index_arc, 5, "late", "blue").await;
+    index_documents(&index_arc, 5, "late", "blue").await;
+    assert!(index_arc.read().await.verify().await.is_consistent);
+    index_arc.close().await;
+    let index_arc = open_index(&index_path).await.unwrap();
+    index_documents(&index_arc, 5, "late", "blue").await;
+    let verify_result = index_arc.read().await.verify().await;
+    assert!(verify_result.is_consistent, "{:?}", verify_result);
+    assert_eq!(verify_result.shard_results[0].level_count, 2);
+    assert_eq!(verify_result.shard_results[0].checksum_level_count, 2);
+    index_arc.close().await;
+
+    // corrupt the last document of the second level, at the end of the document store
+    let docstore_path = shard_path.join("docstore.bin");
+    let mut docstore_bytes = fs::read(&docstore_path).unwrap();
+    *docstore_bytes.last_mut().unwrap() ^= 0xff;
+    fs::write(&docstore_path, docstore_bytes).unwrap();
+
+    let index_arc = open_index(&index_path).await.unwrap();
+    let verify_result = index_arc.read().await.verify().await;
+    assert!(!verify_result.is_consistent);
+    assert_eq!(verify_result.shard_results[0].consistent_level_count, 1);
+    assert!(
+        verify_result.shard_results[0]
+            .errors
+            .contains(&"docstore.bin level 1 checksum mismatch".to_string())
+    );
+    index_arc.close().await;
+
+    let repair_result = repair_index(&index_path).await.unwrap();
+    assert_eq!(repair_result.shard_results[0].level_count, 2);
+    assert_eq!(repair_result.shard_results[0].repaired_level_count, 1);
+    assert_eq!(repair_result.shard_results[0].removed_doc_count, 15);
+    assert!(repair_result.placeholder_doc_ids.is_empty());
+
+    // the facet values of the rolled back documents are cleared
+    let facet_level_size = 2 * ROARING_BLOCK_SIZE;
+    let facet_bytes = fs::read(shard_path.join("facet.bin")).unwrap();
+    assert_eq!(facet_bytes.len(), 2 * facet_level_size);
+    assert!(
+        facet_bytes[facet_level_size..]
+            .iter()
+            .all(|byte| *byte == 0)
+    );
+
+    let index_arc = repair_result.index_arc;
+    assert!(index_arc.read().await.verify().await.is_consistent);
+    assert_eq!(
+        index_arc.read().await.current_doc_count().await,
+        ROARING_BLOCK_SIZE
+    );
+    assert_eq!(search_count(&index_arc, "late").await, 0);
+
+    index_documents(&index_arc, 1, "again", "green").await;
+    assert_eq!(search_count(&index_arc, "again").await, 1);
+    assert_eq!(
+        index_arc
+            .read()
+            .await
+            .get_facet_value("category", ROARING_BLOCK_SIZE)
+            .await,
+        FacetValue::String("green".to_string())
+    );
+    assert!(index_arc.read().await.verify().await.is_consistent);
+    index_arc.close().await;
+}
+
+#[tokio::test]
+/// repair of a truncated document store: the shard loses its only level, its document ids are filled with deleted placeholders
+async fn test_06_repair_rolls_back_truncated_level() {
+    let index_arc = create_test_index("verify_truncated_level", VERIFY_SCHEMA, 2).await;
+    let index_path = test_index_dir("verify_truncated_level");
+    let shard_path = shard_path(&index_path, 1);
+    index_documents(&index_arc, 6, "first", "red").await;
+    index_arc.close().await;
+
+    let docstore_path = shard_path.join("docstore.bin");
+    let docstore_size = fs::metadata(&docstore_path).unwrap().len();
+    fs::OpenOptions::new()
+        .write(true)
+        .open(&docstore_path)
+        .unwrap()
+        .set_len(docstore_size - 8)
+        .unwrap();
+
+    let index_arc = open_index(&index_path).await.unwrap();
+    let verify_result = index_arc.read().await.verify().await;
+    assert!(!verify_result.is_consistent);
+    assert!(verify_result.shard_results[0].errors.is_empty());
+    assert_eq!(verify_result.shard_results[1].consistent_level_count, 0);
+    index_arc.close().await;
+
+    let repair_result = repair_index(&index_path).await.unwrap();
+    assert_eq!(repair_result.shard_results[0].removed_doc_count, 0);
+    assert_eq!(repair_result.shard_results[1].repaired_level_count, 0);
+    assert_eq!(repair_result.shard_results[1].removed_doc_count, 3);
+    assert_eq!(repair_result.placeholder_doc_ids, vec![1, 3]);
+
+    let index_arc = repair_result.index_arc;
+    assert!(index_arc.read().await.verify().await.is_consistent);
+    assert_eq!(index_arc.read().await.current_doc_count().await, 3);
+    assert_eq!(search_count(&index_arc, "first").await, 3);
+
+    index_documents(&index_arc, 2, "again", "green").await;
+    assert_eq!(search_count(&index_arc, "again").await, 2);
+    assert_eq!(
+        index_arc.read().await.get_facet_value("category", 5).await,
+        FacetValue::String("green".to_string())
+    );
+    assert!(index_arc.read().await.verify().await.is_consistent);
+    index_arc.close().await;
+}
+
+#[tokio::test]
+/// single term count queries leave out the deleted documents, before and after the commit
+async fn test_07_count_without_deleted_documents() {
+    let index_arc = create_test_index("count_deleted", VERIFY_SCHEMA, 1).await;
+    index_documents(&index_arc, 10, "first", "red").await;
+    assert_eq!(search_count(&index_arc, "first").await, 10);
+
+    for doc_id in [2, 5, 7] {
+        index_arc.delete_document(doc_id).await;
+    }
+    assert_eq!(search_count(&index_arc, "first").await, 7);
+    index_arc.commit().await;
+    assert_eq!(search_count(&index_arc, "first").await, 7);
+    index_arc.close().await;
+}
+
+#[tokio::test]
+/// documents indexed after reopening an index with a complete last level start a new level, without overwriting the first level
+async fn test_08_reopen_after_complete_level() {
+    let index_arc = create_test_index("reopen_complete_level", VERIFY_SCHEMA, 1).await;
+    let index_path = test_index_dir("reopen_complete_level");
+    index_documents(&index_arc, ROARING_BLOCK_SIZE, "first", "red").await;
+    index_arc.close().await;
+
+    let index_arc = open_index(&index_path).await.unwrap();
+    index_documents(&index_arc, 3, "late", "blue").await;
+    let verify_result = index_arc.read().await.verify().await;
+    assert!(verify_result.is_consistent, "{:?}", verify_result);
+    assert_eq!(verify_result.shard_results[0].level_count, 2);
+    assert_eq!(search_count(&index_arc, "first").await, ROARING_BLOCK_SIZE);
+    assert_eq!(search_count(&index_arc, "late").await, 3);
+    for (doc_id, title) in [(0, "first"), (ROARING_BLOCK_SIZE, "late")] {
+        let index_ref = index_arc.read().await;
+        let document = index_ref
+            .get_document(doc_id, false, &None, &HashSet::new(), &[])
+            .await
+            .unwrap();
+        assert_eq!(document["title"], json!(title));
+    }
+    index_arc.close().await;
+
+    let index_arc = open_index(&index_path).await.unwrap();
+    assert!(index_arc.read().await.verify().await.is_consistent);
+    assert_eq!(
+        index_arc.read().await.current_doc_count().await,
+        ROARING_BLOCK_SIZE + 3
+    );
+    index_arc.close().await;
+}
+
+#[tokio::test]
+/// indices of an older minor format version are opened, indices of a newer minor format version are rejected
+async fn test_09_index_format_version() {
+    let index_arc = create_test_index("format_version", VERIFY_SCHEMA, 2).await;
+    let index_path = test_index_dir("format_version");
+    index_documents(&index_arc, 3, "first", "red").await;
+    index_arc.close().await;
+
+    // the minor version follows the major version in the header of index.bin
+    let set_minor_version = |minor_version: u16| {
+        for path in [
+            index_path.join("index.bin"),
+            shard_path(&index_path, 0).join("index.bin"),
+            shard_path(&index_path, 1).join("index.bin"),
+        ] {
+            let mut index_bytes = fs::read(&path).unwrap();
+            index_bytes[2..4].copy_from_slice(&minor_version.to_le_bytes());
+            fs::write(&path, index_bytes).unwrap();
+        }
+    };
+
+    set_minor_version(INDEX_FORMAT_VERSION_MINOR - 1);
+    let index_arc = open_index(&index_path).await.unwrap();
+    assert_eq!(
+        index_arc.read().await.index_format_version_minor,
+        INDEX_FORMAT_VERSION_MINOR - 1
+    );
+    assert_eq!(search_count(&index_arc, "first").await, 3);
+    index_arc.close().await;
+
+    set_minor_version(INDEX_FORMAT_VERSION_MINOR + 1);
+    assert!(
+        open_index(&index_path)
+            .await
+            .is_err_and(|e| e.starts_with("incompatible index format version"))
+    );
+}
+
+#[tokio::test]
+/// a commit that completes a level starts the next level: the following documents don't commit an empty level
+async fn test_10_commit_complete_level() {
+    let index_arc = create_test_index("commit_complete_level", VERIFY_SCHEMA, 1).await;
+    index_documents(&index_arc, ROARING_BLOCK_SIZE, "first", "red").await;
+    index_documents(&index_arc, 3, "late", "blue").await;
+
+    let verify_result = index_arc.read().await.verify().await;
+    assert!(verify_result.is_consistent, "{:?}", verify_result);
+    assert_eq!(verify_result.shard_results[0].level_count, 2);
+    assert_eq!(search_count(&index_arc, "first").await, ROARING_BLOCK_SIZE);
+    assert_eq!(search_count(&index_arc, "late").await, 3);
+    index_arc.close().await;
+}
+
+#[tokio::test]
+/// a document without stored fields doesn't break the document store entries of the following documents
+async fn test_11_document_without_stored_fields() {
+    let index_arc = create_test_index("docstore_without_stored_fields", VERIFY_SCHEMA, 1).await;
+    index_test_documents(
+        &index_arc,
+        r#"[{"title":"first","category":"red"},{"category":"blue"},{"title":"third","category":"green"}]"#,
+    )
+    .await;
+
+    let verify_result = index_arc.read().await.verify().await;
+    assert!(verify_result.is_consistent, "{:?}", verify_result);
+    let index_ref = index_arc.read().await;
+    for (doc_id, title) in [(0, "first"), (2, "third")] {
+        let document = index_ref
+            .get_document(doc_id, false, &None, &HashSet::new(), &[])
+            .await
+            .unwrap();
+        assert_eq!(document["title"], json!(title));
+    }
+    // the document without stored fields has no document store entry
+    assert!(
+        index_ref
+            .get_document(1, false, &None, &HashSet::new(), &[])
+            .await
+            .is_err()
+    );
+    drop(index_ref);
+    index_arc.close().await;
+}
@@ -0,0 +1,1888 @@
+//! Vector search tests: approximate nearest neighbor search, quantization, calibration, radius and batch search,
+//! late interaction and sparse vectors. Every test creates its own index below the temp dir.
+//! Use: cargo test --test vector_search
+
+mod common;
+
+use ahash::AHashMap;
+use common::{
+    create_test_index_meta, index_test_documents, index_test_vectors, test_index_dir,
+    test_index_path, test_meta, test_vector_meta, test_vectors,
+};
+use seekstorm::ann_calibration::CalibrateAnn;
+use seekstorm::commit::Commit;
+use seekstorm::index::{
+    AccessType, Close, Clustering, DeleteDocument, DeleteDocuments, Document, IndexArc,
+    IndexDocuments, IndexMetaObject, ROARING_BLOCK_SIZE, create_index, open_index,
+};
+use seekstorm::search::{QueryRewriting, QueryType, ResultType, Search, SearchMode};
+use seekstorm::search_batch::SearchBatch;
+use seekstorm::search_radius::SearchRadius;
+use seekstorm::sparse_vector::SparseVector;
+use seekstorm::vector::{ChunkAggregation, Embedding, Inference, Precision, Quantization};
+use seekstorm::vector_merge::MergeVectorLevels;
+use seekstorm::vector_similarity::{AnnMode, VectorSimilarity};
+use serde_json::Value;
+use std::time::Duration;
+
+const VECTOR_SCHEMA: &str = r#"
+[{"field":"vector","field_type":"Json","store":false,"index_lexical":false,"index_vector":true}]"#;
+
+const TITLE_VECTOR_SCHEMA: &str = r#"
+[{"field":"title","field_type":"Text","store":true,"index_lexical":true},
+{"field":"vector","field_type":"Json","store":false,"index_lexical":false,"index_vector":true}]"#;
+
+/// Vector search of the committed documents: doc_id and score of the top length results.
+async fn search_vector(
+    index_arc: &IndexArc,
+    query: &[f32],
+    ann_mode: AnnMode,
+    length: usize,
+) -> Vec<(usize, f32)> {
+    index_arc
+        .search(
+            String::new(),
+            Some(Embedding::F32(query.to_vec())),
+            QueryType::Union,
+            SearchMode::Vector {
+                similarity_threshold: None,
+                ann_mode,
+                chunk_aggregation: ChunkAggregation::Max,
+            },
+            false,
+            0,
+            length,
+            ResultType::Topk,
+            false,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            QueryRewriting::SearchOnly,
+        )
+        .await
+        .results
+        .iter()
+        .map(|result| (result.doc_id, result.score))
+        .collect()
+}
+
+/// Doc ids of the vector search results.
+async fn search_doc_ids(
+    index_arc: &IndexArc,
+    query: &[f32],
+    ann_mode: AnnMode,
+    length: usize,
+) -> Vec<usize> {
+    search_vector(index_arc, query, ann_mode, length)
+        .await
+        .iter()
+        .map(|(doc_id, _)| *doc_id)
+        .collect()
+}
+
+/// Squared euclidean distance.
+fn euclidean(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(a, b)| a * b).sum()
+}
+
+/// Doc ids of the length nearest vectors by euclidean distance, excluding the deleted doc ids.
+fn exact_neighbors(
+    vectors: &[Vec<f32>],
+    query: &[f32],
+    length: usize,
+    deleted: &[usize],
+) -> Vec<usize> {
+    let mut distances: Vec<(f32, usize)> = vectors
+        .iter()
+        .enumerate()
+        .filter(|(doc_id, _)| !deleted.contains(doc_id))
+        .map(|(doc_id, vector)| (euclidean(vector, query), doc_id))
+        .collect();
+    distances.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+    distances
+        .into_iter()
+        .take(length)
+        .map(|(_, doc_id)| doc_id)
+        .collect()
+}
+
+#[tokio::test]
+/// HNSW recall, deleted documents are skipped during the graph traversal
+async fn test_01_hnsw_recall_and_deleted_documents() {
+    let vectors = test_vectors(2000, 16, 1);
+    let queries = test_vectors(20, 16, 2);
+    let meta = test_vector_meta(
+        16,
+        VectorSimilarity::Euclidean,
+        Quantization::None,
+        Clustering::Hnsw {
+            m: 16,
+            ef_construction: 100,
+        },
+    );
+    let index_arc = create_test_index_meta("hnsw_recall", meta, VECTOR_SCHEMA, 1).await;
+    index_test_vectors(&index_arc, &vectors).await;
+
+    let mut hits = 0;
+    for query in queries.iter() {
+        let exact = exact_neighbors(&vectors, query, 10, &[]);
+        let results = search_doc_ids(&index_arc, query, AnnMode::EfSearch(64), 10).await;
+        assert_eq!(results.len(), 10);
+        hits += results
+            .iter()
+            .filter(|doc_id| exact.contains(doc_id))
+            .count();
+    }
+    let recall = hits as f32 / (queries.len() * 10) as f32;
+    assert!(recall >= 0.9, "recall@10 {recall}");
+
+    // deleted documents are skipped during the graph traversal: still length results, with ef == length
+    let deleted = exact_neighbors(&vectors, &queries[0], 10, &[]);
+    for doc_id in deleted.iter() {
+        index_arc.delete_document(*doc_id as u64).await;
+    }
+    let results = search_doc_ids(&index_arc, &queries[0], AnnMode::EfSearch(10), 10).await;
+    assert_eq!(results.len(), 10);
+    assert!(results.iter().all(|doc_id| !deleted.contains(doc_id)));
+    let exact = exact_neighbors(&vectors, &queries[0], 10, &deleted);
+    assert!(
+        results
+            .iter()
+            .filter(|doc_id| exact.contains(doc_id))
+            .count()
+            >= 7
+    );
+    index_arc.close().await;
+}
+
+#[tokio::test]
+/// product quantization: the re-ranking with the full precision vectors finds the query vector itself
+async fn test_02_product_quantization_search() {
+    let vectors = test_vectors(2000, 16, 3);
+    let meta = test_vector_meta(
+        16,
+        VectorSimilarity::Euclidean,
+        Quantization::ProductQuantization {
+            subvectors: 4,
+            rerank_factor: 10,
+        },
+        Clustering::None,
+    );
+    let index_arc = create_test_index_meta("product_quantization", meta, VECTOR_SCHEMA, 1).await;
+    index_test_vectors(&index_arc, &vectors).await;
+
+    for doc_id in [0, 777, 1999] {
+        let results = search_doc_ids(&index_arc, &vectors[doc_id], AnnMode::All, 10).await;
+        assert_eq!(results.len(), 10);
+        assert_eq!(results[0], doc_id);
+    }
+    index_arc.close().await;
+}
+
+#[tokio::test]
+/// binary quantization: recall@10 of the rescored oversampled candidates against the exact nearest neighbors
+async fn test_03_binary_quantization_search() {
+    // 96 dimensions: 2 words per bit vector, the second one half used
+    let vectors = test_vectors(2000, 96, 5);
+    let meta = test_vector_meta(
+        96,
+        VectorSimilarity::Euclidean,
+        Quantization::Binary { oversampling: 10 },
+        Clustering::None,
+    );
+    let index_arc = create_test_index_meta("binary_quantization", meta, VECTOR_SCHEMA, 1).await;
+    index_test_vectors(&index_arc, &vectors).await;
+
+    // uniform random vectors are a hard case for binary quantization, an indexed vector is found at the first position
+    let mut queries = test_vectors(20, 96, 7);
+    queries.push(vectors[1234].clone());
+    let mut hits = 0;
+    for query in queries.iter() {
+        let exact = exact_neighbors(&vectors, query, 10, &[]);
+        let results = search_doc_ids(&index_arc, query, AnnMode::All, 10).await;
+        assert_eq!(results.len(), 10);
+        hits += results
+            .iter()
+            .filter(|doc_id| exact.contains(doc_id))
+            .count();
+        if query == &vectors[1234] {
+            assert_eq!(results[0], 1234);
+        }
+    }
+    let recall = hits as f32 / (queries.len() * 10) as f32;
+    assert!(recall >= 0.6, "recall@10 {recall}");
+    index_arc.close().await;
+}
+
+#[tokio::test]
+/// F16 and BF16 vectors: the query vector itself is found with a distance close to 0
+async fn test_04_half_precision_search() {
+    let vectors = test_vectors(500, 64, 11);
+    for precision in [Precision::F16, Precision::BF16] {
+        let mut meta = test_vector_meta(
+            64,
+            VectorSimilarity::Euclidean,
+            Quantization::None,
+            Clustering::None,
+        );
+        if let Inference::External {
+            precision: meta_precision,
+            ..
+        } = &mut meta.inference
+        {
+            *meta_precision = precision;
+        }
+        let index_arc = create_test_index_meta(
+            &format!("half_precision_{precision}"),
+            meta,
+            VECTOR_SCHEMA,
+            1,
+        )
+        .await;
+        index_test_vectors(&index_arc, &vectors).await;
+
+        for doc_id in [0, 250, 499] {
+            let results = search_vector(&index_arc, &vectors[doc_id], AnnMode::All, 10).await;
+            assert_eq!(results.len(), 10);
+            assert_eq!(results[0].0, doc_id);
+            assert!(results[0].1.abs() < 1e-2);
+        }
+        index_arc.close().await;
+    }
+}
+
+#[cfg(feature = "vb")]
+#[tokio::test]
+/// token vectors of a multi-vector field are indexed as chunks and aggregated per document
+async fn test_05_chunk_aggregation_search() {
+    // token vectors of a multi-vector field are indexed as chunks, their position is the chunk_id
+    let index_arc = create_test_index_meta(
+        "chunk_aggregation",
+        test_vector_meta(
+            8,
+            VectorSimilarity::Euclidean,
+            Quantization::None,
+            Clustering::None,
+        ),
+        r#"[{"field":"tokens","field_type":"MultiVector","store":false,"index_lexical":false,"index_vector":true}]"#,
+        1,
+    )
+    .await;
+    // doc 0: one exact match (distance 0) and two distant chunks (distance 2)
+    // doc 1: two close chunks (distance 0.25 and 0.09)
+    index_test_documents(
+        &index_arc,
+        r#"[{"tokens":[[1,0,0,0,0,0,0,0],[0,1,0,0,0,0,0,0],[0,0,0,0,0,0,0,1]]},
+        {"tokens":[[1,0,0.5,0,0,0,0,0],[1,0,0.3,0,0,0,0,0]]}]"#,
+    )
+    .await;
+
+    for (chunk_aggregation, expected) in [
+        (ChunkAggregation::Max, vec![(0, 0, 0.0), (1, 1, -0.09)]),
+        (
+            ChunkAggregation::MeanTopN(2),
+            vec![(1, 1, -0.17), (0, 0, -1.0)],
+        ),
+        (ChunkAggregation::Sum, vec![(1, 1, -0.34), (0, 0, -4.0)]),
+    ] {
+        let result_object = index_arc
+            .search(
+                String::new(),
+                Some(Embedding::F32(vec![1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0])),
+                QueryType::Union,
+                SearchMode::Vector {
+                    similarity_threshold: None,
+                    ann_mode: AnnMode::All,
+                    chunk_aggregation,
+                },
+                false,
+                0,
+                10,
+                ResultType::TopkCount,
+                false,
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                QueryRewriting::SearchOnly,
+            )
+            .await;
+        let results: Vec<(usize, u32, f32)> = result_object
+            .results
+            .iter()
+            .map(|result| {
+                (
+                    result.doc_id,
+                    result.chunk_id,
+                    (result.score * 1000.0).round() / 1000.0,
+                )
+            })
+            .collect();
+        assert_eq!(results, expected, "{chunk_aggregation:?}");
+        assert_eq!(result_object.result_count_total, 2);
+    }
+    index_arc.close().await;
+}
+
+#[cfg(feature = "vb")]
+#[tokio::test]
+/// named vector fields with their own inference are searched in their own vector index and fused with RRF
+async fn test_06_named_vector_fields() {
+    const SCHEMA: &str = r#"
+    [{"field":"title","field_type":"Text","store":true,"index_lexical":true},
+    {"field":"vector","field_type":"Json","store":false,"index_lexical":false,"index_vector":true},
+    {"field":"image","field_type":"Json","store":false,"index_lexical":false,"index_vector":true,
+    "inference":{"External":{"dimensions":8,"precision":"F32","quantization":"None","similarity":"Euclidean"}}}]"#;
+
+    // document 2 has no image vector
+    const DOCUMENTS: &str = r#"
+    [{"title":"a","vector":[1,0,0,0,0,0,0,0],"image":[0,0,0,0,1,0,0,0]},
+    {"title":"b","vector":[0,1,0,0,0,0,0,0],"image":[0,0,0,0,0,1,0,0]},
+    {"title":"c","vector":[0,0,1,0,0,0,0,0]},
+    {"title":"d","vector":[0,0,0,1,0,0,0,0],"image":[0,0,0,0,0,0,0,1]}]"#;
+
+    async fn search_field(
+        index_arc: &IndexArc,
+        query_vector: Vec<f32>,
+        field_filter: Vec<String>,
+    ) -> Vec<(usize, u32)> {
+        index_arc
+            .search(
+                String::new(),
+                Some(Embedding::F32(query_vector)),
+                QueryType::Union,
+                SearchMode::Vector {
+                    similarity_threshold: None,
+                    ann_mode: AnnMode::All,
+                    chunk_aggregation: ChunkAggregation::Max,
+                },
+                false,
+                0,
+                10,
+                ResultType::Topk,
+                false,
+                field_filter,
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                QueryRewriting::SearchOnly,
+            )
+            .await
+            .results
+            .iter()
+            .map(|result| (result.doc_id, result.field_id))
+            .collect()
+    }
+
+    for shard_number in [1, 2] {
+        let name = format!("vector_fields_{shard_number}");
+        let meta = test_vector_meta(
+            8,
+            VectorSimilarity::Euclidean,
+            Quantization::None,
+            Clustering::None,
+        );
+        let mut index_arc = create_test_index_meta(&name, meta, SCHEMA, shard_number).await;
+        index_test_documents(&index_arc, DOCUMENTS).await;
+
+        for reopen in [false, true] {
+            if reopen {
+                index_arc.close().await;
+                index_arc = open_index(&test_index_dir(&name)).await.unwrap();
+            }
+
+            // the image field is searched in its own vector index, with the document ids of the parent index
+            let image_results = search_field(
+                &index_arc,
+                vec![0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.9],
+                vec!["image".into()],
+            )
+            .await;
+            assert_eq!(image_results.len(), 3, "{name}");
+            assert_eq!(image_results[0].0, 3);
+            assert!(image_results.iter().all(|(doc_id, _)| *doc_id != 2));
+            let image_field_id = image_results[0].1;
+            assert!(
+                image_results
+                    .iter()
+                    .all(|(_, field_id)| *field_id == image_field_id)
+            );
+
+            // the default vector field with the inference of the index
+            let vector_results = search_field(
+                &index_arc,
+                vec![0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                vec!["vector".into()],
+            )
+            .await;
+            assert_eq!(vector_results.len(), 4, "{name}");
+            assert_eq!(vector_results[0].0, 2);
+            let vector_field_id = vector_results[0].1;
+            assert_ne!(vector_field_id, image_field_id);
+            assert!(
+                vector_results
+                    .iter()
+                    .all(|(_, field_id)| *field_id == vector_field_id)
+            );
+
+            // without field filter both vector fields are searched and fused with RRF, document 3 is the nearest in both
+            let results = search_field(
+                &index_arc,
+                vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0],
+                Vec::new(),
+            )
+            .await;
+            assert_eq!(results.len(), 4, "{name}");
+            assert_eq!(results[0], (3, image_field_id));
+            assert!(
+                results
+                    .iter()
+                    .any(|(_, field_id)| *field_id == vector_field_id)
+            );
+        }
+        index_arc.close().await;
+    }
+}
+
+#[tokio::test]
+/// ANN calibration: n-probe per recall, target recall resolution, background re-calibration after a commit that adds levels
+async fn test_07_calibration_reaches_target_recall() {
+    let meta = test_vector_meta(
+        16,
+        VectorSimilarity::Euclidean,
+        Quantization::None,
+        Clustering::Fixed(16),
+    );
+    let index_arc = create_test_index_meta("ann_calibration", meta, TITLE_VECTOR_SCHEMA, 2).await;
+    index_test_vectors(&index_arc, &test_vectors(2000, 16, 21)).await;
+
+    let ann_calibration = index_arc.calibrate_ann(100, 10).await;
+    assert_eq!(
+        ann_calibration.level_count,
+        index_arc.read().await.level_count().await
+    );
+    assert!(ann_calibration.points.len() > 2);
+    // n-probe ascends, the recall does not decrease and reaches 1.0 when all clusters are searched
+    for points in ann_calibration.points.windows(2) {
+        let (AnnMode::Nprobe(a), AnnMode::Nprobe(b)) = (&points[0].ann_mode, &points[1].ann_mode)
+        else {
+            panic!("n-probe expected");
+        };
+        assert!(a < b);
+        assert!(points[0].recall <= points[1].recall + 0.05);
+    }
+    assert_eq!(ann_calibration.points.last().unwrap().recall, 1.0);
+    assert_eq!(
+        index_arc
+            .read()
+            .await
+            .get_ann_calibration()
+            .unwrap()
+            .points
+            .len(),
+        ann_calibration.points.len()
+    );
+
+    // the target recall resolves to the first n-probe that reached it
+    let target_recall = 0.9;
+    let point = ann_calibration
+        .points
+        .iter()
+        .find(|point| point.recall >= target_recall)
+        .unwrap();
+    assert!(ann_calibration.points[0].recall < target_recall);
+
+    // held-out queries reach the target recall (within the sampling error) with the resolved n-probe
+    let queries = test_vectors(50, 16, 22);
+    let mut hit_count = 0;
+    let mut exact_count = 0;
+    for query in queries.iter() {
+        let exact = search_doc_ids(&index_arc, query, AnnMode::All, 10).await;
+        let approximate =
+            search_doc_ids(&index_arc, query, AnnMode::TargetRecall(target_recall), 10).await;
+        assert_eq!(
+            approximate,
+            search_doc_ids(&index_arc, query, point.ann_mode.clone(), 10).await
+        );
+        exact_count += exact.len();
+        hit_count += approximate
+            .iter()
+            .filter(|doc_id| exact.contains(doc_id))
+            .count();
+    }
+    let recall = hit_count as f32 / exact_count as f32;
+    assert!(recall >= target_recall - 0.05, "recall {recall}");
+
+    // a commit that completes the first level and adds a second level per shard re-calibrates in the background
+    let level_count = index_arc.read().await.level_count().await;
+    let mut vectors = test_vectors(ROARING_BLOCK_SIZE / 8, 16, 23).into_iter();
+    let documents: Vec<Document> = (0..2 * ROARING_BLOCK_SIZE)
+        .map(|doc_id| {
+            let mut document = Document::from([("title".to_string(), serde_json::json!("a"))]);
+            if doc_id % 16 == 0 {
+                document.insert("vector".into(), serde_json::json!(vectors.next().unwrap()));
+            }
+            document
+        })
+        .collect();
+    index_arc.index_documents(documents).await;
+    index_arc.commit().await;
+    let recalibrated_level_count = index_arc.read().await.level_count().await;
+    assert_eq!(recalibrated_level_count, 2 * level_count);
+
+    let mut recalibrated = false;
+    for _ in 0..600 {
+        recalibrated = index_arc
+            .read()
+            .await
+            .get_ann_calibration()
+            .is_some_and(|ann_calibration| ann_calibration.level_count == recalibrated_level_count);
+        if recalibrated {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(recalibrated);
+    let ann_calibration = index_arc
+        .read()
+        .await
+        .get_ann_calibration()
+        .unwrap()
+        .clone();
+    assert_eq!(ann_calibration.sample_size, 100);
+    assert_eq!(ann_calibration.length, 10);
+    assert_eq!(ann_calibration.points.last().unwrap().recall, 1.0);
+    // the level count is unchanged since the background re-calibration
+    assert!(index_arc.recalibrate_ann().await.is_none());
+
+    index_arc.close().await;
+}
+
+#[tokio::test]
+/// merge of vector levels: purged deleted vectors, unchanged document ids and scores
+async fn test_08_merge_vector_levels() {
+    let meta = test_vector_meta(
+        8,
+        VectorSimilarity::Euclidean,
+        Quantization::None,
+        Clustering::Fixed(8),
+    );
+    let schema = r#"[{"field":"title","field_type":"Text","store":false,"index_lexical":true},
+    {"field":"vector","field_type":"Json","store":false,"index_lexical":false,"index_vector":true}]"#;
+    let mut index_arc = create_test_index_meta("vector_merge", meta, schema, 1).await;
+
+    // two complete levels and the incomplete last level, every 16th document has a vector
+    let doc_count = 2 * ROARING_BLOCK_SIZE + 1000;
+    let mut vectors = test_vectors(doc_count / 16 + 1, 8, 41).into_iter();
+    let documents: Vec<Document> = (0..doc_count)
+        .map(|doc_id| {
+            let mut document = Document::from([("title".to_string(), serde_json::json!("a"))]);
+            if doc_id % 16 == 0 {
+                document.insert("vector".into(), serde_json::json!(vectors.next().unwrap()));
+            }
+            document
+        })
+        .collect();
+    index_arc.index_documents(documents).await;
+    index_arc.commit().await;
+    let vector_count = index_arc.read().await.indexed_vector_count().await;
+    assert_eq!(vector_count, doc_count.div_ceil(16));
+
+    // the vectors of deleted documents of the complete levels are purged
+    let deleted_doc_ids: Vec<u64> = vec![0, 16, 65_536 + 32, 2 * 65_536 + 16];
+    index_arc.delete_documents(deleted_doc_ids.clone()).await;
+
+    let queries = test_vectors(5, 8, 42);
+    let mut results_before = Vec::new();
+    for query in queries.iter() {
+        results_before.push(search_vector(&index_arc, query, AnnMode::All, 20).await);
+    }
+
+    let merge_result = index_arc.merge_vector_levels(1_000_000).await;
+    assert_eq!(merge_result.level_count_before, 3);
+    assert_eq!(merge_result.level_count_after, 2);
+    assert_eq!(merge_result.purged_vector_count, 3);
+    assert_eq!(
+        merge_result.merged_vector_count,
+        2 * ROARING_BLOCK_SIZE / 16 - 3
+    );
+    assert_eq!(
+        index_arc.read().await.indexed_vector_count().await,
+        vector_count - 3
+    );
+
+    // merging again changes nothing
+    let merge_result = index_arc.merge_vector_levels(1_000_000).await;
+    assert_eq!(merge_result.merged_vector_count, 0);
+    assert_eq!(merge_result.level_count_after, 2);
+
+    // document ids and scores are not changed by the merge, also after reopening the index
+    for reopen in [false, true] {
+        if reopen {
+            index_arc.close().await;
+            index_arc = open_index(&test_index_dir("vector_merge")).await.unwrap();
+        }
+        for (query, results) in queries.iter().zip(results_before.iter()) {
+            let results_after = search_vector(&index_arc, query, AnnMode::All, 20).await;
+            assert_eq!(&results_after, results);
+            assert!(
+                results_after
+                    .iter()
+                    .all(|(doc_id, _)| !deleted_doc_ids.contains(&(*doc_id as u64)))
+            );
+            // searching all clusters of the merged level is exact
+            let results_nprobe = search_vector(&index_arc, query, AnnMode::Nprobe(16), 20).await;
+            assert_eq!(&results_nprobe, results);
+        }
+    }
+
+    index_arc.close().await;
+}
+
+/// Exact scores of all vectors: negative squared distance (Euclidean) or cosine similarity, in descending order.
+fn brute_force(
+    vectors: &[Vec<f32>],
+    query: &[f32],
+    similarity: VectorSimilarity,
+) -> Vec<(usize, f32)> {
+    let mut scores: Vec<(usize, f32)> = vectors
+        .iter()
+        .enumerate()
+        .map(|(doc_id, vector)| {
+            let score = match similarity {
+                VectorSimilarity::Euclidean => -euclidean(vector, query),
+                _ => dot(vector, query) / (dot(vector, vector) * dot(query, query)).sqrt(),
+            };
+            (doc_id, score)
+        })
+        .collect();
+    scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+    scores
+}
+
+#[tokio::test]
+/// radius search: all vectors within the radius, pagination, committed and uncommitted documents, lower bound count of ANN search
+async fn test_09_radius_cutoff() {
+    let vectors = test_vectors(1000, 8, 51);
+    let query = test_vectors(1, 8, 52).remove(0);
+
+    for similarity in [VectorSimilarity::Euclidean, VectorSimilarity::Cosine] {
+        let meta = test_vector_meta(8, similarity, Quantization::None, Clustering::Fixed(8));
+        let index_arc = create_test_index_meta(
+            &format!("radius_{similarity:?}"),
+            meta,
+            TITLE_VECTOR_SCHEMA,
+            2,
+        )
+        .await;
+        // committed and uncommitted documents
+        index_test_vectors(&index_arc, &vectors[..900]).await;
+        let documents: Vec<Document> = vectors[900..]
+            .iter()
+            .map(|vector| Document::from([("vector".to_string(), serde_json::json!(vector))]))
+            .collect();
+        index_arc.index_documents(documents).await;
+
+        // the radius lies between the scores of the 50th and 51st nearest vector,
+        // a distance for Euclidean, a cosine similarity normalized to 0.0..1.0 for Cosine
+        let expected = brute_force(&vectors, &query, similarity);
+        let threshold = (expected[49].1 + expected[50].1) / 2.0;
+        let radius = match similarity {
+            VectorSimilarity::Euclidean => (-threshold).sqrt(),
+            _ => (threshold + 1.0) * 0.5,
+        };
+
+        let result_object = index_arc
+            .search_radius(
+                Embedding::F32(query.clone()),
+                radius,
+                AnnMode::All,
+                0,
+                usize::MAX,
+                true,
+                Vec::new(),
+            )
+            .await;
+        assert!(!result_object.result_count_lower_bound);
+        assert_eq!(result_object.result_count_total, 50, "{similarity:?}");
+        assert_eq!(result_object.result_count, 50);
+        for (result, (doc_id, score)) in result_object.results.iter().zip(expected.iter()) {
+            assert_eq!(result.doc_id, *doc_id, "{similarity:?}");
+            assert!((result.score - score).abs() < 1e-4, "{similarity:?}");
+        }
+
+        // pagination, the total count is not changed
+        let page = index_arc
+            .search_radius(
+                Embedding::F32(query.clone()),
+                radius,
+                AnnMode::All,
+                45,
+                10,
+                true,
+                Vec::new(),
+            )
+            .await;
+        assert_eq!(page.result_count_total, 50);
+        assert_eq!(page.result_count, 5);
+        assert_eq!(page.results[0].doc_id, expected[45].0);
+
+        // without uncommitted documents
+        let committed_count = expected[..50]
+            .iter()
+            .filter(|(doc_id, _)| *doc_id < 900)
+            .count();
+        let result_object = index_arc
+            .search_radius(
+                Embedding::F32(query.clone()),
+                radius,
+                AnnMode::All,
+                0,
+                usize::MAX,
+                false,
+                Vec::new(),
+            )
+            .await;
+        assert_eq!(result_object.result_count_total, committed_count);
+
+        // ANN search counts only the documents within the radius in the selected clusters, a lower bound of the total
+        // that grows with the number of searched clusters, up to the total when all 8 clusters are searched
+        let mut previous_count = 0;
+        for nprobe in 1..=8 {
+            let result_object = index_arc
+                .search_radius(
+                    Embedding::F32(query.clone()),
+                    radius,
+                    AnnMode::Nprobe(nprobe),
+                    0,
+                    usize::MAX,
+                    true,
+                    Vec::new(),
+                )
+                .await;
+            assert!(result_object.result_count_lower_bound);
+            assert_eq!(result_object.result_count_total, result_object.result_count);
+            assert!(result_object.result_count_total >= previous_count);
+            assert!(result_object.results.iter().all(|result| {
+                expected[..50]
+                    .iter()
+                    .any(|(doc_id, _)| *doc_id == result.doc_id)
+            }));
+            previous_count = result_object.result_count_total;
+
+            // pages of an ANN search are slices of the complete result
+            let page = index_arc
+                .search_radius(
+                    Embedding::F32(query.clone()),
+                    radius,
+                    AnnMode::Nprobe(nprobe),
+                    1,
+                    2,
+                    true,
+                    Vec::new(),
+                )
+                .await;
+            assert_eq!(page.result_count_total, result_object.result_count_total);
+            assert_eq!(
+                page.results
+                    .iter()
+                    .map(|result| result.doc_id)
+                    .collect::<Vec<_>>(),
+                result_object
+                    .results
+                    .iter()
+                    .skip(1)
+                    .take(2)
+                    .map(|result| result.doc_id)
+                    .collect::<Vec<_>>()
+            );
+        }
+        assert_eq!(previous_count, 50, "{similarity:?}");
+
+        index_arc.close().await;
+    }
+}
+
+#[tokio::test]
+/// batch search returns the results of the single queries
+async fn test_10_batch_matches_single_queries() {
+    let vectors = test_vectors(1200, 32, 31);
+    let queries = test_vectors(20, 32, 32);
+
+    for (name, similarity, quantization, clustering, ann_mode) in [
+        (
+            "batch_exhaustive",
+            VectorSimilarity::Euclidean,
+            Quantization::None,
+            Clustering::None,
+            AnnMode::All,
+        ),
+        (
+            "batch_nprobe",
+            VectorSimilarity::Cosine,
+            Quantization::None,
+            Clustering::Fixed(8),
+            AnnMode::Nprobe(3),
+        ),
+        (
+            "batch_i8",
+            VectorSimilarity::Dot,
+            Quantization::ScalarQuantizationI8,
+            Clustering::Fixed(8),
+            AnnMode::Nprobe(3),
+        ),
+        (
+            "batch_hnsw",
+            VectorSimilarity::Euclidean,
+            Quantization::None,
+            Clustering::Hnsw {
+                m: 8,
+                ef_construction: 50,
+            },
+            AnnMode::EfSearch(32),
+        ),
+        (
+            "batch_binary",
+            VectorSimilarity::Cosine,
+            Quantization::Binary { oversampling: 4 },
+            Clustering::None,
+            AnnMode::All,
+        ),
+    ] {
+        let meta = test_vector_meta(32, similarity, quantization, clustering);
+        let index_arc = create_test_index_meta(name, meta, TITLE_VECTOR_SCHEMA, 2).await;
+        // committed and uncommitted documents
+        index_test_vectors(&index_arc, &vectors[..1000]).await;
+        let documents: Vec<Document> = vectors[1000..]
+            .iter()
+            .map(|vector| Document::from([("vector".to_string(), serde_json::json!(vector))]))
+            .collect();
+        index_arc.index_documents(documents).await;
+
+        let batch_results = index_arc
+            .search_batch(
+                queries.iter().cloned().map(Embedding::F32).collect(),
+                10,
+                None,
+                ann_mode.clone(),
+                ChunkAggregation::Max,
+                true,
+                Vec::new(),
+            )
+            .await;
+        assert_eq!(batch_results.len(), queries.len());
+
+        for (query, batch_result) in queries.iter().zip(batch_results.iter()) {
+            let result_object = index_arc
+                .search(
+                    String::new(),
+                    Some(Embedding::F32(query.clone())),
+                    QueryType::Union,
+                    SearchMode::Vector {
+                        similarity_threshold: None,
+                        ann_mode: ann_mode.clone(),
+                        chunk_aggregation: ChunkAggregation::Max,
+                    },
+                    false,
+                    0,
+                    10,
+                    ResultType::Topk,
+                    true,
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                    Vec::new(),
+                    QueryRewriting::SearchOnly,
+                )
+                .await;
+            assert_eq!(batch_result.results.len(), 10, "{name}");
+            assert_eq!(batch_result.results.len(), result_object.results.len());
+            for (batch, single) in batch_result
+                .results
+                .iter()
+                .zip(result_object.results.iter())
+            {
+                assert_eq!(batch.doc_id, single.doc_id, "{name}");
+                assert!((batch.score - single.score).abs() < 1e-5, "{name}");
+            }
+        }
+
+        index_arc.close().await;
+    }
+}
+
+/// The results have the exact score and the scores of the top-k of the brute force ranking.
+fn assert_exact(results: &[(usize, f32)], expected: &[(usize, f32)], length: usize, epsilon: f32) {
+    assert_eq!(results.len(), length.min(expected.len()));
+    let exact: AHashMap<usize, f32> = expected.iter().copied().collect();
+    for ((doc_id, score), (_, expected_score)) in results.iter().zip(expected.iter()) {
+        assert!((score - exact[doc_id]).abs() < epsilon, "doc {doc_id}");
+        assert!((score - expected_score).abs() < epsilon, "doc {doc_id}");
+    }
+}
+
+async fn search_max_sim(
+    index_arc: &IndexArc,
+    query: &[Vec<f32>],
+    ann_mode: AnnMode,
+    candidate_count: usize,
+    length: usize,
+    include_uncommitted: bool,
+) -> Vec<(usize, f32)> {
+    index_arc
+        .search(
+            String::new(),
+            None,
+            QueryType::Union,
+            SearchMode::LateInteraction {
+                query_vectors: query.to_vec(),
+                ann_mode,
+                candidate_count,
+            },
+            false,
+            0,
+            length,
+            ResultType::Topk,
+            include_uncommitted,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            QueryRewriting::SearchOnly,
+        )
+        .await
+        .results
+        .iter()
+        .map(|result| (result.doc_id, result.score))
+        .collect()
+}
+
+/// Exact MaxSim (dot product) of the query with the token vectors of a document.
+fn max_sim(query: &[Vec<f32>], vectors: &[Vec<f32>]) -> f32 {
+    query
+        .iter()
+        .map(|query_vector| {
+            vectors
+                .iter()
+                .map(|vector| dot(query_vector, vector))
+                .fold(f32::MIN, f32::max)
+        })
+        .sum()
+}
+
+#[tokio::test]
+/// late interaction: token vectors stored in the vector index, candidates from all or the nearest clusters scored with exact MaxSim,
+/// uncommitted and deleted documents, reopened index
+async fn test_11_late_interaction_search() {
+    // 1 to 4 token vectors with 8 dimensions per document
+    let documents: Vec<Vec<Vec<f32>>> = {
+        let mut vectors = test_vectors(300 * 4, 8, 11).into_iter();
+        (0..300)
+            .map(|doc_id| vectors.by_ref().take(1 + doc_id % 4).collect())
+            .collect()
+    };
+    let query = test_vectors(4, 8, 12);
+
+    // exact MaxSim of the query with all documents, in descending order
+    let mut expected: Vec<(usize, f32)> = documents
+        .iter()
+        .enumerate()
+        .map(|(doc_id, vectors)| (doc_id, max_sim(&query, vectors)))
+        .collect();
+    expected.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let documents_json = serde_json::to_string(
+        &documents
+            .iter()
+            .map(|vectors| serde_json::json!({ "tokens": vectors }))
+            .collect::<Vec<_>>(),
+    )
+    .unwrap();
+
+    for (clustering, shard_number) in [
+        (Clustering::None, 1),
+        (Clustering::None, 2),
+        (Clustering::Fixed(20), 1),
+        (Clustering::Fixed(20), 2),
+    ] {
+        let name = format!("late_interaction_{clustering}_{shard_number}");
+        let meta = test_vector_meta(8, VectorSimilarity::Dot, Quantization::None, clustering);
+        let schema = r#"[{"field":"title","field_type":"Text","store":true,"index_lexical":true},
+            {"field":"tokens","field_type":"MultiVector","store":false,"index_lexical":false}]"#;
+        let mut index_arc = create_test_index_meta(&name, meta, schema, shard_number).await;
+        index_test_documents(&index_arc, &documents_json).await;
+
+        // with all token vectors as candidates the ranking is exact
+        let candidate_count = documents.len() * 4;
+        for length in [1, 10, 300] {
+            let results = search_max_sim(
+                &index_arc,
+                &query,
+                AnnMode::All,
+                candidate_count,
+                length,
+                false,
+            )
+            .await;
+            assert_exact(&results, &expected, length, 1e-4);
+        }
+
+        // with few candidates from the nearest clusters only, the candidates are still scored with their exact MaxSim
+        let results = search_max_sim(&index_arc, &query, AnnMode::Nprobe(2), 20, 10, false).await;
+        assert!(!results.is_empty(), "{name}");
+        let exact: AHashMap<usize, f32> = expected.iter().copied().collect();
+        assert!(
+            results
+                .iter()
+                .all(|(doc_id, score)| (exact[doc_id] - score).abs() < 1e-4),
+            "{name}"
+        );
+
+        // uncommitted documents are only searched with include_uncommitted, their token vectors are scored from the uncommitted vectors
+        let uncommitted_doc_id = documents.len();
+        // the scaled query vectors: the document with the highest MaxSim
+        let uncommitted_vectors: Vec<Vec<f32>> = query
+            .iter()
+            .map(|query_vector| query_vector.iter().map(|x| x * 3.0).collect())
+            .collect();
+        index_arc
+            .index_documents(vec![Document::from([(
+                "tokens".to_string(),
+                serde_json::json!(uncommitted_vectors),
+            )])])
+            .await;
+        let results =
+            search_max_sim(&index_arc, &query, AnnMode::All, candidate_count, 10, false).await;
+        assert_exact(&results, &expected, 10, 1e-4);
+        let results =
+            search_max_sim(&index_arc, &query, AnnMode::All, candidate_count, 1, true).await;
+        assert_eq!(results[0].0, uncommitted_doc_id, "{name}");
+        assert!((results[0].1 - max_sim(&query, &uncommitted_vectors)).abs() < 1e-4);
+        index_arc.commit().await;
+        let results =
+            search_max_sim(&index_arc, &query, AnnMode::All, candidate_count, 1, false).await;
+        assert_eq!(results[0].0, uncommitted_doc_id, "{name}");
+
+        // deleted documents are skipped, also when the index is reopened
+        index_arc.delete_document(uncommitted_doc_id as u64).await;
+        let mut expected_deleted = expected.clone();
+        let (deleted_doc_id, _) = expected_deleted.remove(0);
+        index_arc.delete_document(deleted_doc_id as u64).await;
+        index_arc.close().await;
+        index_arc = open_index(&test_index_dir(&name)).await.unwrap();
+        let results =
+            search_max_sim(&index_arc, &query, AnnMode::All, candidate_count, 10, false).await;
+        assert_exact(&results, &expected_deleted, 10, 1e-4);
+        index_arc.close().await;
+    }
+
+    // the token vectors are stored in the vector index, which requires a vector inference setting
+    let schema = serde_json::from_str(
+        r#"[{"field":"tokens","field_type":"MultiVector","store":false,"index_lexical":false}]"#,
+    )
+    .unwrap();
+    assert!(
+        create_index(
+            &test_index_path("late_interaction_without_inference"),
+            test_meta(),
+            &schema,
+            &Vec::new(),
+            11,
+            true,
+            Some(1),
+        )
+        .await
+        .is_err()
+    );
+}
+
+const SPARSE_TERMS: usize = 16;
+const SPARSE_BOOST: f32 = 2.0;
+
+fn sparse_query_vector() -> SparseVector {
+    SparseVector::from_iter([
+        ("t0".to_string(), 0.9),
+        ("t3".to_string(), 0.5),
+        ("t7".to_string(), 1.3),
+        ("t11".to_string(), 0.2),
+        ("unknown".to_string(), 1.0),
+        ("t12".to_string(), -1.0),
+    ])
+}
+
+/// Document weight as indexed: quantized to a resolution of 1/16, negative weights are absent terms.
+fn quantized_sparse_weight(weight: f32) -> f32 {
+    if weight > 0.0 {
+        (weight * 16.0).round().clamp(1.0, 1023.0) / 16.0
+    } else {
+        0.0
+    }
+}
+
+/// Exact dot products of the query with the quantized document sparse vectors, in descending order.
+fn sparse_brute_force(vectors: &[Vec<f32>], query: &SparseVector) -> Vec<(usize, f32)> {
+    let mut scores: Vec<(usize, f32)> = vectors
+        .iter()
+        .enumerate()
+        .map(|(doc_id, vector)| {
+            let score = query
+                .iter()
+                .filter(|(_, query_weight)| **query_weight > 0.0)
+                .filter_map(|(term, query_weight)| {
+                    let term_id: usize = term.strip_prefix('t')?.parse().ok()?;
+                    Some(query_weight * SPARSE_BOOST * quantized_sparse_weight(vector[term_id]))
+                })
+                .sum();
+            (doc_id, score)
+        })
+        .filter(|(_, score)| *score > 0.0)
+        .collect();
+    scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scores
+}
+
+/// Sparse vector documents with the weights of test_vectors.
+fn sparse_documents(vectors: &[Vec<f32>]) -> String {
+    let documents: Vec<Document> = vectors
+        .iter()
+        .map(|vector| {
+            let sparse_vector: serde_json::Map<String, Value> = vector
+                .iter()
+                .enumerate()
+                .map(|(term_id, weight)| (format!("t{term_id}"), serde_json::json!(weight)))
+                .collect();
+            Document::from([("sparse".to_string(), Value::Object(sparse_vector))])
+        })
+        .collect();
+    serde_json::to_string(&documents).unwrap()
+}
+
+async fn search_sparse_vector(
+    index_arc: &IndexArc,
+    query: &SparseVector,
+    length: usize,
+) -> Vec<(usize, f32)> {
+    index_arc
+        .search(
+            String::new(),
+            None,
+            QueryType::Union,
+            SearchMode::Sparse {
+                sparse_vector: query.clone(),
+            },
+            false,
+            0,
+            length,
+            ResultType::Topk,
+            false,
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            QueryRewriting::SearchOnly,
+        )
+        .await
+        .results
+        .iter()
+        .map(|result| (result.doc_id, result.score))
+        .collect()
+}
+
+#[tokio::test]
+/// sparse vectors: exact dot product top-k with block-max WAND of the inverted index, deleted documents, reopened index, visible after commit
+async fn test_12_sparse_dot_product() {
+    let vectors = test_vectors(600, SPARSE_TERMS, 7);
+    let query = sparse_query_vector();
+
+    for (shard_number, access_type) in [
+        (1, AccessType::Mmap),
+        (2, AccessType::Mmap),
+        (2, AccessType::Ram),
+    ] {
+        let name = format!("sparse_{shard_number}_{access_type:?}");
+        let schema = format!(
+            r#"[{{"field":"title","field_type":"Text","store":true,"index_lexical":true}},
+            {{"field":"sparse","field_type":"SparseVector","store":false,"index_lexical":false,"boost":{SPARSE_BOOST}}}]"#
+        );
+        let meta = IndexMetaObject {
+            access_type,
+            ..test_meta()
+        };
+        let mut index_arc = create_test_index_meta(&name, meta, &schema, shard_number).await;
+        index_test_documents(&index_arc, &sparse_documents(&vectors)).await;
+
+        // the pruning of block-max WAND must not change the top-k
+        let mut expected = sparse_brute_force(&vectors, &query);
+        for length in [1, 10, 100, 1000] {
+            let results = search_sparse_vector(&index_arc, &query, length).await;
+            assert_exact(&results, &expected, length, 1e-5);
+        }
+
+        // deleted documents are skipped, also when the index is reopened
+        let deleted_doc_id = expected[0].0;
+        index_arc.delete_document(deleted_doc_id as u64).await;
+        expected.remove(0);
+        let results = search_sparse_vector(&index_arc, &query, 10).await;
+        assert_exact(&results, &expected, 10, 1e-5);
+
+        index_arc.close().await;
+        index_arc = open_index(&test_index_dir(&name)).await.unwrap();
+        let results = search_sparse_vector(&index_arc, &query, 10).await;
+        assert_exact(&results, &expected, 10, 1e-5);
+
+        // the postings of a new document are not visible before commit
+        let document: Document =
+            serde_json::from_str(r#"{"sparse":{"t7":20.0,"t0":-1.0}}"#).unwrap();
+        index_arc.index_documents(vec![document]).await;
+        let results = search_sparse_vector(&index_arc, &query, 10).await;
+        assert_exact(&results, &expected, 10, 1e-5);
+        index_arc.commit().await;
+        let results = search_sparse_vector(&index_arc, &query, 10).await;
+        assert_eq!(results[0].0, vectors.len(), "{name}");
+        assert!(
+            (results[0].1 - 1.3 * SPARSE_BOOST * 20.0).abs() < 1e-4,
+            "{name}"
+        );
+        index_arc.close().await;
+    }
+}
+
+#[cfg(feature = "vb")]
+#[tokio::test]
+/// MMR diversification by lambda and grouping by field value
+async fn test_13_mmr_diversity_order() {
+    use seekstorm::diversify::{Diversify, DiversifyGroup};
+
+    // documents 0, 1 and 2 are near-duplicates, their squared euclidean distances to the query are 0, 0.01, 0.0225, 0.25 and 0.36
+    const DOCUMENTS: &str = r#"
+    [{"title":"a","group":"x","vector":[1,0.3,0,0,0,0,0,0]},
+    {"title":"b","group":"x","vector":[1,0.3,0.1,0,0,0,0,0]},
+    {"title":"c","group":"x","vector":[1,0.3,0,0.15,0,0,0,0]},
+    {"title":"d","group":"y","vector":[1,0.8,0,0,0,0,0,0]},
+    {"title":"e","vector":[0.4,0.3,0,0,0,0,0,0]}]"#;
+
+    for shard_number in [1, 2] {
+        let meta = test_vector_meta(
+            8,
+            VectorSimilarity::Euclidean,
+            Quantization::None,
+            Clustering::None,
+        );
+        let schema = r#"[{"field":"title","field_type":"Text","store":true,"index_lexical":true},
+        {"field":"group","field_type":"String16","store":true,"index_lexical":false},
+        {"field":"vector","field_type":"Json","store":false,"index_lexical":false,"index_vector":true}]"#;
+        let index_arc = create_test_index_meta(
+            &format!("diversify_{shard_number}"),
+            meta,
+            schema,
+            shard_number,
+        )
+        .await;
+        index_test_documents(&index_arc, DOCUMENTS).await;
+
+        let results = index_arc
+            .search(
+                String::new(),
+                Some(Embedding::F32(vec![1.0, 0.3, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0])),
+                QueryType::Union,
+                SearchMode::Vector {
+                    similarity_threshold: None,
+                    ann_mode: AnnMode::All,
+                    chunk_aggregation: ChunkAggregation::Max,
+                },
+                false,
+                0,
+                10,
+                ResultType::Topk,
+                false,
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                QueryRewriting::SearchOnly,
+            )
+            .await
+            .results;
+        let doc_ids: Vec<usize> = results.iter().map(|result| result.doc_id).collect();
+        assert_eq!(doc_ids, vec![0, 1, 2, 3, 4]);
+
+        // lambda 1.0: relevance only, lambda 0.5: the near-duplicates are still the most relevant,
+        // lambda 0.3 and 0.0: the least redundant results are selected before the near-duplicates
+        for (lambda, expected) in [
+            (1.0, vec![0, 1, 2, 3, 4]),
+            (0.5, vec![0, 1, 2, 3, 4]),
+            (0.3, vec![0, 4, 3, 2, 1]),
+            (0.0, vec![0, 4, 3, 2, 1]),
+        ] {
+            let mut diversified = results.clone();
+            index_arc
+                .read()
+                .await
+                .diversify(
+                    &mut diversified,
+                    &Diversify {
+                        lambda: Some(lambda),
+                        ..Default::default()
+                    },
+                    false,
+                )
+                .await;
+            let doc_ids: Vec<usize> = diversified.iter().map(|result| result.doc_id).collect();
+            assert_eq!(doc_ids, expected, "lambda {lambda}");
+        }
+
+        // at most one result per value of the group field, results without value are not grouped
+        let mut diversified = results.clone();
+        index_arc
+            .read()
+            .await
+            .diversify(
+                &mut diversified,
+                &Diversify {
+                    max_per_group: Some(1),
+                    group_by: DiversifyGroup::Field("group".into()),
+                    ..Default::default()
+                },
+                false,
+            )
+            .await;
+        let doc_ids: Vec<usize> = diversified.iter().map(|result| result.doc_id).collect();
+        assert_eq!(doc_ids, vec![0, 3, 4]);
+
+        index_arc.close().await;
+    }
+}
+
+#[cfg(feature = "vb")]
+#[tokio::test]
+/// Rocchio query expansion: the expanded query vector is re-run
+async fn test_14_rocchio_expansion() {
+    use seekstorm::query_expansion::{QueryExpansion, SearchExpanded};
+
+    let vectors = test_vectors(200, 8, 61);
+    let query = test_vectors(1, 8, 62).remove(0);
+    let weight = 0.5;
+    let normalized = |vector: &[f32]| {
+        let norm = dot(vector, vector).sqrt();
+        vector
+            .iter()
+            .map(|value| value / norm)
+            .collect::<Vec<f32>>()
+    };
+
+    for shard_number in [1, 2] {
+        let meta = test_vector_meta(
+            8,
+            VectorSimilarity::Cosine,
+            Quantization::None,
+            Clustering::None,
+        );
+        let index_arc = create_test_index_meta(
+            &format!("query_expansion_vector_{shard_number}"),
+            meta,
+            TITLE_VECTOR_SCHEMA,
+            shard_number,
+        )
+        .await;
+        index_test_vectors(&index_arc, &vectors).await;
+
+        // expanded query vector: normalize(0.5 * normalize(query) + 0.5 * mean(normalize(feedback embeddings)))
+        let feedback = search_doc_ids(&index_arc, &query, AnnMode::All, 5).await;
+        let mut expected = normalized(&query);
+        for (i, value) in expected.iter_mut().enumerate() {
+            let mean = feedback
+                .iter()
+                .map(|doc_id| normalized(&vectors[*doc_id])[i])
+                .sum::<f32>()
+                / feedback.len() as f32;
+            *value = (1.0 - weight) * *value + weight * mean;
+        }
+        let expected = normalized(&expected);
+
+        // the expanded query vector is re-run, the results are those of a search with the expanded vector
+        let result_object = index_arc
+            .search_expanded(
+                String::new(),
+                Some(Embedding::F32(query.clone())),
+                QueryType::Union,
+                SearchMode::Vector {
+                    similarity_threshold: None,
+                    ann_mode: AnnMode::All,
+                    chunk_aggregation: ChunkAggregation::Max,
+                },
+                false,
+                0,
+                10,
+                ResultType::Topk,
+                false,
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                QueryRewriting::SearchOnly,
+                &QueryExpansion {
+                    depth: 5,
+                    weight,
+                    ..Default::default()
+                },
+                None,
+            )
+            .await;
+        let rerun = search_vector(&index_arc, &expected, AnnMode::All, 10).await;
+        assert_eq!(result_object.results.len(), rerun.len());
+        for (result, (doc_id, score)) in result_object.results.iter().zip(rerun.iter()) {
+            assert_eq!(result.doc_id, *doc_id);
+            assert!((result.score - score).abs() < 1e-5);
+        }
+        // the feedback documents stay close to the expanded query vector
+        assert!(
+            result_object.results[..5]
+                .iter()
+                .filter(|result| feedback.contains(&result.doc_id))
+                .count()
+                >= 3
+        );
+
+        index_arc.close().await;
+    }
+}
+
+/// Minimal HTTP server: answers the n-th request with the n-th (status, body) response, the last response is repeated.
+/// Returns the url of the embeddings endpoint and the number of received requests.
+async fn serve_embeddings(
+    responses: Vec<(u16, String)>,
+) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}/v1/embeddings", listener.local_addr().unwrap());
+    let request_count = Arc::new(AtomicUsize::new(0));
+    let request_count_clone = request_count.clone();
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                break;
+            };
+            let mut request = Vec::new();
+            let mut buffer = [0u8; 4096];
+            loop {
+                let n = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..n]);
+                let request_string = String::from_utf8_lossy(&request);
+                if let Some(header_end) = request_string.find("\r\n\r\n") {
+                    let content_length = request_string[..header_end]
+                        .lines()
+                        .find_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|value| value.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= header_end + 4 + content_length {
+                        break;
+                    }
+                }
+                if n == 0 {
+                    break;
+                }
+            }
+
+            let n = request_count_clone.fetch_add(1, Ordering::SeqCst);
+            let (status, body) = &responses[n.min(responses.len() - 1)];
+            let response = format!(
+                "HTTP/1.1 {status} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.shutdown().await.unwrap();
+        }
+    });
+    (url, request_count)
+}
+
+fn embedding_provider(
+    url: &str,
+    max_retries: usize,
+    cache_path: Option<&std::path::Path>,
+) -> seekstorm::embedding_provider::OpenAiEmbeddingProvider {
+    seekstorm::embedding_provider::OpenAiEmbeddingProvider::new(
+        url,
+        "model",
+        2,
+        Some("key".into()),
+        8,
+        max_retries,
+        cache_path,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+/// OpenAI-compatible embedding endpoint: response order, retries, rejected responses
+async fn test_15_embedding_provider_response_order_and_retries() {
+    use seekstorm::embedding_provider::EmbeddingProvider;
+    use std::sync::atomic::Ordering;
+
+    // the endpoint fails twice with retryable errors, then returns the embeddings out of order
+    let (url, request_count) = serve_embeddings(vec![
+        (503, String::new()),
+        (429, String::new()),
+        (
+            200,
+            r#"{"data":[{"embedding":[3,3],"index":2},{"embedding":[1,1],"index":0},{"embedding":[2,2],"index":1}]}"#
+                .into(),
+        ),
+    ])
+    .await;
+    let texts: Vec<String> = vec!["a".into(), "b".into(), "c".into()];
+    let embeddings = embedding_provider(&url, 2, None)
+        .embed(&texts)
+        .await
+        .unwrap();
+    assert_eq!(
+        embeddings,
+        vec![vec![1.0, 1.0], vec![2.0, 2.0], vec![3.0, 3.0]]
+    );
+    assert_eq!(request_count.load(Ordering::SeqCst), 3);
+
+    // retries are exhausted
+    let (url, request_count) = serve_embeddings(vec![(500, String::new())]).await;
+    let error = embedding_provider(&url, 1, None)
+        .embed(&texts)
+        .await
+        .unwrap_err();
+    assert!(error.contains("after 1 retries"), "{error}");
+    assert_eq!(request_count.load(Ordering::SeqCst), 2);
+
+    // client errors are not retried
+    let (url, request_count) = serve_embeddings(vec![(400, "bad request".into())]).await;
+    let error = embedding_provider(&url, 3, None)
+        .embed(&texts)
+        .await
+        .unwrap_err();
+    assert!(error.contains("bad request"), "{error}");
+    assert_eq!(request_count.load(Ordering::SeqCst), 1);
+
+    // embeddings with wrong dimensions or count are rejected
+    let (url, _) = serve_embeddings(vec![(
+        200,
+        r#"{"data":[{"embedding":[1,1,1],"index":0}]}"#.into(),
+    )])
+    .await;
+    assert!(
+        embedding_provider(&url, 0, None)
+            .embed(&texts[..1])
+            .await
+            .is_err()
+    );
+    assert!(
+        embedding_provider(&url, 0, None)
+            .embed(&texts)
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+/// cached texts are not requested again, also after reloading the cache file
+async fn test_16_embedding_provider_cache() {
+    use seekstorm::embedding_provider::EmbeddingProvider;
+    use std::sync::atomic::Ordering;
+
+    let cache_path = test_index_path("embedding_cache");
+    std::fs::create_dir_all(&cache_path).unwrap();
+    let (url, request_count) = serve_embeddings(vec![(
+        200,
+        r#"{"data":[{"embedding":[1,2],"index":0},{"embedding":[3,4],"index":1}]}"#.into(),
+    )])
+    .await;
+    let texts: Vec<String> = vec!["a".into(), "b".into()];
+    let expected = vec![vec![1.0, 2.0], vec![3.0, 4.0]];
+
+    let provider = embedding_provider(&url, 0, Some(&cache_path));
+    assert_eq!(provider.embed(&texts).await.unwrap(), expected);
+    assert_eq!(provider.embed(&texts).await.unwrap(), expected);
+    assert_eq!(request_count.load(Ordering::SeqCst), 1);
+    let provider = embedding_provider(&url, 0, Some(&cache_path));
+    assert_eq!(provider.embed(&texts).await.unwrap(), expected);
+    assert_eq!(request_count.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+/// hybrid search: the sparse vector results are fused with the lexical and vector results by their ranks (RRF)
+async fn test_17_hybrid_sparse_fusion() {
+    // lexical ranks: doc 0; vector ranks: doc 1, 0, 2; sparse ranks: doc 2, 1, 0
+    const DOCUMENTS: &str = r#"
+    [{"title":"alpha","vector":[0.6,0.8,0,0,0,0,0,0],"sparse":{"s":0.125}},
+    {"title":"beta","vector":[1,0,0,0,0,0,0,0],"sparse":{"s":0.5}},
+    {"title":"gamma","vector":[0.28,0.96,0,0,0,0,0,0],"sparse":{"s":2.0}}]"#;
+    // reciprocal rank fusion constant of hybrid search
+    const RRF_K: f32 = 0.6;
+
+    let schema = r#"[{"field":"title","field_type":"Text","store":true,"index_lexical":true},
+    {"field":"vector","field_type":"Json","store":false,"index_lexical":false,"index_vector":true},
+    {"field":"sparse","field_type":"SparseVector","store":false,"index_lexical":false}]"#;
+    let meta = test_vector_meta(
+        8,
+        VectorSimilarity::Dot,
+        Quantization::None,
+        Clustering::None,
+    );
+    let index_arc = create_test_index_meta("hybrid_sparse", meta, schema, 1).await;
+    index_test_documents(&index_arc, DOCUMENTS).await;
+
+    let search_hybrid = async |sparse_vector: Option<SparseVector>| -> Vec<(usize, f32)> {
+        index_arc
+            .search(
+                "alpha".to_string(),
+                Some(Embedding::F32(vec![1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0])),
+                QueryType::Union,
+                SearchMode::Hybrid {
+                    similarity_threshold: None,
+                    ann_mode: AnnMode::All,
+                    chunk_aggregation: ChunkAggregation::Max,
+                    sparse_vector,
+                },
+                false,
+                0,
+                10,
+                ResultType::Topk,
+                false,
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                QueryRewriting::SearchOnly,
+            )
+            .await
+            .results
+            .iter()
+            .map(|result| (result.doc_id, result.score))
+            .collect()
+    };
+
+    let results_without_sparse: AHashMap<usize, f32> =
+        search_hybrid(None).await.into_iter().collect();
+    let results = search_hybrid(Some(SparseVector::from_iter([("s".to_string(), 1.0)]))).await;
+
+    // each document gains the reciprocal rank of its sparse result
+    assert_eq!(
+        results
+            .iter()
+            .map(|(doc_id, _)| *doc_id)
+            .collect::<Vec<_>>(),
+        vec![0, 1, 2]
+    );
+    for (sparse_rank, doc_id) in [2, 1, 0].into_iter().enumerate() {
+        let score = results.iter().find(|result| result.0 == doc_id).unwrap().1;
+        let sparse_score = 1.0 / (RRF_K + sparse_rank as f32);
+        assert!(
+            (score - results_without_sparse[&doc_id] - sparse_score).abs() < 1e-5,
+            "doc {doc_id}"
+        );
+    }
+
+    // doc 0 is found by all three sources
+    let score_0 = 1.0 / RRF_K + 1.0 / (RRF_K + 1.0) + 1.0 / (RRF_K + 2.0);
+    assert!((results[0].1 - score_0).abs() < 1e-5);
+}
+
+#[tokio::test]
+/// n-probe search of a shard with a level without vectors: the empty level is skipped
+async fn test_18_level_without_vectors() {
+    let meta = test_vector_meta(
+        8,
+        VectorSimilarity::Euclidean,
+        Quantization::None,
+        Clustering::Fixed(8),
+    );
+    let index_arc =
+        create_test_index_meta("vector_empty_level", meta, TITLE_VECTOR_SCHEMA, 1).await;
+    index_test_vectors(&index_arc, &test_vectors(1000, 8, 91)).await;
+
+    // completes the first level and adds a second level without vectors
+    let documents: Vec<Document> = (0..ROARING_BLOCK_SIZE)
+        .map(|_| Document::from([("title".to_string(), serde_json::json!("a"))]))
+        .collect();
+    index_arc.index_documents(documents).await;
+    index_arc.commit().await;
+    assert_eq!(index_arc.read().await.level_count().await, 2);
+
+    for query in test_vectors(5, 8, 92).iter() {
+        let exact = search_doc_ids(&index_arc, query, AnnMode::All, 10).await;
+        assert_eq!(exact.len(), 10);
+        assert_eq!(
+            search_doc_ids(&index_arc, query, AnnMode::Nprobe(8), 10).await,
+            exact
+        );
+        assert_eq!(
+            search_doc_ids(&index_arc, query, AnnMode::Nprobe(1), 10)
+                .await
+                .len(),
+            10
+        );
+    }
+    index_arc.close().await;
+}
+
+#[tokio::test]
+/// vector search with include_uncommitted after a commit of an incomplete level: uncommitted vectors have their own doc ids
+async fn test_19_uncommitted_doc_ids() {
+    let vectors = test_vectors(150, 8, 95);
+    let meta = test_vector_meta(
+        8,
+        VectorSimilarity::Euclidean,
+        Quantization::None,
+        Clustering::None,
+    );
+    let index_arc = create_test_index_meta("vector_uncommitted", meta, VECTOR_SCHEMA, 2).await;
+    index_test_vectors(&index_arc, &vectors[..100]).await;
+    let documents: Vec<Document> = vectors[100..]
+        .iter()
+        .map(|vector| Document::from([("vector".to_string(), serde_json::json!(vector))]))
+        .collect();
+    index_arc.index_documents(documents).await;
+
+    for doc_id in [0, 57, 99, 100, 101, 149] {
+        let results = index_arc
+            .search(
+                String::new(),
+                Some(Embedding::F32(vectors[doc_id].clone())),
+                QueryType::Union,
+                SearchMode::Vector {
+                    similarity_threshold: None,
+                    ann_mode: AnnMode::All,
+                    chunk_aggregation: ChunkAggregation::Max,
+                },
+                false,
+                0,
+                1,
+                ResultType::Topk,
+                true,
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                QueryRewriting::SearchOnly,
+            )
+            .await
+            .results;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].doc_id, doc_id);
+    }
+    index_arc.close().await;
+}
+
+#[cfg(feature = "vb")]
+#[tokio::test]
+/// searches of all clusters return the cluster ids of the results, like n-probe search of all clusters
+async fn test_20_cluster_ids_of_all_clusters() {
+    use std::collections::HashSet;
+
+    let meta = test_vector_meta(
+        8,
+        VectorSimilarity::Euclidean,
+        Quantization::None,
+        Clustering::Fixed(8),
+    );
+    let index_arc = create_test_index_meta("vector_cluster_ids", meta, VECTOR_SCHEMA, 1).await;
+    index_test_vectors(&index_arc, &test_vectors(1000, 8, 97)).await;
+
+    let search_cluster_ids = async |query: &[f32], ann_mode: AnnMode| -> Vec<(usize, u32)> {
+        index_arc
+            .search(
+                String::new(),
+                Some(Embedding::F32(query.to_vec())),
+                QueryType::Union,
+                SearchMode::Vector {
+                    similarity_threshold: None,
+                    ann_mode,
+                    chunk_aggregation: ChunkAggregation::Max,
+                },
+                false,
+                0,
+                50,
+                ResultType::Topk,
+                false,
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+                QueryRewriting::SearchOnly,
+            )
+            .await
+            .results
+            .iter()
+            .map(|result| (result.doc_id, result.cluster_id))
+            .collect()
+    };
+
+    let mut cluster_ids = HashSet::new();
+    for query in test_vectors(5, 8, 98).iter() {
+        let results = search_cluster_ids(query, AnnMode::All).await;
+        assert_eq!(results, search_cluster_ids(query, AnnMode::Nprobe(8)).await);
+        cluster_ids.extend(results.iter().map(|(_, cluster_id)| *cluster_id));
+    }
+    assert!(cluster_ids.len() > 1);
+    index_arc.close().await;
+}
+
+#[tokio::test]
+/// create_index and open_index return an error instead of panicking if the Model2Vec model can't be loaded
+async fn test_21_model2vec_missing_model() {
+    // a model directory without model files
+    let model_path = test_index_path("model2vec_missing_model");
+    std::fs::create_dir_all(&model_path).unwrap();
+    let inference = Inference::Model2VecCustom {
+        path: model_path.to_string_lossy().to_string(),
+        chunk_size: 1000,
+        quantization: Quantization::None,
+    };
+    let meta = IndexMetaObject {
+        inference: inference.clone(),
+        ..test_meta()
+    };
+    let schema = serde_json::from_str(
+        r#"[{"field":"title","field_type":"Text","store":true,"index_lexical":true,"index_vector":true}]"#,
+    )
+    .unwrap();
+
+    let index_path = test_index_path("model2vec_missing_model_index");
+    assert!(
+        create_index(&index_path, meta, &schema, &Vec::new(), 11, true, Some(1))
+            .await
+            .is_err_and(|e| e.contains("Unable to load model"))
+    );
+
+    // an index whose meta references the model directory
+    let index_arc = create_test_index_meta(
+        "model2vec_missing_model_index",
+        test_meta(),
+        TITLE_VECTOR_SCHEMA,
+        1,
+    )
+    .await;
+    index_arc.close().await;
+    let meta_path = index_path.join("index.json");
+    let mut meta_json: Value =
+        serde_json::from_str(&std::fs::read_to_string(&meta_path).unwrap()).unwrap();
+    meta_json["inference"] = serde_json::to_value(&inference).unwrap();
+    std::fs::write(&meta_path, meta_json.to_string()).unwrap();
+    assert!(
+        open_index(&index_path)
+            .await
+            .is_err_and(|e| e.contains("Unable to load model"))
+    );
+}
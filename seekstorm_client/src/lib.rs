@@ -233,6 +233,10 @@
 //!   result_type: ResultType::TopkCount,
 //!   query_type_default: QueryType::Intersection,
 //!   search_mode: SearchMode::Lexical,
+//!   diversify: None,
+//!   rerank: None,
+//!   query_expansion: None,
+//!   routing: None,
 //!   realtime: false,
 //!   query_rewriting: QueryRewriting::SearchOnly,
 //!   highlights: Vec::new(),
@@ -307,6 +311,10 @@
 //!   result_type: ResultType::TopkCount,
 //!   query_type_default: QueryType::Intersection,
 //!   search_mode: SearchMode::Lexical,
+//!   diversify: None,
+//!   rerank: None,
+//!   query_expansion: None,
+//!   routing: None,
 //!   realtime: false,
 //!   query_rewriting: QueryRewriting::SearchOnly,
 //!   highlights: Vec::new(),
@@ -285,6 +285,10 @@ async fn test_24_query_index() {
         result_type: ResultType::TopkCount,
         query_type_default: QueryType::Intersection,
         search_mode: SearchMode::Lexical,
+        diversify: None,
+        rerank: None,
+        query_expansion: None,
+        routing: None,
         realtime: false,
         query_rewriting: QueryRewriting::SearchOnly,
         highlights: Vec::new(),
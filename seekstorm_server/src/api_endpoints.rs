@@ -3,6 +3,7 @@ use std::{
     env::current_exe,
     fs::{self, File},
     path::{Path, PathBuf},
+    sync::Arc,
     time::Instant,
 };
 
@@ -19,17 +20,28 @@ use seekstorm::{
         DeleteDocument, DeleteDocuments, DeleteDocumentsByQuery, Document, DocumentCompression,
         FileType, FrequentwordType, GetDocumentRequest, GetIteratorRequest, IS_AVX2, IS_NEON,
         IS_SYSTEM_LE, IndexArc, IndexDocument, IndexDocuments, IndexMetaObject,
-        IndexResponseObject, LexicalSimilarity, QueryCompletion, SchemaField, SearchRequestObject,
+        IndexResponseObject, LexicalSimilarity, QueryCompletion, SchemaField,
+        SearchBatchRequestObject, SearchBatchResult, SearchBatchResultObject,
+        SearchRadiusRequestObject, SearchRadiusResultObject, SearchRequestObject,
         SearchResultObject, SpellingCorrection, StemmerType, StopwordType, Synonym, TokenizerType,
         UpdateDocument, UpdateDocuments, create_index, open_index,
     },
     ingest::IndexPdfBytes,
     iterator::{GetIterator, IteratorResult},
-    search::{Search, SearchMode},
+    query_expansion::SearchExpanded,
+    rerank::Reranker,
+    search::{Search, SearchMode, SearchRouted},
+    search_batch::SearchBatch,
+    search_radius::SearchRadius,
     utils::decode_bytes_from_base64_string,
-    vector::Inference,
+    vector::{Embedding, Inference},
 };
 
+#[cfg(feature = "rerank")]
+use seekstorm::rerank::CrossEncoder;
+#[cfg(feature = "vb")]
+use seekstorm::vector::ResultSource;
+
 use crate::{VERSION, http_server::calculate_hash};
 
 const APIKEY_PATH: &str = "apikey.json";
@@ -1277,8 +1289,9 @@ pub(crate) async fn clear_index_api(index_arc: &IndexArc) -> Result<u64, String>
 pub(crate) async fn query_index_api_post(
     index_arc: &IndexArc,
     search_request: SearchRequestObject,
+    reranker: Option<Arc<dyn Reranker>>,
 ) -> SearchResultObject {
-    query_index_api(index_arc, search_request).await
+    query_index_api(index_arc, search_request, reranker).await
 }
 
 /// Query Index
@@ -1313,65 +1326,229 @@ pub(crate) async fn query_index_api_post(
 pub(crate) async fn query_index_api_get(
     index_arc: &IndexArc,
     search_request: SearchRequestObject,
+    reranker: Option<Arc<dyn Reranker>>,
 ) -> SearchResultObject {
-    query_index_api(index_arc, search_request).await
+    query_index_api(index_arc, search_request, reranker).await
 }
 
 use seekstorm::vector::{embedding_from_bytes_be, embedding_from_json};
 
+/// Decodes a query vector: a JSON array of numbers, or a base64 encoded byte string in big endian (network order).
+async fn query_vector_from_value(index_arc: &IndexArc, value: &Value) -> Option<Embedding> {
+    let (vector_precision, vector_dimensions) = {
+        let index_ref = index_arc.read().await;
+        (
+            index_ref.vector_precision,
+            index_ref.vector_dimensions_original,
+        )
+    };
+    match value {
+        Value::String(string_base64) => {
+            if let Ok(bytes) = decode_bytes_from_base64_string(string_base64)
+                && let Some(embedding) = embedding_from_bytes_be(
+                    &bytes,
+                    vector_precision,
+                    vector_dimensions,
+                    *IS_SYSTEM_LE,
+                )
+            {
+                Some(embedding)
+            } else {
+                None
+            }
+        }
+        Value::Array(_) => embedding_from_json(value, vector_precision, vector_dimensions),
+        _ => None,
+    }
+}
+
+/// Rerank models configured on the server, loaded once at startup: model id -> reranker.
+pub(crate) type RerankModels = Arc<HashMap<String, Arc<dyn Reranker>>>;
+
+/// Loads the rerank models of the server parameter rerank_models (comma separated Hugging Face model ids or local paths), each on a blocking thread.
+/// Models that fail to load are reported and rejected in search requests like models that are not configured.
+#[cfg(feature = "rerank")]
+pub(crate) async fn load_rerank_models(models: &str) -> RerankModels {
+    let mut rerank_models: HashMap<String, Arc<dyn Reranker>> = HashMap::new();
+    for model in models
+        .split(',')
+        .map(str::trim)
+        .filter(|model| !model.is_empty())
+    {
+        let model_clone = model.to_string();
+        match tokio::task::spawn_blocking(move || CrossEncoder::load(&model_clone)).await {
+            Ok(Ok(cross_encoder)) => {
+                rerank_models.insert(model.to_string(), Arc::new(cross_encoder));
+            }
+            Ok(Err(e)) => println!("Unable to load rerank model {}: {}", model, e),
+            Err(e) => println!("Unable to load rerank model {}: {}", model, e),
+        }
+    }
+    Arc::new(rerank_models)
+}
+
+/// Without the feature rerank no rerank models are loaded, search requests with rerank are rejected.
+#[cfg(not(feature = "rerank"))]
+pub(crate) async fn load_rerank_models(models: &str) -> RerankModels {
+    if !models.trim().is_empty() {
+        println!("rerank_models ignored: feature rerank is disabled");
+    }
+    Arc::new(HashMap::new())
+}
+
+/// Returns the reranker of the rerank model of a search request, or an error if the model is not configured on the server.
+pub(crate) fn search_request_reranker(
+    rerank_models: &RerankModels,
+    search_request: &SearchRequestObject,
+) -> Result<Option<Arc<dyn Reranker>>, String> {
+    match &search_request.rerank {
+        Some(rerank) => match rerank_models.get(&rerank.model) {
+            Some(reranker) => Ok(Some(reranker.clone())),
+            None => Err(format!(
+                "rerank model {} is not configured on the server",
+                rerank.model
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
 pub(crate) async fn query_index_api(
     index_arc: &IndexArc,
     search_request: SearchRequestObject,
+    reranker: Option<Arc<dyn Reranker>>,
 ) -> SearchResultObject {
     let start_time = Instant::now();
 
     let query_vector = if let Some(value) = search_request.query_vector
         && search_request.search_mode != SearchMode::Lexical
     {
-        match &value {
-            Value::String(string_base64) => {
-                if let Ok(bytes) = decode_bytes_from_base64_string(string_base64)
-                    && let Some(embedding) = embedding_from_bytes_be(
-                        &bytes,
-                        index_arc.read().await.vector_precision,
-                        index_arc.read().await.vector_dimensions_original,
-                        *IS_SYSTEM_LE,
-                    )
-                {
-                    Some(embedding)
-                } else {
-                    None
-                }
-            }
-            Value::Array(_) => embedding_from_json(
-                &value,
-                index_arc.read().await.vector_precision,
-                index_arc.read().await.vector_dimensions_original,
-            ),
-            _ => None,
-        }
+        query_vector_from_value(index_arc, &value).await
     } else {
         None
     };
 
-    let result_object = index_arc
-        .search(
-            search_request.query_string.to_owned(),
-            query_vector,
-            search_request.query_type_default,
-            search_request.search_mode,
-            search_request.enable_empty_query,
-            search_request.offset,
-            search_request.length,
-            search_request.result_type,
-            search_request.realtime,
-            search_request.field_filter,
-            search_request.query_facets,
-            search_request.facet_filter,
-            search_request.result_sort,
-            search_request.query_rewriting,
+    let (offset, length) = if search_request.diversify.is_some() || search_request.rerank.is_some()
+    {
+        (
+            0,
+            (search_request.offset + search_request.length)
+                .max(
+                    search_request
+                        .diversify
+                        .as_ref()
+                        .map_or(0, |diversify| diversify.candidates),
+                )
+                .max(
+                    search_request
+                        .rerank
+                        .as_ref()
+                        .map_or(0, |rerank| rerank.depth),
+                ),
         )
-        .await;
+    } else {
+        (search_request.offset, search_request.length)
+    };
+
+    let mut result_object = if let Some(query_expansion) = &search_request.query_expansion {
+        index_arc
+            .search_expanded(
+                search_request.query_string.to_owned(),
+                query_vector,
+                search_request.query_type_default,
+                search_request.search_mode,
+                search_request.enable_empty_query,
+                offset,
+                length,
+                search_request.result_type,
+                search_request.realtime,
+                search_request.field_filter,
+                search_request.query_facets,
+                search_request.facet_filter,
+                search_request.result_sort,
+                search_request.query_rewriting,
+                query_expansion,
+                search_request.routing.as_deref(),
+            )
+            .await
+    } else if let Some(routing) = &search_request.routing {
+        index_arc
+            .search_routed(
+                search_request.query_string.to_owned(),
+                query_vector,
+                search_request.query_type_default,
+                search_request.search_mode,
+                search_request.enable_empty_query,
+                offset,
+                length,
+                search_request.result_type,
+                search_request.realtime,
+                search_request.field_filter,
+                search_request.query_facets,
+                search_request.facet_filter,
+                search_request.result_sort,
+                search_request.query_rewriting,
+                routing,
+            )
+            .await
+    } else {
+        index_arc
+            .search(
+                search_request.query_string.to_owned(),
+                query_vector,
+                search_request.query_type_default,
+                search_request.search_mode,
+                search_request.enable_empty_query,
+                offset,
+                length,
+                search_request.result_type,
+                search_request.realtime,
+                search_request.field_filter,
+                search_request.query_facets,
+                search_request.facet_filter,
+                search_request.result_sort,
+                search_request.query_rewriting,
+            )
+            .await
+    };
+
+    if let Some(rerank) = &search_request.rerank
+        && let Some(reranker) = reranker
+        && let Err(e) = index_arc
+            .read()
+            .await
+            .rerank(
+                &search_request.query_string,
+                &mut result_object.results,
+                rerank,
+                reranker.as_ref(),
+                search_request.realtime,
+            )
+            .await
+    {
+        println!("Rerank failed: {}", e);
+    }
+
+    if let Some(diversify) = &search_request.diversify {
+        index_arc
+            .read()
+            .await
+            .diversify(
+                &mut result_object.results,
+                diversify,
+                search_request.realtime,
+            )
+            .await;
+    }
+
+    if search_request.diversify.is_some() || search_request.rerank.is_some() {
+        result_object.results = result_object
+            .results
+            .into_iter()
+            .skip(search_request.offset)
+            .take(search_request.length)
+            .collect();
+    }
 
     let elapsed_time = start_time.elapsed().as_nanos();
 
@@ -1410,6 +1587,19 @@ pub(crate) async fn query_index_api(
                     let mut doc = doc;
                     doc.insert("_id".to_string(), result.doc_id.into());
                     doc.insert("_score".to_string(), result.score.into());
+                    #[cfg(feature = "vb")]
+                    if matches!(result.source, ResultSource::Vector | ResultSource::Hybrid)
+                        && let Some(matched_chunk) = index_arc
+                            .read()
+                            .await
+                            .get_matched_chunk(result, search_request.realtime)
+                            .await
+                    {
+                        doc.insert(
+                            "_chunk".to_string(),
+                            serde_json::to_value(matched_chunk).unwrap(),
+                        );
+                    }
 
                     results.push(doc);
                 }
@@ -1429,10 +1619,168 @@ pub(crate) async fn query_index_api(
         query_terms: result_object.query_terms,
         results,
         facets: result_object.facets,
+        geo_grids: result_object.geo_grids,
+        significant_terms: result_object.significant_terms,
         suggestions: result_object.suggestions,
     }
 }
 
+/// Batch Query Index
+///
+/// Vector search with several query vectors at once, e.g. for offline evaluation or near-duplicate detection.
+/// Each selected cluster is scanned once for all query vectors that selected it.
+/// Returns the document ids and scores per query vector.
+#[utoipa::path(
+    post,
+    tag = "Query",
+    path = "/api/v1/index/{index_id}/query_batch",
+    params(
+        ("apikey" = String, Header, description = "YOUR_SECRET_API_KEY",example="AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="),
+        ("index_id" = u64, Path, description = "index id"),
+    ),
+    request_body(
+        content = SearchBatchRequestObject,
+        example = json!({
+            "query_vectors": [[0.1, 0.2, 0.3], [0.3, 0.2, 0.1]],
+            "length": 10,
+            "similarity_threshold": null,
+            "ann_mode": {"Nprobe": 15},
+            "realtime": false,
+            "field_filter": []
+        })
+    ),
+    responses(
+        (status = 200, description = "Results found, returns the SearchBatchResultObject", body = SearchBatchResultObject),
+        (status = BAD_REQUEST, description = "Request object incorrect"),
+        (status = NOT_FOUND, description = "Index id does not exist"),
+        (status = NOT_FOUND, description = "API key does not exist"),
+        (status = UNAUTHORIZED, description = "api_key does not exists"),
+        (status = UNAUTHORIZED, description = "api_key missing"),
+    ),
+    operation_id = "query_index_batch"
+)]
+pub(crate) async fn query_index_batch_api(
+    index_arc: &IndexArc,
+    search_batch_request: SearchBatchRequestObject,
+) -> SearchBatchResultObject {
+    let start_time = Instant::now();
+
+    let mut query_ids = Vec::new();
+    let mut query_vectors = Vec::new();
+    for (query_id, value) in search_batch_request.query_vectors.iter().enumerate() {
+        if let Some(query_vector) = query_vector_from_value(index_arc, value).await {
+            query_ids.push(query_id);
+            query_vectors.push(query_vector);
+        }
+    }
+
+    let result_objects = index_arc
+        .search_batch(
+            query_vectors,
+            search_batch_request.length,
+            search_batch_request.similarity_threshold,
+            search_batch_request.ann_mode,
+            search_batch_request.chunk_aggregation,
+            search_batch_request.realtime,
+            search_batch_request.field_filter,
+        )
+        .await;
+
+    let mut results = vec![Vec::new(); search_batch_request.query_vectors.len()];
+    for (query_id, result_object) in query_ids.into_iter().zip(result_objects) {
+        results[query_id] = result_object
+            .results
+            .iter()
+            .map(|result| SearchBatchResult {
+                doc_id: result.doc_id,
+                score: result.score,
+            })
+            .collect();
+    }
+
+    SearchBatchResultObject {
+        time: start_time.elapsed().as_nanos(),
+        results,
+    }
+}
+
+/// Radius Query Index
+///
+/// Vector search for all documents within a radius of the query vector, e.g. for deduplication and clustering jobs, paginated with offset and length.
+/// The total count is exact for exhaustive search (ann_mode All), and estimated for ANN search.
+/// Returns the document ids and scores.
+#[utoipa::path(
+    post,
+    tag = "Query",
+    path = "/api/v1/index/{index_id}/query_radius",
+    params(
+        ("apikey" = String, Header, description = "YOUR_SECRET_API_KEY",example="AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="),
+        ("index_id" = u64, Path, description = "index id"),
+    ),
+    request_body(
+        content = SearchRadiusRequestObject,
+        example = json!({
+            "query_vector": [0.1, 0.2, 0.3],
+            "radius": 0.9,
+            "ann_mode": "All",
+            "offset": 0,
+            "length": 100,
+            "realtime": false,
+            "field_filter": []
+        })
+    ),
+    responses(
+        (status = 200, description = "Results found, returns the SearchRadiusResultObject", body = SearchRadiusResultObject),
+        (status = BAD_REQUEST, description = "Request object incorrect"),
+        (status = NOT_FOUND, description = "Index id does not exist"),
+        (status = NOT_FOUND, description = "API key does not exist"),
+        (status = UNAUTHORIZED, description = "api_key does not exists"),
+        (status = UNAUTHORIZED, description = "api_key missing"),
+    ),
+    operation_id = "query_index_radius"
+)]
+pub(crate) async fn query_index_radius_api(
+    index_arc: &IndexArc,
+    search_radius_request: SearchRadiusRequestObject,
+) -> Result<SearchRadiusResultObject, String> {
+    let start_time = Instant::now();
+
+    let Some(query_vector) =
+        query_vector_from_value(index_arc, &search_radius_request.query_vector).await
+    else {
+        return Err("query_vector could not be decoded".to_string());
+    };
+
+    let result_object = index_arc
+        .search_radius(
+            query_vector,
+            search_radius_request.radius,
+            search_radius_request.ann_mode,
+            search_radius_request.offset,
+            search_radius_request.length,
+            search_radius_request.realtime,
+            search_radius_request.field_filter,
+        )
+        .await;
+
+    Ok(SearchRadiusResultObject {
+        time: start_time.elapsed().as_nanos(),
+        offset: search_radius_request.offset,
+        length: search_radius_request.length,
+        count: result_object.result_count,
+        count_total: result_object.result_count_total,
+        count_lower_bound: result_object.result_count_lower_bound,
+        results: result_object
+            .results
+            .iter()
+            .map(|result| SearchBatchResult {
+                doc_id: result.doc_id,
+                score: result.score,
+            })
+            .collect(),
+    })
+}
+
 #[derive(OpenApi, Default)]
 #[openapi(paths(
     live_api,
@@ -1454,6 +1802,8 @@ pub(crate) async fn query_index_api(
     delete_document_by_object_api,
     query_index_api_post,
     query_index_api_get,
+    query_index_batch_api,
+    query_index_radius_api,
 ),
 tags(
     (name="Info", description="Return info about the server"),
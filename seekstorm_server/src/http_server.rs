@@ -28,11 +28,13 @@ use hyper_util::rt::{TokioExecutor, TokioIo};
 use seekstorm::INDEX_RUNTIME;
 use seekstorm::index::{
     ApikeyObject, ApikeyQuotaObject, CreateIndexRequest, DeleteApikeyRequest, Document,
-    GetDocumentRequest, GetIteratorRequest, SearchRequestObject, Synonym,
+    GetDocumentRequest, GetIteratorRequest, SearchBatchRequestObject, SearchRadiusRequestObject,
+    SearchRequestObject, Synonym,
 };
 use seekstorm::search::{QueryRewriting, QueryType, ResultType, Search, SearchMode};
+use seekstorm::search_batch::SearchBatch;
 
-use seekstorm::vector::Embedding;
+use seekstorm::vector::{ChunkAggregation, Embedding};
 use seekstorm::vector_similarity::AnnMode;
 use sha2::Digest;
 use sha2::Sha256;
@@ -43,7 +45,13 @@ use base64::{Engine as _, engine::general_purpose};
 
 use crate::api_endpoints::create_index_api;
 use crate::api_endpoints::delete_apikey_api;
+use crate::api_endpoints::query_index_batch_api;
+use crate::api_endpoints::query_index_radius_api;
 use crate::api_endpoints::update_documents_api;
+use crate::api_endpoints::{
+    RerankModels, index_document_api, query_index_api_get, query_index_api_post,
+    search_request_reranker,
+};
 use crate::api_endpoints::{add_synonyms_api, get_index_info_api, set_synonyms_api};
 use crate::api_endpoints::{clear_index_api, close_index_api};
 use crate::api_endpoints::{commit_index_api, create_apikey_api};
@@ -57,7 +65,6 @@ use crate::api_endpoints::{get_document_api, get_synonyms_api};
 use crate::api_endpoints::{
     get_iterator_api_get, get_iterator_api_post, live_api, update_document_api,
 };
-use crate::api_endpoints::{index_document_api, query_index_api_get, query_index_api_post};
 use crate::multi_tenancy::get_apikey_hash;
 use crate::{MASTER_KEY_SECRET, VERSION};
 
@@ -179,6 +186,7 @@ pub(crate) async fn http_request_handler(
     req: Request<Incoming>,
     _remote_addr: SocketAddr,
     force_shard_number: Option<usize>,
+    rerank_models: RerankModels,
 ) -> Result<Response<BoxBody<Bytes, Infallible>>, Infallible> {
     let apikey_header = req
         .headers()
@@ -253,6 +261,7 @@ pub(crate) async fn http_request_handler(
                     SearchMode::Vector {
                         similarity_threshold: None,
                         ann_mode: AnnMode::Nprobe(15),
+                        chunk_aggregation: ChunkAggregation::Max,
                     },
                     false,
                     0,
@@ -288,6 +297,126 @@ pub(crate) async fn http_request_handler(
             }
         }
 
+        ("api", "v2", "index", _, "query_batch", _, &Method::POST) => {
+            let Some(apikey) = apikey_header else {
+                return HttpServerError::Unauthorized.into();
+            };
+            let Some(apikey_hash) = get_apikey_hash(apikey, &apikey_list).await else {
+                return HttpServerError::Unauthorized.into();
+            };
+
+            let Ok(index_id) = parts[3].parse() else {
+                return HttpServerError::IndexNotFound.into();
+            };
+
+            let apikey_list_ref = apikey_list.read().await;
+            let Some(apikey_object) = apikey_list_ref.get(&apikey_hash) else {
+                return HttpServerError::Unauthorized.into();
+            };
+
+            let Some(index_arc) = apikey_object.index_list.get(&index_id) else {
+                return HttpServerError::IndexNotFound.into();
+            };
+
+            let index_arc_clone = index_arc.clone();
+            drop(apikey_list_ref);
+
+            let params: HashMap<String, String> = req
+                .uri()
+                .query()
+                .map(|v| {
+                    url::form_urlencoded::parse(v.as_bytes())
+                        .into_owned()
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            let length = if let Some(value) = params.get("length") {
+                let Ok(length) = value.parse::<usize>() else {
+                    return HttpServerError::BadRequest("length invalid".to_string()).into();
+                };
+                length
+            } else {
+                10
+            };
+
+            let ann_mode = if let Some(value) = params.get("ann_mode") {
+                let Ok(ann_mode) = serde_json::from_str::<AnnMode>(value) else {
+                    return HttpServerError::BadRequest("ann_mode invalid".to_string()).into();
+                };
+                ann_mode
+            } else {
+                AnnMode::default()
+            };
+
+            let similarity_threshold = if let Some(value) = params.get("similarity_threshold") {
+                let Ok(similarity_threshold) = value.parse::<f32>() else {
+                    return HttpServerError::BadRequest("similarity_threshold invalid".to_string())
+                        .into();
+                };
+                Some(similarity_threshold)
+            } else {
+                None
+            };
+
+            let realtime = if let Some(value) = params.get("realtime") {
+                let Ok(realtime) = value.parse::<bool>() else {
+                    return HttpServerError::BadRequest("realtime invalid".to_string()).into();
+                };
+                realtime
+            } else {
+                false
+            };
+
+            let field_filter: Vec<String> = params
+                .get("field_filter")
+                .map(|value| value.split(',').map(|field| field.to_string()).collect())
+                .unwrap_or_default();
+
+            let request_bytes = req.into_body().collect().await.unwrap().to_bytes();
+
+            let archived_query_vectors =
+                unsafe { access_unchecked::<ArchivedVec<ArchivedVec<f32>>>(&request_bytes) };
+
+            let query_vectors: Vec<Embedding> = archived_query_vectors
+                .iter()
+                .map(|query_vector| Embedding::F32(query_vector.as_slice().to_vec()))
+                .collect();
+
+            let search_results = index_arc_clone
+                .search_batch(
+                    query_vectors,
+                    length,
+                    similarity_threshold,
+                    ann_mode,
+                    ChunkAggregation::Max,
+                    realtime,
+                    field_filter,
+                )
+                .await;
+
+            let docid_vectors: Vec<Vec<u64>> = search_results
+                .iter()
+                .map(|search_result| {
+                    search_result
+                        .results
+                        .iter()
+                        .map(|result| result.doc_id as u64)
+                        .collect()
+                })
+                .collect();
+
+            let response_bytes = rkyv::to_bytes::<Error>(&docid_vectors).unwrap().into_vec();
+
+            let response = Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", "application/octet-stream")
+                .header("content-length", response_bytes.len())
+                .body(BoxBody::new(Full::new(response_bytes.into())))
+                .unwrap();
+            Ok(response)
+        }
+
         ("api", "v1", "index", _, "query", _, &Method::POST) => {
             let Some(apikey) = apikey_header else {
                 return HttpServerError::Unauthorized.into();
@@ -326,7 +455,21 @@ pub(crate) async fn http_request_handler(
                 }
             };
 
-            let search_result_local = query_index_api_post(&index_arc_clone, search_request).await;
+            if let Some(e) = search_request
+                .facet_filter
+                .iter()
+                .find_map(|filter| filter.validate().err())
+            {
+                return HttpServerError::BadRequest(e).into();
+            }
+
+            let reranker = match search_request_reranker(&rerank_models, &search_request) {
+                Ok(reranker) => reranker,
+                Err(e) => return HttpServerError::BadRequest(e).into(),
+            };
+
+            let search_result_local =
+                query_index_api_post(&index_arc_clone, search_request, reranker).await;
 
             let search_result_json = serde_json::to_vec(&search_result_local).unwrap();
             Ok(Response::new(BoxBody::new(Full::new(
@@ -334,6 +477,103 @@ pub(crate) async fn http_request_handler(
             ))))
         }
 
+        ("api", "v1", "index", _, "query_batch", _, &Method::POST) => {
+            let Some(apikey) = apikey_header else {
+                return HttpServerError::Unauthorized.into();
+            };
+            let Some(apikey_hash) = get_apikey_hash(apikey, &apikey_list).await else {
+                return HttpServerError::Unauthorized.into();
+            };
+
+            if rate_limit(&apikey_list, apikey_hash).await {
+                return HttpServerError::RateLimitExceeded.into();
+            }
+
+            let Ok(index_id) = parts[3].parse() else {
+                return HttpServerError::IndexNotFound.into();
+            };
+
+            let apikey_list_ref = apikey_list.read().await;
+            let Some(apikey_object) = apikey_list_ref.get(&apikey_hash) else {
+                return HttpServerError::Unauthorized.into();
+            };
+
+            let Some(index_arc) = apikey_object.index_list.get(&index_id) else {
+                return HttpServerError::IndexNotFound.into();
+            };
+
+            let index_arc_clone = index_arc.clone();
+            drop(apikey_list_ref);
+
+            let request_bytes = req.into_body().collect().await.unwrap().to_bytes();
+
+            let search_batch_request =
+                match serde_json::from_slice::<SearchBatchRequestObject>(&request_bytes) {
+                    Ok(search_batch_request) => search_batch_request,
+                    Err(e) => {
+                        return HttpServerError::BadRequest(e.to_string()).into();
+                    }
+                };
+
+            let search_batch_result =
+                query_index_batch_api(&index_arc_clone, search_batch_request).await;
+
+            let search_batch_result_json = serde_json::to_vec(&search_batch_result).unwrap();
+            Ok(Response::new(BoxBody::new(Full::new(
+                search_batch_result_json.into(),
+            ))))
+        }
+
+        ("api", "v1", "index", _, "query_radius", _, &Method::POST) => {
+            let Some(apikey) = apikey_header else {
+                return HttpServerError::Unauthorized.into();
+            };
+            let Some(apikey_hash) = get_apikey_hash(apikey, &apikey_list).await else {
+                return HttpServerError::Unauthorized.into();
+            };
+
+            if rate_limit(&apikey_list, apikey_hash).await {
+                return HttpServerError::RateLimitExceeded.into();
+            }
+
+            let Ok(index_id) = parts[3].parse() else {
+                return HttpServerError::IndexNotFound.into();
+            };
+
+            let apikey_list_ref = apikey_list.read().await;
+            let Some(apikey_object) = apikey_list_ref.get(&apikey_hash) else {
+                return HttpServerError::Unauthorized.into();
+            };
+
+            let Some(index_arc) = apikey_object.index_list.get(&index_id) else {
+                return HttpServerError::IndexNotFound.into();
+            };
+
+            let index_arc_clone = index_arc.clone();
+            drop(apikey_list_ref);
+
+            let request_bytes = req.into_body().collect().await.unwrap().to_bytes();
+
+            let search_radius_request =
+                match serde_json::from_slice::<SearchRadiusRequestObject>(&request_bytes) {
+                    Ok(search_radius_request) => search_radius_request,
+                    Err(e) => {
+                        return HttpServerError::BadRequest(e.to_string()).into();
+                    }
+                };
+
+            match query_index_radius_api(&index_arc_clone, search_radius_request).await {
+                Ok(search_radius_result) => {
+                    let search_radius_result_json =
+                        serde_json::to_vec(&search_radius_result).unwrap();
+                    Ok(Response::new(BoxBody::new(Full::new(
+                        search_radius_result_json.into(),
+                    ))))
+                }
+                Err(e) => HttpServerError::BadRequest(e).into(),
+            }
+        }
+
         ("api", "v1", "index", _, "query", _, &Method::GET) => {
             let Some(apikey) = apikey_header else {
                 return HttpServerError::Unauthorized.into();
@@ -445,6 +685,10 @@ pub(crate) async fn http_request_handler(
                     query_type_default: QueryType::Intersection,
                     query_rewriting: QueryRewriting::SearchOnly,
                     search_mode: SearchMode::Lexical,
+                    diversify: None,
+                    rerank: None,
+                    query_expansion: None,
+                    routing: params.get("routing").cloned(),
                 }
             } else {
                 let request_bytes = req.into_body().collect().await.unwrap().to_bytes();
@@ -467,7 +711,21 @@ pub(crate) async fn http_request_handler(
                 }
             };
 
-            let search_result_local = query_index_api_get(&index_arc_clone, search_request).await;
+            if let Some(e) = search_request
+                .facet_filter
+                .iter()
+                .find_map(|filter| filter.validate().err())
+            {
+                return HttpServerError::BadRequest(e).into();
+            }
+
+            let reranker = match search_request_reranker(&rerank_models, &search_request) {
+                Ok(reranker) => reranker,
+                Err(e) => return HttpServerError::BadRequest(e).into(),
+            };
+
+            let search_result_local =
+                query_index_api_get(&index_arc_clone, search_request, reranker).await;
 
             let search_result_json = serde_json::to_vec(&search_result_local).unwrap();
             Ok(Response::new(BoxBody::new(Full::new(
@@ -1483,6 +1741,7 @@ pub(crate) async fn http_server(
     local_ip: &String,
     local_port: &u16,
     force_shard_number: &Option<usize>,
+    rerank_models: RerankModels,
 ) {
     let local_address: SocketAddr = format!("{}:{}", local_ip, local_port)
         .parse()
@@ -1518,6 +1777,7 @@ pub(crate) async fn http_server(
                 let index_path = index_path.clone();
                 let apikey_list = apikey_list.clone();
                 let force_shard_number = *force_shard_number;
+                let rerank_models = rerank_models.clone();
 
                 tokio::spawn(async move {
                     if let Err(err) = server
@@ -1527,6 +1787,7 @@ pub(crate) async fn http_server(
                                 let index_path = index_path.clone();
                                 let apikey_list = apikey_list.clone();
                                 let force_shard_number = force_shard_number;
+                                let rerank_models = rerank_models.clone();
                                 async move {
                                     let t: Result<_, Infallible> = http_request_handler(
                                         index_path,
@@ -1534,6 +1795,7 @@ pub(crate) async fn http_server(
                                         request,
                                         remote_address,
                                         force_shard_number,
+                                        rerank_models,
                                     )
                                     .await;
 
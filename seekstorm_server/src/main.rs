@@ -15,7 +15,8 @@
 //! * index_path   (default = "/seekstorm_index" in current directory)
 //! * local_ip     (default = 0.0.0.0)
 //! * local_port   (default = 80)
-//! ./seekstorm_server.exe local_ip="127.0.0.1" local_port=80 index_path="c:/seekstorm_index"
+//! * rerank_models (default = none) comma separated cross-encoder models (Hugging Face model ids or local paths) that search requests may use for reranking
+//! ./seekstorm_server.exe local_ip="127.0.0.1" local_port=80 index_path="c:/seekstorm_index" rerank_models="cross-encoder/ms-marco-MiniLM-L-6-v2"
 //! ```
 //! &#x26A0; **WARNING**: make sure to set the MASTER_KEY_SECRET environment variable to a secret,
 //! otherwise your generated API keys will be compromised.
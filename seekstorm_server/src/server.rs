@@ -18,8 +18,9 @@ use seekstorm::{
     },
     search::{QueryRewriting, QueryType, ResultType, Search, SearchMode},
     utils::dir_size,
-    vector::{Embedding, Inference, Model, Quantization},
+    vector::{ChunkAggregation, Embedding, Inference, Model, Quantization},
     vector_similarity::{AnnMode, VectorSimilarity},
+    verify::repair_index,
 };
 use sha2::{Digest, Sha256};
 use std::{
@@ -46,7 +47,8 @@ use tokio::sync::RwLock;
 use crate::{
     MASTER_KEY_SECRET, VERSION,
     api_endpoints::{
-        create_apikey_api, create_index_api, delete_apikey_api, generate_openapi, open_all_apikeys,
+        create_apikey_api, create_index_api, delete_apikey_api, generate_openapi,
+        load_rerank_models, open_all_apikeys,
     },
     http_server::{calculate_hash, http_server},
     multi_tenancy::get_apikey_hash,
@@ -402,6 +404,14 @@ pub(crate) async fn initialize(params: HashMap<String, String>) {
         );
     }
 
+    // only the rerank models configured here can be used in search requests, they are loaded once and shared by all requests
+    let rerank_models = load_rerank_models(
+        params
+            .get("rerank_models")
+            .map_or("", |models| models.as_str()),
+    )
+    .await;
+
     let index_path_local = index_path.clone();
 
     tokio::spawn(async move {
@@ -411,6 +421,7 @@ pub(crate) async fn initialize(params: HashMap<String, String>) {
             &local_ip,
             &local_port,
             &force_shard_number,
+            rerank_models,
         )
         .await
     });
@@ -466,7 +477,7 @@ pub(crate) async fn initialize(params: HashMap<String, String>) {
                                     let topk=10;
                                     let similarity_threshold=None;
                                     let field_filter=Vec::new();
-                                    let search_mode=SearchMode::Vector { similarity_threshold , ann_mode:AnnMode::Nprobe(15)};
+                                    let search_mode=SearchMode::Vector { similarity_threshold , ann_mode:AnnMode::Nprobe(15), chunk_aggregation: ChunkAggregation::Max };
 
                                     let mut search_time_sum=0;
                                     let mut results_sum=0;
@@ -592,7 +603,7 @@ pub(crate) async fn initialize(params: HashMap<String, String>) {
                                         query.to_string(),
                                         None,
                                         QueryType::Intersection,
-                                        SearchMode::Vector { similarity_threshold , ann_mode: AnnMode::Similaritythreshold(0.0) },
+                                        SearchMode::Vector { similarity_threshold , ann_mode: AnnMode::Similaritythreshold(0.0), chunk_aggregation: ChunkAggregation::Max },
                                         false,
                                         0,
                                         len,
@@ -646,7 +657,7 @@ pub(crate) async fn initialize(params: HashMap<String, String>) {
                                         query.to_string(),
                                         None,
                                         QueryType::Intersection,
-                                        SearchMode::Vector { similarity_threshold , ann_mode: AnnMode::Similaritythreshold(min_cluster_score)},
+                                        SearchMode::Vector { similarity_threshold , ann_mode: AnnMode::Similaritythreshold(min_cluster_score), chunk_aggregation: ChunkAggregation::Max },
                                         false,
                                         0,
                                         len,
@@ -685,7 +696,7 @@ pub(crate) async fn initialize(params: HashMap<String, String>) {
                                         query.to_string(),
                                         None,
                                         QueryType::Intersection,
-                                        SearchMode::Vector { similarity_threshold  , ann_mode: AnnMode::Nprobe(55) },
+                                        SearchMode::Vector { similarity_threshold  , ann_mode: AnnMode::Nprobe(55), chunk_aggregation: ChunkAggregation::Max },
                                         false,
                                         0,
                                         len,
@@ -1086,6 +1097,47 @@ pub(crate) async fn initialize(params: HashMap<String, String>) {
                             }
                         }
 
+                        "verify" =>
+                        {
+                            let apikey_list_ref=apikey_list_clone.read().await;
+                            for apikey in apikey_list_ref.iter() {
+                                for index in apikey.1.index_list.iter() {
+                                    let index_ref=index.1.read().await;
+                                    let verify_result=index_ref.verify().await;
+                                    println!("index {} {} {}",index_ref.meta.id,index_ref.meta.name,if verify_result.is_consistent {"consistent".green()} else {"inconsistent".red()});
+                                    for shard_result in verify_result.shard_results.iter() {
+                                        for error in shard_result.errors.iter() {
+                                            println!("{} {}",shard_result.path,error);
+                                        }
+                                    }
+                                }
+                            }
+                        }
+
+                        "repair" =>
+                        {
+                            let mut apikey_list_mut=apikey_list_clone.write().await;
+                            for apikey in apikey_list_mut.iter_mut() {
+                                for index in apikey.1.index_list.iter_mut() {
+                                    if index.1.read().await.verify().await.is_consistent {
+                                        continue;
+                                    }
+
+                                    index.1.close().await;
+                                    let index_id_path=index_path.join(apikey.1.id.to_string()).join(index.0.to_string());
+                                    match repair_index(&index_id_path).await {
+                                        Ok(repair_result) => {
+                                            let removed_doc_count:usize=repair_result.shard_results.iter().map(|shard_result| shard_result.removed_doc_count).sum();
+                                            println!("index {} repaired, {} documents removed",index.0,removed_doc_count.to_formatted_string(&Locale::en));
+                                            *index.1=repair_result.index_arc;
+                                        }
+                                        Err(e) => println!("index {} repair failed: {}",index.0,e),
+                                    }
+                                }
+                            }
+                            drop(apikey_list_mut);
+                        }
+
                         "help" =>
                         {
                             println!("{}","Server console commands:".yellow());
@@ -1096,6 +1148,8 @@ pub(crate) async fn initialize(params: HashMap<String, String>) {
                             println!("{:40} Create the demo API key manually to allow a subsequent custom create index via REST API.","create".green());
                             println!("{:40} Delete the demo API key and all its indices.","delete".green());
                             println!("{:40} Display current index information.","info".green());
+                            println!("{:40} Verify the integrity of all indices.","verify".green());
+                            println!("{:40} Roll back inconsistent indices to their last consistent level.","repair".green());
                             println!("{:40} Create OpenAPI JSON file.","openapi".green());
                             println!("{:40} Stop the server.","quit".green());
                             println!("{:40} Show this help.","help".green());
@@ -12,7 +12,7 @@ use seekstorm::iterator::GetIterator;
 use seekstorm::search::{
     FacetValue, QueryRewriting, QueryType, ResultSort, ResultType, Search, SearchMode, SortOrder,
 };
-use seekstorm::vector::{Embedding, Inference, Model, Precision, Quantization};
+use seekstorm::vector::{ChunkAggregation, Embedding, Inference, Model, Precision, Quantization};
 use seekstorm::vector_similarity::{AnnMode, VectorSimilarity};
 use std::collections::HashSet;
 use std::{fs, path::Path};
@@ -255,11 +255,11 @@ async fn test_05_empty_query() {
 
     // descending
 
-    let result_sort = vec![ResultSort {
-        field: "_id".into(),
-        order: SortOrder::Descending,
-        base: FacetValue::None,
-    }];
+    let result_sort = vec![ResultSort::new(
+        "_id".into(),
+        SortOrder::Descending,
+        FacetValue::None,
+    )];
 
     let result_object = index_arc
         .search(
@@ -294,11 +294,11 @@ async fn test_05_empty_query() {
 
     // ascending
 
-    let result_sort = vec![ResultSort {
-        field: "_id".into(),
-        order: SortOrder::Ascending,
-        base: FacetValue::None,
-    }];
+    let result_sort = vec![ResultSort::new(
+        "_id".into(),
+        SortOrder::Ascending,
+        FacetValue::None,
+    )];
 
     let result_object = index_arc
         .search(
@@ -586,6 +586,7 @@ async fn test_11_query_index_vector_internal() {
             SearchMode::Vector {
                 similarity_threshold: Some(0.7),
                 ann_mode: AnnMode::All,
+                chunk_aggregation: ChunkAggregation::Max,
             },
             false,
             0,
@@ -720,6 +721,7 @@ async fn test_14_query_index_vector_external() {
             SearchMode::Vector {
                 similarity_threshold: None,
                 ann_mode: AnnMode::All,
+                chunk_aggregation: ChunkAggregation::Max,
             },
             false,
             0,